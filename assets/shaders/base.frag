@@ -5,6 +5,8 @@ layout (set = 0, binding = 1) uniform sampler2D albedoBuffer;
 layout (set = 0, binding = 2) uniform sampler2D normalBuffer;
 layout (set = 0, binding = 3) uniform sampler2D metallicRoughnessBuffer;
 layout (set = 0, binding = 4) uniform sampler2D shadowBuffer;
+layout (set = 0, binding = 5) uniform sampler2D emissiveBuffer;
+layout (set = 0, binding = 8) uniform sampler2D clearcoatBuffer;
 
 layout (location = 0) in vec2 v_uv;
 
@@ -12,8 +14,27 @@ layout (location = 0) out vec4 fragColor;
 
 layout(push_constant) uniform PostProcessing {
     float brightness;
+    uint debugView;
+    uint backgroundMode;
+    vec3 backgroundColor;
 } post;
 
+// same values as the DebugView enum
+const uint DEBUG_VIEW_SHADED = 0;
+const uint DEBUG_VIEW_ALBEDO = 1;
+const uint DEBUG_VIEW_NORMAL = 2;
+const uint DEBUG_VIEW_ROUGHNESS = 3;
+const uint DEBUG_VIEW_METALLIC = 4;
+const uint DEBUG_VIEW_SHADOW = 5;
+const uint DEBUG_VIEW_NON_FINITE = 6;
+
+// same values as the BackgroundMode enum
+const uint BACKGROUND_COLOR = 0;
+const uint BACKGROUND_CHECKERBOARD = 1;
+const uint BACKGROUND_GRADIENT = 2;
+
+const float CHECKERBOARD_SIZE = 32.0;
+
 struct PointLight {
     vec3 position;
     vec3 color;
@@ -27,8 +48,14 @@ struct DirectionalLight {
     float intensity;
 };
 
+struct AmbientLight {
+    vec3 color;
+    float intensity;
+};
+
 layout(set = 1, binding = 0) uniform Scene {
     DirectionalLight directionalLight;
+    AmbientLight ambientLight;
 } scene;
 
 layout(set = 2, binding = 0) uniform Camera {
@@ -37,12 +64,13 @@ layout(set = 2, binding = 0) uniform Camera {
     mat4 view_inv;
     mat4 proj_inv;
     vec3 position;
+    mat4 unjitteredViewProj;
+    mat4 previousUnjitteredViewProj;
+    float time;
 } camera;
 
 const float PI = 3.14159265359;
 
-vec3 ambientLightColor = vec3(1.0, 1.0, 1.0);
-
 float Convert_sRGB_ToLinear (float thesRGBValue) {
   return thesRGBValue <= 0.04045f
        ? thesRGBValue / 12.92f
@@ -123,6 +151,23 @@ vec3 pbr_common(vec3 lightIntensity, vec3 l, vec3 n, vec3 v, vec3 albedo, vec3 f
     return (diffuseBRDF + specularBRDF) * lightIntensity * nDotL;
 }
 
+// a second, white specular layer on top of the base material, from KHR_materials_clearcoat
+vec3 clearcoat_layer(vec3 baseColor, vec3 lightIntensity, vec3 l, vec3 n, vec3 v, float clearcoat, float clearcoatRoughness) {
+    vec3 h = normalize(v + l);
+    float alpha = clearcoatRoughness * clearcoatRoughness;
+
+    float D = distributionGGXTrowbridgeReitz(n, h, alpha);
+    float G = geometrySmith(n, v, l, alpha);
+    // the coating is a dielectric with an IOR of 1.5
+    vec3 F = fresnelSchlick(vec3(0.04), v, h);
+
+    float denominator = max(4.0 * max(dot(n, l), 0.0) * max(dot(n, v), 0.0), 0.000001);
+    vec3 specular = D * G * F / denominator * lightIntensity * max(dot(n, l), 0.0);
+
+    // the coating reflects some of the light before it reaches the base material
+    return baseColor * (1.0 - clearcoat * F) + clearcoat * specular;
+}
+
 vec3 pbr(PointLight pointLight, vec3 n, vec3 v, vec3 worldPos, vec3 albedo, vec3 f0, float metallic, float roughness) {
     vec3 positionToLight = pointLight.position - worldPos;
     vec3 l = normalize(positionToLight);
@@ -141,10 +186,28 @@ vec3 pbr(DirectionalLight directionalLight, vec3 n, vec3 v, vec3 worldPos, vec3
 }
 
 
+vec3 background() {
+    switch (post.backgroundMode) {
+        case BACKGROUND_CHECKERBOARD: {
+            ivec2 cell = ivec2(gl_FragCoord.xy / CHECKERBOARD_SIZE);
+            return (cell.x + cell.y) % 2 == 0 ? post.backgroundColor : post.backgroundColor * 0.5;
+        }
+        case BACKGROUND_GRADIENT:
+            return post.backgroundColor * (1.0 - v_uv.y);
+        default:
+            return post.backgroundColor;
+    }
+}
 
 void main() {
     vec3 position = texture(positionBuffer, v_uv).rgb;
     vec3 normal = texture(normalBuffer, v_uv).rgb;
+
+    // the geometry pass clears the normals to zero, so nothing was drawn here
+    if (dot(normal, normal) == 0.0) {
+        fragColor = vec4(background(), 1.0);
+        return;
+    }
     vec3 albedo = texture(albedoBuffer, v_uv).rgb;
 
     vec2 metallicRoughness = texture(metallicRoughnessBuffer, v_uv).bg;
@@ -169,14 +232,50 @@ void main() {
 
     Lo += pbr(scene.directionalLight, n, v, position, albedo, f0, metallic, roughness);
 
-    float ka = 0.03;
-    vec3 ambient = (ambientLightColor * ka) * albedo;
+    vec2 clearcoat = texture(clearcoatBuffer, v_uv).rg;
+    if (clearcoat.x > 0.0) {
+        vec3 l = normalize(-scene.directionalLight.direction);
+        vec3 lightIntensity = scene.directionalLight.color * scene.directionalLight.intensity;
+        Lo = clearcoat_layer(Lo, lightIntensity, l, n, v, clearcoat.x, clearcoat.y);
+    }
+
+    // the ambient light fills the shadowed areas, metals have no diffuse part
+    vec3 ambient = scene.ambientLight.color * scene.ambientLight.intensity * albedo * (1.0 - metallic);
 
-    vec3 color = Lo + ambient;
+    // emissive surfaces glow regardless of shadows
+    vec3 emissive = texture(emissiveBuffer, v_uv).rgb;
 
-    vec3 output_color = mix(color, color * 0.1, shadow);
+    vec3 output_color = mix(Lo, Lo * 0.1, shadow) + ambient + emissive;
     // If shadow == 1.0 (true), then red
     //output_color = color * 0.1 + (vec3(1.0, 0.3, 0.3) * shadow);
 
-    fragColor = vec4(output_color * post.brightness, 1.0);
+    bool isNonFinite = any(isnan(output_color)) || any(isinf(output_color));
+
+    // the debug views skip the brightness, so that they show the raw values
+    switch (post.debugView) {
+        case DEBUG_VIEW_ALBEDO:
+            fragColor = vec4(albedo, 1.0);
+            break;
+        case DEBUG_VIEW_NORMAL:
+            fragColor = vec4(n * 0.5 + 0.5, 1.0);
+            break;
+        case DEBUG_VIEW_ROUGHNESS:
+            fragColor = vec4(vec3(roughness), 1.0);
+            break;
+        case DEBUG_VIEW_METALLIC:
+            fragColor = vec4(vec3(metallic), 1.0);
+            break;
+        case DEBUG_VIEW_SHADOW:
+            fragColor = vec4(vec3(shadow), 1.0);
+            break;
+        case DEBUG_VIEW_NON_FINITE:
+            fragColor = vec4(isNonFinite ? vec3(1.0, 0.0, 1.0) : output_color * post.brightness, 1.0);
+            break;
+        default:
+            fragColor = vec4(output_color * post.brightness, 1.0);
+            break;
+    }
+
+    // the alpha channel flags the pixel for the non-finite readback, the TAA pass ignores it
+    fragColor.a = isNonFinite ? 0.0 : 1.0;
 }
@@ -0,0 +1,23 @@
+#version 450
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 color;
+
+layout (location = 0) out vec3 v_color;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_inv;
+    mat4 proj_inv;
+    vec3 position;
+    mat4 unjitteredViewProj;
+    mat4 previousUnjitteredViewProj;
+    float time;
+} camera;
+
+void main() {
+    // drawn after the TAA resolve, so without the jitter
+    gl_Position = camera.unjitteredViewProj * vec4(position, 1.0);
+    v_color = color;
+}
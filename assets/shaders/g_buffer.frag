@@ -4,11 +4,18 @@ layout (location = 0) in vec3 v_position;
 layout (location = 1) in vec3 v_normal;
 layout (location = 2) in vec2 v_uv;
 layout (location = 3) in vec4 v_tangent;
+layout (location = 4) in vec4 v_clipPosition;
+layout (location = 5) in vec4 v_previousClipPosition;
+layout (location = 6) flat in uint v_objectId;
 
 layout (location = 0) out vec3 outPosition;
 layout (location = 1) out vec3 outAlbedo;
 layout (location = 2) out vec3 outNormal;
 layout (location = 3) out vec2 outMetallicRoughness;
+layout (location = 4) out vec3 outEmissive;
+layout (location = 5) out vec2 outMotionVector;
+layout (location = 6) out uint outObjectId;
+layout (location = 7) out vec2 outClearcoat;
 
 struct DirectionalLight {
     vec3 direction;
@@ -21,6 +28,9 @@ layout(set = 0, binding = 0) uniform Camera {
     mat4 view_inv;
     mat4 proj_inv;
     vec3 position;
+    mat4 unjitteredViewProj;
+    mat4 previousUnjitteredViewProj;
+    float time;
 } camera;
 
 layout(set = 1, binding = 0) uniform Material {
@@ -28,6 +38,11 @@ layout(set = 1, binding = 0) uniform Material {
     vec3 emissivity;
     float roughness;
     float metallic;
+    // not used yet, glass would need a transparent pass
+    float transmission;
+    float ior;
+    float clearcoat;
+    float clearcoatRoughness;
 } material;
 
 layout(set = 1, binding = 1) uniform sampler2D baseColorTexture;
@@ -36,8 +51,13 @@ layout(set = 1, binding = 2) uniform sampler2D normalMapTexture;
 
 layout(set = 1, binding = 3) uniform sampler2D metallicRoughnessTexture;
 
+layout(set = 1, binding = 4) uniform sampler2D clearcoatTexture;
+
+layout(set = 1, binding = 5) uniform sampler2D clearcoatRoughnessTexture;
+
 void main() {
-    vec3 N = normalize(v_normal);
+    // back faces are only visible for double sided materials
+    vec3 N = normalize(v_normal) * (gl_FrontFacing ? 1.0 : -1.0);
     vec3 T = normalize(v_tangent.xyz);
     vec3 B = cross(N, T) * v_tangent.w;
     mat3 TBN = mat3(T,B,N);
@@ -46,6 +66,10 @@ void main() {
 
     vec2 metallicRoughness = texture(metallicRoughnessTexture, v_uv).rg * vec2(material.metallic, material.roughness);
 
+    // KHR_materials_clearcoat stores the factor in the red and the roughness in the green channel
+    float clearcoat = texture(clearcoatTexture, v_uv).r * material.clearcoat;
+    float clearcoatRoughness = texture(clearcoatRoughnessTexture, v_uv).g * material.clearcoatRoughness;
+
     // in world space
     vec3 norm = TBN * (texture(normalMapTexture, v_uv).rgb * 2.0 - vec3(1.0));
 
@@ -53,4 +77,9 @@ void main() {
     outAlbedo = albedo;
     outNormal = normalize(norm);
     outMetallicRoughness = metallicRoughness;
+    outEmissive = material.emissivity;
+    outObjectId = v_objectId;
+    outClearcoat = vec2(clearcoat, clearcoatRoughness);
+    // in UV coordinates, from the previous frame to this one
+    outMotionVector = (v_clipPosition.xy / v_clipPosition.w - v_previousClipPosition.xy / v_previousClipPosition.w) * 0.5;
 }
\ No newline at end of file
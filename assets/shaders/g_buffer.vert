@@ -9,6 +9,9 @@ layout (location = 0) out vec3 v_position;
 layout (location = 1) out vec3 v_normal;
 layout (location = 2) out vec2 v_uv;
 layout (location = 3) out vec4 v_tangent;
+layout (location = 4) out vec4 v_clipPosition;
+layout (location = 5) out vec4 v_previousClipPosition;
+layout (location = 6) flat out uint v_objectId;
 
 struct DirectionalLight {
     vec3 direction;
@@ -21,11 +24,17 @@ layout(set = 0, binding = 0) uniform Camera {
     mat4 view_inv;
     mat4 proj_inv;
     vec3 position;
+    mat4 unjitteredViewProj;
+    mat4 previousUnjitteredViewProj;
+    float time;
 } camera;
 
+// the selection mask is rendered without the TAA jitter, since it is not resolved over multiple frames
+layout (constant_id = 0) const bool IS_UNJITTERED = false;
+
 layout(push_constant) uniform Entity {
     mat4 model;
-    mat4 normalMatrix;
+    mat4 previousModel;
 } entity;
 
 void main() {
@@ -33,13 +42,19 @@ void main() {
     vec4 worldPos = entity.model * vec4(position, 1.0);
 
     // in world space
-    vec3 n = normalize(vec3(entity.normalMatrix * vec4(normal, 0.0)));
+    vec3 n = normalize(vec3(mat4(transpose(inverse(mat3(entity.model)))) * vec4(normal, 0.0)));
     vec3 t = normalize(vec3(entity.model * vec4(tangent.rgb, 0.0)));
 
-    gl_Position = camera.proj * camera.view * worldPos;
+    gl_Position = IS_UNJITTERED ? camera.unjitteredViewProj * worldPos : camera.proj * camera.view * worldPos;
+
+    // without the jitter, otherwise it would show up as motion
+    v_clipPosition = camera.unjitteredViewProj * worldPos;
+    v_previousClipPosition = camera.previousUnjitteredViewProj * entity.previousModel * vec4(position, 1.0);
 
     v_position = worldPos.xyz;
     v_normal = n;
     v_uv = uv;
     v_tangent = vec4(t, tangent.w);
-}
\ No newline at end of file
+    // the first instance of the draw call
+    v_objectId = uint(gl_InstanceIndex);
+}
@@ -0,0 +1,78 @@
+#version 450
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
+layout (location = 3) in vec4 tangent;
+
+layout (location = 0) out vec3 v_position;
+layout (location = 1) out vec3 v_normal;
+layout (location = 2) out vec2 v_uv;
+layout (location = 3) out vec4 v_tangent;
+layout (location = 4) out vec4 v_clipPosition;
+layout (location = 5) out vec4 v_previousClipPosition;
+layout (location = 6) flat out uint v_objectId;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_inv;
+    mat4 proj_inv;
+    vec3 position;
+    mat4 unjitteredViewProj;
+    mat4 previousUnjitteredViewProj;
+    float time;
+} camera;
+
+struct MorphVertexDelta {
+    vec4 position;
+    vec4 normal;
+};
+
+// interleaved, the deltas of all targets of a vertex are next to each other
+layout(std430, set = 2, binding = 0) readonly buffer MorphTargets {
+    MorphVertexDelta deltas[];
+};
+
+layout(std430, set = 2, binding = 1) readonly buffer MorphWeights {
+    float weights[];
+};
+
+// the selection mask is rendered without the TAA jitter, since it is not resolved over multiple frames
+layout (constant_id = 0) const bool IS_UNJITTERED = false;
+
+layout(push_constant) uniform Entity {
+    mat4 model;
+    mat4 previousModel;
+} entity;
+
+void main() {
+    vec3 morphedPosition = position;
+    vec3 morphedNormal = normal;
+    int targetCount = weights.length();
+    for (int i = 0; i < targetCount; i++) {
+        MorphVertexDelta delta = deltas[gl_VertexIndex * targetCount + i];
+        morphedPosition += weights[i] * delta.position.xyz;
+        morphedNormal += weights[i] * delta.normal.xyz;
+    }
+
+    // in world space
+    vec4 worldPos = entity.model * vec4(morphedPosition, 1.0);
+
+    // in world space
+    vec3 n = normalize(vec3(mat4(transpose(inverse(mat3(entity.model)))) * vec4(morphedNormal, 0.0)));
+    vec3 t = normalize(vec3(entity.model * vec4(tangent.rgb, 0.0)));
+
+    gl_Position = IS_UNJITTERED ? camera.unjitteredViewProj * worldPos : camera.proj * camera.view * worldPos;
+
+    // without the jitter, otherwise it would show up as motion
+    v_clipPosition = camera.unjitteredViewProj * worldPos;
+    v_previousClipPosition = camera.previousUnjitteredViewProj * entity.previousModel * vec4(morphedPosition, 1.0);
+
+    v_position = worldPos.xyz;
+    v_normal = n;
+    v_uv = uv;
+    v_tangent = vec4(t, tangent.w);
+    // the first instance of the draw call
+    v_objectId = uint(gl_InstanceIndex);
+}
@@ -0,0 +1,67 @@
+#version 450
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
+layout (location = 3) in vec4 tangent;
+layout (location = 4) in uvec4 joints;
+layout (location = 5) in vec4 weights;
+
+layout (location = 0) out vec3 v_position;
+layout (location = 1) out vec3 v_normal;
+layout (location = 2) out vec2 v_uv;
+layout (location = 3) out vec4 v_tangent;
+layout (location = 4) out vec4 v_clipPosition;
+layout (location = 5) out vec4 v_previousClipPosition;
+layout (location = 6) flat out uint v_objectId;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_inv;
+    mat4 proj_inv;
+    vec3 position;
+    mat4 unjitteredViewProj;
+    mat4 previousUnjitteredViewProj;
+    float time;
+} camera;
+
+// in the space of the mesh, already multiplied with the inverse bind matrices
+layout(std430, set = 2, binding = 0) readonly buffer JointMatrices {
+    mat4 joint_matrices[];
+};
+
+// the selection mask is rendered without the TAA jitter, since it is not resolved over multiple frames
+layout (constant_id = 0) const bool IS_UNJITTERED = false;
+
+layout(push_constant) uniform Entity {
+    mat4 model;
+    mat4 previousModel;
+} entity;
+
+void main() {
+    mat4 skin = weights.x * joint_matrices[joints.x]
+              + weights.y * joint_matrices[joints.y]
+              + weights.z * joint_matrices[joints.z]
+              + weights.w * joint_matrices[joints.w];
+
+    // in world space
+    vec4 worldPos = entity.model * skin * vec4(position, 1.0);
+
+    // in world space, assumes that the joints are not scaled non-uniformly
+    vec3 n = normalize(vec3(mat4(transpose(inverse(mat3(entity.model)))) * skin * vec4(normal, 0.0)));
+    vec3 t = normalize(vec3(entity.model * skin * vec4(tangent.rgb, 0.0)));
+
+    gl_Position = IS_UNJITTERED ? camera.unjitteredViewProj * worldPos : camera.proj * camera.view * worldPos;
+
+    // without the jitter, otherwise it would show up as motion
+    v_clipPosition = camera.unjitteredViewProj * worldPos;
+    v_previousClipPosition = camera.previousUnjitteredViewProj * entity.previousModel * skin * vec4(position, 1.0);
+
+    v_position = worldPos.xyz;
+    v_normal = n;
+    v_uv = uv;
+    v_tangent = vec4(t, tangent.w);
+    // the first instance of the draw call
+    v_objectId = uint(gl_InstanceIndex);
+}
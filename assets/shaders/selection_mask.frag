@@ -0,0 +1,7 @@
+#version 450
+
+layout (location = 0) out float outMask;
+
+void main() {
+    outMask = 1.0;
+}
@@ -0,0 +1,37 @@
+#version 450
+
+// 1 where the selected model is
+layout (set = 0, binding = 0) uniform sampler2D selectionMask;
+
+layout (location = 0) in vec2 v_uv;
+
+layout (location = 0) out vec4 outColor;
+
+layout(push_constant) uniform SelectionOutline {
+    vec3 color;
+    // in pixels
+    uint thickness;
+} outline;
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    if (texelFetch(selectionMask, pixel, 0).r > 0.5) {
+        discard;
+    }
+
+    // pixels that are close enough to the selected model, but not on it
+    ivec2 maxPixel = textureSize(selectionMask, 0) - 1;
+    int thickness = int(outline.thickness);
+    for (int y = -thickness; y <= thickness; y++) {
+        for (int x = -thickness; x <= thickness; x++) {
+            if (x * x + y * y > thickness * thickness) {
+                continue;
+            }
+            if (texelFetch(selectionMask, clamp(pixel + ivec2(x, y), ivec2(0), maxPixel), 0).r > 0.5) {
+                outColor = vec4(outline.color, 1.0);
+                return;
+            }
+        }
+    }
+    discard;
+}
@@ -1,4 +1,5 @@
 #extension GL_EXT_ray_tracing : require
+#extension GL_EXT_nonuniform_qualifier : require
 
 struct DirectionalLight {
     vec3 direction;
@@ -21,3 +22,19 @@ layout(set = 1, binding = 0) uniform Camera {
 layout (set = 2, binding = 0) uniform accelerationStructureEXT topLevelAS;
 layout (set = 2, binding = 1) uniform sampler2D depthBuffer;
 layout (set = 2, binding = 2, r8) uniform image2D shadowBuffer;
+
+
+struct BindlessMaterial {
+    uint baseColorTexture;
+    uint normalTexture;
+    uint metallicRoughnessTexture;
+};
+
+// indexed with gl_InstanceCustomIndexEXT
+layout (set = 3, binding = 0, std430) readonly buffer InstanceMaterials {
+    uint materialIndices[];
+} instanceMaterials;
+layout (set = 3, binding = 1, std430) readonly buffer Materials {
+    BindlessMaterial materials[];
+} materials;
+layout (set = 3, binding = 2) uniform sampler2D textures[];
\ No newline at end of file
@@ -0,0 +1,12 @@
+#version 450
+
+layout (location = 0) in vec3 position;
+
+layout(push_constant) uniform ShadowMapEntity {
+    mat4 lightViewProj;
+    mat4 model;
+} entity;
+
+void main() {
+    gl_Position = entity.lightViewProj * entity.model * vec4(position, 1.0);
+}
@@ -0,0 +1,55 @@
+#version 450
+
+layout (set = 0, binding = 6) uniform sampler2D motionVectorBuffer;
+
+// the lit image of this frame, and the result of the previous frame
+layout (set = 1, binding = 0) uniform sampler2D colorBuffer;
+layout (set = 1, binding = 1) uniform sampler2D historyBuffer;
+
+layout (location = 0) in vec2 v_uv;
+
+layout (location = 0) out vec4 outColor;
+layout (location = 1) out vec4 outHistory;
+
+layout(push_constant) uniform Taa {
+    // 0 when there is no usable history
+    float historyWeight;
+} taa;
+
+// the swapchain format is not sRGB, so the output has to be encoded here
+layout (constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, greaterThan(color, vec3(0.0031308)));
+}
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    ivec2 maxPixel = textureSize(colorBuffer, 0) - 1;
+    vec3 color = texelFetch(colorBuffer, pixel, 0).rgb;
+
+    // the history gets clamped to the neighborhood of the current frame, which avoids most of the ghosting
+    vec3 minColor = color;
+    vec3 maxColor = color;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec3 neighbor = texelFetch(colorBuffer, clamp(pixel + ivec2(x, y), ivec2(0), maxPixel), 0).rgb;
+            minColor = min(minColor, neighbor);
+            maxColor = max(maxColor, neighbor);
+        }
+    }
+
+    // the background has no motion vectors and is treated as if it was not moving
+    vec2 previousUv = v_uv - texelFetch(motionVectorBuffer, pixel, 0).xy;
+
+    vec3 result = color;
+    bool isOnScreen = all(greaterThanEqual(previousUv, vec2(0.0))) && all(lessThanEqual(previousUv, vec2(1.0)));
+    if (taa.historyWeight > 0.0 && isOnScreen) {
+        vec3 history = clamp(texture(historyBuffer, previousUv).rgb, minColor, maxColor);
+        result = mix(color, history, taa.historyWeight);
+    }
+
+    // the history stays linear
+    outColor = vec4(ENCODE_SRGB ? linear_to_srgb(result) : result, 1.0);
+    outHistory = vec4(result, 1.0);
+}
@@ -2,7 +2,9 @@ pub mod animation_camera_controller;
 pub mod camera_controller;
 pub mod freecam_controller;
 
-use ultraviolet::{projection, Mat4, Rotor3, Vec3};
+use ultraviolet::{projection, Mat4, Rotor3, Vec2, Vec3, Vec4};
+
+use crate::scene::Aabb;
 
 use self::camera_controller::CameraController;
 
@@ -11,16 +13,33 @@ pub struct Camera {
     pub position: Vec3,
     pub orientation: Rotor3,
     pub settings: CameraSettings,
+    /// Width divided by height, already clamped
+    aspect_ratio: f32,
 
     pub view: Mat4,
     pub proj: Mat4,
 }
 
+/// Keeps very thin windows from producing a degenerate projection
+const MIN_ASPECT_RATIO: f32 = 0.05;
+const MAX_ASPECT_RATIO: f32 = 20.0;
+/// In degrees, the other field of view can get close to 180 degrees for thin windows
+const MAX_FOV: f32 = 170.0;
+
 #[derive(Debug)]
 pub struct CameraSettings {
     pub z_near: f32,
     pub z_far: f32,
+    /// In degrees, along the axis given by the fov mode
     pub fov: f32,
+    pub fov_mode: FovMode,
+}
+
+/// Which field of view stays the same when the aspect ratio changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FovMode {
+    Vertical,
+    Horizontal,
 }
 
 impl Default for CameraSettings {
@@ -29,6 +48,7 @@ impl Default for CameraSettings {
             z_near: 0.1,
             z_far: 100.0,
             fov: 60.0,
+            fov_mode: FovMode::Vertical,
         }
     }
 }
@@ -38,12 +58,8 @@ impl Camera {
         let position = Vec3::zero();
         let orientation = Rotor3::identity();
 
-        let proj = calculate_projection(
-            aspect_ratio,
-            settings.fov.to_radians(),
-            settings.z_near,
-            settings.z_far,
-        );
+        let aspect_ratio = clamp_aspect_ratio(aspect_ratio).unwrap_or(1.0);
+        let proj = calculate_projection(aspect_ratio, &settings);
 
         let view = calculate_view(position, orientation);
 
@@ -51,6 +67,7 @@ impl Camera {
             position,
             orientation,
             settings,
+            aspect_ratio,
             proj,
             view,
         }
@@ -65,6 +82,19 @@ impl Camera {
         self.proj
     }
 
+    /// Shifts the projection by a subpixel offset, which is given in normalized device coordinates
+    pub fn jittered_projection_matrix(&self, jitter: Vec2) -> ultraviolet::Mat4 {
+        let mut proj = self.proj;
+        proj[2][0] += jitter.x;
+        proj[2][1] += jitter.y;
+        proj
+    }
+
+    /// Without the TAA jitter
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_proj(self.proj * self.view)
+    }
+
     pub fn update_camera(&mut self, controller: &impl CameraController) {
         self.position = controller.position();
         self.orientation = controller.orientation();
@@ -72,8 +102,17 @@ impl Camera {
         self.view = calculate_view(self.position, self.orientation);
     }
 
+    /// Ignores the aspect ratio of minimized windows
     pub fn update_aspect_ratio(&mut self, aspect_ratio: f32) {
-        self.proj[0][0] = -self.proj[1][1] / aspect_ratio;
+        if let Some(aspect_ratio) = clamp_aspect_ratio(aspect_ratio) {
+            self.aspect_ratio = aspect_ratio;
+            self.update_projection();
+        }
+    }
+
+    /// Has to be called after changing the settings
+    pub fn update_projection(&mut self) {
+        self.proj = calculate_projection(self.aspect_ratio, &self.settings);
     }
 
     /// in world-space
@@ -92,9 +131,85 @@ impl Camera {
     }
 }
 
-/// fov is expected to be in radians
-fn calculate_projection(aspect_ratio: f32, fov: f32, near: f32, far: f32) -> Mat4 {
-    projection::rh_yup::perspective_vk(fov, aspect_ratio, near, far)
+/// The volume that a camera sees, in world space
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far. Points inside have a positive distance to all of them.
+    pub planes: [Vec4; 6],
+    /// In the same order as [`Aabb::corners`], the near plane corners come first
+    pub corners: [Vec3; 8],
+}
+
+impl Frustum {
+    /// The planes are the rows of the matrix added together, see "Fast Extraction of Viewing Frustum Planes"
+    pub fn from_view_proj(view_proj: Mat4) -> Self {
+        let row = |index: usize| {
+            Vec4::new(
+                view_proj.cols[0][index],
+                view_proj.cols[1][index],
+                view_proj.cols[2][index],
+                view_proj.cols[3][index],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        // Vulkan has a depth range from 0 to 1
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let normal_length = plane.xyz().mag();
+            plane / normal_length
+        });
+
+        let inverse_view_proj = view_proj.inversed();
+        let ndc_box = Aabb {
+            min: Vec3::new(-1.0, -1.0, 0.0),
+            max: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let corners = ndc_box.corners().map(|ndc| {
+            let position = inverse_view_proj * ndc.into_homogeneous_point();
+            position.xyz() / position.w
+        });
+
+        Self { planes, corners }
+    }
+
+    /// Conservative, boxes close to the edges can be counted as visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner that is furthest along the normal of the plane
+            let corner = Vec3::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.xyz().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+fn clamp_aspect_ratio(aspect_ratio: f32) -> Option<f32> {
+    (aspect_ratio.is_finite() && aspect_ratio > 0.0)
+        .then(|| aspect_ratio.clamp(MIN_ASPECT_RATIO, MAX_ASPECT_RATIO))
+}
+
+fn calculate_projection(aspect_ratio: f32, settings: &CameraSettings) -> Mat4 {
+    let fov = settings.fov.clamp(1.0, MAX_FOV).to_radians();
+    let vertical_fov = match settings.fov_mode {
+        FovMode::Vertical => fov,
+        FovMode::Horizontal => 2.0 * ((fov / 2.0).tan() / aspect_ratio).atan(),
+    };
+    let vertical_fov = vertical_fov.min(MAX_FOV.to_radians());
+    projection::rh_yup::perspective_vk(vertical_fov, aspect_ratio, settings.z_near, settings.z_far)
 }
 
 fn calculate_view(position: Vec3, orientation: Rotor3) -> Mat4 {
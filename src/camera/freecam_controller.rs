@@ -1,6 +1,6 @@
 use ultraviolet::{Rotor3, Vec2, Vec3};
-use winit::event::VirtualKeyCode;
 
+use crate::config_loader::KeyBindings;
 use crate::input_map::InputMap;
 
 use super::{camera_controller::CameraController, Camera};
@@ -12,24 +12,49 @@ pub struct FreecamController {
     pub yaw: f32,
     pub speed: f32,
     pub sensitivity: f32,
+    /// Speed factor while the sprint key is held
+    pub sprint_multiplier: f32,
+    pub key_bindings: KeyBindings,
 }
 
 impl FreecamController {
-    pub fn new(speed: f32, sensitivity: f32) -> Self {
+    pub const MIN_SPEED: f32 = 0.1;
+    pub const MAX_SPEED: f32 = 100.0;
+    /// How much one line of scrolling changes the speed
+    const SCROLL_SPEED_FACTOR: f32 = 1.1;
+
+    pub fn new(
+        speed: f32,
+        sensitivity: f32,
+        sprint_multiplier: f32,
+        key_bindings: KeyBindings,
+    ) -> Self {
         Self {
             position: Vec3::zero(),
             pitch: 0.0,
             yaw: 0.0,
             speed,
             sensitivity,
+            sprint_multiplier,
+            key_bindings,
         }
     }
     pub fn update(&mut self, input_map: &InputMap, delta_time: f32) {
         if input_map.is_capturing_mouse() {
             self.update_orientation(input_map.mouse_delta());
+            self.update_speed(input_map.scroll_delta());
         }
 
-        self.update_position(input_to_direction(input_map), delta_time);
+        let speed = if input_map.is_pressed(self.key_bindings.sprint) {
+            self.speed * self.sprint_multiplier
+        } else {
+            self.speed
+        };
+        self.update_position(
+            input_to_direction(input_map, &self.key_bindings),
+            speed,
+            delta_time,
+        );
 
         // normalize yaw
         const TWO_PI: f32 = std::f32::consts::PI * 2.0;
@@ -42,13 +67,19 @@ impl FreecamController {
         self.pitch = (self.pitch + mouse_delta.y * self.sensitivity).clamp(-max_pitch, max_pitch);
     }
 
-    fn update_position(&mut self, direction: Vec3, delta_time: f32) {
+    /// Scrolling up makes the camera faster
+    fn update_speed(&mut self, scroll_delta: f32) {
+        self.speed = (self.speed * FreecamController::SCROLL_SPEED_FACTOR.powf(scroll_delta))
+            .clamp(FreecamController::MIN_SPEED, FreecamController::MAX_SPEED);
+    }
+
+    fn update_position(&mut self, direction: Vec3, speed: f32, delta_time: f32) {
         let horizontal_movement = normalize_if_not_zero(direction * Vec3::new(1.0, 0.0, 1.0));
         let vertical_movement = Camera::up() * direction.y;
         let horizontal_movement = self.get_yaw_rotation() * horizontal_movement;
 
-        self.position += horizontal_movement * self.speed * delta_time;
-        self.position += vertical_movement * self.speed * delta_time;
+        self.position += horizontal_movement * speed * delta_time;
+        self.position += vertical_movement * speed * delta_time;
     }
 
     fn get_yaw_rotation(&self) -> Rotor3 {
@@ -70,26 +101,26 @@ impl CameraController for FreecamController {
     }
 }
 
-fn input_to_direction(input: &InputMap) -> Vec3 {
+fn input_to_direction(input: &InputMap, key_bindings: &KeyBindings) -> Vec3 {
     let mut direction = Vec3::zero();
-    if input.is_pressed(VirtualKeyCode::W) {
+    if input.is_pressed(key_bindings.forward) {
         direction += Camera::forward();
     }
-    if input.is_pressed(VirtualKeyCode::S) {
+    if input.is_pressed(key_bindings.backward) {
         direction -= Camera::forward();
     }
 
-    if input.is_pressed(VirtualKeyCode::D) {
+    if input.is_pressed(key_bindings.right) {
         direction += Camera::right();
     }
-    if input.is_pressed(VirtualKeyCode::A) {
+    if input.is_pressed(key_bindings.left) {
         direction -= Camera::right();
     }
 
-    if input.is_pressed(VirtualKeyCode::Space) {
+    if input.is_pressed(key_bindings.up) {
         direction += Camera::up();
     }
-    if input.is_pressed(VirtualKeyCode::LShift) {
+    if input.is_pressed(key_bindings.down) {
         direction -= Camera::up();
     }
     direction
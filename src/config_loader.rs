@@ -2,7 +2,11 @@ use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 use ultraviolet::Vec3;
+use winit::event::VirtualKeyCode;
 
+use crate::render::shader_types::MAX_SHADOW_CASCADES;
+use crate::render::{BackgroundMode, DebugView};
+use crate::scene::SamplerOverrides;
 use crate::vulkan::window_settings::PresentMode;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -12,6 +16,22 @@ pub struct Config {
     pub is_demo_mode: bool,
     pub cached: CachedData,
     pub brightness: f32,
+    #[serde(default)]
+    pub shadow_map: ShadowMapSettings,
+    /// Vertex cache and vertex fetch optimization when loading the scene
+    #[serde(default = "default_optimize_meshes")]
+    pub optimize_meshes: bool,
+    /// Smaller BLASes, at the cost of a second submit when loading the scene
+    #[serde(default = "default_compact_acceleration_structures")]
+    pub compact_acceleration_structures: bool,
+    /// Lower memory usage, but the images are decoded while uploading the scene
+    #[serde(default)]
+    pub keep_images_compressed: bool,
+    /// Shows the models while the rest of the scene is still loading
+    #[serde(default = "default_stream_scene_loading")]
+    pub stream_scene_loading: bool,
+    #[serde(default)]
+    pub settings: Settings,
 }
 
 impl Default for Config {
@@ -22,13 +42,179 @@ impl Default for Config {
             is_demo_mode: true,
             cached: CachedData::default(),
             brightness: 1.0,
+            shadow_map: ShadowMapSettings::default(),
+            optimize_meshes: true,
+            compact_acceleration_structures: true,
+            keep_images_compressed: false,
+            stream_scene_loading: true,
+            settings: Settings::default(),
         }
     }
 }
 
+fn default_optimize_meshes() -> bool {
+    true
+}
+
+fn default_compact_acceleration_structures() -> bool {
+    true
+}
+
+fn default_stream_scene_loading() -> bool {
+    true
+}
+
 impl Config {
-    pub fn from_str(value: &str) -> Self {
-        serde_json::from_str(value).unwrap()
+    pub fn from_str(value: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(value)
+    }
+}
+
+/// Changed in the UI, and saved when the app exits
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub debug_view: DebugView,
+    pub background_mode: BackgroundMode,
+    pub background_color: Vec3,
+    pub is_taa_enabled: bool,
+    pub camera_speed: f32,
+    pub camera_sensitivity: f32,
+    pub camera_sprint_multiplier: f32,
+    pub key_bindings: KeyBindings,
+    pub ambient_color: Vec3,
+    pub ambient_intensity: f32,
+    pub sampler_overrides: SamplerOverrides,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            debug_view: DebugView::Shaded,
+            background_mode: BackgroundMode::Color,
+            background_color: Vec3::zero(),
+            is_taa_enabled: true,
+            camera_speed: 5.0,
+            camera_sensitivity: 0.01,
+            camera_sprint_multiplier: 3.0,
+            key_bindings: KeyBindings::default(),
+            ambient_color: Vec3::new(1.0, 1.0, 1.0),
+            ambient_intensity: 0.03,
+            sampler_overrides: SamplerOverrides::default(),
+        }
+    }
+}
+
+/// Keys for moving the free camera
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub forward: VirtualKeyCode,
+    pub backward: VirtualKeyCode,
+    pub left: VirtualKeyCode,
+    pub right: VirtualKeyCode,
+    pub up: VirtualKeyCode,
+    pub down: VirtualKeyCode,
+    pub sprint: VirtualKeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            forward: VirtualKeyCode::W,
+            backward: VirtualKeyCode::S,
+            left: VirtualKeyCode::A,
+            right: VirtualKeyCode::D,
+            up: VirtualKeyCode::Space,
+            down: VirtualKeyCode::LControl,
+            sprint: VirtualKeyCode::LShift,
+        }
+    }
+}
+
+/// Used for the cascaded shadow map when raytracing is not supported
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShadowMapSettings {
+    /// View space distance where each cascade ends, at most 4 cascades
+    pub cascade_splits: Vec<f32>,
+    /// Width and height of a single cascade
+    pub resolution: u32,
+}
+
+impl ShadowMapSettings {
+    /// Drops the cascades that the shadow map pass can't render
+    pub fn clamp_cascades(&mut self) {
+        if self.cascade_splits.len() > MAX_SHADOW_CASCADES {
+            log::warn!(
+                "Only {} of the {} configured shadow cascades are used",
+                MAX_SHADOW_CASCADES,
+                self.cascade_splits.len()
+            );
+            self.cascade_splits.truncate(MAX_SHADOW_CASCADES);
+        }
+    }
+}
+
+impl Default for ShadowMapSettings {
+    fn default() -> Self {
+        Self {
+            cascade_splits: vec![8.0, 20.0, 50.0, 100.0],
+            resolution: 2048,
+        }
+    }
+}
+
+/// Overrides for a single run, they are not saved to the config file
+#[derive(Debug, Default)]
+pub struct CommandLineArgs {
+    /// `--scene <path>`
+    pub scene_path: Option<String>,
+    /// `--exit-after-frames <n>`, for test runs under the validation layers
+    pub exit_after_frames: Option<u32>,
+    /// `--switch-scene <path>`, halfway through `--exit-after-frames`, to test the teardown of a scene
+    pub switch_scene_path: Option<String>,
+    /// `--reload-scene-count <n>`, switches scenes n times and exits, to check that reloading doesn't leak
+    pub reload_scene_count: Option<u32>,
+    /// `--dump-scene <path>`, writes a summary of the loaded scene to a JSON file and exits
+    pub dump_scene_path: Option<String>,
+}
+
+impl CommandLineArgs {
+    pub fn parse() -> Self {
+        let mut command_line_args = Self::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scene" => {
+                    command_line_args.scene_path =
+                        Some(args.next().expect("Expected a path after --scene"))
+                }
+                "--dump-scene" => {
+                    command_line_args.dump_scene_path =
+                        Some(args.next().expect("Expected a path after --dump-scene"))
+                }
+                "--switch-scene" => {
+                    command_line_args.switch_scene_path =
+                        Some(args.next().expect("Expected a path after --switch-scene"))
+                }
+                "--reload-scene-count" => {
+                    command_line_args.reload_scene_count = Some(
+                        args.next()
+                            .and_then(|count| count.parse().ok())
+                            .expect("Expected a number of reloads after --reload-scene-count"),
+                    )
+                }
+                "--exit-after-frames" => {
+                    command_line_args.exit_after_frames = Some(
+                        args.next()
+                            .and_then(|frames| frames.parse().ok())
+                            .expect("Expected a number of frames after --exit-after-frames"),
+                    )
+                }
+                _ => log::warn!("Ignoring unknown argument {}", arg),
+            }
+        }
+        command_line_args
     }
 }
 
@@ -46,8 +232,15 @@ impl ConfigFileLoader {
     }
 
     pub fn load_config(&mut self) -> &mut Config {
-        let config = match std::fs::read_to_string(&self.path) {
-            Ok(content) => Config::from_str(&content),
+        let mut config = match std::fs::read_to_string(&self.path) {
+            Ok(content) => Config::from_str(&content).unwrap_or_else(|error| {
+                log::warn!(
+                    "Could not parse {}, using the default config: {}",
+                    self.path.display(),
+                    error
+                );
+                Config::default()
+            }),
             Err(_) => {
                 let config = Config::default();
                 self.config = Some(config.clone());
@@ -55,6 +248,7 @@ impl ConfigFileLoader {
                 config
             }
         };
+        config.shadow_map.clamp_cascades();
         self.config = Some(config);
         self.config.as_mut().unwrap()
     }
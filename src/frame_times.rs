@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The frame times of the last few frames, to make stutter visible
+pub struct FrameTimes {
+    /// In milliseconds, the oldest frame comes first
+    history: VecDeque<f32>,
+}
+
+pub struct FrameTimeStats {
+    pub min: f32,
+    pub average: f32,
+    pub max: f32,
+    /// Average of the slowest 1% of the frames
+    pub one_percent_low: f32,
+}
+
+impl FrameTimes {
+    pub const HISTORY_LENGTH: usize = 240;
+    const GRAPH_HEIGHT: f32 = 60.0;
+
+    pub fn new() -> Self {
+        Self {
+            history: VecDeque::with_capacity(FrameTimes::HISTORY_LENGTH),
+        }
+    }
+
+    pub fn push(&mut self, frame_time: Duration) {
+        if self.history.len() == FrameTimes::HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame_time.as_secs_f32() * 1000.0);
+    }
+
+    /// None before the first frame
+    pub fn stats(&self) -> Option<FrameTimeStats> {
+        if self.history.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<f32> = self.history.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        let slowest_count = sorted.len().div_ceil(100);
+        let slowest = &sorted[sorted.len() - slowest_count..];
+
+        Some(FrameTimeStats {
+            min: sorted[0],
+            average: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            max: sorted[sorted.len() - 1],
+            one_percent_low: slowest.iter().sum::<f32>() / slowest.len() as f32,
+        })
+    }
+
+    pub fn render_ui(&self, ui: &mut egui::Ui) {
+        let Some(stats) = self.stats() else {
+            return;
+        };
+
+        ui.label(format!(
+            "Frametime: min {:.2}ms, avg {:.2}ms, max {:.2}ms",
+            stats.min, stats.average, stats.max
+        ));
+        ui.label(format!(
+            "FPS: avg {:.0}, 1% low {:.0}",
+            1000.0 / stats.average,
+            1000.0 / stats.one_percent_low
+        ));
+
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), FrameTimes::GRAPH_HEIGHT),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        // the slowest frame fills the whole height
+        let bar_width = rect.width() / FrameTimes::HISTORY_LENGTH as f32;
+        let color = ui.visuals().widgets.active.bg_fill;
+        for (index, frame_time) in self.history.iter().enumerate() {
+            let height = frame_time / stats.max * rect.height();
+            let left = rect.left() + index as f32 * bar_width;
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(left, rect.bottom() - height),
+                    egui::pos2(left + bar_width, rect.bottom()),
+                ),
+                0.0,
+                color,
+            );
+        }
+
+        // a line at the average frame time
+        let average_y = rect.bottom() - stats.average / stats.max * rect.height();
+        painter.hline(
+            rect.x_range(),
+            average_y,
+            egui::Stroke::new(1.0, ui.visuals().warn_fg_color),
+        );
+    }
+}
@@ -8,6 +8,8 @@ pub struct InputMap {
     state: [bool; NUM_KEYS],
     mouse_state: [bool; NUM_MOUSE_BUTTONS],
     mouse_delta: Vec2,
+    /// In lines, positive when scrolling up
+    scroll_delta: f32,
     /// Where the mouse was when we started capturing it
     captured_mouse_position: Option<Vec2>,
 }
@@ -18,6 +20,7 @@ impl InputMap {
             state: [false; NUM_KEYS],
             mouse_state: [false; NUM_MOUSE_BUTTONS],
             mouse_delta: Vec2::zero(),
+            scroll_delta: 0.0,
             captured_mouse_position: None,
         }
     }
@@ -46,14 +49,20 @@ impl InputMap {
         }
     }
 
+    /// Also clears the scroll delta
     pub fn clear_mouse_delta(&mut self) {
         self.mouse_delta = Vec2::zero();
+        self.scroll_delta = 0.0;
     }
 
     pub fn accumulate_mouse_delta(&mut self, delta: Vec2) {
         self.mouse_delta += delta;
     }
 
+    pub fn accumulate_scroll_delta(&mut self, delta: f32) {
+        self.scroll_delta += delta;
+    }
+
     pub fn start_capturing_mouse(&mut self, position: Vec2) {
         self.captured_mouse_position = Some(position);
     }
@@ -70,6 +79,10 @@ impl InputMap {
         self.mouse_delta
     }
 
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll_delta
+    }
+
     pub fn is_pressed(&self, key: VirtualKeyCode) -> bool {
         self.state[key as usize]
     }
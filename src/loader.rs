@@ -1,19 +1,35 @@
 mod animation;
 mod asset;
+mod light;
 mod material;
+mod material_overrides;
 mod mesh;
+mod mesh_optimizer;
+mod mesh_simplifier;
 mod model;
+mod primitive_scene;
 mod scene;
+mod scene_dump;
+mod scene_graph;
 mod scene_loader;
+mod skin;
 mod texture;
 
 pub use animation::*;
 pub use asset::*;
+pub use light::*;
 pub use material::*;
+pub use material_overrides::*;
 pub use mesh::*;
 pub use model::*;
+pub use primitive_scene::*;
 pub use scene::*;
+pub use scene_dump::*;
+pub use scene_graph::*;
+pub use skin::*;
 pub use texture::*;
+
+use std::path::{Path, PathBuf};
 use ultraviolet::{Rotor3, Vec3};
 
 use crate::transform::Transform;
@@ -24,6 +40,10 @@ pub struct AssetLoader {
     pub images: Assets<LoadedImage>,
     pub samplers: Assets<LoadedSampler>,
     pub id_generator: AssetIdGenerator,
+    /// Reorders the indices and vertices of each mesh for the post-transform cache
+    pub optimize_meshes: bool,
+    /// Keeps PNG and JPEG images compressed until they are uploaded
+    pub keep_images_compressed: bool,
 }
 
 impl AssetLoader {
@@ -34,8 +54,35 @@ impl AssetLoader {
             images: Assets::new(),
             samplers: Assets::new(),
             id_generator: AssetIdGenerator::new(),
+            optimize_meshes: true,
+            keep_images_compressed: false,
+        }
+    }
+}
+
+/// All .glb and .gltf files in the directory and its subdirectories, sorted by path
+pub fn find_scene_files(directory: impl AsRef<Path>) -> Vec<PathBuf> {
+    let mut scene_files = vec![];
+    let mut directories = vec![directory.as_ref().to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else if matches!(
+                path.extension().and_then(|extension| extension.to_str()),
+                Some("glb") | Some("gltf")
+            ) {
+                scene_files.push(path);
+            }
         }
     }
+    scene_files.sort();
+    scene_files
 }
 
 impl From<gltf::scene::Transform> for Transform {
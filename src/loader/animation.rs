@@ -59,6 +59,99 @@ impl Animation {
     }
 }
 
+/// Animates the morph target weights of the meshes of one node
+pub struct MorphWeightsAnimation {
+    /// Node of the scene graph
+    pub node: usize,
+    pub timestamps: Vec<f32>,
+    /// The weights of all morph targets, for each timestamp
+    pub weights: Vec<Vec<f32>>,
+}
+
+impl MorphWeightsAnimation {
+    pub fn duration(&self) -> f32 {
+        self.timestamps.last().copied().unwrap_or_default()
+    }
+
+    /// Loops the animation, and interpolates linearly between the keyframes
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let duration = self.duration();
+        let time = if duration > 0.0 { time % duration } else { 0.0 };
+
+        let next = self
+            .timestamps
+            .partition_point(|&timestamp| timestamp <= time);
+        if next == 0 {
+            return self.weights[0].clone();
+        }
+        if next == self.timestamps.len() {
+            return self.weights[next - 1].clone();
+        }
+
+        let previous = next - 1;
+        let t = (time - self.timestamps[previous])
+            / (self.timestamps[next] - self.timestamps[previous]).max(0.0001);
+        self.weights[previous]
+            .iter()
+            .zip(self.weights[next].iter())
+            .map(|(a, b)| a + (b - a) * t)
+            .collect()
+    }
+}
+
+/// The property of a node that an animation channel changes, with one value per timestamp
+pub enum NodeAnimationValues {
+    Translations(Vec<Vec3>),
+    Rotations(Vec<Rotor3>),
+    Scales(Vec<Vec3>),
+}
+
+/// Animates the local transform of one node, for example a joint of a skin
+pub struct NodeAnimation {
+    /// Node of the scene graph
+    pub node: usize,
+    pub timestamps: Vec<f32>,
+    pub values: NodeAnimationValues,
+}
+
+impl NodeAnimation {
+    pub fn duration(&self) -> f32 {
+        self.timestamps.last().copied().unwrap_or_default()
+    }
+
+    /// Loops the animation, and interpolates linearly between the keyframes
+    pub fn apply(&self, time: f32, transform: &mut Transform) {
+        let duration = self.duration();
+        let time = if duration > 0.0 { time % duration } else { 0.0 };
+
+        let next = self
+            .timestamps
+            .partition_point(|&timestamp| timestamp <= time);
+        let (previous, next, t) = if next == 0 {
+            (0, 0, 0.0)
+        } else if next == self.timestamps.len() {
+            (next - 1, next - 1, 0.0)
+        } else {
+            let previous = next - 1;
+            let t = (time - self.timestamps[previous])
+                / (self.timestamps[next] - self.timestamps[previous]).max(0.0001);
+            (previous, next, t)
+        };
+
+        match &self.values {
+            NodeAnimationValues::Translations(translations) => {
+                transform.position = translations[previous].lerp(translations[next], t);
+            }
+            NodeAnimationValues::Rotations(rotations) => {
+                transform.orientation = rotations[previous].lerp(rotations[next], t).normalized();
+            }
+            NodeAnimationValues::Scales(scales) => {
+                transform.scale = scales[previous].lerp(scales[next], t);
+            }
+        }
+    }
+}
+
 fn get_and_next<T: Copy>(values: &Vec<T>, index: usize, make_default: fn() -> T) -> (T, T) {
     let value = values.get(index).copied().unwrap_or_else(make_default);
     let next_value = values
@@ -70,3 +163,49 @@ fn get_and_next<T: Copy>(values: &Vec<T>, index: usize, make_default: fn() -> T)
 }
 #[derive(Default, Copy, Clone)]
 pub struct AnimationKeyframe(usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_animation_interpolates_and_loops() {
+        let animation = NodeAnimation {
+            node: 0,
+            timestamps: vec![0.0, 1.0, 2.0],
+            values: NodeAnimationValues::Translations(vec![
+                Vec3::zero(),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(2.0, 4.0, 0.0),
+            ]),
+        };
+        let mut transform = Transform {
+            scale: Vec3::new(3.0, 3.0, 3.0),
+            ..Default::default()
+        };
+
+        animation.apply(0.5, &mut transform);
+        assert_eq!(transform.position, Vec3::new(1.0, 0.0, 0.0));
+        animation.apply(1.5, &mut transform);
+        assert_eq!(transform.position, Vec3::new(2.0, 2.0, 0.0));
+        animation.apply(2.5, &mut transform);
+        assert_eq!(transform.position, Vec3::new(1.0, 0.0, 0.0));
+        // only the animated property changes
+        assert_eq!(transform.scale, Vec3::new(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    fn node_animation_rotations_stay_normalized() {
+        let animation = NodeAnimation {
+            node: 0,
+            timestamps: vec![0.0, 1.0],
+            values: NodeAnimationValues::Rotations(vec![
+                Rotor3::identity(),
+                Rotor3::from_rotation_xz(std::f32::consts::PI * 0.9),
+            ]),
+        };
+        let mut transform = Transform::default();
+        animation.apply(0.5, &mut transform);
+        assert!((transform.orientation.mag() - 1.0).abs() < 1e-5);
+    }
+}
@@ -3,11 +3,13 @@ use std::{
     sync::{atomic::AtomicU32, Arc},
 };
 
+use serde::Serialize;
+
 pub trait Asset {
     fn id(&self) -> AssetId;
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Ord, PartialOrd, Serialize)]
 pub struct AssetId(u32);
 impl AssetId {
     pub fn new(id: u32) -> Self {
@@ -0,0 +1,45 @@
+use ultraviolet::Vec3;
+
+use crate::transform::Transform;
+
+/// A KHR_lights_punctual light. Only shown as a gizmo, the lighting only has the sun.
+#[derive(Debug, Clone)]
+pub struct Light {
+    /// Index of the node in the scene graph
+    pub node: usize,
+    /// World transform of the node, the light points along its negative z axis
+    pub transform: Transform,
+    pub kind: LightKind,
+    pub color: Vec3,
+    /// Lux for directional lights, candela for the others
+    pub intensity: f32,
+    /// None means that the light reaches infinitely far
+    pub range: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    /// Angles in radians, from the center of the cone
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+impl LightKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LightKind::Directional => "Directional",
+            LightKind::Point => "Point",
+            LightKind::Spot { .. } => "Spot",
+        }
+    }
+}
+
+impl Light {
+    pub fn direction(&self) -> Vec3 {
+        self.transform.orientation * Vec3::new(0.0, 0.0, -1.0)
+    }
+}
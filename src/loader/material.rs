@@ -4,6 +4,8 @@ use super::{texture::LoadedTexture, Asset, AssetId};
 
 pub struct LoadedMaterial {
     pub id: AssetId,
+    /// Index of the material in the glTF file, None for the missing material
+    pub source_index: Option<usize>,
     pub base_color: Vec3,
     pub base_color_texture: Option<LoadedTexture>,
     pub normal_texture: Option<LoadedTexture>,
@@ -11,12 +13,29 @@ pub struct LoadedMaterial {
     pub metallic_factor: f32,
     pub metallic_roughness_texture: Option<LoadedTexture>,
     pub emissivity: Vec3,
+    /// Back faces are visible and must not be culled
+    pub double_sided: bool,
+    /// How much light goes through the surface, from KHR_materials_transmission
+    pub transmission_factor: f32,
+    /// Index of refraction, from KHR_materials_ior
+    pub ior: f32,
+    /// Strength of the clearcoat layer, from KHR_materials_clearcoat
+    pub clearcoat_factor: f32,
+    /// Read from the red channel
+    pub clearcoat_texture: Option<LoadedTexture>,
+    pub clearcoat_roughness_factor: f32,
+    /// Read from the green channel
+    pub clearcoat_roughness_texture: Option<LoadedTexture>,
 }
 
 impl LoadedMaterial {
+    /// Used by glTF when KHR_materials_ior is missing
+    pub const DEFAULT_IOR: f32 = 1.5;
+
     pub fn missing_material(id: AssetId) -> Self {
         Self {
             id,
+            source_index: None,
             base_color: Vec3::new(0.8, 0.8, 0.0),
             base_color_texture: None,
             normal_texture: None,
@@ -24,6 +43,13 @@ impl LoadedMaterial {
             roughness_factor: 0.0,
             metallic_factor: 0.0,
             emissivity: Vec3::zero(),
+            double_sided: false,
+            transmission_factor: 0.0,
+            ior: LoadedMaterial::DEFAULT_IOR,
+            clearcoat_factor: 0.0,
+            clearcoat_texture: None,
+            clearcoat_roughness_factor: 0.0,
+            clearcoat_roughness_texture: None,
         }
     }
 }
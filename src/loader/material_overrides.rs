@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use super::LoadedMaterial;
+
+/// Material factors that were edited in the viewer, keyed by the index of the material in the glTF file.
+/// They are saved next to the scene, and applied on top of the glTF values when it gets loaded.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct MaterialOverrides {
+    pub materials: BTreeMap<usize, MaterialOverride>,
+}
+
+/// Only the edited factors are set
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MaterialOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_color: Option<Vec3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emissivity: Option<Vec3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roughness: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metallic: Option<f32>,
+}
+
+impl MaterialOverrides {
+    /// `scene.glb` gets `scene.materials.json`
+    pub fn path_for_scene(scene_path: &Path) -> PathBuf {
+        scene_path.with_extension("materials.json")
+    }
+
+    /// Empty when the scene has no overrides, or when they can't be read
+    pub fn load(scene_path: &Path) -> Self {
+        let path = MaterialOverrides::path_for_scene(scene_path);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|error| {
+            log::warn!(
+                "Could not parse {}, ignoring the material overrides: {}",
+                path.display(),
+                error
+            );
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, scene_path: &Path) -> std::io::Result<()> {
+        let path = MaterialOverrides::path_for_scene(scene_path);
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)
+    }
+
+    /// Keeps the factors of earlier overrides that were not edited again
+    pub fn merge(&mut self, index: usize, material_override: MaterialOverride) {
+        let existing = self.materials.entry(index).or_default();
+        existing.base_color = material_override.base_color.or(existing.base_color);
+        existing.emissivity = material_override.emissivity.or(existing.emissivity);
+        existing.roughness = material_override.roughness.or(existing.roughness);
+        existing.metallic = material_override.metallic.or(existing.metallic);
+    }
+
+    pub fn apply(&self, index: usize, material: &mut LoadedMaterial) {
+        let Some(material_override) = self.materials.get(&index) else {
+            return;
+        };
+        if let Some(base_color) = material_override.base_color {
+            material.base_color = base_color;
+        }
+        if let Some(emissivity) = material_override.emissivity {
+            material.emissivity = emissivity;
+        }
+        if let Some(roughness) = material_override.roughness {
+            material.roughness_factor = roughness;
+        }
+        if let Some(metallic) = material_override.metallic {
+            material.metallic_factor = metallic;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::AssetIdGenerator;
+
+    #[test]
+    fn save_load_and_apply() {
+        let directory = std::env::temp_dir().join(format!(
+            "round-cat-material-overrides-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).expect("Could not create the test directory");
+        let scene_path = directory.join("scene.glb");
+
+        let mut material_overrides = MaterialOverrides::default();
+        material_overrides.merge(
+            3,
+            MaterialOverride {
+                base_color: Some(Vec3::new(0.25, 0.5, 1.0)),
+                roughness: Some(0.125),
+                ..Default::default()
+            },
+        );
+        material_overrides.merge(
+            3,
+            MaterialOverride {
+                metallic: Some(1.0),
+                ..Default::default()
+            },
+        );
+        material_overrides
+            .save(&scene_path)
+            .expect("Could not save the material overrides");
+
+        let loaded = MaterialOverrides::load(&scene_path);
+        std::fs::remove_dir_all(&directory).expect("Could not remove the test directory");
+        assert_eq!(loaded.materials, material_overrides.materials);
+
+        let id_generator = AssetIdGenerator::new();
+        let mut material = LoadedMaterial::missing_material(id_generator.next());
+        let emissivity = material.emissivity;
+        loaded.apply(3, &mut material);
+        assert_eq!(material.base_color, Vec3::new(0.25, 0.5, 1.0));
+        assert_eq!(material.roughness_factor, 0.125);
+        assert_eq!(material.metallic_factor, 1.0);
+        assert_eq!(material.emissivity, emissivity);
+
+        let mut other_material = LoadedMaterial::missing_material(id_generator.next());
+        let base_color = other_material.base_color;
+        loaded.apply(2, &mut other_material);
+        assert_eq!(other_material.base_color, base_color);
+    }
+}
@@ -1,13 +1,26 @@
 use ultraviolet::{Vec2, Vec3};
 
-use crate::scene::Vertex;
+use crate::scene::{Aabb, SkinVertex, Vertex};
 
 use super::{Asset, AssetId};
 
 pub struct LoadedMesh {
     pub id: AssetId,
     pub vertices: Vec<Vertex>,
+    /// Only exists for skinned meshes, with one entry per vertex
+    pub skin_vertices: Option<Vec<SkinVertex>>,
+    pub morph_targets: Vec<MorphTarget>,
     pub indices: Vec<u32>,
+    /// Simplified versions of the indices, from the most detailed to the coarsest.
+    /// They use the same vertices as the full resolution mesh.
+    pub lods: Vec<Vec<u32>>,
+    pub bounds: Aabb,
+}
+
+/// Offsets that get added to the vertices, scaled by the weight of the target
+pub struct MorphTarget {
+    pub position_deltas: Vec<[f32; 3]>,
+    pub normal_deltas: Vec<[f32; 3]>,
 }
 
 impl Asset for LoadedMesh {
@@ -80,8 +93,9 @@ impl LoadedMesh {
             .iter()
             .flat_map(|face| {
                 // this uses the face's bottom two vertices to calculate the face tangent
-                let face_tangent =
-                    positions[face.position_indices[2]] - positions[face.position_indices[3]].normalized();
+                let face_tangent = (positions[face.position_indices[2]]
+                    - positions[face.position_indices[3]])
+                    .normalized();
 
                 face.position_indices
                     .iter()
@@ -111,8 +125,68 @@ impl LoadedMesh {
 
         LoadedMesh {
             id,
+            bounds: Aabb::from_vertices(&vertices),
+            vertices,
+            skin_vertices: None,
+            morph_targets: vec![],
+            indices,
+            lods: vec![],
+        }
+    }
+
+    /// With a diameter of 1, and twice as many segments around the equator as rings
+    pub fn new_uv_sphere(id: AssetId, rings: u32) -> LoadedMesh {
+        let rings = rings.max(2);
+        let segments = rings * 2;
+
+        let mut vertices = vec![];
+        for ring in 0..=rings {
+            // from the north pole to the south pole
+            let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+            for segment in 0..=segments {
+                // the first and the last segment overlap, so that the uvs wrap around
+                let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+                let normal = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let tangent = Vec3::new(-phi.sin(), 0.0, phi.cos());
+                vertices.push(Vertex {
+                    position: (normal * 0.5).into(),
+                    normal: normal.into(),
+                    uv: [segment as f32 / segments as f32, ring as f32 / rings as f32],
+                    tangent: tangent.into_homogeneous_point().into(),
+                });
+            }
+        }
+
+        let mut indices = vec![];
+        let row_length = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let top_left = ring * row_length + segment;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + row_length;
+                let bottom_right = bottom_left + 1;
+                // the triangles that touch a pole would be degenerate
+                if ring != 0 {
+                    indices.extend_from_slice(&[top_left, top_right, bottom_right]);
+                }
+                if ring != rings - 1 {
+                    indices.extend_from_slice(&[top_left, bottom_right, bottom_left]);
+                }
+            }
+        }
+
+        LoadedMesh {
+            id,
+            bounds: Aabb::from_vertices(&vertices),
             vertices,
+            skin_vertices: None,
+            morph_targets: vec![],
             indices,
+            lods: vec![],
         }
     }
 }
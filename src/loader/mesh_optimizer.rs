@@ -0,0 +1,286 @@
+//! Reorders meshes for the GPU, without changing what gets rendered.
+//! The vertex cache optimization is Tom Forsyth's "Linear-Speed Vertex Cache Optimisation",
+//! see https://tomforsyth1000.github.io/papers/fast_vert_cache_opt.html
+
+/// Size of the simulated cache that the optimization targets
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Cache size used for measuring the ACMR, close to what actual hardware has
+const MEASURE_CACHE_SIZE: usize = 16;
+
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: u32) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        // the vertices of the last triangle get a fixed score, so that the next triangle doesn't depend on their order
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scaler = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scaler).powf(CACHE_DECAY_POWER)
+        }
+    };
+
+    // vertices with few triangles left should be finished off quickly
+    let valence_boost =
+        VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER);
+
+    cache_score + valence_boost
+}
+
+/// Reorders the triangles, so that the vertices are reused while they are still in the post-transform cache
+pub fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    // triangles of each vertex, stored as offsets into one list
+    let mut remaining_triangles = vec![0u32; vertex_count];
+    for &index in indices.iter() {
+        remaining_triangles[index as usize] += 1;
+    }
+    let mut vertex_triangle_offsets = Vec::with_capacity(vertex_count + 1);
+    vertex_triangle_offsets.push(0);
+    for &count in remaining_triangles.iter() {
+        vertex_triangle_offsets.push(vertex_triangle_offsets.last().unwrap() + count as usize);
+    }
+    let mut vertex_triangles = vec![0usize; indices.len()];
+    let mut fill = vertex_triangle_offsets.clone();
+    for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+        for &vertex in vertices {
+            vertex_triangles[fill[vertex as usize]] = triangle;
+            fill[vertex as usize] += 1;
+        }
+    }
+
+    let mut cache_positions: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = remaining_triangles
+        .iter()
+        .map(|&remaining| vertex_score(None, remaining))
+        .collect();
+    let triangle_score = |vertex_scores: &[f32], triangle: usize| -> f32 {
+        indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|&vertex| vertex_scores[vertex as usize])
+            .sum()
+    };
+    let mut triangle_scores: Vec<f32> = (0..triangle_count)
+        .map(|triangle| triangle_score(&vertex_scores, triangle))
+        .collect();
+    let mut triangle_added = vec![false; triangle_count];
+
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut output: Vec<u32> = Vec::with_capacity(indices.len());
+    let mut best_triangle = None;
+    // every triangle before it has been added, so the fallback stays linear over the whole mesh
+    let mut next_unadded_triangle = 0;
+
+    for _ in 0..triangle_count {
+        // Fall back to the next triangle in the input order when no triangle in the cache is left
+        let triangle = best_triangle.unwrap_or_else(|| {
+            while triangle_added[next_unadded_triangle] {
+                next_unadded_triangle += 1;
+            }
+            next_unadded_triangle
+        });
+
+        triangle_added[triangle] = true;
+        let triangle_vertices = [
+            indices[triangle * 3],
+            indices[triangle * 3 + 1],
+            indices[triangle * 3 + 2],
+        ];
+        output.extend_from_slice(&triangle_vertices);
+
+        for &vertex in triangle_vertices.iter() {
+            remaining_triangles[vertex as usize] -= 1;
+        }
+
+        // the vertices of the triangle move to the front of the cache
+        let mut new_cache: Vec<u32> = triangle_vertices.to_vec();
+        new_cache.extend(
+            cache
+                .iter()
+                .copied()
+                .filter(|vertex| !triangle_vertices.contains(vertex)),
+        );
+        let evicted: Vec<u32> = new_cache.split_off(CACHE_SIZE.min(new_cache.len()));
+        cache = new_cache;
+
+        for &vertex in evicted.iter() {
+            cache_positions[vertex as usize] = None;
+        }
+        for (position, &vertex) in cache.iter().enumerate() {
+            cache_positions[vertex as usize] = Some(position);
+        }
+
+        best_triangle = None;
+        let mut best_score = f32::MIN;
+        for &vertex in cache.iter().chain(evicted.iter()) {
+            let vertex = vertex as usize;
+            vertex_scores[vertex] =
+                vertex_score(cache_positions[vertex], remaining_triangles[vertex]);
+
+            for &adjacent in &vertex_triangles
+                [vertex_triangle_offsets[vertex]..vertex_triangle_offsets[vertex + 1]]
+            {
+                if triangle_added[adjacent] {
+                    continue;
+                }
+                triangle_scores[adjacent] = triangle_score(&vertex_scores, adjacent);
+                if triangle_scores[adjacent] > best_score {
+                    best_score = triangle_scores[adjacent];
+                    best_triangle = Some(adjacent);
+                }
+            }
+        }
+    }
+
+    indices[..output.len()].copy_from_slice(&output);
+}
+
+/// Reorders the vertices in the order that they are first used, which makes the vertex fetches more linear.
+/// Unused vertices are kept at the end.
+pub fn optimize_vertex_fetch<V: Copy>(vertices: &mut Vec<V>, indices: &mut [u32]) {
+    let mut remap: Vec<Option<u32>> = vec![None; vertices.len()];
+    let mut new_vertices = Vec::with_capacity(vertices.len());
+
+    for index in indices.iter_mut() {
+        let new_index = *remap[*index as usize].get_or_insert_with(|| {
+            new_vertices.push(vertices[*index as usize]);
+            new_vertices.len() as u32 - 1
+        });
+        *index = new_index;
+    }
+
+    for (old_index, new_index) in remap.iter().enumerate() {
+        if new_index.is_none() {
+            new_vertices.push(vertices[old_index]);
+        }
+    }
+
+    *vertices = new_vertices;
+}
+
+/// Average cache miss ratio, the number of transformed vertices per triangle with a FIFO cache.
+/// 3.0 is the worst case, and 0.5 is about the best that is possible.
+pub fn average_cache_miss_ratio(indices: &[u32]) -> f32 {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return 0.0;
+    }
+
+    let mut cache: std::collections::VecDeque<u32> =
+        std::collections::VecDeque::with_capacity(MEASURE_CACHE_SIZE);
+    let mut misses = 0;
+    for &index in indices {
+        if !cache.contains(&index) {
+            misses += 1;
+            if cache.len() == MEASURE_CACHE_SIZE {
+                cache.pop_front();
+            }
+            cache.push_back(index);
+        }
+    }
+
+    misses as f32 / triangle_count as f32
+}
+
+/// Sums up the ACMR of all optimized meshes, weighted by their triangle count
+#[derive(Default)]
+pub struct MeshOptimizationStats {
+    triangle_count: usize,
+    misses_before: f32,
+    misses_after: f32,
+}
+
+impl MeshOptimizationStats {
+    pub fn add(&mut self, triangle_count: usize, acmr_before: f32, acmr_after: f32) {
+        self.triangle_count += triangle_count;
+        self.misses_before += acmr_before * triangle_count as f32;
+        self.misses_after += acmr_after * triangle_count as f32;
+    }
+}
+
+impl std::fmt::Display for MeshOptimizationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let triangle_count = self.triangle_count.max(1) as f32;
+        write!(
+            f,
+            "ACMR {:.3} -> {:.3} over {} triangles",
+            self.misses_before / triangle_count,
+            self.misses_after / triangle_count,
+            self.triangle_count
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two triangles per quad, with the quads in row order
+    fn grid_indices(size: u32) -> Vec<u32> {
+        let mut indices = vec![];
+        for y in 0..size {
+            for x in 0..size {
+                let corner = y * (size + 1) + x;
+                let next_row = corner + size + 1;
+                indices.extend_from_slice(&[corner, next_row, corner + 1]);
+                indices.extend_from_slice(&[corner + 1, next_row, next_row + 1]);
+            }
+        }
+        indices
+    }
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn vertex_cache_keeps_the_triangles() {
+        let size = 40;
+        let grid = grid_indices(size);
+        // shuffled, so that there is something to optimize
+        let triangle_count = grid.len() / 3;
+        let mut indices: Vec<u32> = (0..triangle_count)
+            .flat_map(|triangle| {
+                let shuffled = (triangle * 7919) % triangle_count;
+                grid[shuffled * 3..shuffled * 3 + 3].to_vec()
+            })
+            .collect();
+        let acmr_before = average_cache_miss_ratio(&indices);
+
+        optimize_vertex_cache(&mut indices, ((size + 1) * (size + 1)) as usize);
+
+        assert_eq!(sorted_triangles(&indices), sorted_triangles(&grid));
+        assert!(average_cache_miss_ratio(&indices) <= acmr_before);
+    }
+
+    #[test]
+    fn vertex_cache_handles_a_triangle_soup() {
+        // like a non-indexed primitive, no two triangles share a vertex
+        let vertex_count = 300_000;
+        let mut indices: Vec<u32> = (0..vertex_count as u32).collect();
+
+        optimize_vertex_cache(&mut indices, vertex_count);
+
+        assert_eq!(
+            sorted_triangles(&indices),
+            sorted_triangles(&(0..vertex_count as u32).collect::<Vec<_>>())
+        );
+        assert_eq!(average_cache_miss_ratio(&indices), 3.0);
+    }
+}
@@ -0,0 +1,405 @@
+//! Mesh simplification with quadric error metrics, see
+//! "Surface Simplification Using Quadric Error Metrics" by Garland and Heckbert.
+//! Uses half edge collapses, so that the simplified indices still work with the original vertices.
+
+use ultraviolet::Vec3;
+
+use crate::scene::{Aabb, Vertex};
+
+/// Sum of squared distances to a set of planes, weighted by the triangle areas
+#[derive(Clone, Copy, Default)]
+struct Quadric {
+    a00: f32,
+    a01: f32,
+    a02: f32,
+    a11: f32,
+    a12: f32,
+    a22: f32,
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    c: f32,
+    weight: f32,
+}
+
+impl Quadric {
+    fn from_plane(normal: Vec3, distance: f32, weight: f32) -> Self {
+        Self {
+            a00: weight * normal.x * normal.x,
+            a01: weight * normal.x * normal.y,
+            a02: weight * normal.x * normal.z,
+            a11: weight * normal.y * normal.y,
+            a12: weight * normal.y * normal.z,
+            a22: weight * normal.z * normal.z,
+            b0: weight * normal.x * distance,
+            b1: weight * normal.y * distance,
+            b2: weight * normal.z * distance,
+            c: weight * distance * distance,
+            weight,
+        }
+    }
+
+    fn add(&self, other: &Quadric) -> Self {
+        Self {
+            a00: self.a00 + other.a00,
+            a01: self.a01 + other.a01,
+            a02: self.a02 + other.a02,
+            a11: self.a11 + other.a11,
+            a12: self.a12 + other.a12,
+            a22: self.a22 + other.a22,
+            b0: self.b0 + other.b0,
+            b1: self.b1 + other.b1,
+            b2: self.b2 + other.b2,
+            c: self.c + other.c,
+            weight: self.weight + other.weight,
+        }
+    }
+
+    /// Average squared distance of the point to the planes
+    fn error(&self, p: Vec3) -> f32 {
+        let rx = self.a00 * p.x + self.a01 * p.y + self.a02 * p.z;
+        let ry = self.a01 * p.x + self.a11 * p.y + self.a12 * p.z;
+        let rz = self.a02 * p.x + self.a12 * p.y + self.a22 * p.z;
+        let error = p.x * rx
+            + p.y * ry
+            + p.z * rz
+            + 2.0 * (self.b0 * p.x + self.b1 * p.y + self.b2 * p.z)
+            + self.c;
+
+        if self.weight > 0.0 {
+            error.abs() / self.weight
+        } else {
+            0.0
+        }
+    }
+}
+
+struct Collapse {
+    from: u32,
+    to: u32,
+    error: f32,
+}
+
+/// Removes triangles until at most `target_index_count` indices are left, or until the next collapse
+/// would move the surface by more than `target_error`, relative to the size of the mesh.
+/// Vertices on borders and UV seams are never moved, so that the mesh doesn't get holes.
+pub fn simplify(
+    indices: &[u32],
+    vertices: &[Vertex],
+    target_index_count: usize,
+    target_error: f32,
+) -> Vec<u32> {
+    let positions: Vec<Vec3> = vertices.iter().map(|v| Vec3::from(v.position)).collect();
+    let mesh_scale = Aabb::from_vertices(vertices).extent().component_max();
+    let max_error = (target_error * mesh_scale).powi(2);
+
+    let mut indices = indices.to_vec();
+    let locked = find_border_vertices(&indices, vertices.len());
+
+    let mut quadrics = vec![Quadric::default(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [p0, p1, p2] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+        let cross = (p1 - p0).cross(p2 - p0);
+        let double_area = cross.mag();
+        if double_area <= 0.0 {
+            continue;
+        }
+        let normal = cross / double_area;
+        let quadric = Quadric::from_plane(normal, -normal.dot(p0), double_area * 0.5);
+        for &vertex in triangle {
+            quadrics[vertex as usize] = quadrics[vertex as usize].add(&quadric);
+        }
+    }
+
+    // Collapses edges in passes, where every vertex can only be touched once per pass
+    while indices.len() > target_index_count {
+        let adjacency = TriangleAdjacency::new(&indices, vertices.len());
+
+        let mut collapses = Vec::with_capacity(indices.len());
+        for triangle in indices.chunks_exact(3) {
+            for (from, to) in [(0, 1), (1, 2), (2, 0), (1, 0), (2, 1), (0, 2)] {
+                let (from, to) = (triangle[from], triangle[to]);
+                if locked[from as usize] {
+                    continue;
+                }
+                let quadric = quadrics[from as usize].add(&quadrics[to as usize]);
+                collapses.push(Collapse {
+                    from,
+                    to,
+                    error: quadric.error(positions[to as usize]),
+                });
+            }
+        }
+        collapses.sort_by(|a, b| a.error.total_cmp(&b.error));
+
+        let mut remap: Vec<u32> = (0..vertices.len() as u32).collect();
+        let mut touched = vec![false; vertices.len()];
+        let mut index_count = indices.len();
+        let mut collapse_count = 0;
+
+        for collapse in collapses {
+            if index_count <= target_index_count || collapse.error > max_error {
+                break;
+            }
+            let (from, to) = (collapse.from as usize, collapse.to as usize);
+            if touched[from] || touched[to] {
+                continue;
+            }
+
+            let from_triangles = adjacency.triangles(from);
+            let would_flip = from_triangles.iter().any(|&triangle| {
+                let triangle = [0, 1, 2].map(|i| remap[indices[triangle * 3 + i] as usize]);
+                if triangle.contains(&collapse.to) || is_degenerate(triangle) {
+                    return false;
+                }
+                let [p0, p1, p2] = triangle.map(|v| positions[v as usize]);
+                let [q0, q1, q2] = triangle.map(|v| {
+                    if v == collapse.from {
+                        positions[to]
+                    } else {
+                        positions[v as usize]
+                    }
+                });
+                let before = (p1 - p0).cross(p2 - p0);
+                let after = (q1 - q0).cross(q2 - q0);
+                before.dot(after) <= 0.0
+            });
+            if would_flip {
+                continue;
+            }
+
+            let removed_triangles = from_triangles
+                .iter()
+                .filter(|&&triangle| {
+                    let triangle = [0, 1, 2].map(|i| remap[indices[triangle * 3 + i] as usize]);
+                    triangle.contains(&collapse.to) && !is_degenerate(triangle)
+                })
+                .count();
+
+            remap[from] = collapse.to;
+            quadrics[to] = quadrics[to].add(&quadrics[from]);
+            touched[from] = true;
+            touched[to] = true;
+            index_count -= removed_triangles * 3;
+            collapse_count += 1;
+        }
+
+        if collapse_count == 0 {
+            break;
+        }
+
+        indices = indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| remap[triangle[i] as usize]))
+            .filter(|&triangle| !is_degenerate(triangle))
+            .flatten()
+            .collect();
+    }
+
+    indices
+}
+
+fn is_degenerate([a, b, c]: [u32; 3]) -> bool {
+    a == b || b == c || c == a
+}
+
+/// Edges that are only used by one triangle are on a border. Since UV seams
+/// have different vertices on each side, they count as borders as well.
+fn find_border_vertices(indices: &[u32], vertex_count: usize) -> Vec<bool> {
+    let mut edge_counts = std::collections::HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            let (a, b) = (triangle[a], triangle[b]);
+            *edge_counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+        }
+    }
+
+    let mut locked = vec![false; vertex_count];
+    for ((a, b), count) in edge_counts {
+        if count == 1 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+    locked
+}
+
+/// The triangles of each vertex, stored as offsets into one list
+struct TriangleAdjacency {
+    offsets: Vec<usize>,
+    triangles: Vec<usize>,
+}
+
+impl TriangleAdjacency {
+    fn new(indices: &[u32], vertex_count: usize) -> Self {
+        let mut offsets = vec![0; vertex_count + 1];
+        for &index in indices {
+            offsets[index as usize + 1] += 1;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+
+        let mut triangles = vec![0; indices.len()];
+        let mut fill = offsets.clone();
+        for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+            for &vertex in vertices {
+                triangles[fill[vertex as usize]] = triangle;
+                fill[vertex as usize] += 1;
+            }
+        }
+
+        Self { offsets, triangles }
+    }
+
+    fn triangles(&self, vertex: usize) -> &[usize] {
+        &self.triangles[self.offsets[vertex]..self.offsets[vertex + 1]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn vertex(position: Vec3) -> Vertex {
+        Vertex {
+            position: position.into(),
+            normal: [0.0, 0.0, 1.0],
+            uv: [0.0, 0.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// A flat grid of quads in the xy plane, with two triangles per quad
+    fn grid(size: u32) -> (Vec<u32>, Vec<Vertex>) {
+        let vertices = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| vertex(Vec3::new(x as f32, y as f32, 0.0))))
+            .collect();
+        let mut indices = vec![];
+        for y in 0..size {
+            for x in 0..size {
+                let corner = y * (size + 1) + x;
+                let next_row = corner + size + 1;
+                indices.extend_from_slice(&[corner, corner + 1, next_row]);
+                indices.extend_from_slice(&[corner + 1, next_row + 1, next_row]);
+            }
+        }
+        (indices, vertices)
+    }
+
+    /// A closed sphere around the origin, with its triangles facing outwards
+    fn sphere(rings: u32, segments: u32) -> (Vec<u32>, Vec<Vertex>) {
+        let mut vertices = vec![vertex(Vec3::unit_z()), vertex(-Vec3::unit_z())];
+        for ring in 1..rings {
+            let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+            for segment in 0..segments {
+                let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+                vertices.push(vertex(Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )));
+            }
+        }
+        let ring_vertex = |ring: u32, segment: u32| 2 + (ring - 1) * segments + segment % segments;
+
+        let mut triangles = vec![];
+        for segment in 0..segments {
+            triangles.push([0, ring_vertex(1, segment), ring_vertex(1, segment + 1)]);
+            triangles.push([
+                1,
+                ring_vertex(rings - 1, segment + 1),
+                ring_vertex(rings - 1, segment),
+            ]);
+            for ring in 1..rings - 1 {
+                let [a, b] = [ring_vertex(ring, segment), ring_vertex(ring, segment + 1)];
+                let [c, d] = [
+                    ring_vertex(ring + 1, segment),
+                    ring_vertex(ring + 1, segment + 1),
+                ];
+                triangles.push([a, c, b]);
+                triangles.push([b, c, d]);
+            }
+        }
+        let indices = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                if is_facing_outwards(&vertices, [a, b, c]) {
+                    [a, b, c]
+                } else {
+                    [a, c, b]
+                }
+            })
+            .collect();
+        (indices, vertices)
+    }
+
+    /// Slivers in a plane through the center, like along a meridian, count as facing outwards
+    fn is_facing_outwards(vertices: &[Vertex], triangle: [u32; 3]) -> bool {
+        let [p0, p1, p2] = triangle.map(|index| Vec3::from(vertices[index as usize].position));
+        let centroid = (p0 + p1 + p2) / 3.0;
+        (p1 - p0).cross(p2 - p0).normalized().dot(centroid) > -1e-4
+    }
+
+    #[test]
+    fn grid_gets_simplified_to_the_target() {
+        let (indices, vertices) = grid(20);
+        let target_index_count = indices.len() / 4;
+
+        let simplified = simplify(&indices, &vertices, target_index_count, 0.01);
+
+        assert!(
+            simplified.len() <= target_index_count,
+            "{}",
+            simplified.len()
+        );
+        assert!(!simplified.is_empty());
+        assert_eq!(simplified.len() % 3, 0);
+        assert!(simplified
+            .iter()
+            .all(|&index| (index as usize) < vertices.len()));
+        assert!(simplified.chunks_exact(3).all(|triangle| !is_degenerate([
+            triangle[0],
+            triangle[1],
+            triangle[2]
+        ])));
+    }
+
+    #[test]
+    fn border_vertices_stay_in_place() {
+        let size = 20;
+        let (indices, vertices) = grid(size);
+        let border: HashSet<u32> = (0..vertices.len() as u32)
+            .filter(|&index| {
+                let [x, y] = [index % (size + 1), index / (size + 1)];
+                x == 0 || y == 0 || x == size || y == size
+            })
+            .collect();
+
+        let simplified = simplify(&indices, &vertices, 0, 0.01);
+
+        // half edge collapses only remove vertices, so the kept ones have their original positions
+        let kept: HashSet<u32> = simplified.iter().copied().collect();
+        assert!(border.is_subset(&kept));
+        assert!(simplified.len() < indices.len());
+    }
+
+    #[test]
+    fn closed_mesh_keeps_facing_outwards() {
+        let (indices, vertices) = sphere(16, 24);
+        assert!(indices.chunks_exact(3).all(|triangle| is_facing_outwards(
+            &vertices,
+            [triangle[0], triangle[1], triangle[2]]
+        )));
+
+        let simplified = simplify(&indices, &vertices, indices.len() / 8, 1.0);
+
+        assert!(simplified.len() < indices.len() / 2, "{}", simplified.len());
+        for triangle in simplified.chunks_exact(3) {
+            let triangle = [triangle[0], triangle[1], triangle[2]];
+            assert!(is_facing_outwards(&vertices, triangle), "{:?}", triangle);
+        }
+    }
+}
@@ -5,7 +5,14 @@ use crate::transform::Transform;
 use super::{LoadedMaterial, LoadedMesh};
 
 pub struct LoadedModel {
+    /// World transform of the node
     pub transform: Transform,
+    /// Index of the node in the scene graph
+    pub node: usize,
+    /// Index into the skins of the scene
+    pub skin: Option<usize>,
+    /// One weight per morph target of the meshes, empty when they have none
+    pub morph_weights: Vec<f32>,
     pub primitives: Vec<LoadedPrimitive>,
 }
 
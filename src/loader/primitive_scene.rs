@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use crate::transform::Transform;
+
+use super::{AssetLoader, LoadedMaterial, LoadedMesh, LoadedModel, LoadedPrimitive, LoadedScene};
+
+/// Generated scenes with a single model, for testing the renderer without a glTF file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimitiveScene {
+    Cube,
+    Sphere,
+}
+
+impl PrimitiveScene {
+    pub const ALL: [PrimitiveScene; 2] = [PrimitiveScene::Cube, PrimitiveScene::Sphere];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PrimitiveScene::Cube => "Cube",
+            PrimitiveScene::Sphere => "Sphere",
+        }
+    }
+}
+
+impl AssetLoader {
+    const SPHERE_RINGS: u32 = 32;
+
+    pub fn load_primitive_scene(&mut self, primitive_scene: PrimitiveScene) -> LoadedScene {
+        match primitive_scene {
+            PrimitiveScene::Cube => self.load_primitive_cube(),
+            PrimitiveScene::Sphere => self.load_primitive_sphere(AssetLoader::SPHERE_RINGS),
+        }
+    }
+
+    pub fn load_primitive_cube(&mut self) -> LoadedScene {
+        let mesh = LoadedMesh::new_unit_cube(self.id_generator.next());
+        self.load_single_mesh_scene(PrimitiveScene::Cube.name(), mesh)
+    }
+
+    pub fn load_primitive_sphere(&mut self, rings: u32) -> LoadedScene {
+        let mesh = LoadedMesh::new_uv_sphere(self.id_generator.next(), rings);
+        self.load_single_mesh_scene(PrimitiveScene::Sphere.name(), mesh)
+    }
+
+    /// The mesh sits at the origin, with the same material as glTF primitives without one
+    fn load_single_mesh_scene(&mut self, name: &str, mesh: LoadedMesh) -> LoadedScene {
+        let mesh = Arc::new(mesh);
+        self.meshes.assets.insert(mesh.id, mesh.clone());
+        let material = Arc::new(LoadedMaterial::missing_material(self.id_generator.next()));
+
+        let mut scene = LoadedScene::new();
+        let node = scene
+            .scene_graph
+            .add_node(None, Some(name.to_owned()), Transform::default());
+        scene.scene_graph.nodes[node].model = Some(scene.models.len());
+        scene.models.push(LoadedModel {
+            transform: Transform::default(),
+            node,
+            skin: None,
+            morph_weights: Vec::new(),
+            primitives: vec![LoadedPrimitive { material, mesh }],
+        });
+        scene
+    }
+}
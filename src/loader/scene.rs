@@ -1,15 +1,41 @@
-use super::{animation::Animation, LoadedModel};
+use super::{
+    animation::{Animation, MorphWeightsAnimation, NodeAnimation},
+    Light, LoadedModel, LoadedSkin, SceneGraph,
+};
+
+/// Sent by [`super::AssetLoader::load_scene_streaming`]
+pub enum SceneStreamEvent {
+    /// A model without a skin, which can be uploaded right away.
+    /// The joints of a skin are nodes, so skins only exist once all nodes are loaded.
+    Model(LoadedModel),
+    /// The rest of the scene, including the models with a skin. Always the last event,
+    /// and its models come after the streamed ones, in the indices of the scene graph.
+    Finished(anyhow::Result<LoadedScene>),
+}
 
 pub struct LoadedScene {
+    /// Flattened view of the scene graph, with the world transforms of the nodes.
+    /// When streaming, only the models that were not sent on their own
     pub models: Vec<LoadedModel>,
+    pub scene_graph: SceneGraph,
+    pub skins: Vec<LoadedSkin>,
     pub camera_animations: Vec<Animation>,
+    pub morph_weights_animations: Vec<MorphWeightsAnimation>,
+    /// Moves the joints of the skins, and any other node that isn't a camera
+    pub node_animations: Vec<NodeAnimation>,
+    pub lights: Vec<Light>,
 }
 
 impl LoadedScene {
     pub fn new() -> Self {
         Self {
             models: Vec::new(),
+            scene_graph: SceneGraph::new(),
+            skins: Vec::new(),
             camera_animations: Vec::new(),
+            morph_weights_animations: Vec::new(),
+            node_animations: Vec::new(),
+            lights: Vec::new(),
         }
     }
 }
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::transform::Transform;
+
+use super::{
+    AssetId, ImageBytes, LoadedImage, LoadedMaterial, LoadedMesh, LoadedScene, LoadedTexture,
+};
+
+/// Snapshot of a loaded scene, for finding out why a model looks wrong.
+/// Only has the sizes of the vertex and image data, not the data itself.
+#[derive(Serialize)]
+pub struct SceneDump {
+    pub nodes: Vec<NodeDump>,
+    pub models: Vec<ModelDump>,
+    /// Sorted by id, like the meshes and the images
+    pub materials: Vec<MaterialDump>,
+    pub meshes: Vec<MeshDump>,
+    pub images: Vec<ImageDump>,
+    pub skin_count: usize,
+    pub camera_animation_count: usize,
+    pub morph_weights_animation_count: usize,
+    pub light_count: usize,
+}
+
+#[derive(Serialize)]
+pub struct NodeDump {
+    pub name: Option<String>,
+    pub local_transform: TransformDump,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub model: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct ModelDump {
+    pub node: usize,
+    pub transform: TransformDump,
+    pub skin: Option<usize>,
+    pub morph_weights: Vec<f32>,
+    pub primitives: Vec<PrimitiveDump>,
+}
+
+#[derive(Serialize)]
+pub struct PrimitiveDump {
+    pub mesh: AssetId,
+    pub material: AssetId,
+}
+
+#[derive(Serialize)]
+pub struct TransformDump {
+    pub position: [f32; 3],
+    /// As a quaternion, x y z w
+    pub orientation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+#[derive(Serialize)]
+pub struct MaterialDump {
+    pub id: AssetId,
+    pub base_color: [f32; 3],
+    pub base_color_texture: Option<TextureDump>,
+    pub normal_texture: Option<TextureDump>,
+    pub roughness_factor: f32,
+    pub metallic_factor: f32,
+    pub metallic_roughness_texture: Option<TextureDump>,
+    pub emissivity: [f32; 3],
+    pub double_sided: bool,
+    pub transmission_factor: f32,
+    pub ior: f32,
+    pub clearcoat_factor: f32,
+    pub clearcoat_texture: Option<TextureDump>,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_roughness_texture: Option<TextureDump>,
+}
+
+#[derive(Serialize)]
+pub struct TextureDump {
+    pub image: AssetId,
+    pub sampler: AssetId,
+}
+
+#[derive(Serialize)]
+pub struct MeshDump {
+    pub id: AssetId,
+    pub vertex_count: usize,
+    pub index_count: usize,
+    /// One entry per simplified version
+    pub lod_index_counts: Vec<usize>,
+    pub morph_target_count: usize,
+    pub is_skinned: bool,
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+}
+
+#[derive(Serialize)]
+pub struct ImageDump {
+    pub id: AssetId,
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    pub color_space: String,
+    pub is_compressed: bool,
+    /// Of the compressed file, or of the decoded pixels
+    pub byte_count: usize,
+}
+
+impl SceneDump {
+    pub fn new(loaded_scene: &LoadedScene) -> Self {
+        let mut materials = HashMap::new();
+        let mut meshes = HashMap::new();
+        for primitive in loaded_scene
+            .models
+            .iter()
+            .flat_map(|model| model.primitives.iter())
+        {
+            materials.insert(primitive.material.id, primitive.material.clone());
+            meshes.insert(primitive.mesh.id, primitive.mesh.clone());
+        }
+        let mut images = HashMap::new();
+        for material in materials.values() {
+            for texture in material_textures(material).into_iter().flatten() {
+                images.insert(texture.image.id, texture.image.clone());
+            }
+        }
+
+        let nodes = loaded_scene
+            .scene_graph
+            .nodes
+            .iter()
+            .map(|node| NodeDump {
+                name: node.name.clone(),
+                local_transform: TransformDump::new(&node.local_transform),
+                parent: node.parent,
+                children: node.children.clone(),
+                model: node.model,
+            })
+            .collect();
+        let models = loaded_scene
+            .models
+            .iter()
+            .map(|model| ModelDump {
+                node: model.node,
+                transform: TransformDump::new(&model.transform),
+                skin: model.skin,
+                morph_weights: model.morph_weights.clone(),
+                primitives: model
+                    .primitives
+                    .iter()
+                    .map(|primitive| PrimitiveDump {
+                        mesh: primitive.mesh.id,
+                        material: primitive.material.id,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            nodes,
+            models,
+            materials: sorted_by_id(materials, |material| MaterialDump::new(material)),
+            meshes: sorted_by_id(meshes, |mesh| MeshDump::new(mesh)),
+            images: sorted_by_id(images, |image| ImageDump::new(image)),
+            skin_count: loaded_scene.skins.len(),
+            camera_animation_count: loaded_scene.camera_animations.len(),
+            morph_weights_animation_count: loaded_scene.morph_weights_animations.len(),
+            light_count: loaded_scene.lights.len(),
+        }
+    }
+}
+
+impl TransformDump {
+    fn new(transform: &Transform) -> Self {
+        Self {
+            position: transform.position.into(),
+            orientation: transform.orientation.into_quaternion_array(),
+            scale: transform.scale.into(),
+        }
+    }
+}
+
+impl TextureDump {
+    fn new(texture: &Option<LoadedTexture>) -> Option<Self> {
+        texture.as_ref().map(|texture| Self {
+            image: texture.image.id,
+            sampler: texture.sampler.id,
+        })
+    }
+}
+
+impl MaterialDump {
+    fn new(material: &LoadedMaterial) -> Self {
+        Self {
+            id: material.id,
+            base_color: material.base_color.into(),
+            base_color_texture: TextureDump::new(&material.base_color_texture),
+            normal_texture: TextureDump::new(&material.normal_texture),
+            roughness_factor: material.roughness_factor,
+            metallic_factor: material.metallic_factor,
+            metallic_roughness_texture: TextureDump::new(&material.metallic_roughness_texture),
+            emissivity: material.emissivity.into(),
+            double_sided: material.double_sided,
+            transmission_factor: material.transmission_factor,
+            ior: material.ior,
+            clearcoat_factor: material.clearcoat_factor,
+            clearcoat_texture: TextureDump::new(&material.clearcoat_texture),
+            clearcoat_roughness_factor: material.clearcoat_roughness_factor,
+            clearcoat_roughness_texture: TextureDump::new(&material.clearcoat_roughness_texture),
+        }
+    }
+}
+
+impl MeshDump {
+    fn new(mesh: &LoadedMesh) -> Self {
+        Self {
+            id: mesh.id,
+            vertex_count: mesh.vertices.len(),
+            index_count: mesh.indices.len(),
+            lod_index_counts: mesh.lods.iter().map(|lod| lod.len()).collect(),
+            morph_target_count: mesh.morph_targets.len(),
+            is_skinned: mesh.skin_vertices.is_some(),
+            bounds_min: mesh.bounds.min.into(),
+            bounds_max: mesh.bounds.max.into(),
+        }
+    }
+}
+
+impl ImageDump {
+    fn new(image: &LoadedImage) -> Self {
+        let (width, height) = image.data.dimensions;
+        let (is_compressed, byte_count) = match &image.data.bytes {
+            ImageBytes::Decoded(bytes) => (false, bytes.len()),
+            ImageBytes::Compressed(compressed) => (true, compressed.bytes.len()),
+        };
+        Self {
+            id: image.id,
+            width,
+            height,
+            format: format!("{:?}", image.data.format),
+            color_space: format!("{:?}", image.data.color_space),
+            is_compressed,
+            byte_count,
+        }
+    }
+}
+
+fn material_textures(material: &LoadedMaterial) -> [&Option<LoadedTexture>; 5] {
+    [
+        &material.base_color_texture,
+        &material.normal_texture,
+        &material.metallic_roughness_texture,
+        &material.clearcoat_texture,
+        &material.clearcoat_roughness_texture,
+    ]
+}
+
+fn sorted_by_id<T, U>(assets: HashMap<AssetId, Arc<T>>, dump: impl Fn(&T) -> U) -> Vec<U> {
+    let mut assets: Vec<_> = assets.into_iter().collect();
+    assets.sort_by_key(|(id, _)| *id);
+    assets.into_iter().map(|(_, asset)| dump(&asset)).collect()
+}
@@ -0,0 +1,159 @@
+use crate::{scene::Model, transform::Transform};
+
+/// A node of the glTF hierarchy. Nodes refer to each other by their index in the scene graph.
+pub struct LoadedNode {
+    pub name: Option<String>,
+    pub local_transform: Transform,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Index into the models of the scene, when the node has a mesh
+    pub model: Option<usize>,
+}
+
+/// Keeps the parent/child relationships of the nodes, so that whole subtrees can be moved.
+/// World transforms are only recomputed when they are asked for.
+pub struct SceneGraph {
+    pub nodes: Vec<LoadedNode>,
+    pub roots: Vec<usize>,
+    /// None when the node or one of its ancestors has moved since the last computation
+    world_transforms: Vec<Option<Transform>>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            world_transforms: Vec::new(),
+        }
+    }
+
+    pub fn add_node(
+        &mut self,
+        parent: Option<usize>,
+        name: Option<String>,
+        local_transform: Transform,
+    ) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(LoadedNode {
+            name,
+            local_transform,
+            parent,
+            children: Vec::new(),
+            model: None,
+        });
+        self.world_transforms.push(None);
+
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(index),
+            None => self.roots.push(index),
+        }
+        index
+    }
+
+    pub fn set_local_transform(&mut self, node: usize, local_transform: Transform) {
+        self.nodes[node].local_transform = local_transform;
+        self.invalidate(node);
+    }
+
+    pub fn world_transform(&mut self, node: usize) -> Transform {
+        if let Some(world_transform) = &self.world_transforms[node] {
+            return world_transform.clone();
+        }
+
+        let local_transform = self.nodes[node].local_transform.clone();
+        let world_transform = match self.nodes[node].parent {
+            Some(parent) => &self.world_transform(parent) * local_transform,
+            None => local_transform,
+        };
+        self.world_transforms[node] = Some(world_transform.clone());
+        world_transform
+    }
+
+    /// Copies the world transforms of the nodes into the flat list of models.
+    /// Returns whether any model has moved.
+    pub fn update_model_transforms(&mut self, models: &mut [Model]) -> bool {
+        let mut has_moved = false;
+        for model in models {
+            let world_transform = self.world_transform(model.node);
+            if model.transform != world_transform {
+                model.transform = world_transform;
+                has_moved = true;
+            }
+        }
+        has_moved
+    }
+
+    fn invalidate(&mut self, node: usize) {
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            self.world_transforms[node] = None;
+            stack.extend_from_slice(&self.nodes[node].children);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use super::*;
+
+    fn translation(x: f32) -> Transform {
+        Transform {
+            position: Vec3::new(x, 0.0, 0.0),
+            ..Default::default()
+        }
+    }
+
+    fn model(node: usize) -> Model {
+        Model {
+            transform: Transform::default(),
+            node,
+            primitives: vec![],
+            skin: None,
+            morph_weights: None,
+            casts_shadows: true,
+        }
+    }
+
+    #[test]
+    fn world_transforms_follow_the_parents() {
+        let mut scene_graph = SceneGraph::new();
+        let root = scene_graph.add_node(None, None, translation(1.0));
+        let child = scene_graph.add_node(Some(root), None, translation(2.0));
+        let grandchild = scene_graph.add_node(Some(child), None, translation(4.0));
+        let sibling = scene_graph.add_node(None, None, translation(8.0));
+
+        assert_eq!(scene_graph.world_transform(grandchild), translation(7.0));
+        assert_eq!(scene_graph.world_transform(sibling), translation(8.0));
+
+        // moving the child moves its whole subtree, and nothing else
+        scene_graph.set_local_transform(child, translation(16.0));
+        assert!(scene_graph.world_transforms[root].is_some());
+        assert!(scene_graph.world_transforms[child].is_none());
+        assert!(scene_graph.world_transforms[grandchild].is_none());
+        assert!(scene_graph.world_transforms[sibling].is_some());
+
+        assert_eq!(scene_graph.world_transform(grandchild), translation(21.0));
+        assert_eq!(scene_graph.world_transform(child), translation(17.0));
+        assert_eq!(scene_graph.world_transform(root), translation(1.0));
+    }
+
+    #[test]
+    fn model_transforms_only_change_when_their_node_moved() {
+        let mut scene_graph = SceneGraph::new();
+        let root = scene_graph.add_node(None, None, translation(1.0));
+        let child = scene_graph.add_node(Some(root), None, translation(2.0));
+        let mut models = [model(root), model(child)];
+
+        assert!(scene_graph.update_model_transforms(&mut models));
+        assert_eq!(models[1].transform, translation(3.0));
+        assert!(!scene_graph.update_model_transforms(&mut models));
+
+        scene_graph.set_local_transform(root, translation(5.0));
+        assert!(scene_graph.update_model_transforms(&mut models));
+        assert_eq!(models[0].transform, translation(5.0));
+        assert_eq!(models[1].transform, translation(7.0));
+    }
+}
@@ -1,47 +1,92 @@
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::Instant,
+};
 
 use gltf::{accessor::Iter, texture::Sampler, Semantic, Texture};
-use ultraviolet::{Rotor3, Vec2, Vec3};
+use ultraviolet::{Mat4, Rotor3, Vec2, Vec3, Vec4};
 
-use crate::{scene::Vertex, transform::Transform};
+use crate::{
+    scene::{Aabb, SkinVertex, Vertex, MAX_LOD_COUNT},
+    transform::Transform,
+};
 
 use super::{
-    animation::Animation,
+    animation::{Animation, MorphWeightsAnimation, NodeAnimation, NodeAnimationValues},
+    mesh_optimizer::{
+        average_cache_miss_ratio, optimize_vertex_cache, optimize_vertex_fetch,
+        MeshOptimizationStats,
+    },
+    mesh_simplifier::simplify,
     texture::{
-        AddressMode, BytesImageData, Filter, ImageFormat, LoadedImage, LoadedSampler,
-        LoadedTexture, MipmapMode, SamplerInfo,
+        AddressMode, BytesImageData, CompressedImage, Filter, ImageBytes, ImageEncoding,
+        ImageFormat, LoadedImage, LoadedSampler, LoadedTexture, MipmapMode, SamplerInfo,
     },
-    AssetId, AssetIdGenerator, AssetLoader, ColorSpace, LoadedMaterial, LoadedMesh, LoadedModel,
-    LoadedPrimitive, LoadedScene,
+    AssetId, AssetIdGenerator, AssetLoader, ColorSpace, Light, LightKind, LoadedMaterial,
+    LoadedMesh, LoadedModel, LoadedPrimitive, LoadedScene, LoadedSkin, MaterialOverrides,
+    MorphTarget, SceneStreamEvent,
 };
 
+/// Maximum change of the surface when simplifying, relative to the size of the mesh
+const LOD_TARGET_ERROR: f32 = 0.02;
+
 struct SceneLoadingData {
     scene: LoadedScene,
     buffers: Vec<gltf::buffer::Data>,
-    images: HashMap<usize, gltf::image::Data>,
+    /// Empty when the images are kept compressed, or decoded when a material uses them
+    images: HashMap<usize, DecodedImage>,
+    /// For resolving relative image paths
+    base_path: PathBuf,
     material_ids: HashMap<MaterialKey, AssetId>,
     mesh_ids: HashMap<MeshKey, AssetId>,
     sampler_ids: HashMap<SamplerKey, AssetId>,
     image_ids: HashMap<ImageKey, AssetId>,
     id_generator: AssetIdGenerator,
+    optimization_stats: MeshOptimizationStats,
+    /// From glTF node indices to scene graph indices
+    node_indices: HashMap<usize, usize>,
+    /// Receives the models as soon as they are loaded, when the scene is streamed
+    model_sender: Option<Sender<SceneStreamEvent>>,
+    /// They come before the models of the loaded scene
+    streamed_model_count: usize,
+    /// Set when the receiver is gone, because another scene got loaded in the meantime
+    is_stream_abandoned: bool,
+    /// Edited in the viewer, applied on top of the glTF materials
+    material_overrides: MaterialOverrides,
 }
 
 impl SceneLoadingData {
     fn new(
         buffers: Vec<gltf::buffer::Data>,
-        images: Vec<gltf::image::Data>,
+        images: Vec<DecodedImage>,
+        base_path: PathBuf,
         id_generator: AssetIdGenerator,
+        model_sender: Option<Sender<SceneStreamEvent>>,
+        material_overrides: MaterialOverrides,
     ) -> Self {
         let images = images.into_iter().enumerate().collect();
         Self {
             scene: LoadedScene::new(),
             buffers,
             images,
+            base_path,
             material_ids: HashMap::new(),
             mesh_ids: HashMap::new(),
             sampler_ids: HashMap::new(),
             image_ids: HashMap::new(),
             id_generator,
+            optimization_stats: MeshOptimizationStats::default(),
+            node_indices: HashMap::new(),
+            model_sender,
+            streamed_model_count: 0,
+            is_stream_abandoned: false,
+            material_overrides,
         }
     }
 }
@@ -66,9 +111,11 @@ impl ToAssetId for MaterialKey {
 
 #[derive(Hash, Eq, PartialEq, Debug)]
 struct MeshKey {
-    index_buffer_id: usize,
+    /// None for meshes without indices, which get `0..n` as their indices
+    index_buffer_id: Option<usize>,
     vertex_buffer_positions_id: usize,
-    vertex_buffer_normals_id: usize,
+    /// None for meshes without normals, which get flat normals
+    vertex_buffer_normals_id: Option<usize>,
     vertex_buffer_uvs_id: Option<usize>,
 }
 
@@ -111,58 +158,179 @@ impl ToAssetId for SamplerKey {
 
 impl AssetLoader {
     pub fn load_scene(&mut self, path: impl AsRef<Path>) -> anyhow::Result<LoadedScene> {
-        let (gltf, buffers, images) = gltf::import(path)?;
+        self.load_scene_with_sender(path.as_ref(), None)
+    }
+
+    /// Loads the scene on another thread, and sends each model as soon as it is loaded.
+    /// Models with a skin and the rest of the scene are sent at the end.
+    pub fn load_scene_streaming(mut self, path: PathBuf) -> Receiver<SceneStreamEvent> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let loaded_scene = self.load_scene_with_sender(&path, Some(sender.clone()));
+            // the receiver is gone when another scene got loaded in the meantime
+            let _ = sender.send(SceneStreamEvent::Finished(loaded_scene));
+        });
+        receiver
+    }
+
+    fn load_scene_with_sender(
+        &mut self,
+        path: &Path,
+        model_sender: Option<Sender<SceneStreamEvent>>,
+    ) -> anyhow::Result<LoadedScene> {
+        let base_path = path.parent().unwrap_or_else(|| Path::new("./"));
+        let gltf::Gltf {
+            document: gltf,
+            blob,
+        } = gltf::Gltf::open(path)?;
+        let buffers = gltf::import_buffers(&gltf, Some(base_path), blob)?;
+        let images = if self.keep_images_compressed || model_sender.is_some() {
+            // the images are read when a material uses them
+            vec![]
+        } else {
+            let start_time = Instant::now();
+            let images = decode_images(&gltf, base_path, &buffers)?;
+            log::info!(
+                "Decoded {} images in {:?}",
+                images.len(),
+                start_time.elapsed()
+            );
+            images
+        };
 
         let scene = gltf.default_scene().expect("Expected a default scene");
-        let mut loading_data = SceneLoadingData::new(buffers, images, self.id_generator.clone());
+        let mut loading_data = SceneLoadingData::new(
+            buffers,
+            images,
+            base_path.to_path_buf(),
+            self.id_generator.clone(),
+            model_sender,
+            MaterialOverrides::load(path),
+        );
         for node in scene.nodes() {
-            self.load_node(&mut loading_data, &node, Transform::default());
+            self.load_node(&gltf, &mut loading_data, &node, None);
+        }
+        if loading_data.is_stream_abandoned {
+            anyhow::bail!(
+                "Stopped loading {}, it is not needed anymore",
+                path.display()
+            );
+        }
+        for (index, model) in loading_data.scene.models.iter().enumerate() {
+            loading_data.scene.scene_graph.nodes[model.node].model =
+                Some(loading_data.streamed_model_count + index);
         }
 
+        loading_data.scene.skins = load_skins(&gltf, &loading_data);
         loading_data.scene.camera_animations = load_animations(&gltf, &loading_data);
+        loading_data.scene.morph_weights_animations =
+            load_morph_weights_animations(&gltf, &loading_data);
+        loading_data.scene.node_animations = load_node_animations(&gltf, &loading_data);
+
+        if self.optimize_meshes {
+            log::info!("Optimized meshes: {}", loading_data.optimization_stats);
+        }
 
         Ok(loading_data.scene)
     }
 
     fn load_node(
         &mut self,
+        gltf: &gltf::Document,
         loading_data: &mut SceneLoadingData,
         node: &gltf::Node<'_>,
-        parent_transform: Transform,
+        parent: Option<usize>,
     ) {
-        let local_transform = node.transform().into();
-        let global_transform = &parent_transform * local_transform;
+        if loading_data.is_stream_abandoned {
+            return;
+        }
+        let scene_graph = &mut loading_data.scene.scene_graph;
+        let index = scene_graph.add_node(
+            parent,
+            node.name().map(str::to_owned),
+            node.transform().into(),
+        );
+        let global_transform = scene_graph.world_transform(index);
+        loading_data.node_indices.insert(node.index(), index);
 
         for child in node.children() {
-            self.load_node(loading_data, &child, global_transform.clone());
+            self.load_node(gltf, loading_data, &child, Some(index));
+        }
+        if loading_data.is_stream_abandoned {
+            return;
         }
 
-        if let Some(_light) = node.light() {
-            // TODO: load the light
+        if let Some(light) = node.light() {
+            loading_data
+                .scene
+                .lights
+                .push(load_light(&light, index, global_transform.clone()));
         }
 
         if let Some(mesh) = node.mesh() {
-            let model = self.load_model(loading_data, &mesh, global_transform.clone());
-            loading_data.scene.models.push(model);
+            let mut model = self.load_model(gltf, loading_data, &mesh, index, global_transform);
+            model.skin = node.skin().map(|skin| skin.index());
+
+            // the node can override the default weights of the mesh
+            let morph_target_count = model
+                .primitives
+                .iter()
+                .map(|primitive| primitive.mesh.morph_targets.len())
+                .max()
+                .unwrap_or(0);
+            model.morph_weights = node
+                .weights()
+                .or_else(|| mesh.weights())
+                .map(<[f32]>::to_vec)
+                .unwrap_or_default();
+            model.morph_weights.resize(morph_target_count, 0.0);
+
+            // skins can only be loaded once all nodes exist
+            match &loading_data.model_sender {
+                Some(model_sender) if model.skin.is_none() => {
+                    loading_data.scene.scene_graph.nodes[index].model =
+                        Some(loading_data.streamed_model_count);
+                    loading_data.streamed_model_count += 1;
+                    if model_sender.send(SceneStreamEvent::Model(model)).is_err() {
+                        loading_data.is_stream_abandoned = true;
+                    }
+                }
+                _ => loading_data.scene.models.push(model),
+            }
         }
     }
 
     fn load_model(
         &mut self,
+        gltf: &gltf::Document,
         loading_data: &mut SceneLoadingData,
         mesh: &gltf::Mesh<'_>,
+        node: usize,
         transform: Transform,
     ) -> LoadedModel {
         let mut model = LoadedModel {
             transform,
+            node,
+            skin: None,
+            morph_weights: Vec::new(),
             primitives: Vec::new(),
         };
 
         for primitive in mesh.primitives() {
+            let Some(loaded_mesh) = self.load_mesh(loading_data, &primitive) else {
+                log::warn!(
+                    "Skipping primitive {} of mesh {:?}, it has no positions",
+                    primitive.index(),
+                    mesh.name()
+                );
+                continue;
+            };
             let material = primitive.material();
-            let material = self.load_material(loading_data, &material);
-            let mesh = self.load_mesh(loading_data, &primitive);
-            model.primitives.push(LoadedPrimitive { material, mesh });
+            let material = self.load_material(gltf, loading_data, &material);
+            model.primitives.push(LoadedPrimitive {
+                material,
+                mesh: loaded_mesh,
+            });
         }
 
         model
@@ -170,6 +338,7 @@ impl AssetLoader {
 
     fn load_material(
         &mut self,
+        gltf: &gltf::Document,
         loading_data: &mut SceneLoadingData,
         material: &gltf::Material<'_>,
     ) -> std::sync::Arc<LoadedMaterial> {
@@ -183,12 +352,7 @@ impl AssetLoader {
         }
 
         let material_pbr = material.pbr_metallic_roughness();
-        let emissive_factor = material.emissive_factor();
-        let emissivity = material
-            .emissive_strength()
-            .map(|value| emissive_factor.map(|v| v * value))
-            .unwrap_or([0.0; 3])
-            .into();
+        let emissivity = emissivity(material.emissive_factor(), material.emissive_strength());
 
         let base_color = {
             let [r, g, b, _] = material_pbr.base_color_factor();
@@ -225,8 +389,40 @@ impl AssetLoader {
                     LoadedTexture { image, sampler }
                 });
 
-        let material = Arc::new(LoadedMaterial {
+        // Only the factor for now, the transmission texture is ignored
+        let transmission_factor = material
+            .transmission()
+            .map(|transmission| transmission.transmission_factor())
+            .unwrap_or(0.0);
+        let ior = material.ior().unwrap_or(LoadedMaterial::DEFAULT_IOR);
+
+        // the gltf crate doesn't support KHR_materials_clearcoat, so it's read from the json
+        let clearcoat = material.extension_value("KHR_materials_clearcoat");
+        let clearcoat_value = |name: &str| clearcoat.and_then(|clearcoat| clearcoat.get(name));
+        let clearcoat_factor = |name: &str| {
+            clearcoat_value(name)
+                .and_then(|value| value.as_f64())
+                .unwrap_or(0.0) as f32
+        };
+        let mut load_clearcoat_texture = |name: &str| {
+            let index = clearcoat_value(name)
+                .and_then(|texture_info| texture_info.get("index"))
+                .and_then(|index| index.as_u64())?;
+            let texture = gltf
+                .textures()
+                .nth(index as usize)
+                .expect("Could not find the clearcoat texture");
+            let sampler = self.load_sampler(loading_data, texture.sampler());
+            let image = self.load_images(loading_data, texture, ColorSpace::Linear);
+            Some(LoadedTexture { image, sampler })
+        };
+        let clearcoat_texture = load_clearcoat_texture("clearcoatTexture");
+        let clearcoat_roughness_texture = load_clearcoat_texture("clearcoatRoughnessTexture");
+
+        let source_index = material.index();
+        let mut material = LoadedMaterial {
             id,
+            source_index,
             base_color,
             base_color_texture,
             roughness_factor,
@@ -234,35 +430,62 @@ impl AssetLoader {
             metallic_roughness_texture,
             emissivity,
             normal_texture,
-        });
+            double_sided: material.double_sided(),
+            transmission_factor,
+            ior,
+            clearcoat_factor: clearcoat_factor("clearcoatFactor"),
+            clearcoat_texture,
+            clearcoat_roughness_factor: clearcoat_factor("clearcoatRoughnessFactor"),
+            clearcoat_roughness_texture,
+        };
+        if let Some(index) = source_index {
+            loading_data.material_overrides.apply(index, &mut material);
+        }
+        let material = Arc::new(material);
 
         self.materials.assets.insert(id, material.clone());
         material
     }
 
+    /// None when the primitive has no positions, glTF says that it should not be rendered then
     fn load_mesh(
         &mut self,
         loading_data: &mut SceneLoadingData,
         primitive: &gltf::Primitive<'_>,
-    ) -> std::sync::Arc<LoadedMesh> {
+    ) -> Option<Arc<LoadedMesh>> {
         assert_eq!(primitive.mode(), gltf::mesh::Mode::Triangles);
 
+        let positions = primitive
+            .get(&Semantic::Positions)
+            .filter(|positions| positions.count() > 0)?;
         let id = MeshKey {
-            index_buffer_id: primitive.indices().unwrap().index(),
-            vertex_buffer_positions_id: primitive.get(&Semantic::Positions).unwrap().index(),
-            vertex_buffer_normals_id: primitive.get(&Semantic::Normals).unwrap().index(),
+            index_buffer_id: primitive.indices().map(|indices| indices.index()),
+            vertex_buffer_positions_id: positions.index(),
+            vertex_buffer_normals_id: primitive.get(&Semantic::Normals).map(|a| a.index()),
             vertex_buffer_uvs_id: primitive.get(&Semantic::TexCoords(0)).map(|a| a.index()),
         }
         .to_asset_id(loading_data);
 
-        self.meshes
+        let optimize_meshes = self.optimize_meshes;
+        let mesh = self
+            .meshes
             .assets
             .entry(id)
             .or_insert_with(|| {
                 let reader = primitive
                     .reader(|buffer| loading_data.buffers.get(buffer.index()).map(|v| &v.0[..]));
-                let positions = reader.read_positions().unwrap();
-                let normals = reader.read_normals().unwrap();
+                let positions = reader
+                    .read_positions()
+                    .expect("Could not read the positions");
+                let mut normals_missing = false;
+
+                let normals: Box<dyn Iterator<Item = _>> =
+                    if let Some(read_normals) = reader.read_normals() {
+                        Box::new(read_normals)
+                    } else {
+                        normals_missing = true;
+                        Box::new(std::iter::repeat([0.0f32; 3]))
+                    };
 
                 let mut uv_missing = false;
 
@@ -298,27 +521,47 @@ impl AssetLoader {
                     });
                 }
 
-                let indices: Vec<_> = reader
+                let mut skin_vertices: Option<Vec<SkinVertex>> = reader
+                    .read_joints(0)
+                    .zip(reader.read_weights(0))
+                    .map(|(joints, weights)| {
+                        joints
+                            .into_u16()
+                            .zip(weights.into_f32())
+                            .map(|(joints, weights)| SkinVertex {
+                                joints: joints.map(u32::from),
+                                weights,
+                            })
+                            .collect()
+                    });
+
+                let mut morph_targets: Vec<MorphTarget> = reader
+                    .read_morph_targets()
+                    .map(|(positions, normals, _tangents)| MorphTarget {
+                        position_deltas: positions
+                            .map(|positions| positions.collect())
+                            .unwrap_or_else(|| vec![[0.0; 3]; vertices.len()]),
+                        normal_deltas: normals
+                            .map(|normals| normals.collect())
+                            .unwrap_or_else(|| vec![[0.0; 3]; vertices.len()]),
+                    })
+                    .collect();
+
+                let mut indices: Vec<_> = reader
                     .read_indices()
                     .map(|indices| indices.into_u32().collect())
                     .unwrap_or_else(|| (0..(vertices.len() as u32)).collect());
 
-                fn compute_tangent(
-                    p0: Vec3,
-                    p1: Vec3,
-                    p2: Vec3,
-                    uv0: Vec2,
-                    uv1: Vec2,
-                    uv2: Vec2,
-                ) -> Vec3 {
-                    let edge0 = p1 - p0;
-                    let delta_uv0 = uv1 - uv0;
-                    let edge1 = p2 - p0;
-                    let delta_uv1 = uv2 - uv0;
-
-                    let f = 1.0 / (delta_uv0.x * delta_uv1.y - delta_uv1.x * delta_uv0.y);
-
-                    f * (edge0 * delta_uv1.y - edge1 * delta_uv0.y)
+                // glTF asks for flat normals, so every triangle needs its own vertices
+                if normals_missing {
+                    reorder_vertices(
+                        &indices,
+                        &mut vertices,
+                        &mut skin_vertices,
+                        &mut morph_targets,
+                    );
+                    indices = (0..(vertices.len() as u32)).collect();
+                    compute_flat_normals(&mut vertices);
                 }
 
                 if tangents_missing && !uv_missing {
@@ -336,23 +579,82 @@ impl AssetLoader {
                         let uv1 = vertices[triangle[1]].uv.into();
                         let uv2 = vertices[triangle[2]].uv.into();
 
-                        let tangent = compute_tangent(p0, p1, p2, uv0, uv1, uv2);
+                        let Some(tangent) = compute_tangent(p0, p1, p2, uv0, uv1, uv2) else {
+                            continue;
+                        };
 
-                        vertices[triangle[0]].tangent = tangent.into_homogeneous_point().into();
-                        vertices[triangle[1]].tangent = tangent.into_homogeneous_point().into();
-                        vertices[triangle[2]].tangent = tangent.into_homogeneous_point().into();
+                        // shared vertices get the sum of their triangles, which gets normalized below
+                        for index in triangle {
+                            let sum = Vec4::from(vertices[index].tangent).xyz() + tangent;
+                            vertices[index].tangent = sum.into_homogeneous_point().into();
+                        }
                     }
                 } else if tangents_missing && uv_missing {
-                    println!("Can't manually calculate tangents without uvs");
+                    log::warn!("Can't manually calculate tangents without uvs");
+                }
+
+                let replaced_vertex_count = vertices
+                    .iter_mut()
+                    .map(sanitize_normal_and_tangent)
+                    .filter(|is_replaced| *is_replaced)
+                    .count();
+                if replaced_vertex_count > 0 {
+                    log::warn!(
+                        "Replaced the normals or tangents of {} vertices, they were zero or not finite",
+                        replaced_vertex_count
+                    );
+                }
+
+                if optimize_meshes {
+                    let acmr_before = average_cache_miss_ratio(&indices);
+                    optimize_vertex_cache(&mut indices, vertices.len());
+
+                    let mut order: Vec<u32> = (0..vertices.len() as u32).collect();
+                    optimize_vertex_fetch(&mut order, &mut indices);
+                    reorder_vertices(
+                        &order,
+                        &mut vertices,
+                        &mut skin_vertices,
+                        &mut morph_targets,
+                    );
+                    loading_data.optimization_stats.add(
+                        indices.len() / 3,
+                        acmr_before,
+                        average_cache_miss_ratio(&indices),
+                    );
+                }
+
+                // every level of detail has about half the triangles of the previous one
+                let mut lods: Vec<Vec<u32>> = vec![];
+                for level in 1..MAX_LOD_COUNT {
+                    let target_index_count = (indices.len() >> level) / 3 * 3;
+                    let mut lod =
+                        simplify(&indices, &vertices, target_index_count, LOD_TARGET_ERROR);
+
+                    // not worth keeping when the simplification got stuck
+                    let previous_index_count = lods.last().unwrap_or(&indices).len();
+                    if lod.len() as f32 > previous_index_count as f32 * 0.9 {
+                        break;
+                    }
+
+                    if optimize_meshes {
+                        optimize_vertex_cache(&mut lod, vertices.len());
+                    }
+                    lods.push(lod);
                 }
 
                 Arc::new(LoadedMesh {
                     id,
+                    bounds: Aabb::from_vertices(&vertices),
                     vertices,
+                    skin_vertices,
+                    morph_targets,
                     indices,
+                    lods,
                 })
             })
-            .clone()
+            .clone();
+        Some(mesh)
     }
 
     fn load_images(
@@ -372,19 +674,43 @@ impl AssetLoader {
             .assets
             .entry(id)
             .or_insert_with(|| {
-                let image = loading_data.images.remove(&texture_index).unwrap();
-                let (bytes, format) =
-                    gltf_image_format_to_vulkan_format(image.pixels, &image.format);
+                let compressed_image = if self.keep_images_compressed {
+                    read_compressed_image(loading_data, texture.source().source())
+                } else {
+                    None
+                };
+                let data = match compressed_image {
+                    Some(compressed_image) => {
+                        let (dimensions, format) = compressed_image
+                            .dimensions_and_format()
+                            .expect("Could not read image header");
+                        BytesImageData {
+                            dimensions,
+                            format,
+                            color_space,
+                            bytes: ImageBytes::Compressed(compressed_image),
+                        }
+                    }
+                    None => {
+                        let image = match loading_data.images.remove(&texture_index) {
+                            Some(image) => image,
+                            None => decode_image(
+                                texture.source().source(),
+                                &loading_data.base_path,
+                                &loading_data.buffers,
+                            )
+                            .expect("Could not load image"),
+                        };
+                        BytesImageData {
+                            dimensions: image.dimensions,
+                            format: image.format,
+                            color_space,
+                            bytes: ImageBytes::Decoded(image.bytes),
+                        }
+                    }
+                };
 
-                Arc::new(LoadedImage {
-                    id,
-                    data: BytesImageData {
-                        dimensions: (image.width, image.height),
-                        format,
-                        color_space,
-                        bytes,
-                    },
-                })
+                Arc::new(LoadedImage { id, data })
             })
             .clone()
     }
@@ -431,6 +757,59 @@ impl AssetLoader {
     }
 }
 
+fn load_light(
+    light: &gltf::khr_lights_punctual::Light<'_>,
+    node: usize,
+    transform: Transform,
+) -> Light {
+    let kind = match light.kind() {
+        gltf::khr_lights_punctual::Kind::Directional => LightKind::Directional,
+        gltf::khr_lights_punctual::Kind::Point => LightKind::Point,
+        gltf::khr_lights_punctual::Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => LightKind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        },
+    };
+    Light {
+        node,
+        transform,
+        kind,
+        color: light.color().into(),
+        intensity: light.intensity(),
+        range: light.range(),
+    }
+}
+
+fn load_skins(gltf: &gltf::Document, loading_data: &SceneLoadingData) -> Vec<LoadedSkin> {
+    gltf.skins()
+        .map(|skin| {
+            let joints: Vec<usize> = skin
+                .joints()
+                .map(|joint| {
+                    *loading_data
+                        .node_indices
+                        .get(&joint.index())
+                        .expect("Skin joints must be in the scene")
+                })
+                .collect();
+
+            let reader = skin.reader(|buffer| Some(&loading_data.buffers[buffer.index()]));
+            let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+                Some(matrices) => matrices.map(Mat4::from).collect(),
+                None => vec![Mat4::identity(); joints.len()],
+            };
+
+            LoadedSkin {
+                joints,
+                inverse_bind_matrices,
+            }
+        })
+        .collect()
+}
+
 fn load_animations(gltf: &gltf::Document, loading_data: &SceneLoadingData) -> Vec<Animation> {
     let mut animations = vec![];
     for animation in gltf.animations() {
@@ -448,11 +827,11 @@ fn load_animations(gltf: &gltf::Document, loading_data: &SceneLoadingData) -> Ve
             timestamps = match reader.read_inputs() {
                 Some(gltf::accessor::Iter::Standard(times)) => times.collect::<Vec<_>>(),
                 Some(_) => {
-                    println!("Unexpected accessor type for animation timestamps");
+                    log::warn!("Unexpected accessor type for animation timestamps");
                     continue;
                 }
                 None => {
-                    println!("No timestamps for animations");
+                    log::warn!("No timestamps for animations");
                     continue;
                 }
             };
@@ -475,7 +854,7 @@ fn load_animations(gltf: &gltf::Document, loading_data: &SceneLoadingData) -> Ve
             if timestamps.len() != translation_keyframes.len()
                 || timestamps.len() != rotation_keyframes.len()
             {
-                println!("Animation data is not consistent");
+                log::warn!("Animation data is not consistent");
                 continue;
             }
 
@@ -492,6 +871,217 @@ fn load_animations(gltf: &gltf::Document, loading_data: &SceneLoadingData) -> Ve
     animations
 }
 
+fn load_morph_weights_animations(
+    gltf: &gltf::Document,
+    loading_data: &SceneLoadingData,
+) -> Vec<MorphWeightsAnimation> {
+    let mut animations = vec![];
+    for animation in gltf.animations() {
+        for channel in animation.channels() {
+            let Some(&node) = loading_data
+                .node_indices
+                .get(&channel.target().node().index())
+            else {
+                continue;
+            };
+
+            let reader = channel.reader(|buffer| Some(&loading_data.buffers[buffer.index()]));
+            let Some(gltf::animation::util::ReadOutputs::MorphTargetWeights(weights)) =
+                reader.read_outputs()
+            else {
+                continue;
+            };
+            if channel.sampler().interpolation() == gltf::animation::Interpolation::CubicSpline {
+                log::warn!("Cubic spline interpolation is not supported for morph target weights");
+                continue;
+            }
+
+            let timestamps = match reader.read_inputs() {
+                Some(gltf::accessor::Iter::Standard(times)) => times.collect::<Vec<_>>(),
+                _ => {
+                    log::warn!("No timestamps for morph target weights");
+                    continue;
+                }
+            };
+            let weights: Vec<f32> = weights.into_f32().collect();
+            if timestamps.is_empty() || weights.len() % timestamps.len() != 0 {
+                log::warn!("Morph target weights are not consistent");
+                continue;
+            }
+
+            // the weights of all targets for the first timestamp, then for the second...
+            let target_count = weights.len() / timestamps.len();
+            animations.push(MorphWeightsAnimation {
+                node,
+                timestamps,
+                weights: weights
+                    .chunks_exact(target_count)
+                    .map(<[f32]>::to_vec)
+                    .collect(),
+            });
+        }
+    }
+    animations
+}
+
+/// The translation, rotation and scale channels of every node except the cameras,
+/// which are animated by [`load_animations`]
+fn load_node_animations(
+    gltf: &gltf::Document,
+    loading_data: &SceneLoadingData,
+) -> Vec<NodeAnimation> {
+    let mut animations = vec![];
+    for animation in gltf.animations() {
+        for channel in animation.channels() {
+            let target_node = channel.target().node();
+            if target_node.camera().is_some() {
+                continue;
+            }
+            let Some(&node) = loading_data.node_indices.get(&target_node.index()) else {
+                continue;
+            };
+
+            let reader = channel.reader(|buffer| Some(&loading_data.buffers[buffer.index()]));
+            let values = match reader.read_outputs() {
+                Some(gltf::animation::util::ReadOutputs::Translations(v)) => {
+                    NodeAnimationValues::Translations(v.map(Vec3::from).collect())
+                }
+                Some(gltf::animation::util::ReadOutputs::Rotations(v)) => {
+                    NodeAnimationValues::Rotations(
+                        v.into_f32().map(Rotor3::from_quaternion_array).collect(),
+                    )
+                }
+                Some(gltf::animation::util::ReadOutputs::Scales(v)) => {
+                    NodeAnimationValues::Scales(v.map(Vec3::from).collect())
+                }
+                _ => continue,
+            };
+            if channel.sampler().interpolation() == gltf::animation::Interpolation::CubicSpline {
+                log::warn!("Cubic spline interpolation is not supported for node animations");
+                continue;
+            }
+
+            let timestamps = match reader.read_inputs() {
+                Some(gltf::accessor::Iter::Standard(times)) => times.collect::<Vec<_>>(),
+                _ => {
+                    log::warn!("No timestamps for node animations");
+                    continue;
+                }
+            };
+            let value_count = match &values {
+                NodeAnimationValues::Translations(v) | NodeAnimationValues::Scales(v) => v.len(),
+                NodeAnimationValues::Rotations(v) => v.len(),
+            };
+            if timestamps.is_empty() || value_count != timestamps.len() {
+                log::warn!("Node animation data is not consistent");
+                continue;
+            }
+
+            animations.push(NodeAnimation {
+                node,
+                timestamps,
+                values,
+            });
+        }
+    }
+    animations
+}
+
+/// Without KHR_materials_emissive_strength, the factor is used as it is
+fn emissivity(emissive_factor: [f32; 3], emissive_strength: Option<f32>) -> Vec3 {
+    Vec3::from(emissive_factor) * emissive_strength.unwrap_or(1.0)
+}
+
+fn reorder<T: Copy>(values: &[T], order: &[u32]) -> Vec<T> {
+    order.iter().map(|&index| values[index as usize]).collect()
+}
+
+/// Every per vertex attribute needs the same order
+fn reorder_vertices(
+    order: &[u32],
+    vertices: &mut Vec<Vertex>,
+    skin_vertices: &mut Option<Vec<SkinVertex>>,
+    morph_targets: &mut [MorphTarget],
+) {
+    *vertices = reorder(vertices, order);
+    if let Some(skin_vertices) = skin_vertices {
+        *skin_vertices = reorder(skin_vertices, order);
+    }
+    for morph_target in morph_targets {
+        morph_target.position_deltas = reorder(&morph_target.position_deltas, order);
+        morph_target.normal_deltas = reorder(&morph_target.normal_deltas, order);
+    }
+}
+
+/// Every three vertices are a triangle, and get the normal of its face.
+/// Degenerate triangles end up with a zero normal.
+fn compute_flat_normals(vertices: &mut [Vertex]) {
+    for triangle in vertices.chunks_exact_mut(3) {
+        let [p0, p1, p2] = [0, 1, 2].map(|corner| Vec3::from(triangle[corner].position));
+        let normal = (p1 - p0).cross(p2 - p0);
+        let normal = if normal.mag_sq() > 0.0 {
+            normal.normalized()
+        } else {
+            Vec3::zero()
+        };
+        for vertex in triangle {
+            vertex.normal = normal.into();
+        }
+    }
+}
+
+/// None for triangles without an area in uv space
+fn compute_tangent(p0: Vec3, p1: Vec3, p2: Vec3, uv0: Vec2, uv1: Vec2, uv2: Vec2) -> Option<Vec3> {
+    let edge0 = p1 - p0;
+    let delta_uv0 = uv1 - uv0;
+    let edge1 = p2 - p0;
+    let delta_uv1 = uv2 - uv0;
+
+    let determinant = delta_uv0.x * delta_uv1.y - delta_uv1.x * delta_uv0.y;
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let f = 1.0 / determinant;
+
+    let tangent = f * (edge0 * delta_uv1.y - edge1 * delta_uv0.y);
+    (tangent.x.is_finite() && tangent.y.is_finite() && tangent.z.is_finite()).then_some(tangent)
+}
+
+/// Normalizes the normal and the tangent, and makes the tangent perpendicular to the normal (Gram-Schmidt).
+/// Vectors that are zero or not finite get replaced by an arbitrary orthonormal basis.
+/// Returns whether something had to be replaced.
+fn sanitize_normal_and_tangent(vertex: &mut Vertex) -> bool {
+    let is_usable = |vector: Vec3| vector.mag_sq().is_finite() && vector.mag_sq() > 1e-12;
+    let mut is_replaced = false;
+
+    let normal = Vec3::from(vertex.normal);
+    let normal = if is_usable(normal) {
+        normal.normalized()
+    } else {
+        is_replaced = true;
+        Vec3::unit_y()
+    };
+
+    let tangent = Vec4::from(vertex.tangent).xyz();
+    let tangent = tangent - normal * normal.dot(tangent);
+    let tangent = if is_usable(tangent) {
+        tangent.normalized()
+    } else {
+        is_replaced = true;
+        let axis = if normal.x.abs() < 0.9 {
+            Vec3::unit_x()
+        } else {
+            Vec3::unit_y()
+        };
+        normal.cross(axis).normalized()
+    };
+    let handedness = if vertex.tangent[3] < 0.0 { -1.0 } else { 1.0 };
+
+    vertex.normal = normal.into();
+    vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+    is_replaced
+}
+
 impl From<gltf::texture::WrappingMode> for AddressMode {
     fn from(wrapping_mode: gltf::texture::WrappingMode) -> Self {
         match wrapping_mode {
@@ -535,6 +1125,108 @@ impl From<gltf::texture::MinFilter> for FilterAndMipmapMode {
     }
 }
 
+/// None for embedded data URIs and unknown encodings, those get decoded right away
+fn read_compressed_image(
+    loading_data: &SceneLoadingData,
+    source: gltf::image::Source,
+) -> Option<CompressedImage> {
+    fn encoding_from_mime_type(mime_type: &str) -> Option<ImageEncoding> {
+        match mime_type {
+            "image/png" => Some(ImageEncoding::Png),
+            "image/jpeg" => Some(ImageEncoding::Jpeg),
+            _ => None,
+        }
+    }
+
+    match source {
+        gltf::image::Source::View { view, mime_type } => {
+            let encoding = encoding_from_mime_type(mime_type)?;
+            let buffer = &loading_data.buffers[view.buffer().index()];
+            let bytes = buffer[view.offset()..view.offset() + view.length()].to_vec();
+            Some(CompressedImage { encoding, bytes })
+        }
+        gltf::image::Source::Uri { uri, mime_type } => {
+            if uri.starts_with("data:") {
+                return None;
+            }
+            let encoding = match mime_type {
+                Some(mime_type) => encoding_from_mime_type(mime_type)?,
+                None => match uri.rsplit('.').next() {
+                    Some("png") => ImageEncoding::Png,
+                    Some("jpg") | Some("jpeg") => ImageEncoding::Jpeg,
+                    _ => return None,
+                },
+            };
+            let bytes = std::fs::read(loading_data.base_path.join(uri)).ok()?;
+            Some(CompressedImage { encoding, bytes })
+        }
+    }
+}
+
+/// In a format that Vulkan supports
+struct DecodedImage {
+    dimensions: (u32, u32),
+    format: ImageFormat,
+    bytes: Vec<u8>,
+}
+
+fn decode_image(
+    source: gltf::image::Source<'_>,
+    base_path: &Path,
+    buffers: &[gltf::buffer::Data],
+) -> gltf::Result<DecodedImage> {
+    let image = gltf::image::Data::from_source(source, Some(base_path), buffers)?;
+    let (bytes, format) = gltf_image_format_to_vulkan_format(image.pixels, &image.format);
+    Ok(DecodedImage {
+        dimensions: (image.width, image.height),
+        format,
+        bytes,
+    })
+}
+
+/// Decodes all images of the document on every core, in the order of their indices
+fn decode_images(
+    document: &gltf::Document,
+    base_path: &Path,
+    buffers: &[gltf::buffer::Data],
+) -> gltf::Result<Vec<DecodedImage>> {
+    let images: Vec<_> = document.images().collect();
+    let thread_count = std::thread::available_parallelism()
+        .map_or(1, |count| count.get())
+        .min(images.len());
+    // large and small images are mixed, so every thread takes the next image when it is done
+    let next_index = AtomicUsize::new(0);
+
+    let mut decoded_images = std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..thread_count)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut decoded_images = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::Relaxed);
+                        let Some(image) = images.get(index) else {
+                            break;
+                        };
+                        decoded_images
+                            .push((index, decode_image(image.source(), base_path, buffers)));
+                    }
+                    decoded_images
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .flat_map(|thread| thread.join().expect("Could not decode images"))
+            .collect::<Vec<_>>()
+    });
+
+    decoded_images.sort_by_key(|(index, _)| *index);
+    decoded_images
+        .into_iter()
+        .map(|(_, decoded_image)| decoded_image)
+        .collect()
+}
+
 fn gltf_image_format_to_vulkan_format(
     image: Vec<u8>,
     format: &gltf::image::Format,
@@ -544,11 +1236,9 @@ fn gltf_image_format_to_vulkan_format(
         gltf::image::Format::R8G8 => (image, ImageFormat::R8G8_UNORM),
         gltf::image::Format::R8G8B8 => {
             // rarely supported format
-            let mut image_with_alpha = Vec::new();
-            for i in 0..image.len() / 3 {
-                image_with_alpha.push(image[i * 3]);
-                image_with_alpha.push(image[i * 3 + 1]);
-                image_with_alpha.push(image[i * 3 + 2]);
+            let mut image_with_alpha = Vec::with_capacity(image.len() / 3 * 4);
+            for pixel in image.chunks_exact(3) {
+                image_with_alpha.extend_from_slice(pixel);
                 image_with_alpha.push(255);
             }
             (image_with_alpha, ImageFormat::R8G8B8A8_UNORM)
@@ -568,3 +1258,117 @@ fn gltf_image_format_to_vulkan_format(
         gltf::image::Format::R32G32B32A32FLOAT => (image, ImageFormat::R32G32B32A32_SFLOAT),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangle_soup_without_normals() {
+        let scene = AssetLoader::new()
+            .load_scene("assets/scene/tests/triangle_soup_without_normals.gltf")
+            .expect("Could not load the test scene");
+        let mesh = &scene.models[0].primitives[0].mesh;
+
+        let mut indices = mesh.indices.clone();
+        indices.sort();
+        assert_eq!(indices, (0..6).collect::<Vec<u32>>());
+
+        let mut face_normals = vec![];
+        for triangle in mesh.indices.chunks_exact(3) {
+            let vertices = [0, 1, 2].map(|corner| mesh.vertices[triangle[corner] as usize]);
+            let [p0, p1, p2] = vertices.map(|vertex| Vec3::from(vertex.position));
+            let face_normal = (p1 - p0).cross(p2 - p0).normalized();
+            for vertex in vertices {
+                assert!((Vec3::from(vertex.normal) - face_normal).mag() < 1e-6);
+            }
+            face_normals.push(face_normal);
+        }
+        assert!(face_normals.contains(&Vec3::unit_z()));
+        assert!(face_normals.contains(&Vec3::unit_y()));
+    }
+
+    #[test]
+    fn joints_are_animated() {
+        let mut scene = AssetLoader::new()
+            .load_scene("assets/scene/tests/animated_skin.gltf")
+            .expect("Could not load the test scene");
+        assert_eq!(scene.skins.len(), 1);
+        assert_eq!(scene.node_animations.len(), 1);
+        assert!(scene.camera_animations.is_empty());
+
+        let animation = &scene.node_animations[0];
+        let tip_joint = scene.skins[0].joints[1];
+        assert_eq!(animation.node, tip_joint);
+
+        let scene_graph = &mut scene.scene_graph;
+        let mut local_transform = scene_graph.nodes[tip_joint].local_transform.clone();
+        animation.apply(1.0 - 1e-4, &mut local_transform);
+        scene_graph.set_local_transform(tip_joint, local_transform);
+
+        // the x axis of the tip joint now points along y
+        let world_transform = Mat4::from(scene_graph.world_transform(tip_joint));
+        let x_axis = world_transform.transform_vec3(Vec3::unit_x());
+        assert!((x_axis - Vec3::unit_y()).mag() < 1e-3, "{:?}", x_axis);
+        assert_eq!(world_transform.extract_translation(), Vec3::unit_y());
+    }
+
+    #[test]
+    fn emissive_strength_scales_the_factor() {
+        assert_eq!(
+            emissivity([1.0, 0.5, 0.0], Some(4.0)),
+            Vec3::new(4.0, 2.0, 0.0)
+        );
+        assert_eq!(emissivity([1.0, 0.5, 0.0], None), Vec3::new(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn tangent_of_a_triangle() {
+        let tangent = compute_tangent(
+            Vec3::zero(),
+            Vec3::unit_x(),
+            Vec3::unit_y(),
+            Vec2::zero(),
+            Vec2::unit_x(),
+            Vec2::unit_y(),
+        );
+        assert_eq!(tangent, Some(Vec3::unit_x()));
+
+        // all three corners share one uv
+        let tangent = compute_tangent(
+            Vec3::zero(),
+            Vec3::unit_x(),
+            Vec3::unit_y(),
+            Vec2::one(),
+            Vec2::one(),
+            Vec2::one(),
+        );
+        assert_eq!(tangent, None);
+    }
+
+    #[test]
+    fn sanitize_broken_normals_and_tangents() {
+        let broken_vectors = [
+            [0.0, 0.0, 0.0],
+            [f32::NAN, 0.0, 0.0],
+            [f32::INFINITY, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ];
+        for normal in broken_vectors {
+            for [x, y, z] in broken_vectors {
+                let mut vertex = Vertex {
+                    position: [0.0; 3],
+                    normal,
+                    uv: [0.0; 2],
+                    tangent: [x, y, z, 1.0],
+                };
+                sanitize_normal_and_tangent(&mut vertex);
+
+                assert!(vertex.has_valid_normal_and_tangent(), "{:?}", vertex);
+                let normal = Vec3::from(vertex.normal);
+                let tangent = Vec4::from(vertex.tangent).xyz();
+                assert!(normal.dot(tangent).abs() < 1e-6, "{:?}", vertex);
+            }
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use ultraviolet::Mat4;
+
+pub struct LoadedSkin {
+    /// Nodes of the scene graph
+    pub joints: Vec<usize>,
+    /// Transform from the space of the mesh into the space of each joint, in its rest pose
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
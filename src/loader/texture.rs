@@ -1,4 +1,6 @@
-use std::sync::Arc;
+use std::{borrow::Cow, io::Cursor, sync::Arc};
+
+use image::ImageDecoder;
 
 use super::{Asset, AssetId};
 
@@ -22,9 +24,96 @@ pub struct BytesImageData {
     pub dimensions: (u32, u32),
     pub format: ImageFormat,
     pub color_space: ColorSpace,
+    pub bytes: ImageBytes,
+}
+
+impl BytesImageData {
+    /// Decodes compressed images, so it should only be called once per image
+    pub fn pixels(&self) -> Cow<'_, [u8]> {
+        match &self.bytes {
+            ImageBytes::Decoded(bytes) => Cow::Borrowed(bytes),
+            ImageBytes::Compressed(compressed) => Cow::Owned(compressed.decode(self.format)),
+        }
+    }
+}
+
+pub enum ImageBytes {
+    /// In the layout of the image format
+    Decoded(Vec<u8>),
+    /// Only decoded right before the upload, which keeps a loaded scene small
+    Compressed(CompressedImage),
+}
+
+/// The unchanged bytes of a PNG or JPEG file
+pub struct CompressedImage {
+    pub encoding: ImageEncoding,
     pub bytes: Vec<u8>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageEncoding {
+    Png,
+    Jpeg,
+}
+
+impl CompressedImage {
+    /// Only reads the header, the pixels stay compressed
+    pub fn dimensions_and_format(&self) -> image::ImageResult<((u32, u32), ImageFormat)> {
+        let (dimensions, color_type) = match self.encoding {
+            ImageEncoding::Png => {
+                let decoder = image::codecs::png::PngDecoder::new(Cursor::new(&self.bytes))?;
+                (decoder.dimensions(), decoder.color_type())
+            }
+            ImageEncoding::Jpeg => {
+                let decoder = image::codecs::jpeg::JpegDecoder::new(Cursor::new(&self.bytes))?;
+                (decoder.dimensions(), decoder.color_type())
+            }
+        };
+
+        // three channel formats are rarely supported, so they get an alpha channel
+        let format = match color_type {
+            image::ColorType::L8 => ImageFormat::R8_UNORM,
+            image::ColorType::La8 => ImageFormat::R8G8_UNORM,
+            image::ColorType::L16 => ImageFormat::R16_UNORM,
+            image::ColorType::La16 => ImageFormat::R16G16_UNORM,
+            image::ColorType::Rgb16 | image::ColorType::Rgba16 => ImageFormat::R16G16B16A16_UNORM,
+            image::ColorType::Rgb32F | image::ColorType::Rgba32F => {
+                ImageFormat::R32G32B32A32_SFLOAT
+            }
+            _ => ImageFormat::R8G8B8A8_UNORM,
+        };
+        Ok((dimensions, format))
+    }
+
+    fn decode(&self, format: ImageFormat) -> Vec<u8> {
+        let encoding = match self.encoding {
+            ImageEncoding::Png => image::ImageFormat::Png,
+            ImageEncoding::Jpeg => image::ImageFormat::Jpeg,
+        };
+        let image = image::load_from_memory_with_format(&self.bytes, encoding)
+            .expect("Could not decode image");
+
+        match format {
+            ImageFormat::R8_UNORM => image.into_luma8().into_raw(),
+            ImageFormat::R8G8_UNORM => image.into_luma_alpha8().into_raw(),
+            ImageFormat::R8G8B8A8_UNORM => image.into_rgba8().into_raw(),
+            ImageFormat::R16_UNORM => bytes_of(image.into_luma16().into_raw()),
+            ImageFormat::R16G16_UNORM => bytes_of(image.into_luma_alpha16().into_raw()),
+            ImageFormat::R16G16B16A16_UNORM => bytes_of(image.into_rgba16().into_raw()),
+            ImageFormat::R32G32B32A32_SFLOAT => image
+                .into_rgba32f()
+                .into_raw()
+                .into_iter()
+                .flat_map(f32::to_ne_bytes)
+                .collect(),
+        }
+    }
+}
+
+fn bytes_of(values: Vec<u16>) -> Vec<u8> {
+    values.into_iter().flat_map(u16::to_ne_bytes).collect()
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 /// A list of the more common image formats that we actually support.
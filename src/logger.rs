@@ -0,0 +1,35 @@
+use std::str::FromStr;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Set this to a log level like `debug` to see more messages
+const LOG_LEVEL_ENV_VAR: &str = "RTR_LOG";
+
+/// Prints everything to stderr, prefixed with the level and the target
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+pub fn init() {
+    let level = std::env::var(LOG_LEVEL_ENV_VAR)
+        .ok()
+        .and_then(|value| LevelFilter::from_str(&value).ok())
+        .unwrap_or(LevelFilter::Info);
+
+    log::set_logger(&LOGGER).expect("Could not set logger");
+    log::set_max_level(level);
+}
@@ -1,10 +1,14 @@
 mod camera;
 mod config_loader;
+mod frame_times;
 mod input_map;
 mod loader;
+mod logger;
 mod render;
 mod scene;
+mod scene_reload_stress;
 mod scene_uploader;
+mod texture_inspector;
 mod time;
 mod transform;
 mod utility;
@@ -12,31 +16,45 @@ mod vulkan;
 
 use camera::animation_camera_controller::AnimationCameraController;
 use gpu_allocator::vulkan::*;
-use loader::AssetLoader;
+use loader::{AssetLoader, LoadedScene, PrimitiveScene, SceneStreamEvent};
 use render::{MainRenderer, SwapchainIndex};
 use scene::Scene;
+use scene_uploader::{SceneStream, SceneUploader};
 use std::mem::ManuallyDrop;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use ash::{self, vk};
 use camera::freecam_controller::FreecamController;
-use camera::Camera;
+use camera::{Camera, FovMode};
 use input_map::InputMap;
 use time::Time;
 use ultraviolet::Vec2;
 use winit::dpi::{self, PhysicalSize};
 use winit::event::{
-    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
 };
 use winit::event_loop::EventLoop;
 use winit::window::{CursorGrabMode, Window, WindowBuilder};
 
+use crate::frame_times::FrameTimes;
 use crate::render::set_layout_cache::DescriptorSetLayoutCache;
+use crate::scene_reload_stress::SceneReloadStress;
+use crate::texture_inspector::TextureInspector;
 use crate::vulkan::command_pool::CommandPool;
 use crate::vulkan::context::Context;
+use crate::vulkan::debug_label::{begin_debug_label, end_debug_label};
+use crate::vulkan::descriptor_pool::DescriptorPool;
+use crate::vulkan::image_view_cache::ImageViewCache;
+use crate::vulkan::memory_stats::MemoryReport;
+use crate::vulkan::shader_create_info::ShaderError;
 use crate::vulkan::swapchain::SwapchainContainer;
 
-// Rust will drop these fields in the order they are declared
+// Rust will drop these fields in the order they are declared, after `Drop::drop`.
+// Everything that owns GPU resources has to come before the low level Vulkan stuff and the context.
 struct CatDemo {
     egui_integration:
         Option<ManuallyDrop<egui_winit_ash_integration::Integration<Arc<Mutex<Allocator>>>>>,
@@ -46,20 +64,50 @@ struct CatDemo {
     renderer: MainRenderer,
 
     scene: Scene,
+    /// Set while the models of the scene are still arriving
+    scene_stream: Option<SceneStream>,
     input_map: InputMap,
     time: Time,
+    frame_times: FrameTimes,
+    texture_inspector: TextureInspector,
     freecam_controller: FreecamController,
     animation_camera_controller: AnimationCameraController,
     camera: Camera,
     is_playing_camera_animation: bool,
+    /// From the last shader reload, shown until the shaders compile again
+    shader_errors: Vec<ShaderError>,
+    /// Scenes in the assets directory that can be picked in the UI
+    scene_files: Vec<PathBuf>,
+    /// None for the generated test scenes
+    scene_path: Option<PathBuf>,
+    /// Gets loaded before the next frame
+    next_scene_path: Option<PathBuf>,
+    next_primitive_scene: Option<PrimitiveScene>,
+    /// A pasted camera position, see `config_loader::CameraPosition`
+    viewpoint_text: String,
+    /// Result of the last copy or paste of a viewpoint
+    viewpoint_status: String,
+    /// Result of the last save of the material edits
+    material_edits_status: String,
+    /// Counts down, the demo exits at 0
+    remaining_frames: Option<u32>,
+    /// Switches to the scene when `remaining_frames` reaches the count
+    scheduled_scene_switch: Option<(u32, PathBuf)>,
+    scene_reload_stress: Option<SceneReloadStress>,
+    /// Refreshed every `MEMORY_REPORT_INTERVAL`, so that the stats aren't locked every frame
+    memory_report: MemoryReport,
+    memory_report_time: Instant,
 
     // Low level Vulkan stuff
-    descriptor_set_pool: vk::DescriptorPool,
-    _descriptor_set_layout_cache: DescriptorSetLayoutCache,
+    descriptor_set_pool: DescriptorPool,
+    descriptor_set_layout_cache: DescriptorSetLayoutCache,
+    image_view_cache: ImageViewCache,
     command_pool: CommandPool,
 
     command_buffers: Vec<vk::CommandBuffer>,
     should_recreate_swapchain: bool,
+    /// Frames since the last resize event, the swapchain gets recreated once the size is stable
+    frames_since_resize: Option<u32>,
 
     /// wait semaphore
     present_complete_semaphore: vk::Semaphore,
@@ -76,9 +124,27 @@ struct CatDemo {
 }
 
 impl CatDemo {
-    pub fn new(event_loop: &EventLoop<()>) -> Self {
+    /// Dragging the window border sends a resize event almost every frame
+    const RESIZE_DEBOUNCE_FRAMES: u32 = 3;
+    const MEMORY_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(
+        event_loop: &EventLoop<()>,
+        command_line_args: config_loader::CommandLineArgs,
+    ) -> Self {
         let mut config_file_loader = config_loader::ConfigFileLoader::new("config.json");
         let config = config_file_loader.load_config();
+        let scene_path = command_line_args
+            .scene_path
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(&config.scene_path));
+        if !scene_path.is_file() {
+            panic!(
+                "Could not find the scene {}, set scene_path in {} or pass --scene <path>",
+                scene_path.display(),
+                config_file_loader.path.display()
+            );
+        }
         let (window_width, window_height) = (800, 600);
 
         let window = WindowBuilder::new()
@@ -91,23 +157,30 @@ impl CatDemo {
             .build(event_loop)
             .expect("Could not create window");
 
-        let mut asset_loader = AssetLoader::new();
-        let mut loaded_scene = asset_loader
-            .load_scene(&config.scene_path)
-            .expect("Could not load scene");
-        println!("Loaded scene : {:?}", loaded_scene.models.len());
-
-        let mut freecam_controller = FreecamController::new(5.0, 0.01);
+        // the scene loads on another thread while the window and the device get created
+        let scene_receiver = config
+            .stream_scene_loading
+            .then(|| stream_scene_file(config, &scene_path));
+        let mut loaded_scene = scene_receiver
+            .is_none()
+            .then(|| load_scene_file(config, &scene_path));
+
+        let mut freecam_controller = FreecamController::new(
+            config.settings.camera_speed,
+            config.settings.camera_sensitivity,
+            config.settings.camera_sprint_multiplier,
+            config.settings.key_bindings.clone(),
+        );
         if let Some(camera_position) = &config.cached.camera_position {
             freecam_controller.position = camera_position.position;
             freecam_controller.pitch = camera_position.pitch;
             freecam_controller.yaw = camera_position.yaw;
         }
 
-        let animation_camera_controller = if loaded_scene.camera_animations.is_empty() {
-            AnimationCameraController::new(Default::default())
-        } else {
-            AnimationCameraController::new(loaded_scene.camera_animations.swap_remove(0))
+        let animation_camera_controller = match &mut loaded_scene {
+            Some(loaded_scene) => take_camera_animation(loaded_scene),
+            // replaced once the scene is loaded
+            None => AnimationCameraController::new(Default::default()),
         };
         let camera = Camera::new(
             window_width as f32 / window_height as f32,
@@ -131,7 +204,8 @@ impl CatDemo {
             device: device.clone(),
             physical_device: context.physical_device,
             debug_settings: Default::default(),
-            buffer_device_address: false,
+            // Matches the bufferDeviceAddress feature, which the context always enables
+            buffer_device_address: true,
             allocation_sizes: Default::default(),
         })
         .expect("Could not create allocator");
@@ -144,18 +218,27 @@ impl CatDemo {
                     ty: vk::DescriptorType::UNIFORM_BUFFER,
                     descriptor_count: 200,
                 },
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                    descriptor_count: 10,
+                },
+                // Includes the bindless texture array of the raytracing scene
                 vk::DescriptorPoolSize {
                     ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    descriptor_count: 1000,
+                },
+                // Includes the skinned models and the primitives with morph targets
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::STORAGE_BUFFER,
                     descriptor_count: 200,
                 },
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::STORAGE_IMAGE,
+                    descriptor_count: 10,
+                },
             ];
 
-            let create_info = vk::DescriptorPoolCreateInfo::builder()
-                .max_sets(1000)
-                .pool_sizes(&pool_sizes);
-
-            unsafe { device.create_descriptor_pool(&create_info, None) }
-                .expect("Could not create descriptor pool")
+            DescriptorPool::new(context.clone(), &pool_sizes, 1000)
         };
 
         let command_buffers = {
@@ -193,6 +276,7 @@ impl CatDemo {
         };
 
         let descriptor_set_layout_cache = DescriptorSetLayoutCache::new(context.clone());
+        let mut image_view_cache = ImageViewCache::new(context.clone());
 
         let fence = {
             let create_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
@@ -213,24 +297,51 @@ impl CatDemo {
             (present_complete_semaphore, rendering_complete_semaphore)
         };
 
-        let scene = scene_uploader::setup(
-            loaded_scene,
-            context.clone(),
-            descriptor_pool,
-            &descriptor_set_layout_cache,
-            context.queue,
-            command_pool.clone(),
-        );
+        let scene_stream = scene_receiver.map(|scene_receiver| {
+            let uploader = SceneUploader::new(
+                context.clone(),
+                &mut image_view_cache,
+                context.queue,
+                command_pool.clone(),
+                config.compact_acceleration_structures,
+                config.settings.sampler_overrides,
+            );
+            SceneStream::new(scene_receiver, uploader)
+        });
+        let scene = match loaded_scene {
+            Some(loaded_scene) => scene_uploader::setup(
+                loaded_scene,
+                context.clone(),
+                &descriptor_pool,
+                &descriptor_set_layout_cache,
+                &mut image_view_cache,
+                context.queue,
+                command_pool.clone(),
+                config.compact_acceleration_structures,
+                config.settings.sampler_overrides,
+            ),
+            None => Scene::new(config.settings.sampler_overrides),
+        };
         let renderer = MainRenderer::new(
             context.clone(),
-            descriptor_pool,
+            &descriptor_pool,
             &descriptor_set_layout_cache,
             &scene,
             &swapchain,
             config.brightness,
+            &config.shadow_map,
+            &config.settings,
         );
 
+        let scene_reload_stress = command_line_args.reload_scene_count.map(|reload_count| {
+            let second_scene_path = command_line_args
+                .switch_scene_path
+                .as_ref()
+                .map_or_else(|| scene_path.clone(), PathBuf::from);
+            SceneReloadStress::new(reload_count, scene_path.clone(), second_scene_path)
+        });
         let time = Time::new();
+        let memory_report = context.memory_stats.report();
         Self {
             window,
             context,
@@ -238,10 +349,12 @@ impl CatDemo {
 
             command_pool,
             descriptor_set_pool: descriptor_pool,
-            _descriptor_set_layout_cache: descriptor_set_layout_cache,
+            descriptor_set_layout_cache,
+            image_view_cache,
 
             command_buffers,
             should_recreate_swapchain: false,
+            frames_since_resize: None,
 
             draw_fence: fence,
             present_complete_semaphore,
@@ -252,10 +365,32 @@ impl CatDemo {
             animation_camera_controller,
             camera,
             is_playing_camera_animation: config.is_demo_mode,
+            shader_errors: vec![],
+            scene_files: loader::find_scene_files("assets"),
+            scene_path: Some(scene_path),
+            next_scene_path: None,
+            next_primitive_scene: None,
+            viewpoint_text: String::new(),
+            viewpoint_status: String::new(),
+            material_edits_status: String::new(),
+            remaining_frames: command_line_args.exit_after_frames,
+            scheduled_scene_switch: command_line_args
+                .switch_scene_path
+                .filter(|_| scene_reload_stress.is_none())
+                .map(|path| {
+                    let frames = command_line_args.exit_after_frames.unwrap_or(0);
+                    (frames / 2, PathBuf::from(path))
+                }),
+            scene_reload_stress,
+            memory_report,
+            memory_report_time: Instant::now(),
             time,
+            frame_times: FrameTimes::new(),
+            texture_inspector: TextureInspector::new(&scene),
 
             renderer,
             scene,
+            scene_stream,
             egui_integration,
             is_demo_mode: config.is_demo_mode,
             config_file_loader,
@@ -283,7 +418,7 @@ impl CatDemo {
                             let aspect_ratio = width as f32 / height as f32;
 
                             self.camera.update_aspect_ratio(aspect_ratio);
-                            self.should_recreate_swapchain = true;
+                            self.frames_since_resize = Some(0);
                         }
                         WindowEvent::KeyboardInput {
                             input:
@@ -301,6 +436,9 @@ impl CatDemo {
                                 (Some(VirtualKeyCode::Escape), ElementState::Pressed) => {
                                     control_flow.set_exit();
                                 }
+                                (Some(VirtualKeyCode::F5), ElementState::Pressed) => {
+                                    self.reload_shaders();
+                                }
                                 _ => (),
                             };
                             match (virtual_keycode, state) {
@@ -347,9 +485,23 @@ impl CatDemo {
                                     self.window.set_cursor_grab(CursorGrabMode::None).unwrap();
                                     self.window.set_cursor_visible(true);
                                 }
+                                (MouseButton::Left, ElementState::Pressed) => {
+                                    self.renderer.pick(mouse_position);
+                                }
                                 _ => {}
                             };
                         }
+                        WindowEvent::MouseWheel { delta, .. } => {
+                            if already_consumed {
+                                return;
+                            }
+                            let lines = match delta {
+                                MouseScrollDelta::LineDelta(_, y) => y,
+                                // roughly the height of a line
+                                MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                            };
+                            self.input_map.accumulate_scroll_delta(lines);
+                        }
                         WindowEvent::CursorMoved { position, .. } => {
                             mouse_position = Vec2::new(position.x as f32, position.y as f32);
                         }
@@ -371,20 +523,43 @@ impl CatDemo {
 
                     self.input_map.clear_mouse_delta();
                     self.draw_frame();
+
+                    if let Some(remaining_frames) = &mut self.remaining_frames {
+                        *remaining_frames = remaining_frames.saturating_sub(1);
+                        if *remaining_frames == 0 {
+                            control_flow.set_exit();
+                        }
+                    }
+                    let remaining_frames = self.remaining_frames.unwrap_or(0);
+                    let is_scene_switch_due = self
+                        .scheduled_scene_switch
+                        .as_ref()
+                        .is_some_and(|(frames, _)| *frames >= remaining_frames);
+                    if is_scene_switch_due {
+                        self.next_scene_path =
+                            self.scheduled_scene_switch.take().map(|(_, path)| path);
+                    }
+                    if self.update_scene_reload_stress() {
+                        control_flow.set_exit();
+                    }
                 }
                 _ => (),
             };
 
             match control_flow {
                 winit::event_loop::ControlFlow::ExitWithCode(_) => {
-                    self.config_file_loader
-                        .get_or_load_config()
-                        .cached
-                        .camera_position = Some(config_loader::CameraPosition {
+                    let config = self.config_file_loader.get_or_load_config();
+                    config.cached.camera_position = Some(config_loader::CameraPosition {
                         position: self.freecam_controller.position,
                         pitch: self.freecam_controller.pitch,
                         yaw: self.freecam_controller.yaw,
                     });
+                    self.renderer.save_settings(&mut config.settings);
+                    config.settings.sampler_overrides = self.scene.sampler_overrides;
+                    config.settings.camera_speed = self.freecam_controller.speed;
+                    config.settings.camera_sensitivity = self.freecam_controller.sensitivity;
+                    config.settings.camera_sprint_multiplier =
+                        self.freecam_controller.sprint_multiplier;
                     self.config_file_loader.save_config();
                 }
                 _ => {}
@@ -404,6 +579,138 @@ impl CatDemo {
         }
     }
 
+    fn reload_shaders(&mut self) {
+        log::info!("Reloading shaders");
+        unsafe { self.context.device.device_wait_idle() }.expect("Could not wait for device idle");
+        self.shader_errors = self
+            .renderer
+            .reload_shaders(&self.descriptor_set_layout_cache);
+        for error in self.shader_errors.iter() {
+            log::warn!("Could not compile shader {}", error);
+        }
+    }
+
+    fn switch_scene(&mut self, path: PathBuf) {
+        log::info!("Switching to scene {}", path.display());
+        unsafe { self.context.device.device_wait_idle() }.expect("Could not wait for device idle");
+
+        // a scene that is still streaming stops loading at its next model once this is dropped
+        self.scene_stream = None;
+        let config = self.config_file_loader.get_or_load_config();
+        if config.stream_scene_loading {
+            let sampler_overrides = self.scene.sampler_overrides;
+            let uploader = SceneUploader::new(
+                self.context.clone(),
+                &mut self.image_view_cache,
+                self.context.queue,
+                self.command_pool.clone(),
+                config.compact_acceleration_structures,
+                sampler_overrides,
+            );
+            self.scene_stream = Some(SceneStream::new(stream_scene_file(config, &path), uploader));
+            self.animation_camera_controller = AnimationCameraController::new(Default::default());
+            self.replace_scene(Scene::new(sampler_overrides));
+        } else {
+            let loaded_scene = load_scene_file(config, &path);
+            self.upload_scene(loaded_scene);
+        }
+        self.scene_path = Some(path);
+    }
+
+    fn switch_to_primitive_scene(&mut self, primitive_scene: PrimitiveScene) {
+        log::info!("Switching to the {} test scene", primitive_scene.name());
+        unsafe { self.context.device.device_wait_idle() }.expect("Could not wait for device idle");
+
+        self.scene_stream = None;
+        let loaded_scene = AssetLoader::new().load_primitive_scene(primitive_scene);
+        self.upload_scene(loaded_scene);
+        self.scene_path = None;
+    }
+
+    fn upload_scene(&mut self, mut loaded_scene: LoadedScene) {
+        let config = self.config_file_loader.get_or_load_config();
+        self.animation_camera_controller = take_camera_animation(&mut loaded_scene);
+        let scene = scene_uploader::setup(
+            loaded_scene,
+            self.context.clone(),
+            &self.descriptor_set_pool,
+            &self.descriptor_set_layout_cache,
+            &mut self.image_view_cache,
+            self.context.queue,
+            self.command_pool.clone(),
+            config.compact_acceleration_structures,
+            self.scene.sampler_overrides,
+        );
+        self.replace_scene(scene);
+    }
+
+    /// Replaces the scene and the renderer, since the renderer keeps the acceleration structures of the scene.
+    /// The device has to be idle.
+    fn replace_scene(&mut self, scene: Scene) {
+        // the old descriptor sets go back into the pool when they are dropped
+        self.scene = scene;
+        self.recreate_renderer();
+        self.recreate_texture_inspector();
+        self.image_view_cache.remove_unused();
+    }
+
+    /// Uploads the models that were loaded since the last frame
+    fn update_scene_stream(&mut self) {
+        let Some(scene_stream) = &mut self.scene_stream else {
+            return;
+        };
+        let Some(mut loaded_scene) = scene_stream.upload_arrived_models(
+            &mut self.scene,
+            &self.descriptor_set_pool,
+            &self.descriptor_set_layout_cache,
+            &mut self.image_view_cache,
+        ) else {
+            return;
+        };
+
+        self.animation_camera_controller = take_camera_animation(&mut loaded_scene);
+        let scene_stream = self.scene_stream.take().unwrap();
+        scene_stream.finish(
+            &mut self.scene,
+            loaded_scene,
+            &self.descriptor_set_pool,
+            &self.descriptor_set_layout_cache,
+            &mut self.image_view_cache,
+        );
+        // the renderer only traces shadow rays when it gets created with a TLAS,
+        // and it must not keep tracing against the TLAS of the previous scene
+        if self.context.context_raytracing.is_some() {
+            unsafe { self.context.device.device_wait_idle() }
+                .expect("Could not wait for device idle");
+            self.recreate_renderer();
+        }
+        self.recreate_texture_inspector();
+    }
+
+    /// Has to be called after the scene got replaced, while the device is idle
+    fn recreate_renderer(&mut self) {
+        let config = self.config_file_loader.get_or_load_config();
+        // the new renderer starts with the current settings
+        self.renderer.save_settings(&mut config.settings);
+        self.renderer = MainRenderer::new(
+            self.context.clone(),
+            &self.descriptor_set_pool,
+            &self.descriptor_set_layout_cache,
+            &self.scene,
+            &self.swapchain,
+            config.brightness,
+            &config.shadow_map,
+            &config.settings,
+        );
+    }
+
+    fn recreate_texture_inspector(&mut self) {
+        if let Some(egui_integration) = &mut self.egui_integration {
+            self.texture_inspector.unregister(egui_integration);
+        }
+        self.texture_inspector = TextureInspector::new(&self.scene);
+    }
+
     fn draw_frame(&mut self) {
         let window_size = self.window.inner_size();
         if window_size.width == 0 || window_size.height == 0 {
@@ -419,23 +726,16 @@ impl CatDemo {
             )
         }
         .expect("Could not wait for fences");
-        // reset fence
-        unsafe {
-            self.context
-                .device
-                .reset_fences(std::slice::from_ref(&self.draw_fence))
-        }
-        .expect("Could not reset fences");
 
-        let viewport = vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: window_size.width as f32,
-            height: window_size.height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        };
+        if let Some(frames_since_resize) = &mut self.frames_since_resize {
+            *frames_since_resize += 1;
+            if *frames_since_resize >= CatDemo::RESIZE_DEBOUNCE_FRAMES {
+                self.frames_since_resize = None;
+                self.should_recreate_swapchain = true;
+            }
+        }
 
+        // out of date and suboptimal swapchains are recreated right away
         if self.should_recreate_swapchain {
             self.swapchain.recreate(window_size);
             if let Some(egui_integration) = &mut self.egui_integration {
@@ -450,6 +750,16 @@ impl CatDemo {
             self.should_recreate_swapchain = false;
         }
 
+        // until the swapchain catches up with a resize, it gets stretched to the window
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.swapchain.extent.width as f32,
+            height: self.swapchain.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+
         let acquire_result = unsafe {
             self.swapchain.loader.acquire_next_image(
                 self.swapchain.inner,
@@ -467,13 +777,29 @@ impl CatDemo {
                 index
             }
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                // nothing was signaled, so the fence must stay signaled for the next frame
                 self.should_recreate_swapchain = true;
                 return;
             }
             _ => panic!("Could not accquire next image"),
         };
 
-        self.renderer.update_descriptor_sets(&self.camera);
+        // From here on the frame always gets submitted and presented,
+        // otherwise the present complete semaphore would stay signaled without a wait.
+        unsafe {
+            self.context
+                .device
+                .reset_fences(std::slice::from_ref(&self.draw_fence))
+        }
+        .expect("Could not reset fences");
+
+        self.scene
+            .update_node_animations(self.time.elapsed().as_secs_f32());
+        self.renderer
+            .update_descriptor_sets(&self.camera, &self.scene, &self.time);
+        self.scene.update_skins();
+        self.scene
+            .update_morph_weights(self.time.elapsed().as_secs_f32());
 
         let command_buffer = self.command_buffers[present_index as usize];
         unsafe {
@@ -502,7 +828,9 @@ impl CatDemo {
         );
 
         if !self.is_demo_mode {
+            begin_debug_label(&self.context, command_buffer, "egui");
             self.draw_ui(&command_buffer, present_index as usize);
+            end_debug_label(&self.context, command_buffer);
         }
         unsafe { self.context.device.end_command_buffer(command_buffer) }
             .expect("Could not end command buffer");
@@ -526,6 +854,11 @@ impl CatDemo {
         }
         .expect("Could not submit to queue");
 
+        // the window changed while the frame was recorded, so the debounce starts over
+        if self.window.inner_size() != window_size {
+            self.frames_since_resize = Some(0);
+        }
+
         let present_info = vk::PresentInfoKHR::builder()
             .wait_semaphores(std::slice::from_ref(&self.rendering_complete_semaphore))
             .swapchains(std::slice::from_ref(&self.swapchain.inner))
@@ -568,6 +901,22 @@ impl CatDemo {
                 "Frametime: {:.4}ms",
                 self.time.delta().as_secs_f64() * 1000.0
             ));
+            self.frame_times.render_ui(ui);
+            ui.separator();
+            let to_mib = |bytes: vk::DeviceSize| bytes as f64 / (1024.0 * 1024.0);
+            ui.label(format!(
+                "Device local memory: {:.1} MiB",
+                to_mib(self.memory_report.device_local_bytes)
+            ));
+            ui.label(format!(
+                "Host visible memory: {:.1} MiB",
+                to_mib(self.memory_report.host_visible_bytes)
+            ));
+            ui.label(format!(
+                "Allocations: {}, largest {:.1} MiB",
+                self.memory_report.allocation_count,
+                to_mib(self.memory_report.largest_allocation)
+            ));
             ui.separator();
             ui.label("Camera Settings: ");
             ui.label("Position: ");
@@ -595,14 +944,140 @@ impl CatDemo {
                 ui.label("pitch:");
                 ui.drag_angle(&mut self.freecam_controller.pitch);
             });
+            ui.add(
+                egui::Slider::new(
+                    &mut self.freecam_controller.speed,
+                    FreecamController::MIN_SPEED..=FreecamController::MAX_SPEED,
+                )
+                .logarithmic(true)
+                .text("Speed (scroll while flying)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.freecam_controller.sensitivity, 0.001..=0.1)
+                    .logarithmic(true)
+                    .text("Sensitivity"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.freecam_controller.sprint_multiplier, 1.0..=10.0)
+                    .text("Sprint multiplier"),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Copy viewpoint").clicked() {
+                    let camera_position = config_loader::CameraPosition {
+                        position: self.freecam_controller.position,
+                        pitch: self.freecam_controller.pitch,
+                        yaw: self.freecam_controller.yaw,
+                    };
+                    let text = serde_json::to_string(&camera_position)
+                        .expect("Could not serialize camera position");
+                    ui.output_mut(|output| output.copied_text = text);
+                    self.viewpoint_status = "Copied to clipboard".to_string();
+                }
+                if ui.button("Jump to").clicked() {
+                    match serde_json::from_str::<config_loader::CameraPosition>(
+                        self.viewpoint_text.trim(),
+                    ) {
+                        Ok(camera_position) => {
+                            self.freecam_controller.position = camera_position.position;
+                            self.freecam_controller.pitch = camera_position.pitch;
+                            self.freecam_controller.yaw = camera_position.yaw;
+                            self.is_playing_camera_animation = false;
+                            self.viewpoint_status = "Jumped to viewpoint".to_string();
+                        }
+                        Err(error) => {
+                            self.viewpoint_status = format!("Invalid viewpoint: {}", error);
+                        }
+                    }
+                }
+            });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.viewpoint_text)
+                    .hint_text("Paste a viewpoint here"),
+            );
+            if !self.viewpoint_status.is_empty() {
+                ui.label(&self.viewpoint_status);
+            }
+            ui.label("Field of view:");
+            let mut fov = self.camera.settings.fov;
+            let mut fov_mode = self.camera.settings.fov_mode;
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::widgets::DragValue::new(&mut fov)
+                        .clamp_range(10.0..=120.0)
+                        .suffix("°"),
+                );
+                ui.radio_value(&mut fov_mode, FovMode::Vertical, "Vertical");
+                ui.radio_value(&mut fov_mode, FovMode::Horizontal, "Horizontal");
+            });
+            if fov != self.camera.settings.fov || fov_mode != self.camera.settings.fov_mode {
+                self.camera.settings.fov = fov;
+                self.camera.settings.fov_mode = fov_mode;
+                self.camera.update_projection();
+            }
             ui.separator();
             ui.checkbox(
                 &mut self.is_playing_camera_animation,
                 "Play Camera Animation",
             );
+            ui.separator();
+            egui::ComboBox::from_label("Scene")
+                .selected_text(
+                    self.scene_path
+                        .as_ref()
+                        .map(|scene_path| scene_path.display().to_string())
+                        .unwrap_or_else(|| "Test scene".to_string()),
+                )
+                .show_ui(ui, |ui| {
+                    for scene_file in self.scene_files.iter() {
+                        let is_selected = self.scene_path.as_ref() == Some(scene_file);
+                        if ui
+                            .selectable_label(is_selected, scene_file.display().to_string())
+                            .clicked()
+                            && !is_selected
+                        {
+                            self.next_scene_path = Some(scene_file.clone());
+                        }
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.label("Test scene:");
+                for primitive_scene in PrimitiveScene::ALL {
+                    if ui.button(primitive_scene.name()).clicked() {
+                        self.next_primitive_scene = Some(primitive_scene);
+                    }
+                }
+            });
+            if let Some(scene_path) = &self.scene_path {
+                if ui
+                    .button("Save material edits")
+                    .on_hover_text("They get applied whenever the scene is loaded")
+                    .clicked()
+                {
+                    self.material_edits_status = save_material_overrides(&self.scene, scene_path);
+                }
+                if !self.material_edits_status.is_empty() {
+                    ui.label(&self.material_edits_status);
+                }
+            }
         });
 
-        self.renderer.render_ui(&mut egui_integration);
+        self.renderer
+            .render_ui(&mut egui_integration, &mut self.scene);
+        self.texture_inspector.render_ui(&mut egui_integration);
+
+        if !self.shader_errors.is_empty() {
+            egui::Window::new("Shader Errors")
+                .resizable(true)
+                .scroll2([true, true])
+                .show(&egui_integration.context(), |ui| {
+                    ui.label("The previous shaders are still in use. Press F5 to reload.");
+                    for error in self.shader_errors.iter() {
+                        ui.separator();
+                        ui.strong(error.path.display().to_string());
+                        ui.monospace(&error.message);
+                    }
+                });
+        }
 
         let output = egui_integration.end_frame(&self.window);
         let clipped_meshes = egui_integration.context().tessellate(output.shapes);
@@ -614,8 +1089,75 @@ impl CatDemo {
         );
     }
 
+    /// Schedules the next reload once the scene has finished loading. Returns true when all reloads are done.
+    fn update_scene_reload_stress(&mut self) -> bool {
+        if self.scene_stream.is_some() || self.next_scene_path.is_some() {
+            return false;
+        }
+        let (Some(scene_reload_stress), Some(scene_path)) =
+            (&mut self.scene_reload_stress, &self.scene_path)
+        else {
+            return false;
+        };
+        match scene_reload_stress
+            .next_scene(scene_path, self.descriptor_set_pool.allocated_set_count())
+        {
+            Some(next_scene_path) => {
+                self.next_scene_path = Some(next_scene_path);
+                false
+            }
+            None => {
+                self.scene_reload_stress = None;
+                true
+            }
+        }
+    }
+
     fn update(&mut self) {
+        if let Some(scene_path) = self.next_scene_path.take() {
+            self.switch_scene(scene_path);
+        }
+        if let Some(primitive_scene) = self.next_primitive_scene.take() {
+            self.switch_to_primitive_scene(primitive_scene);
+        }
+        self.update_scene_stream();
+        if self.memory_report_time.elapsed() >= CatDemo::MEMORY_REPORT_INTERVAL {
+            self.memory_report = self.context.memory_stats.report();
+            self.memory_report_time = Instant::now();
+        }
+        if self
+            .scene
+            .raytracing_scene
+            .as_ref()
+            .is_some_and(|raytracing_scene| raytracing_scene.is_tlas_outdated)
+        {
+            // The last frame could still be tracing rays against the TLAS
+            self.context.wait_idle();
+            scene_uploader::rebuild_tlas(
+                &mut self.scene,
+                self.context.clone(),
+                self.context.queue,
+                self.command_pool.clone(),
+            );
+        }
+        // the samplers of a scene that is still streaming in only exist once it is done
+        if self.scene_stream.is_none() && self.scene.are_samplers_outdated {
+            // The last frame could still be sampling with the old samplers
+            self.context.wait_idle();
+            scene_uploader::apply_sampler_overrides(&mut self.scene, self.context.clone());
+        }
+        if let Some((model_index, primitive_index)) = self.scene.material_to_fork.take() {
+            scene_uploader::fork_material(
+                &mut self.scene,
+                model_index,
+                primitive_index,
+                self.context.clone(),
+                &self.descriptor_set_pool,
+                &self.descriptor_set_layout_cache,
+            );
+        }
         self.time.update();
+        self.frame_times.push(self.time.delta());
         self.update_camera();
         if self.is_demo_mode {
             self.renderer.update_sun(&self.time);
@@ -632,22 +1174,87 @@ impl Drop for CatDemo {
             unsafe { egui_integration.destroy() };
             unsafe { ManuallyDrop::drop(egui_integration) };
         }
+        // stops the loading thread, and frees the models that were not added to the scene yet
+        self.scene_stream = None;
 
         unsafe { device.destroy_semaphore(self.present_complete_semaphore, None) };
         unsafe { device.destroy_semaphore(self.rendering_complete_semaphore, None) };
         unsafe { device.destroy_fence(self.draw_fence, None) };
 
         unsafe { device.free_command_buffers(*self.command_pool, &self.command_buffers) };
-        unsafe { device.destroy_descriptor_pool(self.descriptor_set_pool, None) };
+        // The scene and the renderer get dropped next, and their descriptor sets go back into the pool before it gets destroyed.
+        // The buffers, images and acceleration structures keep the context alive, so the device gets destroyed last.
+    }
+}
+
+fn create_asset_loader(config: &config_loader::Config) -> AssetLoader {
+    let mut asset_loader = AssetLoader::new();
+    asset_loader.optimize_meshes = config.optimize_meshes;
+    asset_loader.keep_images_compressed = config.keep_images_compressed;
+    asset_loader
+}
+
+fn load_scene_file(config: &config_loader::Config, path: &Path) -> LoadedScene {
+    let mut asset_loader = create_asset_loader(config);
+    asset_loader.load_scene(path).expect("Could not load scene")
+}
+
+fn stream_scene_file(config: &config_loader::Config, path: &Path) -> Receiver<SceneStreamEvent> {
+    create_asset_loader(config).load_scene_streaming(path.to_path_buf())
+}
+
+/// Returns a status message for the UI
+fn save_material_overrides(scene: &Scene, scene_path: &Path) -> String {
+    let mut material_overrides = loader::MaterialOverrides::load(scene_path);
+    scene.add_edited_materials(&mut material_overrides);
+    let path = loader::MaterialOverrides::path_for_scene(scene_path);
+    match material_overrides.save(scene_path) {
+        Ok(()) => format!("Saved to {}", path.display()),
+        Err(error) => format!("Could not save to {}: {}", path.display(), error),
+    }
+}
+
+fn take_camera_animation(loaded_scene: &mut LoadedScene) -> AnimationCameraController {
+    if loaded_scene.camera_animations.is_empty() {
+        AnimationCameraController::new(Default::default())
+    } else {
+        AnimationCameraController::new(loaded_scene.camera_animations.swap_remove(0))
     }
 }
 
 fn main() {
+    logger::init();
+    let command_line_args = config_loader::CommandLineArgs::parse();
+    if let Some(dump_scene_path) = &command_line_args.dump_scene_path {
+        dump_scene(&command_line_args, Path::new(dump_scene_path));
+        return;
+    }
     let event_loop = EventLoop::new();
-    let demo = CatDemo::new(&event_loop);
+    let demo = CatDemo::new(&event_loop, command_line_args);
     demo.main_loop(event_loop);
 }
 
+/// Loads the scene without uploading it, since the dump is only about what the loader produced
+fn dump_scene(command_line_args: &config_loader::CommandLineArgs, output_path: &Path) {
+    let mut config_file_loader = config_loader::ConfigFileLoader::new("config.json");
+    let config = config_file_loader.load_config();
+    let scene_path = command_line_args
+        .scene_path
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(&config.scene_path));
+
+    let loaded_scene = load_scene_file(config, &scene_path);
+    let scene_dump = loader::SceneDump::new(&loaded_scene);
+    let json = serde_json::to_string_pretty(&scene_dump).expect("Could not serialize scene dump");
+    std::fs::write(output_path, json).expect("Could not write scene dump");
+    log::info!(
+        "Dumped scene {} to {}",
+        scene_path.display(),
+        output_path.display()
+    );
+}
+
 pub fn find_memorytype_index(
     memory_req: &vk::MemoryRequirements,
     memory_prop: &vk::PhysicalDeviceMemoryProperties,
@@ -1,30 +1,56 @@
+mod culling_debug;
+pub mod debug_draw;
 mod gbuffer;
+mod light_gizmos;
+mod non_finite_readback;
+mod object_picking;
 mod pass;
 pub mod set_layout_cache;
 pub mod shader_types;
 
 use std::sync::Arc;
 
-use ash::vk;
+use ash::vk::{self, AccessFlags2, PipelineStageFlags2};
 use crevice::std140::AsStd140;
 use egui_winit_ash_integration::{AllocatorTrait, Integration};
-use ultraviolet::{Bivec3, Rotor3, Vec3};
+use ultraviolet::{Bivec3, Mat4, Rotor3, Vec3};
 
+use crate::config_loader::{Settings, ShadowMapSettings};
 use crate::time::Time;
 use crate::vulkan::buffer::Buffer;
 use crate::vulkan::context::Context;
+use crate::vulkan::debug_label::DebugLabel;
+use crate::vulkan::descriptor_pool::DescriptorPool;
 use crate::vulkan::descriptor_set::{DescriptorSet, WriteDescriptorSet};
+use crate::vulkan::shader_create_info::ShaderError;
 use crate::vulkan::swapchain::SwapchainContainer;
-use crate::{camera::Camera, scene::Scene};
+use crate::{
+    camera::Camera,
+    scene::{FilterOverride, Scene, MAX_LOD_COUNT},
+};
 
 use self::{
+    culling_debug::CullingDebug,
+    debug_draw::DebugDraw,
+    light_gizmos::LightGizmos,
+    non_finite_readback::NonFiniteReadback,
+    object_picking::ObjectPicking,
     pass::{
-        geometry::GeometryPass, lighting::LightingPass, post_processing::PostProcessingPass,
+        debug_lines::DebugLinesPass,
+        geometry::{GeometryPass, LodSelector},
+        lighting::LightingPass,
+        post_processing::PostProcessingPass,
+        selection::SelectionPass,
         shadow::ShadowPass,
+        shadow_denoise::ShadowDenoisePass,
+        shadow_map::ShadowMapPass,
+        taa::TaaPass,
     },
     set_layout_cache::DescriptorSetLayoutCache,
 };
 
+pub use self::pass::lighting::{BackgroundMode, DebugView};
+
 #[repr(transparent)]
 #[derive(Debug, Copy, Clone)]
 pub struct SwapchainIndex(usize);
@@ -39,30 +65,102 @@ pub struct SceneDescriptorSet {
     pub descriptor_set: DescriptorSet,
 }
 
+/// How many frames the CPU can prepare while the GPU is still rendering the older ones
+pub const FRAMES_IN_FLIGHT: usize = 2;
+
+/// The camera buffer has one slice per frame in flight, which gets selected with a dynamic offset
 pub struct CameraDescriptorSet {
     pub buffer: Buffer<shader_types::Std140Camera>,
     pub descriptor_set: DescriptorSet,
+    /// Size of one slice, aligned to minUniformBufferOffsetAlignment
+    slice_size: vk::DeviceSize,
+    frame_index: usize,
+}
+
+impl CameraDescriptorSet {
+    /// Offset of the slice that the current frame reads
+    pub fn dynamic_offset(&self) -> u32 {
+        (self.frame_index as vk::DeviceSize * self.slice_size) as u32
+    }
+
+    /// Moves on to the next slice, so that frames which are still in flight keep their camera
+    pub fn write_next_frame(&mut self, camera: &shader_types::Std140Camera) {
+        self.frame_index = (self.frame_index + 1) % FRAMES_IN_FLIGHT;
+        self.buffer
+            .copy_data_at_offset(self.dynamic_offset() as vk::DeviceSize, camera);
+    }
+}
+
+/// What writes the shadow buffer that the lighting pass reads
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShadowMode {
+    Raytraced,
+    ShadowMap,
+    /// The shadow buffer only gets cleared
+    Disabled,
+}
+
+impl ShadowMode {
+    /// The stage and access of the last write to the shadow buffer
+    pub fn shadow_buffer_write(&self) -> (PipelineStageFlags2, AccessFlags2) {
+        match self {
+            // the shadow denoise pass might run after the raytraced shadow pass
+            ShadowMode::Raytraced => (
+                PipelineStageFlags2::RAY_TRACING_SHADER_KHR | PipelineStageFlags2::COMPUTE_SHADER,
+                AccessFlags2::SHADER_WRITE,
+            ),
+            ShadowMode::ShadowMap => (
+                PipelineStageFlags2::COMPUTE_SHADER,
+                AccessFlags2::SHADER_WRITE,
+            ),
+            ShadowMode::Disabled => (PipelineStageFlags2::CLEAR, AccessFlags2::TRANSFER_WRITE),
+        }
+    }
 }
 
 pub struct MainRenderer {
     geometry_pass: GeometryPass,
-    shadow_pass: ShadowPass,
+    /// None when the device doesn't support raytracing
+    shadow_pass: Option<ShadowPass>,
+    /// Only used with the raytraced shadow pass
+    shadow_denoise_pass: Option<ShadowDenoisePass>,
+    /// Only used when there is no raytraced shadow pass
+    shadow_map_pass: Option<ShadowMapPass>,
+    shadow_mode: ShadowMode,
     lighting_pass: LightingPass,
+    taa_pass: TaaPass,
+    selection_pass: SelectionPass,
+    debug_lines_pass: DebugLinesPass,
     post_processing_pass: PostProcessingPass,
+    object_picking: ObjectPicking,
+    non_finite_readback: NonFiniteReadback,
+    culling_debug: CullingDebug,
+    light_gizmos: LightGizmos,
+    /// Gets drawn and cleared every frame
+    pub debug_draw: DebugDraw,
 
     scene_descriptor_set: SceneDescriptorSet,
     camera_descriptor_set: CameraDescriptorSet,
     sun_direction: Vec3,
+    ambient_color: Vec3,
+    ambient_intensity: f32,
+    lod_selector: LodSelector,
+    /// Without the TAA jitter, None before the first frame
+    previous_view_proj: Option<Mat4>,
+
+    context: Arc<Context>,
 }
 
 impl MainRenderer {
     pub fn new(
         context: Arc<Context>,
-        descriptor_pool: vk::DescriptorPool,
+        descriptor_pool: &DescriptorPool,
         set_layout_cache: &DescriptorSetLayoutCache,
         scene: &Scene,
         swapchain: &SwapchainContainer,
         brightness: f32,
+        shadow_map_settings: &ShadowMapSettings,
+        settings: &Settings,
     ) -> Self {
         let scene_descriptor_set = {
             let buffer = Buffer::new(
@@ -73,7 +171,6 @@ impl MainRenderer {
             );
 
             let descriptor_set = DescriptorSet::new(
-                context.clone(),
                 descriptor_pool,
                 set_layout_cache.scene(),
                 vec![WriteDescriptorSet::buffer(0, &buffer)],
@@ -86,23 +183,35 @@ impl MainRenderer {
         };
 
         let camera_descriptor_set = {
+            let camera_size = shader_types::Camera::std140_size_static() as vk::DeviceSize;
+            let alignment = context
+                .physical_device_properties
+                .limits
+                .min_uniform_buffer_offset_alignment;
+            let slice_size = camera_size.div_ceil(alignment) * alignment;
+
             let buffer = Buffer::new(
                 context.clone(),
-                shader_types::Camera::std140_size_static() as u64,
+                slice_size * FRAMES_IN_FLIGHT as vk::DeviceSize,
                 vk::BufferUsageFlags::UNIFORM_BUFFER,
                 vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
             );
 
             let descriptor_set = DescriptorSet::new(
-                context.clone(),
                 descriptor_pool,
                 set_layout_cache.camera(),
-                vec![WriteDescriptorSet::buffer(0, &buffer)],
+                vec![WriteDescriptorSet::uniform_buffer_dynamic(
+                    0,
+                    &buffer,
+                    camera_size,
+                )],
             );
 
             CameraDescriptorSet {
                 buffer,
                 descriptor_set,
+                slice_size,
+                frame_index: 0,
             }
         };
 
@@ -113,22 +222,81 @@ impl MainRenderer {
             set_layout_cache,
         );
 
-        let shadow_pass = ShadowPass::new(
+        let shadow_pass = scene.raytracing_scene.as_ref().map(|raytracing_scene| {
+            ShadowPass::new(
+                context.clone(),
+                geometry_pass.gbuffer(),
+                &set_layout_cache,
+                descriptor_pool,
+                raytracing_scene.tlas.clone(),
+            )
+        });
+
+        let shadow_denoise_pass = shadow_pass.is_some().then(|| {
+            ShadowDenoisePass::new(context.clone(), geometry_pass.gbuffer(), descriptor_pool)
+        });
+
+        let shadow_map_pass =
+            (shadow_pass.is_none() && !shadow_map_settings.cascade_splits.is_empty()).then(|| {
+                ShadowMapPass::new(
+                    context.clone(),
+                    geometry_pass.gbuffer(),
+                    set_layout_cache,
+                    descriptor_pool,
+                    shadow_map_settings,
+                )
+            });
+
+        let shadow_mode = if shadow_pass.is_some() {
+            ShadowMode::Raytraced
+        } else if shadow_map_pass.is_some() {
+            ShadowMode::ShadowMap
+        } else {
+            ShadowMode::Disabled
+        };
+        let no_raytracing_reason = if context.context_raytracing.is_some() {
+            "The scene has nothing to raytrace"
+        } else {
+            "Raytracing is not supported"
+        };
+        match shadow_mode {
+            ShadowMode::Raytraced => log::info!("Rendering with raytraced shadows"),
+            ShadowMode::ShadowMap => {
+                log::info!("{}, rendering with shadow maps", no_raytracing_reason)
+            }
+            ShadowMode::Disabled => {
+                log::info!("{}, rendering without shadows", no_raytracing_reason)
+            }
+        }
+
+        let mut taa_pass = TaaPass::new(
             context.clone(),
+            swapchain,
             geometry_pass.gbuffer(),
-            &set_layout_cache,
             descriptor_pool,
-            scene.raytracing_scene.tlas.clone(),
         );
-
-        let lighting_pass = LightingPass::new(
+        taa_pass.enabled = settings.is_taa_enabled;
+        let mut lighting_pass = LightingPass::new(
             context.clone(),
-            swapchain,
+            taa_pass.color_target(),
+            swapchain.extent,
             geometry_pass.gbuffer(),
             set_layout_cache,
             brightness,
         );
+        lighting_pass.debug_view = settings.debug_view;
+        lighting_pass.background_mode = settings.background_mode;
+        lighting_pass.background_color = settings.background_color;
+        let selection_pass = SelectionPass::new(
+            context.clone(),
+            swapchain,
+            descriptor_pool,
+            set_layout_cache,
+        );
+        let debug_lines_pass = DebugLinesPass::new(context.clone(), swapchain, set_layout_cache);
         let post_processing_pass = PostProcessingPass::new();
+        let object_picking = ObjectPicking::new(context.clone());
+        let non_finite_readback = NonFiniteReadback::new(context.clone());
 
         let sun_direction = Vec3 {
             x: 0.2,
@@ -139,16 +307,37 @@ impl MainRenderer {
         MainRenderer {
             geometry_pass,
             shadow_pass,
+            shadow_denoise_pass,
+            shadow_map_pass,
+            shadow_mode,
             lighting_pass,
+            taa_pass,
+            selection_pass,
+            debug_lines_pass,
             post_processing_pass,
+            object_picking,
+            non_finite_readback,
+            culling_debug: CullingDebug::new(),
+            light_gizmos: LightGizmos::new(),
+            debug_draw: DebugDraw::new(),
 
             scene_descriptor_set,
             camera_descriptor_set,
             sun_direction,
+            ambient_color: settings.ambient_color,
+            ambient_intensity: settings.ambient_intensity,
+            lod_selector: LodSelector::new(),
+            previous_view_proj: None,
+
+            context,
         }
     }
 
-    pub fn render_ui<A: AllocatorTrait>(&mut self, egui_integration: &mut Integration<A>) {
+    pub fn render_ui<A: AllocatorTrait>(
+        &mut self,
+        egui_integration: &mut Integration<A>,
+        scene: &mut Scene,
+    ) {
         egui::Window::new("")
             .resizable(true)
             .scroll2([true, true])
@@ -163,9 +352,316 @@ impl MainRenderer {
                     ui.label("z:");
                     ui.add(egui::widgets::DragValue::new(&mut self.sun_direction.z).speed(0.1));
                 });
+                ui.horizontal(|ui| {
+                    ui.label("Ambient:");
+                    let mut color: [f32; 3] = self.ambient_color.into();
+                    ui.color_edit_button_rgb(&mut color);
+                    self.ambient_color = color.into();
+                    ui.add(
+                        egui::Slider::new(&mut self.ambient_intensity, 0.0..=1.0).logarithmic(true),
+                    );
+                });
+                if DebugDraw::IS_AVAILABLE {
+                    self.render_light_ui(ui, scene);
+                }
+                if let Some(shadow_pass) = &mut self.shadow_pass {
+                    ui.label("Raytraced Shadows: ");
+                    ui.add(
+                        egui::Slider::new(&mut shadow_pass.depth_bias, 0.0..=0.001)
+                            .logarithmic(true)
+                            .text("Depth bias"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut shadow_pass.normal_bias, 0.0..=0.5)
+                            .logarithmic(true)
+                            .text("Normal bias"),
+                    );
+                    ui.add(
+                        egui::Slider::new(
+                            &mut shadow_pass.sample_count,
+                            1..=ShadowPass::MAX_SAMPLE_COUNT,
+                        )
+                        .text("Rays per pixel"),
+                    );
+                    ui.add_enabled(
+                        shadow_pass.sample_count > 1,
+                        egui::Slider::new(&mut shadow_pass.light_angular_diameter, 0.0..=10.0)
+                            .text("Light size (degrees)"),
+                    );
+                }
+                if let Some(shadow_denoise_pass) = &mut self.shadow_denoise_pass {
+                    ui.checkbox(&mut shadow_denoise_pass.enabled, "Shadow accumulation");
+                    ui.add_enabled(
+                        shadow_denoise_pass.enabled,
+                        egui::Slider::new(&mut shadow_denoise_pass.accumulation_weight, 0.0..=0.98)
+                            .text("Accumulation weight"),
+                    );
+                }
+                ui.separator();
+                ui.label("Level of Detail: ");
+                let mut is_lod_fixed = self.lod_selector.fixed_lod.is_some();
+                ui.checkbox(&mut is_lod_fixed, "Fixed LOD");
+                if is_lod_fixed {
+                    let fixed_lod = self.lod_selector.fixed_lod.get_or_insert(0);
+                    ui.add(egui::Slider::new(fixed_lod, 0..=MAX_LOD_COUNT - 1));
+                } else {
+                    self.lod_selector.fixed_lod = None;
+                }
+                if DebugDraw::IS_AVAILABLE {
+                    ui.checkbox(
+                        &mut self.lod_selector.show_bounding_spheres,
+                        "Show bounding spheres",
+                    );
+                }
+                if DebugDraw::IS_AVAILABLE {
+                    ui.separator();
+                    ui.label("Frustum Culling: ");
+                    ui.checkbox(&mut self.culling_debug.enabled, "Show bounding boxes");
+                    if self.culling_debug.enabled {
+                        ui.checkbox(&mut self.culling_debug.freeze_frustum, "Freeze frustum");
+                        ui.label(format!(
+                            "Visible: {}, culled: {}",
+                            self.culling_debug.visible_count, self.culling_debug.culled_count
+                        ));
+                    }
+                }
+                ui.separator();
+                ui.label("Texture Filtering: ");
+                let mut sampler_overrides = scene.sampler_overrides;
+                egui::ComboBox::from_label("Filter")
+                    .selected_text(sampler_overrides.filter.name())
+                    .show_ui(ui, |ui| {
+                        for filter in FilterOverride::ALL {
+                            ui.selectable_value(
+                                &mut sampler_overrides.filter,
+                                filter,
+                                filter.name(),
+                            );
+                        }
+                    });
+                let max_sampler_anisotropy = self
+                    .context
+                    .physical_device_properties
+                    .limits
+                    .max_sampler_anisotropy;
+                ui.add(
+                    egui::Slider::new(
+                        &mut sampler_overrides.max_anisotropy,
+                        1.0..=max_sampler_anisotropy,
+                    )
+                    .text("Anisotropy"),
+                );
+                if sampler_overrides != scene.sampler_overrides {
+                    scene.set_sampler_overrides(sampler_overrides);
+                }
+                ui.separator();
+                ui.checkbox(&mut self.taa_pass.enabled, "Temporal Anti-Aliasing");
+                egui::ComboBox::from_label("Debug View")
+                    .selected_text(self.lighting_pass.debug_view.name())
+                    .show_ui(ui, |ui| {
+                        for debug_view in DebugView::ALL {
+                            ui.selectable_value(
+                                &mut self.lighting_pass.debug_view,
+                                debug_view,
+                                debug_view.name(),
+                            );
+                        }
+                    });
+                if self.lighting_pass.debug_view == DebugView::NonFinite {
+                    match self.non_finite_readback.flagged_pixel_count {
+                        Some(count) => ui.label(format!("Non-finite pixels: {}", count)),
+                        None => ui.label("Non-finite pixels: counting"),
+                    };
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Background:");
+                    let mut color: [f32; 3] = self.lighting_pass.background_color.into();
+                    ui.color_edit_button_rgb(&mut color);
+                    self.lighting_pass.background_color = color.into();
+                    egui::ComboBox::from_id_source("background_mode")
+                        .selected_text(self.lighting_pass.background_mode.name())
+                        .show_ui(ui, |ui| {
+                            for background_mode in BackgroundMode::ALL {
+                                ui.selectable_value(
+                                    &mut self.lighting_pass.background_mode,
+                                    background_mode,
+                                    background_mode.name(),
+                                );
+                            }
+                        });
+                });
+                ui.separator();
+                match self.object_picking.picked_model {
+                    Some(model_index) => {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Picked model: {}", model_index));
+                            if ui.button("Clear").clicked() {
+                                self.object_picking.picked_model = None;
+                            }
+                        });
+                        if let Some(model) = scene.models.get(model_index) {
+                            let mut casts_shadows = model.casts_shadows;
+                            if ui.checkbox(&mut casts_shadows, "Casts shadows").changed() {
+                                scene.set_casts_shadows(model_index, casts_shadows);
+                            }
+                        }
+                        self.render_material_ui(ui, scene, model_index);
+                    }
+                    None => {
+                        ui.label("Left click to pick a model");
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Outline:");
+                    let mut color: [f32; 3] = self.selection_pass.color.into();
+                    ui.color_edit_button_rgb(&mut color);
+                    self.selection_pass.color = color.into();
+                    ui.add(egui::Slider::new(
+                        &mut self.selection_pass.thickness,
+                        1..=SelectionPass::MAX_THICKNESS,
+                    ));
+                });
             });
     }
 
+    /// Edits the lights of the scene, which are only shown as gizmos
+    fn render_light_ui(&mut self, ui: &mut egui::Ui, scene: &mut Scene) {
+        ui.checkbox(&mut self.light_gizmos.enabled, "Show light gizmos");
+        if scene.lights.is_empty() {
+            self.light_gizmos.selected_light = None;
+            return;
+        }
+        let light_name = |index: usize| {
+            let node = &scene.scene_graph.nodes[scene.lights[index].node];
+            match &node.name {
+                Some(name) => format!("{}: {}", index, name),
+                None => format!("{}", index),
+            }
+        };
+        let selected_text = match self.light_gizmos.selected_light {
+            Some(index) => light_name(index),
+            None => "None".to_owned(),
+        };
+        egui::ComboBox::from_label("Light")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.light_gizmos.selected_light, None, "None");
+                for index in 0..scene.lights.len() {
+                    ui.selectable_value(
+                        &mut self.light_gizmos.selected_light,
+                        Some(index),
+                        light_name(index),
+                    );
+                }
+            });
+        let Some(light) = self
+            .light_gizmos
+            .selected_light
+            .and_then(|index| scene.lights.get_mut(index))
+        else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label(format!("{} light:", light.kind.name()));
+            let mut color: [f32; 3] = light.color.into();
+            ui.color_edit_button_rgb(&mut color);
+            light.color = color.into();
+            ui.add(
+                egui::DragValue::new(&mut light.intensity)
+                    .speed(0.1)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+        });
+        if let Some(range) = &mut light.range {
+            ui.add(
+                egui::Slider::new(range, 0.01..=100.0)
+                    .logarithmic(true)
+                    .text("Range"),
+            );
+        }
+    }
+
+    /// Edits the factors of the material of a primitive, until the scene gets reloaded
+    fn render_material_ui(&mut self, ui: &mut egui::Ui, scene: &mut Scene, model_index: usize) {
+        let Some(model) = scene.models.get(model_index) else {
+            return;
+        };
+        if model.primitives.is_empty() {
+            return;
+        }
+        let primitive_index = &mut self.object_picking.picked_primitive;
+        *primitive_index = (*primitive_index).min(model.primitives.len() - 1);
+        if model.primitives.len() > 1 {
+            ui.add(
+                egui::Slider::new(primitive_index, 0..=model.primitives.len() - 1)
+                    .text("Primitive"),
+            );
+        }
+        let primitive_index = *primitive_index;
+        let material = &model.primitives[primitive_index].material;
+
+        ui.label("Material:");
+        let mut factors = material.read_factors();
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Base color:");
+            let mut color: [f32; 3] = factors.base_color.into();
+            changed |= ui.color_edit_button_rgb(&mut color).changed();
+            factors.base_color = color.into();
+        });
+        ui.horizontal(|ui| {
+            // can be brighter than 1
+            ui.label("Emissivity:");
+            for value in [
+                &mut factors.emissivity.x,
+                &mut factors.emissivity.y,
+                &mut factors.emissivity.z,
+            ] {
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(value)
+                            .speed(0.01)
+                            .clamp_range(0.0..=f32::MAX),
+                    )
+                    .changed();
+            }
+        });
+        changed |= ui
+            .add(egui::Slider::new(&mut factors.roughness, 0.0..=1.0).text("Roughness"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut factors.metallic, 0.0..=1.0).text("Metallic"))
+            .changed();
+        if changed {
+            material.write_factors(&factors);
+        }
+
+        let user_count = scene.material_user_count(model_index, primitive_index);
+        if user_count > 1 {
+            ui.horizontal(|ui| {
+                ui.label(format!("Shared by {} primitives", user_count));
+                if ui.button("Make unique").clicked() {
+                    scene.material_to_fork = Some((model_index, primitive_index));
+                }
+            });
+        }
+    }
+
+    /// Writes the settings that can be changed in the UI
+    pub fn save_settings(&self, settings: &mut Settings) {
+        settings.is_taa_enabled = self.taa_pass.enabled;
+        settings.debug_view = self.lighting_pass.debug_view;
+        settings.background_mode = self.lighting_pass.background_mode;
+        settings.background_color = self.lighting_pass.background_color;
+        settings.ambient_color = self.ambient_color;
+        settings.ambient_intensity = self.ambient_intensity;
+    }
+
+    /// The picked model shows up one frame later
+    pub fn pick(&mut self, cursor_position: ultraviolet::Vec2) {
+        self.object_picking.pick(cursor_position);
+    }
+
     pub fn update_sun(&mut self, time: &Time) {
         let rotor = Rotor3::from_angle_plane(
             5.0f32.to_radians() * time.delta_seconds(),
@@ -176,7 +672,7 @@ impl MainRenderer {
     }
 
     pub fn render(
-        &self,
+        &mut self,
         scene: &Scene,
         command_buffer: vk::CommandBuffer,
         swapchain: &SwapchainContainer,
@@ -185,65 +681,212 @@ impl MainRenderer {
     ) {
         // all commands are recorded into one command buffer
 
-        self.geometry_pass.render(
-            scene,
-            &self.camera_descriptor_set,
-            command_buffer,
-            swapchain,
-            swapchain_index,
-            viewport,
-        );
+        {
+            let _label = DebugLabel::new(&self.context, command_buffer, "Geometry");
+            self.geometry_pass.render(
+                scene,
+                &self.camera_descriptor_set,
+                &self.lod_selector,
+                command_buffer,
+                swapchain,
+                swapchain_index,
+                viewport,
+            );
+        }
+        {
+            let _label = DebugLabel::new(&self.context, command_buffer, "Object picking");
+            self.object_picking.render(
+                command_buffer,
+                self.geometry_pass.gbuffer(),
+                swapchain.extent,
+            );
+        }
 
-        self.shadow_pass.render(
-            self.geometry_pass.gbuffer(),
-            &self.scene_descriptor_set,
-            &self.camera_descriptor_set,
-            swapchain.extent,
-            command_buffer,
-        );
+        {
+            let _label = DebugLabel::new(&self.context, command_buffer, "Shadow");
+            if let Some(shadow_pass) = &mut self.shadow_pass {
+                shadow_pass.render(
+                    scene,
+                    self.geometry_pass.gbuffer(),
+                    &self.scene_descriptor_set,
+                    &self.camera_descriptor_set,
+                    swapchain.extent,
+                    command_buffer,
+                );
+                if let Some(shadow_denoise_pass) = &mut self.shadow_denoise_pass {
+                    shadow_denoise_pass.render(
+                        self.geometry_pass.gbuffer(),
+                        swapchain.extent,
+                        command_buffer,
+                    );
+                }
+            } else if let Some(shadow_map_pass) = &self.shadow_map_pass {
+                shadow_map_pass.render(
+                    scene,
+                    self.geometry_pass.gbuffer(),
+                    &self.camera_descriptor_set,
+                    swapchain.extent,
+                    command_buffer,
+                );
+            } else {
+                self.geometry_pass
+                    .gbuffer()
+                    .clear_shadow_buffer(&self.context, command_buffer);
+            }
+        }
 
-        self.lighting_pass.render(
-            command_buffer,
-            self.geometry_pass.gbuffer(),
-            &self.scene_descriptor_set,
-            &self.camera_descriptor_set,
-            swapchain,
-            swapchain_index,
-            viewport,
-        );
+        {
+            let _label = DebugLabel::new(&self.context, command_buffer, "Lighting");
+            self.lighting_pass.render(
+                command_buffer,
+                self.geometry_pass.gbuffer(),
+                &self.scene_descriptor_set,
+                &self.camera_descriptor_set,
+                viewport,
+                self.shadow_mode,
+            );
+        }
+        if self.lighting_pass.debug_view == DebugView::NonFinite {
+            let _label = DebugLabel::new(&self.context, command_buffer, "Non-finite readback");
+            self.non_finite_readback.render(
+                command_buffer,
+                self.taa_pass.color_target(),
+                swapchain.extent,
+            );
+        }
+        {
+            let _label = DebugLabel::new(&self.context, command_buffer, "TAA");
+            self.taa_pass.render(
+                command_buffer,
+                self.geometry_pass.gbuffer(),
+                swapchain_index,
+                viewport,
+            );
+        }
+        {
+            let _label = DebugLabel::new(&self.context, command_buffer, "Selection");
+            self.selection_pass.render(
+                command_buffer,
+                scene,
+                self.object_picking.picked_model,
+                &self.camera_descriptor_set,
+                &self.lod_selector,
+                swapchain_index,
+                viewport,
+            );
+        }
+        {
+            let _label = DebugLabel::new(&self.context, command_buffer, "Debug lines");
+            self.debug_lines_pass.render(
+                command_buffer,
+                &self.camera_descriptor_set,
+                swapchain_index,
+                viewport,
+            );
+        }
         self.post_processing_pass.render();
     }
 
-    pub fn update_descriptor_sets(&self, camera: &Camera) {
+    pub fn update_descriptor_sets(&mut self, camera: &Camera, scene: &Scene, time: &Time) {
+        self.lod_selector.update(camera);
+        self.geometry_pass.update(scene);
+        self.object_picking.update();
+        self.non_finite_readback.update();
+        self.culling_debug.draw(camera, scene, &mut self.debug_draw);
+        self.lod_selector
+            .draw_bounding_spheres(scene, &mut self.debug_draw);
+        self.light_gizmos.draw(scene, &mut self.debug_draw);
+        self.debug_lines_pass.update(&mut self.debug_draw);
+        if let Some(shadow_map_pass) = &mut self.shadow_map_pass {
+            shadow_map_pass.update(camera, self.sun_direction);
+        }
+
         let scene = shader_types::Scene {
             directional_light: shader_types::DirectionalLight {
                 direction: self.sun_direction.normalized(),
                 color: Vec3::new(1.0, 1.0, 1.0),
                 intensity: 3.0,
             },
+            ambient_light: shader_types::AmbientLight {
+                color: self.ambient_color,
+                intensity: self.ambient_intensity,
+            },
         };
 
+        let jitter = self.taa_pass.update(camera);
+        if let Some(shadow_denoise_pass) = &mut self.shadow_denoise_pass {
+            if self.taa_pass.is_camera_cut() {
+                shadow_denoise_pass.reset_history();
+            }
+        }
+        let proj = camera.jittered_projection_matrix(jitter);
+        let unjittered_view_proj = camera.projection_matrix() * camera.view_matrix();
+        let previous_unjittered_view_proj = self
+            .previous_view_proj
+            .replace(unjittered_view_proj)
+            .unwrap_or(unjittered_view_proj);
         let camera = shader_types::Camera {
             view: camera.view_matrix(),
-            proj: camera.projection_matrix(),
+            proj,
             view_inv: camera.view_matrix().inversed(),
-            proj_inv: camera.projection_matrix().inversed(),
+            proj_inv: proj.inversed(),
             position: camera.position,
+            unjittered_view_proj,
+            previous_unjittered_view_proj,
+            time: time.elapsed().as_secs_f32(),
         };
 
         self.scene_descriptor_set
             .buffer
             .copy_data(&scene.as_std140());
         self.camera_descriptor_set
-            .buffer
-            .copy_data(&camera.as_std140());
+            .write_next_frame(&camera.as_std140());
+    }
+
+    /// Rebuilds all pipelines with the current shaders. The device must be idle.
+    /// Passes with shaders that don't compile keep their old pipelines.
+    pub fn reload_shaders(
+        &mut self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Vec<ShaderError> {
+        let mut results = vec![self.geometry_pass.reload_shaders(set_layout_cache)];
+        if let Some(shadow_pass) = &mut self.shadow_pass {
+            results.push(shadow_pass.reload_shaders(set_layout_cache));
+        }
+        if let Some(shadow_denoise_pass) = &mut self.shadow_denoise_pass {
+            results.push(shadow_denoise_pass.reload_shaders());
+        }
+        if let Some(shadow_map_pass) = &mut self.shadow_map_pass {
+            results.push(shadow_map_pass.reload_shaders(set_layout_cache));
+        }
+        results.push(
+            self.lighting_pass
+                .reload_shaders(set_layout_cache, self.geometry_pass.gbuffer()),
+        );
+        results.push(self.taa_pass.reload_shaders(self.geometry_pass.gbuffer()));
+        results.push(self.selection_pass.reload_shaders(set_layout_cache));
+        results.push(self.debug_lines_pass.reload_shaders(set_layout_cache));
+
+        results.into_iter().filter_map(Result::err).collect()
     }
 
     pub fn resize(&mut self, swapchain: &SwapchainContainer) {
         self.geometry_pass.resize(swapchain);
 
-        self.shadow_pass.resize(self.geometry_pass.gbuffer());
-        self.lighting_pass.resize(swapchain);
+        if let Some(shadow_pass) = &mut self.shadow_pass {
+            shadow_pass.resize(self.geometry_pass.gbuffer());
+        }
+        if let Some(shadow_denoise_pass) = &mut self.shadow_denoise_pass {
+            shadow_denoise_pass.resize(self.geometry_pass.gbuffer());
+        }
+        if let Some(shadow_map_pass) = &mut self.shadow_map_pass {
+            shadow_map_pass.resize(self.geometry_pass.gbuffer());
+        }
+        self.taa_pass.resize(swapchain);
+        self.selection_pass.resize(swapchain);
+        self.debug_lines_pass.resize(swapchain);
+        self.lighting_pass
+            .resize(self.taa_pass.color_target(), swapchain.extent);
         self.post_processing_pass.resize();
     }
 }
@@ -0,0 +1,65 @@
+use ultraviolet::{Mat4, Vec3};
+
+use crate::camera::{Camera, Frustum};
+use crate::render::debug_draw::DebugDraw;
+use crate::scene::Scene;
+
+/// Shows the camera frustum and the bounding box of every primitive,
+/// green when the box intersects the frustum and red when it would be culled.
+/// The frustum can be frozen, to look at it from the outside.
+pub struct CullingDebug {
+    pub enabled: bool,
+    /// Keeps the last frustum
+    pub freeze_frustum: bool,
+    /// Primitives, counted while this is enabled
+    pub visible_count: usize,
+    pub culled_count: usize,
+    frustum: Option<Frustum>,
+}
+
+impl CullingDebug {
+    const FRUSTUM_COLOR: Vec3 = Vec3::new(1.0, 1.0, 0.0);
+    const VISIBLE_COLOR: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+    const CULLED_COLOR: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            freeze_frustum: false,
+            visible_count: 0,
+            culled_count: 0,
+            frustum: None,
+        }
+    }
+
+    pub fn draw(&mut self, camera: &Camera, scene: &Scene, debug_draw: &mut DebugDraw) {
+        if !self.enabled {
+            self.frustum = None;
+            return;
+        }
+        if !self.freeze_frustum || self.frustum.is_none() {
+            self.frustum = Some(camera.frustum());
+        }
+        let Some(frustum) = self.frustum else {
+            return;
+        };
+
+        debug_draw.draw_frustum(&frustum, Self::FRUSTUM_COLOR);
+        self.visible_count = 0;
+        self.culled_count = 0;
+        for model in &scene.models {
+            let model_matrix: Mat4 = model.transform.clone().into();
+            for primitive in &model.primitives {
+                let bounds = primitive.mesh.bounds.transformed(model_matrix);
+                let color = if frustum.intersects_aabb(&bounds) {
+                    self.visible_count += 1;
+                    Self::VISIBLE_COLOR
+                } else {
+                    self.culled_count += 1;
+                    Self::CULLED_COLOR
+                };
+                debug_draw.draw_aabb(&bounds, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use ash::vk;
+use ultraviolet::Vec3;
+
+use crate::camera::Frustum;
+use crate::offset_of;
+use crate::scene::Aabb;
+
+/// Collects lines during a frame, which the debug lines pass draws over the scene and then clears.
+/// Only for debugging, release builds ignore everything that gets drawn.
+#[derive(Default)]
+pub struct DebugDraw {
+    vertices: Vec<DebugLineVertex>,
+}
+
+#[derive(Clone, Debug, Copy)]
+#[repr(C)]
+pub struct DebugLineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl DebugLineVertex {
+    pub fn binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
+        [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
+        [
+            vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, position) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, color) as u32,
+            },
+        ]
+    }
+}
+
+/// Pairs of corner indices, the corners of an edge differ in one bit
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+impl DebugDraw {
+    pub const IS_AVAILABLE: bool = cfg!(debug_assertions);
+
+    const CIRCLE_SEGMENT_COUNT: usize = 32;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// In world space
+    pub fn draw_line(&mut self, start: Vec3, end: Vec3, color: Vec3) {
+        if !Self::IS_AVAILABLE {
+            return;
+        }
+        for position in [start, end] {
+            self.vertices.push(DebugLineVertex {
+                position: position.into(),
+                color: color.into(),
+            });
+        }
+    }
+
+    pub fn draw_aabb(&mut self, aabb: &Aabb, color: Vec3) {
+        self.draw_box(&aabb.corners(), color);
+    }
+
+    pub fn draw_frustum(&mut self, frustum: &Frustum, color: Vec3) {
+        self.draw_box(&frustum.corners, color);
+    }
+
+    /// As three circles around the axes
+    pub fn draw_sphere(&mut self, center: Vec3, radius: f32, color: Vec3) {
+        let axes = [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()];
+        for (index, &first_axis) in axes.iter().enumerate() {
+            let second_axis = axes[(index + 1) % axes.len()];
+            self.draw_circle(center, first_axis, second_axis, radius, color);
+        }
+    }
+
+    /// In the plane spanned by the two axes, which have to be normalized and orthogonal
+    pub fn draw_circle(
+        &mut self,
+        center: Vec3,
+        first_axis: Vec3,
+        second_axis: Vec3,
+        radius: f32,
+        color: Vec3,
+    ) {
+        let point = |segment: usize| {
+            let angle = segment as f32 / Self::CIRCLE_SEGMENT_COUNT as f32 * std::f32::consts::TAU;
+            center + (first_axis * angle.cos() + second_axis * angle.sin()) * radius
+        };
+        for segment in 0..Self::CIRCLE_SEGMENT_COUNT {
+            self.draw_line(point(segment), point(segment + 1), color);
+        }
+    }
+
+    /// With a head that is a fifth of the length
+    pub fn draw_arrow(&mut self, start: Vec3, end: Vec3, color: Vec3) {
+        self.draw_line(start, end, color);
+        let length = (end - start).mag();
+        if length == 0.0 {
+            return;
+        }
+        let direction = (end - start) / length;
+        let head_length = length * 0.2;
+        let (first_axis, second_axis) = orthogonal_axes(direction);
+        for axis in [first_axis, -first_axis, second_axis, -second_axis] {
+            let head_point = end - direction * head_length + axis * head_length * 0.5;
+            self.draw_line(end, head_point, color);
+        }
+    }
+
+    /// From the apex along the normalized direction.
+    /// The angle between the direction and the side of the cone is in radians.
+    pub fn draw_cone(&mut self, apex: Vec3, direction: Vec3, length: f32, angle: f32, color: Vec3) {
+        let base_center = apex + direction * length;
+        let base_radius = length * angle.tan();
+        let (first_axis, second_axis) = orthogonal_axes(direction);
+        self.draw_circle(base_center, first_axis, second_axis, base_radius, color);
+        for axis in [first_axis, -first_axis, second_axis, -second_axis] {
+            self.draw_line(apex, base_center + axis * base_radius, color);
+        }
+    }
+
+    /// Corners in the order of [`Aabb::corners`]
+    fn draw_box(&mut self, corners: &[Vec3; 8], color: Vec3) {
+        for (start, end) in BOX_EDGES {
+            self.draw_line(corners[start], corners[end], color);
+        }
+    }
+
+    pub fn vertices(&self) -> &[DebugLineVertex] {
+        &self.vertices
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+/// Two normalized axes that are orthogonal to the direction and to each other
+fn orthogonal_axes(direction: Vec3) -> (Vec3, Vec3) {
+    let helper = if direction.y.abs() < 0.99 {
+        Vec3::unit_y()
+    } else {
+        Vec3::unit_x()
+    };
+    let first_axis = direction.cross(helper).normalized();
+    let second_axis = direction.cross(first_axis);
+    (first_axis, second_axis)
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::vulkan::context::Context;
+use crate::vulkan::descriptor_pool::DescriptorPool;
 use crate::vulkan::descriptor_set::{DescriptorSet, DescriptorSetLayout, WriteDescriptorSet};
 use crate::vulkan::image::{simple_image_create_info, Image};
 use crate::vulkan::image_view::ImageView;
@@ -13,6 +14,13 @@ pub struct GBuffer {
     pub albedo_buffer: Arc<ImageView>,
     pub normals_buffer: Arc<ImageView>,
     pub metallic_roughness_buffer: Arc<ImageView>,
+    pub emissive_buffer: Arc<ImageView>,
+    /// Screen space movement since the previous frame, in UV coordinates
+    pub motion_vector_buffer: Arc<ImageView>,
+    /// Index of the model plus one, 0 is the background
+    pub object_id_buffer: Arc<ImageView>,
+    /// Clearcoat factor and clearcoat roughness
+    pub clearcoat_buffer: Arc<ImageView>,
     pub depth_buffer: Arc<ImageView>,
     pub shadow_buffer: Arc<ImageView>,
 
@@ -20,18 +28,89 @@ pub struct GBuffer {
     pub sampler: Arc<Sampler>,
 }
 
+/// One attachment of the geometry pass
+pub struct GBufferAttachment {
+    pub format: vk::Format,
+    pub clear_value: vk::ClearValue,
+}
+
+const CLEAR_COLOR: vk::ClearValue = vk::ClearValue {
+    color: vk::ClearColorValue {
+        float32: [0.0, 0.0, 0.0, 0.0],
+    },
+};
+
+const CLEAR_OBJECT_ID: vk::ClearValue = vk::ClearValue {
+    color: vk::ClearColorValue { uint32: [0; 4] },
+};
+
+const CLEAR_DEPTH: vk::ClearValue = vk::ClearValue {
+    depth_stencil: vk::ClearDepthStencilValue {
+        depth: 1.0,
+        stencil: 0,
+    },
+};
+
 impl GBuffer {
     pub const POSITION_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
     pub const NORMALS_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
     pub const ALBEDO_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
     pub const METALLIC_ROUGHNESS_FORMAT: vk::Format = vk::Format::R8G8_UNORM;
+    pub const EMISSIVE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+    pub const MOTION_VECTOR_FORMAT: vk::Format = vk::Format::R16G16_SFLOAT;
+    pub const OBJECT_ID_FORMAT: vk::Format = vk::Format::R32_UINT;
+    pub const CLEARCOAT_FORMAT: vk::Format = vk::Format::R8G8_UNORM;
     pub const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
     pub const SHADOW_FORMAT: vk::Format = vk::Format::R8_UNORM; // TODO: Check if good
 
+    pub const COLOR_ATTACHMENT_COUNT: usize = 8;
+    /// The color attachments followed by the depth attachment
+    pub const ATTACHMENT_COUNT: usize = GBuffer::COLOR_ATTACHMENT_COUNT + 1;
+
+    /// The attachments of the geometry pass, in the same order as [`GBuffer::attachment_views`]
+    pub const ATTACHMENTS: [GBufferAttachment; GBuffer::ATTACHMENT_COUNT] = [
+        GBufferAttachment {
+            format: GBuffer::POSITION_FORMAT,
+            clear_value: CLEAR_COLOR,
+        },
+        GBufferAttachment {
+            format: GBuffer::ALBEDO_FORMAT,
+            clear_value: CLEAR_COLOR,
+        },
+        GBufferAttachment {
+            format: GBuffer::NORMALS_FORMAT,
+            clear_value: CLEAR_COLOR,
+        },
+        GBufferAttachment {
+            format: GBuffer::METALLIC_ROUGHNESS_FORMAT,
+            clear_value: CLEAR_COLOR,
+        },
+        GBufferAttachment {
+            format: GBuffer::EMISSIVE_FORMAT,
+            clear_value: CLEAR_COLOR,
+        },
+        GBufferAttachment {
+            format: GBuffer::MOTION_VECTOR_FORMAT,
+            clear_value: CLEAR_COLOR,
+        },
+        GBufferAttachment {
+            format: GBuffer::OBJECT_ID_FORMAT,
+            clear_value: CLEAR_OBJECT_ID,
+        },
+        GBufferAttachment {
+            format: GBuffer::CLEARCOAT_FORMAT,
+            clear_value: CLEAR_COLOR,
+        },
+        GBufferAttachment {
+            format: GBuffer::DEPTH_FORMAT,
+            clear_value: CLEAR_DEPTH,
+        },
+    ];
+
     pub fn new(
         context: Arc<Context>,
         swapchain_extent: vk::Extent2D,
-        descriptor_pool: vk::DescriptorPool,
+        descriptor_pool: &DescriptorPool,
     ) -> Self {
         let swapchain_extent_3d = vk::Extent3D {
             width: swapchain_extent.width,
@@ -106,6 +185,77 @@ impl GBuffer {
             ImageAspectFlags::COLOR,
         ));
 
+        let emissive_buffer_image = {
+            let create_info = vk::ImageCreateInfo {
+                extent: swapchain_extent_3d,
+                format: GBuffer::EMISSIVE_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                ..simple_image_create_info()
+            };
+
+            Arc::new(Image::new(context.clone(), &create_info))
+        };
+
+        let emissive_buffer_imageview = Arc::new(ImageView::new_default(
+            context.clone(),
+            emissive_buffer_image.clone(),
+            ImageAspectFlags::COLOR,
+        ));
+
+        let motion_vector_buffer_image = {
+            let create_info = vk::ImageCreateInfo {
+                extent: swapchain_extent_3d,
+                format: GBuffer::MOTION_VECTOR_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                ..simple_image_create_info()
+            };
+
+            Arc::new(Image::new(context.clone(), &create_info))
+        };
+
+        let motion_vector_buffer_imageview = Arc::new(ImageView::new_default(
+            context.clone(),
+            motion_vector_buffer_image.clone(),
+            ImageAspectFlags::COLOR,
+        ));
+
+        // copied to the host for picking objects
+        let object_id_buffer_image = {
+            let create_info = vk::ImageCreateInfo {
+                extent: swapchain_extent_3d,
+                format: GBuffer::OBJECT_ID_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC,
+                ..simple_image_create_info()
+            };
+
+            Arc::new(Image::new(context.clone(), &create_info))
+        };
+
+        let object_id_buffer_imageview = Arc::new(ImageView::new_default(
+            context.clone(),
+            object_id_buffer_image.clone(),
+            ImageAspectFlags::COLOR,
+        ));
+
+        let clearcoat_buffer_image = {
+            let create_info = vk::ImageCreateInfo {
+                extent: swapchain_extent_3d,
+                format: GBuffer::CLEARCOAT_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                ..simple_image_create_info()
+            };
+
+            Arc::new(Image::new(context.clone(), &create_info))
+        };
+
+        let clearcoat_buffer_imageview = Arc::new(ImageView::new_default(
+            context.clone(),
+            clearcoat_buffer_image.clone(),
+            ImageAspectFlags::COLOR,
+        ));
+
         let depth_buffer_image = {
             let create_info = vk::ImageCreateInfo {
                 extent: swapchain_extent_3d,
@@ -127,7 +277,9 @@ impl GBuffer {
             let create_info = vk::ImageCreateInfo {
                 extent: swapchain_extent_3d,
                 format: GBuffer::SHADOW_FORMAT,
-                usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+                usage: vk::ImageUsageFlags::STORAGE
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_DST,
                 ..simple_image_create_info()
             };
 
@@ -173,6 +325,30 @@ impl GBuffer {
                     .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
                     .stage_flags(vk::ShaderStageFlags::FRAGMENT)
                     .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(5)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(6)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(7)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(8)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
             ],
             None,
         ));
@@ -228,14 +404,33 @@ impl GBuffer {
                     vk::ImageLayout::GENERAL,
                     sampler.clone(),
                 ),
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    5,
+                    emissive_buffer_imageview.clone(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    6,
+                    motion_vector_buffer_imageview.clone(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    7,
+                    object_id_buffer_imageview.clone(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    8,
+                    clearcoat_buffer_imageview.clone(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler.clone(),
+                ),
             ];
 
-            DescriptorSet::new(
-                context.clone(),
-                descriptor_pool,
-                descriptor_set_layout,
-                writes,
-            )
+            DescriptorSet::new(descriptor_pool, descriptor_set_layout, writes)
         };
 
         GBuffer {
@@ -243,10 +438,71 @@ impl GBuffer {
             albedo_buffer: albedo_buffer_imageview,
             normals_buffer: normals_buffer_imageview,
             metallic_roughness_buffer: metallic_roughness_buffer_imageview,
+            emissive_buffer: emissive_buffer_imageview,
+            motion_vector_buffer: motion_vector_buffer_imageview,
+            object_id_buffer: object_id_buffer_imageview,
+            clearcoat_buffer: clearcoat_buffer_imageview,
             depth_buffer: depth_buffer_imageview,
             shadow_buffer: shadow_buffer_imageview,
             descriptor_set,
             sampler,
         }
     }
+
+    /// The image views of the geometry pass attachments, in the same order as [`GBuffer::ATTACHMENTS`]
+    pub fn attachment_views(&self) -> [vk::ImageView; GBuffer::ATTACHMENT_COUNT] {
+        [
+            self.position_buffer.inner,
+            self.albedo_buffer.inner,
+            self.normals_buffer.inner,
+            self.metallic_roughness_buffer.inner,
+            self.emissive_buffer.inner,
+            self.motion_vector_buffer.inner,
+            self.object_id_buffer.inner,
+            self.clearcoat_buffer.inner,
+            self.depth_buffer.inner,
+        ]
+    }
+
+    pub fn clear_values() -> [vk::ClearValue; GBuffer::ATTACHMENT_COUNT] {
+        GBuffer::ATTACHMENTS.map(|attachment| attachment.clear_value)
+    }
+
+    /// Marks everything as lit, for when there is no shadow pass that writes the shadow buffer
+    pub fn clear_shadow_buffer(&self, context: &Context, command_buffer: vk::CommandBuffer) {
+        let image_memory_barrier = vk::ImageMemoryBarrier2 {
+            src_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            src_access_mask: vk::AccessFlags2::SHADER_READ,
+            dst_stage_mask: vk::PipelineStageFlags2::CLEAR,
+            dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image: self.shadow_buffer.image.inner,
+            subresource_range: self.shadow_buffer.subresource_range(),
+            ..vk::ImageMemoryBarrier2::default()
+        };
+
+        let dependency_info = vk::DependencyInfo::builder()
+            .image_memory_barriers(std::slice::from_ref(&image_memory_barrier));
+
+        unsafe {
+            context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+
+        unsafe {
+            context.device.cmd_clear_color_image(
+                command_buffer,
+                self.shadow_buffer.image.inner,
+                vk::ImageLayout::GENERAL,
+                &vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                },
+                std::slice::from_ref(&self.shadow_buffer.subresource_range()),
+            )
+        };
+    }
 }
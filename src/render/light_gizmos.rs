@@ -0,0 +1,69 @@
+use ultraviolet::Vec3;
+
+use crate::loader::LightKind;
+use crate::render::debug_draw::DebugDraw;
+use crate::scene::Scene;
+
+/// Shows the lights of the scene with their color, as an arrow for directional lights,
+/// a sphere with the range of point lights and a cone for spot lights
+pub struct LightGizmos {
+    pub enabled: bool,
+    /// Index into the lights of the scene, gets drawn in white
+    pub selected_light: Option<usize>,
+}
+
+impl LightGizmos {
+    const ARROW_LENGTH: f32 = 1.0;
+    /// For lights without a range
+    const DEFAULT_RANGE: f32 = 1.0;
+    const SELECTED_COLOR: Vec3 = Vec3::new(1.0, 1.0, 1.0);
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            selected_light: None,
+        }
+    }
+
+    pub fn draw(&self, scene: &Scene, debug_draw: &mut DebugDraw) {
+        if !self.enabled {
+            return;
+        }
+        for (index, light) in scene.lights.iter().enumerate() {
+            let color = if self.selected_light == Some(index) {
+                Self::SELECTED_COLOR
+            } else {
+                light.color
+            };
+            let position = light.transform.position;
+            let range = light.range.unwrap_or(Self::DEFAULT_RANGE);
+            match light.kind {
+                LightKind::Directional => debug_draw.draw_arrow(
+                    position,
+                    position + light.direction() * Self::ARROW_LENGTH,
+                    color,
+                ),
+                LightKind::Point => debug_draw.draw_sphere(position, range, color),
+                LightKind::Spot {
+                    inner_cone_angle,
+                    outer_cone_angle,
+                } => {
+                    debug_draw.draw_cone(
+                        position,
+                        light.direction(),
+                        range,
+                        outer_cone_angle,
+                        color,
+                    );
+                    debug_draw.draw_cone(
+                        position,
+                        light.direction(),
+                        range,
+                        inner_cone_angle,
+                        color * 0.5,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use ash::vk::{self, AccessFlags2, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2};
+
+use crate::render::pass::taa::TaaPass;
+use crate::vulkan::buffer::Buffer;
+use crate::vulkan::context::Context;
+use crate::vulkan::image_view::ImageView;
+
+/// Counts the pixels that the lighting pass flagged as NaN or infinite, by setting their alpha to 0.
+/// Copies the whole lit image to the host, so it only runs while the non-finite debug view is shown.
+/// Like the object picking, the count arrives one frame later.
+pub struct NonFiniteReadback {
+    /// Has the size of the lit image, gets recreated when that changes
+    readback_buffer: Option<(Arc<Buffer<u16>>, vk::Extent2D)>,
+    is_copy_pending: bool,
+    pub flagged_pixel_count: Option<usize>,
+
+    context: Arc<Context>,
+}
+
+impl NonFiniteReadback {
+    /// Four half floats per pixel
+    const CHANNEL_COUNT: usize = 4;
+
+    pub fn new(context: Arc<Context>) -> Self {
+        assert_eq!(
+            TaaPass::COLOR_FORMAT,
+            vk::Format::R16G16B16A16_SFLOAT,
+            "The readback expects four half floats per pixel"
+        );
+        Self {
+            readback_buffer: None,
+            is_copy_pending: false,
+            flagged_pixel_count: None,
+            context,
+        }
+    }
+
+    /// Counts the flagged pixels that the previous frame copied. Its fence must have been waited on.
+    pub fn update(&mut self) {
+        if !self.is_copy_pending {
+            return;
+        }
+        self.is_copy_pending = false;
+
+        let Some((readback_buffer, extent)) = &self.readback_buffer else {
+            return;
+        };
+        let pixels = readback_buffer
+            .cast::<[u16; Self::CHANNEL_COUNT]>()
+            .expect("Could not read the lit image as pixels")
+            .read_to_vec();
+        debug_assert_eq!(pixels.len(), extent.width as usize * extent.height as usize);
+        // both +0.0 and -0.0 count, the sign is the highest bit of a half float
+        let flagged_pixel_count = pixels.iter().filter(|pixel| pixel[3] & 0x7fff == 0).count();
+        self.flagged_pixel_count = Some(flagged_pixel_count);
+    }
+
+    /// Copies the lit image after the lighting pass, and leaves it ready to be read by the TAA pass
+    pub fn render(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        target: &ImageView,
+        extent: vk::Extent2D,
+    ) {
+        let readback_buffer = match &self.readback_buffer {
+            Some((buffer, buffer_extent)) if *buffer_extent == extent => buffer.clone(),
+            _ => {
+                let pixel_count = extent.width as usize * extent.height as usize;
+                let buffer = Arc::new(Buffer::new(
+                    self.context.clone(),
+                    (pixel_count * Self::CHANNEL_COUNT * std::mem::size_of::<u16>())
+                        as vk::DeviceSize,
+                    vk::BufferUsageFlags::TRANSFER_DST,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                ));
+                self.readback_buffer = Some((buffer.clone(), extent));
+                buffer
+            }
+        };
+
+        let barrier =
+            |old_layout: ImageLayout,
+             new_layout: ImageLayout,
+             (src_stage_mask, src_access_mask),
+             (dst_stage_mask, dst_access_mask)| ImageMemoryBarrier2 {
+                src_stage_mask,
+                src_access_mask,
+                dst_stage_mask,
+                dst_access_mask,
+                old_layout,
+                new_layout,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: target.image.inner,
+                subresource_range: target.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            };
+        let lighting_pass_write = (
+            PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        );
+
+        let image_memory_barrier = barrier(
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            lighting_pass_write,
+            (PipelineStageFlags2::COPY, AccessFlags2::TRANSFER_READ),
+        );
+        let dependency_info = vk::DependencyInfo::builder()
+            .image_memory_barriers(std::slice::from_ref(&image_memory_barrier));
+        unsafe {
+            self.context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+        };
+        unsafe {
+            self.context.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                target.image.inner,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                **readback_buffer,
+                std::slice::from_ref(&region),
+            )
+        };
+        self.is_copy_pending = true;
+
+        // the TAA pass expects the image in the layout that the lighting pass left it in
+        let image_memory_barrier = barrier(
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            (PipelineStageFlags2::COPY, AccessFlags2::NONE),
+            lighting_pass_write,
+        );
+        // together with the fence of the frame, this makes the copied pixels visible to the host
+        let buffer_memory_barrier = vk::BufferMemoryBarrier2 {
+            src_stage_mask: PipelineStageFlags2::COPY,
+            src_access_mask: AccessFlags2::TRANSFER_WRITE,
+            dst_stage_mask: PipelineStageFlags2::HOST,
+            dst_access_mask: AccessFlags2::HOST_READ,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: **readback_buffer,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+            ..vk::BufferMemoryBarrier2::default()
+        };
+        let dependency_info = vk::DependencyInfo::builder()
+            .image_memory_barriers(std::slice::from_ref(&image_memory_barrier))
+            .buffer_memory_barriers(std::slice::from_ref(&buffer_memory_barrier));
+
+        unsafe {
+            self.context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+    }
+}
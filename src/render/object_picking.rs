@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use ash::vk::{self, AccessFlags2, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2};
+use ultraviolet::Vec2;
+
+use crate::render::gbuffer::GBuffer;
+use crate::vulkan::buffer::Buffer;
+use crate::vulkan::context::Context;
+
+/// The id that the geometry pass writes into the object id buffer, 0 is the background
+pub fn object_id(model_index: usize) -> u32 {
+    model_index as u32 + 1
+}
+
+/// Finds the model under the cursor, by copying a single pixel of the object id buffer to the host.
+/// The result arrives one frame later, after the frame that copied the pixel has finished.
+pub struct ObjectPicking {
+    readback_buffer: Arc<Buffer<u32>>,
+    /// In pixels, gets copied in the next frame
+    requested_pixel: Option<Vec2>,
+    /// The last frame copied a pixel that has not been read yet
+    is_copy_pending: bool,
+    /// Index into the models of the scene
+    pub picked_model: Option<usize>,
+    /// Index into the primitives of the picked model, for editing its material
+    pub picked_primitive: usize,
+
+    context: Arc<Context>,
+}
+
+impl ObjectPicking {
+    pub fn new(context: Arc<Context>) -> Self {
+        let readback_buffer = Arc::new(Buffer::new(
+            context.clone(),
+            std::mem::size_of::<u32>() as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        ));
+
+        Self {
+            readback_buffer,
+            requested_pixel: None,
+            is_copy_pending: false,
+            picked_model: None,
+            picked_primitive: 0,
+            context,
+        }
+    }
+
+    /// Picks whatever is at the cursor position, in physical pixels
+    pub fn pick(&mut self, cursor_position: Vec2) {
+        self.requested_pixel = Some(cursor_position);
+    }
+
+    /// Reads the pixel that the previous frame copied. Its fence must have been waited on.
+    pub fn update(&mut self) {
+        if !self.is_copy_pending {
+            return;
+        }
+        self.is_copy_pending = false;
+
+        let mut object_id = [0];
+        self.readback_buffer.read_to_slice(&mut object_id);
+        self.picked_model = object_id[0].checked_sub(1).map(|index| index as usize);
+        self.picked_primitive = 0;
+    }
+
+    /// Copies the requested pixel after the geometry pass,
+    /// and leaves the object id buffer ready to be read by the fragment shaders
+    pub fn render(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        gbuffer: &GBuffer,
+        extent: vk::Extent2D,
+    ) {
+        let object_id_buffer = &gbuffer.object_id_buffer;
+        let requested_pixel = self.requested_pixel.take().filter(|pixel| {
+            pixel.x >= 0.0
+                && pixel.y >= 0.0
+                && (pixel.x as u32) < extent.width
+                && (pixel.y as u32) < extent.height
+        });
+
+        let barrier =
+            |old_layout: ImageLayout,
+             new_layout: ImageLayout,
+             (src_stage_mask, src_access_mask),
+             (dst_stage_mask, dst_access_mask)| ImageMemoryBarrier2 {
+                src_stage_mask,
+                src_access_mask,
+                dst_stage_mask,
+                dst_access_mask,
+                old_layout,
+                new_layout,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: object_id_buffer.image.inner,
+                subresource_range: object_id_buffer.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            };
+        let geometry_pass_write = (
+            PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        );
+        let fragment_shader_read = (
+            PipelineStageFlags2::FRAGMENT_SHADER,
+            AccessFlags2::SHADER_READ,
+        );
+        let transfer_read = (PipelineStageFlags2::COPY, AccessFlags2::TRANSFER_READ);
+
+        let Some(pixel) = requested_pixel else {
+            let image_memory_barrier = barrier(
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                geometry_pass_write,
+                fragment_shader_read,
+            );
+            self.pipeline_barrier(command_buffer, &image_memory_barrier);
+            return;
+        };
+
+        let image_memory_barrier = barrier(
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            geometry_pass_write,
+            transfer_read,
+        );
+        self.pipeline_barrier(command_buffer, &image_memory_barrier);
+
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D {
+                x: pixel.x as i32,
+                y: pixel.y as i32,
+                z: 0,
+            },
+            image_extent: vk::Extent3D {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+        };
+        unsafe {
+            self.context.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                object_id_buffer.image.inner,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                **self.readback_buffer,
+                std::slice::from_ref(&region),
+            )
+        };
+        self.is_copy_pending = true;
+
+        let image_memory_barrier = barrier(
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            (PipelineStageFlags2::COPY, AccessFlags2::NONE),
+            fragment_shader_read,
+        );
+        // together with the fence of the frame, this makes the copied pixel visible to the host
+        let buffer_memory_barrier = vk::BufferMemoryBarrier2 {
+            src_stage_mask: PipelineStageFlags2::COPY,
+            src_access_mask: AccessFlags2::TRANSFER_WRITE,
+            dst_stage_mask: PipelineStageFlags2::HOST,
+            dst_access_mask: AccessFlags2::HOST_READ,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: **self.readback_buffer,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+            ..vk::BufferMemoryBarrier2::default()
+        };
+        let dependency_info = vk::DependencyInfo::builder()
+            .image_memory_barriers(std::slice::from_ref(&image_memory_barrier))
+            .buffer_memory_barriers(std::slice::from_ref(&buffer_memory_barrier));
+
+        unsafe {
+            self.context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+    }
+
+    fn pipeline_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image_memory_barrier: &ImageMemoryBarrier2,
+    ) {
+        let dependency_info = vk::DependencyInfo::builder()
+            .image_memory_barriers(std::slice::from_ref(image_memory_barrier));
+
+        unsafe {
+            self.context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+    }
+}
@@ -0,0 +1,352 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::include_shader;
+use crate::render::debug_draw::{DebugDraw, DebugLineVertex};
+use crate::render::set_layout_cache::DescriptorSetLayoutCache;
+use crate::render::{CameraDescriptorSet, SwapchainIndex, FRAMES_IN_FLIGHT};
+use crate::vulkan::buffer::Buffer;
+use crate::vulkan::context::Context;
+use crate::vulkan::shader_create_info::ShaderError;
+use crate::vulkan::swapchain::SwapchainContainer;
+
+/// Draws the lines of the debug draw over everything else, after the TAA resolve
+pub struct DebugLinesPass {
+    render_pass: vk::RenderPass,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    /// One per swapchain image
+    framebuffers: Vec<vk::Framebuffer>,
+    /// One per frame in flight, with their capacity in vertices. They grow when a frame has more lines.
+    vertex_buffers: Vec<(Buffer<DebugLineVertex>, usize)>,
+    frame_index: usize,
+    vertex_count: u32,
+
+    extent: vk::Extent2D,
+    context: Arc<Context>,
+}
+
+impl DebugLinesPass {
+    const INITIAL_VERTEX_CAPACITY: usize = 1024;
+
+    pub fn new(
+        context: Arc<Context>,
+        swapchain: &SwapchainContainer,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Self {
+        let render_pass = create_render_pass(context.clone(), swapchain.format);
+        let (pipeline, pipeline_layout) =
+            create_pipeline(context.clone(), render_pass, set_layout_cache)
+                .expect("Could not compile debug line shaders");
+        let framebuffers = create_framebuffers(context.clone(), swapchain, render_pass);
+
+        let vertex_buffers = (0..FRAMES_IN_FLIGHT)
+            .map(|_| {
+                (
+                    create_vertex_buffer(context.clone(), Self::INITIAL_VERTEX_CAPACITY),
+                    Self::INITIAL_VERTEX_CAPACITY,
+                )
+            })
+            .collect();
+
+        DebugLinesPass {
+            render_pass,
+            pipeline,
+            pipeline_layout,
+            framebuffers,
+            vertex_buffers,
+            frame_index: 0,
+            vertex_count: 0,
+
+            extent: swapchain.extent,
+            context,
+        }
+    }
+
+    /// Moves the lines into the vertex buffer of the next frame, and clears the debug draw
+    pub fn update(&mut self, debug_draw: &mut DebugDraw) {
+        let vertices = debug_draw.vertices();
+        self.vertex_count = vertices.len() as u32;
+        if vertices.is_empty() {
+            return;
+        }
+
+        self.frame_index = (self.frame_index + 1) % FRAMES_IN_FLIGHT;
+        let (vertex_buffer, capacity) = &mut self.vertex_buffers[self.frame_index];
+        // the previous frame that used this buffer has finished
+        if vertices.len() > *capacity {
+            *capacity = vertices.len().next_power_of_two();
+            *vertex_buffer = create_vertex_buffer(self.context.clone(), *capacity);
+        }
+        vertex_buffer.copy_data(vertices);
+        debug_draw.clear();
+    }
+
+    /// Does nothing when there are no lines
+    pub fn render(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        camera_descriptor_set: &CameraDescriptorSet,
+        swapchain_index: SwapchainIndex,
+        viewport: vk::Viewport,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        let device = &self.context.device;
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffers[swapchain_index.0])
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.extent,
+            });
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                std::slice::from_ref(&camera_descriptor_set.descriptor_set.inner),
+                &[camera_descriptor_set.dynamic_offset()],
+            );
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                std::slice::from_ref(&*self.vertex_buffers[self.frame_index].0),
+                &[0],
+            );
+            device.cmd_draw(command_buffer, self.vertex_count, 1, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    /// The pipeline must not be in use. Keeps the old pipeline when a shader doesn't compile.
+    pub fn reload_shaders(
+        &mut self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Result<(), ShaderError> {
+        let (pipeline, pipeline_layout) =
+            create_pipeline(self.context.clone(), self.render_pass, set_layout_cache)?;
+        self.destroy_pipeline();
+        self.pipeline = pipeline;
+        self.pipeline_layout = pipeline_layout;
+        Ok(())
+    }
+
+    pub fn resize(&mut self, swapchain: &SwapchainContainer) {
+        self.destroy_framebuffers();
+        self.framebuffers = create_framebuffers(self.context.clone(), swapchain, self.render_pass);
+        self.extent = swapchain.extent;
+    }
+
+    fn destroy_pipeline(&self) {
+        let device = &self.context.device;
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+    }
+
+    fn destroy_framebuffers(&self) {
+        for &framebuffer in self.framebuffers.iter() {
+            unsafe { self.context.device.destroy_framebuffer(framebuffer, None) };
+        }
+    }
+}
+
+impl Drop for DebugLinesPass {
+    fn drop(&mut self) {
+        self.destroy_framebuffers();
+        self.destroy_pipeline();
+        unsafe {
+            self.context
+                .device
+                .destroy_render_pass(self.render_pass, None)
+        };
+    }
+}
+
+fn create_vertex_buffer(context: Arc<Context>, capacity: usize) -> Buffer<DebugLineVertex> {
+    Buffer::new(
+        context,
+        (capacity * std::mem::size_of::<DebugLineVertex>()) as vk::DeviceSize,
+        vk::BufferUsageFlags::VERTEX_BUFFER,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )
+}
+
+fn create_framebuffers(
+    context: Arc<Context>,
+    swapchain: &SwapchainContainer,
+    render_pass: vk::RenderPass,
+) -> Vec<vk::Framebuffer> {
+    swapchain
+        .imageviews
+        .iter()
+        .map(|swapchain_image| {
+            let create_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(render_pass)
+                .attachments(std::slice::from_ref(swapchain_image))
+                .width(swapchain.extent.width)
+                .height(swapchain.extent.height)
+                .layers(1);
+
+            unsafe { context.device.create_framebuffer(&create_info, None) }
+                .expect("Could not create framebuffer")
+        })
+        .collect()
+}
+
+fn create_pipeline(
+    context: Arc<Context>,
+    render_pass: vk::RenderPass,
+    set_layout_cache: &DescriptorSetLayoutCache,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let device = &context.device;
+
+    let mut vertex_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::VERTEX,
+        "/debug_lines.vert.spv"
+    )?;
+    let mut fragment_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::FRAGMENT,
+        "/debug_lines.frag.spv"
+    )?;
+
+    let shader_stages = [vertex_shader.build(), fragment_shader.build()];
+
+    let vertex_input_binding_descriptions = DebugLineVertex::binding_descriptions();
+    let vertex_input_attribute_descriptions = DebugLineVertex::attribute_descriptions();
+
+    let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&vertex_input_binding_descriptions)
+        .vertex_attribute_descriptions(&vertex_input_attribute_descriptions);
+
+    let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::LINE_LIST);
+
+    let scissors = [vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+            // Evaluation of (offset.x + extent.width) must not cause a ***signed*** integer addition overflow
+            width: i32::MAX as u32,
+            height: i32::MAX as u32,
+        },
+    }];
+
+    let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissors(&scissors);
+
+    let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .line_width(1.0)
+        .polygon_mode(vk::PolygonMode::FILL);
+
+    let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    // the boxes are also visible through the models
+    let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false);
+
+    let color_blend_attachment_state = super::opaque_color_blend_attachment();
+
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op(vk::LogicOp::CLEAR)
+        .attachments(std::slice::from_ref(&color_blend_attachment_state));
+
+    let descriptor_set_layouts = [set_layout_cache.camera().inner];
+
+    let layout_create_info =
+        vk::PipelineLayoutCreateInfo::builder().set_layouts(&descriptor_set_layouts);
+
+    let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
+        .expect("Could not create pipeline layout");
+
+    let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+        .dynamic_states(std::slice::from_ref(&vk::DynamicState::VIEWPORT));
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_state_create_info)
+        .input_assembly_state(&input_assembly_state_create_info)
+        .viewport_state(&viewport_state_create_info)
+        .rasterization_state(&rasterization_state_create_info)
+        .multisample_state(&multisample_state_create_info)
+        .depth_stencil_state(&depth_stencil_state_create_info)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(layout)
+        .render_pass(render_pass);
+
+    let pipeline = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&create_info),
+            None,
+        )
+    }
+    .expect("Could not create graphics pipeline");
+
+    Ok((pipeline[0], layout))
+}
+
+/// Draws on top of the output of the TAA pass
+fn create_render_pass(context: Arc<Context>, swapchain_format: vk::Format) -> vk::RenderPass {
+    let attachment = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: swapchain_format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::LOAD,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(std::slice::from_ref(&color_attachment_ref));
+
+    let dependencies = [vk::SubpassDependency {
+        src_subpass: vk::SUBPASS_EXTERNAL,
+        dst_subpass: 0,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ..Default::default()
+    }];
+
+    let create_info = vk::RenderPassCreateInfo::builder()
+        .attachments(std::slice::from_ref(&attachment))
+        .subpasses(std::slice::from_ref(&subpass))
+        .dependencies(&dependencies);
+
+    unsafe { context.device.create_render_pass(&create_info, None) }
+        .expect("Could not create render pass")
+}
@@ -2,42 +2,258 @@ use std::sync::Arc;
 
 use ash::vk::{self};
 use crevice::std140::AsStd140;
+use ultraviolet::{Mat4, Vec3};
 
+use crate::vulkan::descriptor_pool::DescriptorPool;
+use crate::vulkan::shader_create_info::{ShaderCreateInfo, ShaderError};
 use crate::vulkan::swapchain::SwapchainContainer;
-use crate::{include_shader, vulkan::context::Context};
 use crate::{
+    camera::Camera,
     render::{
-        gbuffer::GBuffer, set_layout_cache::DescriptorSetLayoutCache, shader_types,
-        CameraDescriptorSet, SwapchainIndex,
+        debug_draw::DebugDraw, gbuffer::GBuffer, object_picking,
+        set_layout_cache::DescriptorSetLayoutCache, shader_types, CameraDescriptorSet,
+        SwapchainIndex,
     },
-    scene::{Scene, Vertex},
+    scene::{Mesh, MeshLod, Model, Primitive, Scene, SkinVertex, Vertex},
 };
+use crate::{include_shader, vulkan::context::Context};
 
 pub struct GeometryPass {
     render_pass: vk::RenderPass,
-    pipeline: vk::Pipeline,
-    pipeline_layout: vk::PipelineLayout,
+    /// Indexed by the vertex deformation
+    pipelines: Vec<(vk::Pipeline, vk::PipelineLayout)>,
     framebuffers: Vec<vk::Framebuffer>,
 
     gbuffer: GBuffer,
-    descriptor_pool: vk::DescriptorPool,
+    descriptor_pool: DescriptorPool,
+    /// Model matrices of this frame and of the previous one, indexed like the models of the scene
+    model_matrices: Vec<Mat4>,
+    previous_model_matrices: Vec<Mat4>,
 
     context: Arc<Context>,
 }
 
+/// Each kind of deformation has its own vertex shader,
+/// so that meshes without one keep the cheapest vertex shader
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VertexDeformation {
+    None,
+    Skinned,
+    MorphTargets,
+}
+
+impl VertexDeformation {
+    pub const ALL: [VertexDeformation; 3] = [
+        VertexDeformation::None,
+        VertexDeformation::Skinned,
+        VertexDeformation::MorphTargets,
+    ];
+
+    pub fn of(model: &Model, primitive: &Primitive) -> Self {
+        if model.skin.is_some() && primitive.mesh.skin_buffer.is_some() {
+            VertexDeformation::Skinned
+        } else if primitive.morph_targets_descriptor_set.is_some() {
+            VertexDeformation::MorphTargets
+        } else {
+            VertexDeformation::None
+        }
+    }
+
+    pub fn vertex_shader(
+        self,
+        context: Arc<Context>,
+    ) -> Result<ShaderCreateInfo<'static>, ShaderError> {
+        match self {
+            VertexDeformation::None => {
+                include_shader!(context, vk::ShaderStageFlags::VERTEX, "/g_buffer.vert.spv")
+            }
+            VertexDeformation::Skinned => include_shader!(
+                context,
+                vk::ShaderStageFlags::VERTEX,
+                "/g_buffer_skinned.vert.spv"
+            ),
+            VertexDeformation::MorphTargets => include_shader!(
+                context,
+                vk::ShaderStageFlags::VERTEX,
+                "/g_buffer_morph_targets.vert.spv"
+            ),
+        }
+    }
+
+    pub fn vertex_input_descriptions(
+        self,
+    ) -> (
+        Vec<vk::VertexInputBindingDescription>,
+        Vec<vk::VertexInputAttributeDescription>,
+    ) {
+        let mut binding_descriptions = Vertex::binding_descriptions().to_vec();
+        let mut attribute_descriptions = Vertex::attribute_descriptions().to_vec();
+        if self == VertexDeformation::Skinned {
+            binding_descriptions.extend(SkinVertex::binding_descriptions());
+            attribute_descriptions.extend(SkinVertex::attribute_descriptions());
+        }
+        (binding_descriptions, attribute_descriptions)
+    }
+
+    /// The camera at set 0, the material at set 1 and the deformation at set 2
+    pub fn descriptor_set_layouts(
+        self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Vec<vk::DescriptorSetLayout> {
+        let mut descriptor_set_layouts = vec![
+            set_layout_cache.camera().inner,
+            set_layout_cache.material().inner,
+        ];
+        match self {
+            VertexDeformation::None => {}
+            VertexDeformation::Skinned => {
+                descriptor_set_layouts.push(set_layout_cache.skin().inner)
+            }
+            VertexDeformation::MorphTargets => {
+                descriptor_set_layouts.push(set_layout_cache.morph_targets().inner)
+            }
+        }
+        descriptor_set_layouts
+    }
+
+    /// Binds the joint matrices or the morph targets of a primitive
+    pub fn bind(
+        self,
+        context: &Context,
+        command_buffer: vk::CommandBuffer,
+        pipeline_layout: vk::PipelineLayout,
+        model: &Model,
+        primitive: &Primitive,
+    ) {
+        match self {
+            VertexDeformation::None => {}
+            VertexDeformation::Skinned => {
+                let skin = model.skin.as_ref().unwrap();
+                let skin_buffer = primitive.mesh.skin_buffer.as_ref().unwrap();
+                unsafe {
+                    context.device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline_layout,
+                        2,
+                        std::slice::from_ref(&skin.descriptor_set.inner),
+                        &[],
+                    );
+                    context.device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        1,
+                        std::slice::from_ref(&***skin_buffer),
+                        &[0],
+                    );
+                }
+            }
+            VertexDeformation::MorphTargets => {
+                let morph_targets_descriptor_set =
+                    primitive.morph_targets_descriptor_set.as_ref().unwrap();
+                unsafe {
+                    context.device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline_layout,
+                        2,
+                        std::slice::from_ref(&morph_targets_descriptor_set.inner),
+                        &[],
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Picks a level of detail for each primitive, based on how large it is on the screen
+pub struct LodSelector {
+    camera_position: Vec3,
+    /// Turns a size divided by the distance into a fraction of the screen height
+    projection_scale: f32,
+    /// Overrides the automatic selection, for debugging
+    pub fixed_lod: Option<usize>,
+    /// Draws the spheres that the selection is based on
+    pub show_bounding_spheres: bool,
+}
+
+impl LodSelector {
+    /// Fraction of the screen height below which LOD1 is used, every further LOD halves it
+    const LOD1_SCREEN_SIZE: f32 = 0.5;
+
+    pub fn new() -> Self {
+        Self {
+            camera_position: Vec3::zero(),
+            projection_scale: 1.0,
+            fixed_lod: None,
+            show_bounding_spheres: false,
+        }
+    }
+
+    pub fn update(&mut self, camera: &Camera) {
+        self.camera_position = camera.position;
+        self.projection_scale = camera.projection_matrix()[1][1].abs();
+    }
+
+    pub fn select(&self, model: &Model, mesh: &Mesh) -> MeshLod {
+        let (center, radius) = Self::bounding_sphere(model, mesh);
+        mesh.lods[self.select_index(center, radius, mesh.lods.len())]
+    }
+
+    /// For a bounding sphere in world space
+    fn select_index(&self, center: Vec3, radius: f32, lod_count: usize) -> usize {
+        let last_lod = lod_count - 1;
+        if let Some(fixed_lod) = self.fixed_lod {
+            return fixed_lod.min(last_lod);
+        }
+
+        let distance = (center - self.camera_position).mag();
+        if distance <= radius {
+            return 0;
+        }
+
+        let screen_size = radius * self.projection_scale / distance;
+        let lod = if screen_size >= Self::LOD1_SCREEN_SIZE {
+            0
+        } else {
+            (Self::LOD1_SCREEN_SIZE / screen_size).log2() as usize + 1
+        };
+        lod.min(last_lod)
+    }
+
+    /// In world space
+    fn bounding_sphere(model: &Model, mesh: &Mesh) -> (Vec3, f32) {
+        let model_matrix: ultraviolet::Mat4 = model.transform.clone().into();
+        let center = model_matrix.transform_point3(mesh.bounds.center());
+        let radius = mesh.bounds.extent().mag() * 0.5 * model.transform.scale.abs().component_max();
+        (center, radius)
+    }
+
+    pub fn draw_bounding_spheres(&self, scene: &Scene, debug_draw: &mut DebugDraw) {
+        if !self.show_bounding_spheres {
+            return;
+        }
+        for model in &scene.models {
+            for primitive in &model.primitives {
+                let (center, radius) = Self::bounding_sphere(model, &primitive.mesh);
+                debug_draw.draw_sphere(center, radius, Vec3::new(0.0, 1.0, 1.0));
+            }
+        }
+    }
+}
+
 impl GeometryPass {
     pub fn new(
         context: Arc<Context>,
         swapchain: &SwapchainContainer,
-        descriptor_pool: vk::DescriptorPool,
+        descriptor_pool: &DescriptorPool,
         set_layout_cache: &DescriptorSetLayoutCache,
     ) -> Self {
         let device = &context.device;
 
         let render_pass = create_render_pass(device);
 
-        let (pipeline, pipeline_layout) =
-            create_pipeline(context.clone(), render_pass, set_layout_cache);
+        let pipelines = create_pipelines(context.clone(), render_pass, set_layout_cache)
+            .expect("Could not compile geometry shaders");
 
         let gbuffer = GBuffer::new(context.clone(), swapchain.extent, descriptor_pool);
 
@@ -45,53 +261,44 @@ impl GeometryPass {
 
         GeometryPass {
             render_pass,
-            pipeline,
-            pipeline_layout,
+            pipelines,
             framebuffers,
             gbuffer,
 
             context,
-            descriptor_pool,
+            descriptor_pool: descriptor_pool.clone(),
+            model_matrices: vec![],
+            previous_model_matrices: vec![],
         }
     }
 
+    /// Remembers the model matrices of the last frame, before a new frame gets rendered
+    pub fn update(&mut self, scene: &Scene) {
+        let model_matrices: Vec<Mat4> = scene
+            .models
+            .iter()
+            .map(|model| model.transform.clone().into())
+            .collect();
+        // a new scene has nothing to compare against
+        self.previous_model_matrices = if self.model_matrices.len() == model_matrices.len() {
+            std::mem::replace(&mut self.model_matrices, model_matrices)
+        } else {
+            self.model_matrices = model_matrices;
+            self.model_matrices.clone()
+        };
+    }
+
     pub fn render(
         &self,
         scene: &Scene,
         camera_descriptor_set: &CameraDescriptorSet,
+        lod_selector: &LodSelector,
         command_buffer: vk::CommandBuffer,
         swapchain: &SwapchainContainer,
         swapchain_index: SwapchainIndex,
         viewport: vk::Viewport,
     ) {
-        let clear_values = [
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                },
-            },
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                },
-            },
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                },
-            },
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                },
-            },
-            vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.0,
-                    stencil: 0,
-                },
-            },
-        ];
+        let clear_values = GBuffer::clear_values();
 
         let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.render_pass)
@@ -110,13 +317,15 @@ impl GeometryPass {
             )
         };
 
+        let (pipeline, pipeline_layout) = self.pipelines[VertexDeformation::None as usize];
         unsafe {
             self.context.device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
+                pipeline,
             )
         };
+        let mut bound_pipeline = pipeline;
 
         unsafe {
             self.context
@@ -128,33 +337,63 @@ impl GeometryPass {
             self.context.device.cmd_bind_descriptor_sets(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
+                pipeline_layout,
                 0,
                 std::slice::from_ref(&camera_descriptor_set.descriptor_set.inner),
-                &[],
+                &[camera_descriptor_set.dynamic_offset()],
             )
         };
 
-        for model in &scene.models {
-            let entity = {
-                let model_matrix = model.transform.clone().into();
-                shader_types::Entity {
-                    model: model_matrix,
-                    normal_matrix: model_matrix.inversed().transposed(),
-                }
+        for (model_index, model) in scene.models.iter().enumerate() {
+            let entity = shader_types::Entity {
+                model: self.model_matrices[model_index],
+                previous_model: self.previous_model_matrices[model_index],
             };
             for primitive in &model.primitives {
+                // the camera set stays bound, since all pipeline layouts start with it
+                let deformation = VertexDeformation::of(model, primitive);
+                let (pipeline, pipeline_layout) = self.pipelines[deformation as usize];
+                if pipeline != bound_pipeline {
+                    unsafe {
+                        self.context.device.cmd_bind_pipeline(
+                            command_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline,
+                        )
+                    };
+                    bound_pipeline = pipeline;
+                }
+
+                deformation.bind(
+                    &self.context,
+                    command_buffer,
+                    pipeline_layout,
+                    model,
+                    primitive,
+                );
+
                 unsafe {
                     self.context.device.cmd_bind_descriptor_sets(
                         command_buffer,
                         vk::PipelineBindPoint::GRAPHICS,
-                        self.pipeline_layout,
+                        pipeline_layout,
                         1,
                         std::slice::from_ref(&primitive.material.descriptor_set.inner),
                         &[],
                     );
                 }
 
+                let cull_mode = if primitive.material.double_sided {
+                    vk::CullModeFlags::NONE
+                } else {
+                    vk::CullModeFlags::BACK
+                };
+                unsafe {
+                    self.context
+                        .device
+                        .cmd_set_cull_mode(command_buffer, cull_mode)
+                };
+
                 unsafe {
                     self.context.device.cmd_bind_index_buffer(
                         command_buffer,
@@ -177,21 +416,23 @@ impl GeometryPass {
                 unsafe {
                     self.context.device.cmd_push_constants(
                         command_buffer,
-                        self.pipeline_layout,
+                        pipeline_layout,
                         vk::ShaderStageFlags::VERTEX,
                         0,
                         entity.as_std140().as_bytes(),
                     );
                 }
 
+                // the first instance is the object id, which saves space in the push constants
+                let lod = lod_selector.select(model, &primitive.mesh);
                 unsafe {
                     self.context.device.cmd_draw_indexed(
                         command_buffer,
-                        primitive.mesh.num_indices,
+                        lod.num_indices,
                         1,
+                        lod.first_index,
                         0,
-                        0,
-                        0,
+                        object_picking::object_id(model_index),
                     )
                 };
             }
@@ -200,6 +441,23 @@ impl GeometryPass {
         unsafe { self.context.device.cmd_end_render_pass(command_buffer) };
     }
 
+    /// The pipelines must not be in use. Keeps the old pipelines when a shader doesn't compile.
+    pub fn reload_shaders(
+        &mut self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Result<(), ShaderError> {
+        let pipelines = create_pipelines(self.context.clone(), self.render_pass, set_layout_cache)?;
+
+        let device = &self.context.device;
+        for &(pipeline, pipeline_layout) in self.pipelines.iter() {
+            unsafe { device.destroy_pipeline(pipeline, None) };
+            unsafe { device.destroy_pipeline_layout(pipeline_layout, None) };
+        }
+
+        self.pipelines = pipelines;
+        Ok(())
+    }
+
     pub fn resize(&mut self, swapchain: &SwapchainContainer) {
         let device = &self.context.device;
         let render_pass = self.render_pass;
@@ -208,7 +466,11 @@ impl GeometryPass {
             unsafe { device.destroy_framebuffer(framebuffer, None) };
         }
 
-        let gbuffer = GBuffer::new(self.context.clone(), swapchain.extent, self.descriptor_pool);
+        let gbuffer = GBuffer::new(
+            self.context.clone(),
+            swapchain.extent,
+            &self.descriptor_pool,
+        );
 
         let framebuffers =
             create_framebuffers(self.context.clone(), swapchain, &gbuffer, render_pass);
@@ -229,8 +491,10 @@ impl Drop for GeometryPass {
         for &framebuffer in self.framebuffers.iter() {
             unsafe { device.destroy_framebuffer(framebuffer, None) };
         }
-        unsafe { device.destroy_pipeline(self.pipeline, None) };
-        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+        for &(pipeline, pipeline_layout) in self.pipelines.iter() {
+            unsafe { device.destroy_pipeline(pipeline, None) };
+            unsafe { device.destroy_pipeline_layout(pipeline_layout, None) };
+        }
 
         unsafe { device.destroy_render_pass(self.render_pass, None) };
     }
@@ -249,13 +513,7 @@ fn create_framebuffers(
             .imageviews
             .iter()
             .map(|_| {
-                let image_views = [
-                    gbuffer.position_buffer.inner,
-                    gbuffer.albedo_buffer.inner,
-                    gbuffer.normals_buffer.inner,
-                    gbuffer.metallic_roughness_buffer.inner,
-                    gbuffer.depth_buffer.inner,
-                ];
+                let image_views = gbuffer.attachment_views();
 
                 let create_info = vk::FramebufferCreateInfo::builder()
                     .render_pass(render_pass)
@@ -273,30 +531,51 @@ fn create_framebuffers(
     framebuffers
 }
 
+/// Destroys the already created pipelines when one of them fails
+fn create_pipelines(
+    context: Arc<Context>,
+    render_pass: vk::RenderPass,
+    set_layout_cache: &DescriptorSetLayoutCache,
+) -> Result<Vec<(vk::Pipeline, vk::PipelineLayout)>, ShaderError> {
+    let mut pipelines = Vec::with_capacity(VertexDeformation::ALL.len());
+    for deformation in VertexDeformation::ALL {
+        match create_pipeline(context.clone(), render_pass, set_layout_cache, deformation) {
+            Ok(pipeline) => pipelines.push(pipeline),
+            Err(error) => {
+                for (pipeline, pipeline_layout) in pipelines {
+                    unsafe { context.device.destroy_pipeline(pipeline, None) };
+                    unsafe {
+                        context
+                            .device
+                            .destroy_pipeline_layout(pipeline_layout, None)
+                    };
+                }
+                return Err(error);
+            }
+        }
+    }
+    Ok(pipelines)
+}
+
 fn create_pipeline(
     context: Arc<Context>,
     render_pass: vk::RenderPass,
     set_layout_cache: &DescriptorSetLayoutCache,
-) -> (vk::Pipeline, vk::PipelineLayout) {
+    deformation: VertexDeformation,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
     let device = &context.device;
 
-    let mut vertex_shader = include_shader!(
-        context.clone(),
-        vk::ShaderStageFlags::VERTEX,
-        "/g_buffer.vert.spv"
-    );
+    let mut vertex_shader = deformation.vertex_shader(context.clone())?;
     let mut fragment_shader = include_shader!(
         context.clone(),
         vk::ShaderStageFlags::FRAGMENT,
         "/g_buffer.frag.spv"
-    );
+    )?;
 
     let shader_stages = [vertex_shader.build(), fragment_shader.build()];
 
-    let (vertex_input_binding_descriptions, vertex_input_attribute_descriptions) = (
-        Vertex::binding_descriptions(),
-        Vertex::attribute_descriptions(),
-    );
+    let (vertex_input_binding_descriptions, vertex_input_attribute_descriptions) =
+        deformation.vertex_input_descriptions();
 
     let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
         .vertex_binding_descriptions(&vertex_input_binding_descriptions)
@@ -318,6 +597,7 @@ fn create_pipeline(
         .viewport_count(1)
         .scissors(&scissors);
 
+    // The cull mode is dynamic, since double sided materials must not be culled
     let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
         .cull_mode(vk::CullModeFlags::BACK)
         .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
@@ -348,31 +628,19 @@ fn create_pipeline(
         .max_depth_bounds(1.0)
         .min_depth_bounds(0.0);
 
-    let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
-        blend_enable: 0,
-        src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
-        dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
-        color_blend_op: vk::BlendOp::ADD,
-        src_alpha_blend_factor: vk::BlendFactor::ZERO,
-        dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-        alpha_blend_op: vk::BlendOp::ADD,
-        color_write_mask: vk::ColorComponentFlags::RGBA,
-    }; 4];
+    let color_blend_attachment_states =
+        [super::opaque_color_blend_attachment(); GBuffer::COLOR_ATTACHMENT_COUNT];
 
     let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
         .logic_op(vk::LogicOp::CLEAR)
         .attachments(&color_blend_attachment_states);
 
-    let descriptor_set_layouts = [
-        set_layout_cache.camera().inner,
-        set_layout_cache.material().inner,
-    ];
+    let descriptor_set_layouts = deformation.descriptor_set_layouts(set_layout_cache);
 
-    let push_constants_ranges = vk::PushConstantRange {
-        stage_flags: vk::ShaderStageFlags::VERTEX,
-        offset: 0,
-        size: std::mem::size_of::<shader_types::Entity>() as u32,
-    };
+    let push_constants_ranges = context.push_constant_range(
+        vk::ShaderStageFlags::VERTEX,
+        std::mem::size_of::<shader_types::Std140Entity>(),
+    );
 
     let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
         .set_layouts(&descriptor_set_layouts)
@@ -382,8 +650,10 @@ fn create_pipeline(
     let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
         .expect("Could not create pipeline layout");
 
-    let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
-        .dynamic_states(std::slice::from_ref(&vk::DynamicState::VIEWPORT));
+    // cull mode is core since Vulkan 1.3
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::CULL_MODE];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
 
     let create_info = vk::GraphicsPipelineCreateInfo::builder()
         .stages(&shader_stages)
@@ -407,115 +677,54 @@ fn create_pipeline(
     }
     .expect("Could not create graphics pipeline");
 
-    (pipeline[0], layout)
+    Ok((pipeline[0], layout))
 }
 
 fn create_render_pass(device: &ash::Device) -> vk::RenderPass {
-    let position_attachment = vk::AttachmentDescription {
-        flags: vk::AttachmentDescriptionFlags::empty(),
-        format: GBuffer::POSITION_FORMAT,
-        samples: vk::SampleCountFlags::TYPE_1,
-        load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::STORE,
-        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-        initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
-
-    let albedo_attachment = vk::AttachmentDescription {
-        flags: vk::AttachmentDescriptionFlags::empty(),
-        format: GBuffer::ALBEDO_FORMAT,
-        samples: vk::SampleCountFlags::TYPE_1,
-        load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::STORE,
-        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-        initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
-
-    let normal_attachment = vk::AttachmentDescription {
-        flags: vk::AttachmentDescriptionFlags::empty(),
-        format: GBuffer::NORMALS_FORMAT,
-        samples: vk::SampleCountFlags::TYPE_1,
-        load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::STORE,
-        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-        initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
-
-    let metallic_roughness_attachment = vk::AttachmentDescription {
-        flags: vk::AttachmentDescriptionFlags::empty(),
-        format: GBuffer::METALLIC_ROUGHNESS_FORMAT,
-        samples: vk::SampleCountFlags::TYPE_1,
-        load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::STORE,
-        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-        initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
-
-    let depth_stencil_attachment = vk::AttachmentDescription {
-        flags: vk::AttachmentDescriptionFlags::empty(),
-        format: GBuffer::DEPTH_FORMAT,
-        samples: vk::SampleCountFlags::TYPE_1,
-        load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::STORE,
-        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-        initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-    };
-
-    let position_attachment_ref = vk::AttachmentReference {
-        attachment: 0,
-        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
+    // The clear values and framebuffer attachments rely on the same order
+    assert_eq!(
+        GBuffer::ATTACHMENTS[GBuffer::COLOR_ATTACHMENT_COUNT].format,
+        GBuffer::DEPTH_FORMAT,
+        "The depth attachment must come after the color attachments"
+    );
 
-    let albedo_attachment_ref = vk::AttachmentReference {
-        attachment: 1,
-        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
+    let attachments: Vec<vk::AttachmentDescription> = GBuffer::ATTACHMENTS
+        .iter()
+        .enumerate()
+        .map(|(index, attachment)| vk::AttachmentDescription {
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            format: attachment.format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: if index < GBuffer::COLOR_ATTACHMENT_COUNT {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            },
+        })
+        .collect();
 
-    let normal_attachment_ref = vk::AttachmentReference {
-        attachment: 2,
-        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
-
-    let metallic_roughness_attachment_ref = vk::AttachmentReference {
-        attachment: 3,
-        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
+    let color_attachment_refs: Vec<vk::AttachmentReference> = (0..GBuffer::COLOR_ATTACHMENT_COUNT)
+        .map(|index| vk::AttachmentReference {
+            attachment: index as u32,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        })
+        .collect();
 
     let depth_attachment_ref = vk::AttachmentReference {
-        attachment: 4,
+        attachment: GBuffer::COLOR_ATTACHMENT_COUNT as u32,
         layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
     };
 
-    let color_attachment_refs = [
-        position_attachment_ref,
-        albedo_attachment_ref,
-        normal_attachment_ref,
-        metallic_roughness_attachment_ref,
-    ];
-
     let subpass = vk::SubpassDescription::builder()
         .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
         .color_attachments(&color_attachment_refs)
         .depth_stencil_attachment(&depth_attachment_ref);
 
-    let attachments = [
-        position_attachment,
-        albedo_attachment,
-        normal_attachment,
-        metallic_roughness_attachment,
-        depth_stencil_attachment,
-    ];
-
     let dependencies = [
         vk::SubpassDependency {
             src_subpass: vk::SUBPASS_EXTERNAL,
@@ -548,3 +757,35 @@ fn create_render_pass(device: &ash::Device) -> vk::RenderPass {
 
     unsafe { device.create_render_pass(&create_info, None) }.expect("Could not create render pass")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_selection() {
+        let mut lod_selector = LodSelector::new();
+        let center = Vec3::new(0.0, 0.0, -10.0);
+        let radius = 1.0;
+
+        // inside of the bounding sphere
+        lod_selector.camera_position = center + Vec3::new(0.0, 0.5, 0.0);
+        assert_eq!(lod_selector.select_index(center, radius, 4), 0);
+
+        // the screen size halves with every doubling of the distance
+        let lods: Vec<usize> = [1.5, 3.0, 6.0, 12.0, 24.0, 1000.0]
+            .iter()
+            .map(|&distance| {
+                lod_selector.camera_position = center + Vec3::new(0.0, 0.0, distance);
+                lod_selector.select_index(center, radius, 4)
+            })
+            .collect();
+        assert_eq!(lods, [0, 1, 2, 3, 3, 3]);
+
+        lod_selector.fixed_lod = Some(1);
+        assert_eq!(lod_selector.select_index(center, radius, 4), 1);
+        lod_selector.fixed_lod = Some(7);
+        assert_eq!(lod_selector.select_index(center, radius, 4), 3);
+        assert_eq!(lod_selector.select_index(center, radius, 1), 0);
+    }
+}
@@ -2,25 +2,101 @@ use std::sync::Arc;
 
 use ash::vk::{self, AccessFlags2, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2};
 use crevice::std140::AsStd140;
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
 
 use crate::render::shader_types::{self, PostProcessing};
 use crate::vulkan::context::Context;
-use crate::vulkan::swapchain::SwapchainContainer;
+use crate::vulkan::image_view::ImageView;
+use crate::vulkan::shader_create_info::ShaderError;
 use crate::{
     include_shader,
     render::{
         gbuffer::GBuffer, set_layout_cache::DescriptorSetLayoutCache, CameraDescriptorSet,
-        SceneDescriptorSet, SwapchainIndex,
+        SceneDescriptorSet, ShadowMode,
     },
 };
 
+use super::taa::TaaPass;
+
+/// What the lighting pass outputs, the other views show a single GBuffer channel
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DebugView {
+    Shaded = 0,
+    Albedo = 1,
+    Normal = 2,
+    Roughness = 3,
+    Metallic = 4,
+    /// There is no ambient occlusion, so this shows how much the sun is occluded
+    Shadow = 5,
+    /// Highlights the pixels where the lighting produced NaNs or infinities
+    NonFinite = 6,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 7] = [
+        DebugView::Shaded,
+        DebugView::Albedo,
+        DebugView::Normal,
+        DebugView::Roughness,
+        DebugView::Metallic,
+        DebugView::Shadow,
+        DebugView::NonFinite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Shaded => "Shaded",
+            DebugView::Albedo => "Albedo",
+            DebugView::Normal => "Normal",
+            DebugView::Roughness => "Roughness",
+            DebugView::Metallic => "Metallic",
+            DebugView::Shadow => "Shadow",
+            DebugView::NonFinite => "Non-finite",
+        }
+    }
+}
+
+/// What the lighting pass outputs where no geometry was drawn
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackgroundMode {
+    Color = 0,
+    /// Alternates between the background color and a darker version of it
+    Checkerboard = 1,
+    /// From the background color at the top to black at the bottom
+    Gradient = 2,
+}
+
+impl BackgroundMode {
+    pub const ALL: [BackgroundMode; 3] = [
+        BackgroundMode::Color,
+        BackgroundMode::Checkerboard,
+        BackgroundMode::Gradient,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackgroundMode::Color => "Color",
+            BackgroundMode::Checkerboard => "Checkerboard",
+            BackgroundMode::Gradient => "Gradient",
+        }
+    }
+}
+
+/// Renders into the color target of the TAA pass
 pub struct LightingPass {
     render_pass: vk::RenderPass,
     pipeline: vk::Pipeline,
     pipeline_layout: vk::PipelineLayout,
-    framebuffers: Vec<vk::Framebuffer>,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
 
-    post_processing: PostProcessing,
+    brightness: f32,
+    pub debug_view: DebugView,
+    pub background_mode: BackgroundMode,
+    pub background_color: Vec3,
 
     context: Arc<Context>,
 }
@@ -28,25 +104,31 @@ pub struct LightingPass {
 impl LightingPass {
     pub fn new(
         context: Arc<Context>,
-        swapchain: &SwapchainContainer,
+        target: &ImageView,
+        extent: vk::Extent2D,
         gbuffer: &GBuffer,
         set_layout_cache: &DescriptorSetLayoutCache,
         brightness: f32,
     ) -> Self {
-        let render_pass = create_render_pass(context.clone(), swapchain.format);
+        let render_pass = create_render_pass(context.clone(), TaaPass::COLOR_FORMAT);
 
         let (pipeline, pipeline_layout) =
-            create_pipeline(context.clone(), render_pass, set_layout_cache, gbuffer);
+            create_pipeline(context.clone(), render_pass, set_layout_cache, gbuffer)
+                .expect("Could not compile lighting shaders");
 
-        let framebuffers = create_framebuffers(context.clone(), swapchain, render_pass);
+        let framebuffer = create_framebuffer(context.clone(), target, extent, render_pass);
 
         LightingPass {
             render_pass,
             pipeline,
             pipeline_layout,
-            framebuffers,
+            framebuffer,
+            extent,
 
-            post_processing: PostProcessing { brightness },
+            brightness,
+            debug_view: DebugView::Shaded,
+            background_mode: BackgroundMode::Color,
+            background_color: Vec3::zero(),
 
             context,
         }
@@ -58,15 +140,18 @@ impl LightingPass {
         gbuffer: &GBuffer,
         scene_descriptor_set: &SceneDescriptorSet,
         camera_descriptor_set: &CameraDescriptorSet,
-        swapchain: &SwapchainContainer,
-        swapchain_index: SwapchainIndex,
         viewport: vk::Viewport,
+        shadow_mode: ShadowMode,
     ) {
+        let (shadow_src_stage_mask, shadow_src_access_mask) = shadow_mode.shadow_buffer_write();
+
         let image_memory_barriers: Vec<ImageMemoryBarrier2> = [
             &gbuffer.position_buffer,
             &gbuffer.albedo_buffer,
             &gbuffer.normals_buffer,
             &gbuffer.metallic_roughness_buffer,
+            &gbuffer.emissive_buffer,
+            &gbuffer.clearcoat_buffer,
         ]
         .into_iter()
         .map(|image| vk::ImageMemoryBarrier2 {
@@ -84,8 +169,8 @@ impl LightingPass {
         })
         .chain(
             [&gbuffer.shadow_buffer].map(|image| vk::ImageMemoryBarrier2 {
-                src_stage_mask: PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
-                src_access_mask: AccessFlags2::SHADER_WRITE,
+                src_stage_mask: shadow_src_stage_mask,
+                src_access_mask: shadow_src_access_mask,
                 dst_stage_mask: PipelineStageFlags2::FRAGMENT_SHADER,
                 dst_access_mask: AccessFlags2::SHADER_READ,
                 old_layout: ImageLayout::GENERAL,
@@ -110,16 +195,16 @@ impl LightingPass {
 
         let clear_values = [vk::ClearValue {
             color: vk::ClearColorValue {
-                float32: [0.0, 0.0, 0.0, 0.0],
+                float32: self.background_color.into_homogeneous_point().into(),
             },
         }];
 
         let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.render_pass)
-            .framebuffer(self.framebuffers[swapchain_index.0])
+            .framebuffer(self.framebuffer)
             .render_area(vk::Rect2D {
                 offset: vk::Offset2D { x: 0, y: 0 },
-                extent: swapchain.extent,
+                extent: self.extent,
             })
             .clear_values(&clear_values);
 
@@ -151,13 +236,19 @@ impl LightingPass {
             camera_descriptor_set.descriptor_set.inner,
         ];
 
+        let post_processing = PostProcessing {
+            brightness: self.brightness,
+            debug_view: self.debug_view as u32,
+            background_mode: self.background_mode as u32,
+            background_color: self.background_color,
+        };
         unsafe {
             self.context.device.cmd_push_constants(
                 command_buffer,
                 self.pipeline_layout,
                 vk::ShaderStageFlags::FRAGMENT,
                 0,
-                self.post_processing.as_std140().as_bytes(),
+                post_processing.as_std140().as_bytes(),
             )
         }
 
@@ -168,7 +259,7 @@ impl LightingPass {
                 self.pipeline_layout,
                 0,
                 &descriptor_set,
-                &[],
+                &[camera_descriptor_set.dynamic_offset()],
             )
         };
 
@@ -177,17 +268,35 @@ impl LightingPass {
         unsafe { self.context.device.cmd_end_render_pass(command_buffer) };
     }
 
-    pub fn resize(&mut self, swapchain: &SwapchainContainer) {
+    /// The pipeline must not be in use. Keeps the old pipeline when a shader doesn't compile.
+    pub fn reload_shaders(
+        &mut self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+        gbuffer: &GBuffer,
+    ) -> Result<(), ShaderError> {
+        let (pipeline, pipeline_layout) = create_pipeline(
+            self.context.clone(),
+            self.render_pass,
+            set_layout_cache,
+            gbuffer,
+        )?;
+
         let device = &self.context.device;
-        let render_pass = self.render_pass;
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
 
-        for &framebuffer in self.framebuffers.iter() {
-            unsafe { device.destroy_framebuffer(framebuffer, None) };
-        }
+        self.pipeline = pipeline;
+        self.pipeline_layout = pipeline_layout;
+        Ok(())
+    }
 
-        let framebuffers = create_framebuffers(self.context.clone(), swapchain, render_pass);
+    pub fn resize(&mut self, target: &ImageView, extent: vk::Extent2D) {
+        let device = &self.context.device;
+        unsafe { device.destroy_framebuffer(self.framebuffer, None) };
 
-        self.framebuffers = framebuffers;
+        self.framebuffer =
+            create_framebuffer(self.context.clone(), target, extent, self.render_pass);
+        self.extent = extent;
     }
 }
 
@@ -196,19 +305,19 @@ fn create_pipeline(
     render_pass: vk::RenderPass,
     set_layout_cache: &DescriptorSetLayoutCache,
     gbuffer: &GBuffer,
-) -> (vk::Pipeline, vk::PipelineLayout) {
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
     let device = &context.device;
 
     let mut vertex_shader = include_shader!(
         context.clone(),
         vk::ShaderStageFlags::VERTEX,
         "/base.vert.spv"
-    );
+    )?;
     let mut fragment_shader = include_shader!(
         context.clone(),
         vk::ShaderStageFlags::FRAGMENT,
         "/base.frag.spv"
-    );
+    )?;
 
     let shader_stages = [vertex_shader.build(), fragment_shader.build()];
 
@@ -260,16 +369,7 @@ fn create_pipeline(
         .max_depth_bounds(1.0)
         .min_depth_bounds(0.0);
 
-    let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
-        blend_enable: 0,
-        src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
-        dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
-        color_blend_op: vk::BlendOp::ADD,
-        src_alpha_blend_factor: vk::BlendFactor::ZERO,
-        dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-        alpha_blend_op: vk::BlendOp::ADD,
-        color_write_mask: vk::ColorComponentFlags::RGBA,
-    }];
+    let color_blend_attachment_states = [super::opaque_color_blend_attachment()];
 
     let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
         .logic_op(vk::LogicOp::CLEAR)
@@ -281,11 +381,10 @@ fn create_pipeline(
         set_layout_cache.camera().inner,
     ];
 
-    let push_constants_ranges: vk::PushConstantRange = vk::PushConstantRange {
-        stage_flags: vk::ShaderStageFlags::FRAGMENT,
-        offset: 0,
-        size: std::mem::size_of::<shader_types::Std140PostProcessing>() as u32,
-    };
+    let push_constants_ranges = context.push_constant_range(
+        vk::ShaderStageFlags::FRAGMENT,
+        std::mem::size_of::<shader_types::Std140PostProcessing>(),
+    );
 
     let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
         .set_layouts(&descriptor_set_layouts)
@@ -320,37 +419,32 @@ fn create_pipeline(
     }
     .expect("Could not create graphics pipeline");
 
-    (pipeline[0], layout)
+    Ok((pipeline[0], layout))
 }
 
-fn create_framebuffers(
+fn create_framebuffer(
     context: Arc<Context>,
-    swapchain: &SwapchainContainer,
+    target: &ImageView,
+    extent: vk::Extent2D,
     render_pass: vk::RenderPass,
-) -> Vec<vk::Framebuffer> {
-    swapchain
-        .imageviews
-        .iter()
-        .map(|swapchain_image| {
-            let image_views = [swapchain_image.clone()];
-
-            let create_info = vk::FramebufferCreateInfo::builder()
-                .render_pass(render_pass)
-                .attachments(&image_views)
-                .width(swapchain.extent.width)
-                .height(swapchain.extent.height)
-                .layers(1);
-
-            unsafe { context.device.create_framebuffer(&create_info, None) }
-                .expect("Could not create framebuffer")
-        })
-        .collect::<Vec<_>>()
+) -> vk::Framebuffer {
+    let image_views = [target.inner];
+
+    let create_info = vk::FramebufferCreateInfo::builder()
+        .render_pass(render_pass)
+        .attachments(&image_views)
+        .width(extent.width)
+        .height(extent.height)
+        .layers(1);
+
+    unsafe { context.device.create_framebuffer(&create_info, None) }
+        .expect("Could not create framebuffer")
 }
 
-fn create_render_pass(context: Arc<Context>, swapchain_format: vk::Format) -> vk::RenderPass {
+fn create_render_pass(context: Arc<Context>, format: vk::Format) -> vk::RenderPass {
     let color_attachment = vk::AttachmentDescription {
         flags: vk::AttachmentDescriptionFlags::empty(),
-        format: swapchain_format,
+        format,
         samples: vk::SampleCountFlags::TYPE_1,
         load_op: vk::AttachmentLoadOp::CLEAR,
         store_op: vk::AttachmentStoreOp::STORE,
@@ -369,9 +463,11 @@ fn create_render_pass(context: Arc<Context>, swapchain_format: vk::Format) -> vk
         .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
         .color_attachments(std::slice::from_ref(&color_attachment_ref));
 
+    // the TAA pass of the previous frame reads the target
     let dependencies = [vk::SubpassDependency {
         src_subpass: vk::SUBPASS_EXTERNAL,
-        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::FRAGMENT_SHADER,
         dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
             | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
         dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
@@ -393,9 +489,7 @@ impl Drop for LightingPass {
     fn drop(&mut self) {
         let device = &self.context.device;
 
-        for &framebuffer in self.framebuffers.iter() {
-            unsafe { device.destroy_framebuffer(framebuffer, None) };
-        }
+        unsafe { device.destroy_framebuffer(self.framebuffer, None) };
         unsafe { device.destroy_pipeline(self.pipeline, None) };
         unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
 
@@ -0,0 +1,755 @@
+use std::sync::Arc;
+
+use ash::vk;
+use crevice::std140::AsStd140;
+use ultraviolet::Vec3;
+
+use crate::include_shader;
+use crate::render::pass::geometry::{LodSelector, VertexDeformation};
+use crate::render::set_layout_cache::DescriptorSetLayoutCache;
+use crate::render::{shader_types, CameraDescriptorSet, SwapchainIndex};
+use crate::scene::Scene;
+use crate::vulkan::context::Context;
+use crate::vulkan::descriptor_pool::DescriptorPool;
+use crate::vulkan::descriptor_set::{DescriptorSet, DescriptorSetLayout, WriteDescriptorSet};
+use crate::vulkan::image::{simple_image_create_info, Image};
+use crate::vulkan::image_view::ImageView;
+use crate::vulkan::sampler::Sampler;
+use crate::vulkan::shader_create_info::ShaderError;
+use crate::vulkan::swapchain::SwapchainContainer;
+
+/// Outlines the selected model. Its primitives are rendered into a mask,
+/// and every pixel close to the mask gets the outline color.
+/// Runs after the TAA resolve, so that the outline doesn't end up in the history.
+pub struct SelectionPass {
+    mask_render_pass: vk::RenderPass,
+    /// Indexed by the vertex deformation
+    mask_pipelines: Vec<(vk::Pipeline, vk::PipelineLayout)>,
+    outline_render_pass: vk::RenderPass,
+    outline_pipeline: vk::Pipeline,
+    outline_pipeline_layout: vk::PipelineLayout,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    targets: SelectionTargets,
+
+    pub color: Vec3,
+    /// In pixels, at most [`SelectionPass::MAX_THICKNESS`]
+    pub thickness: u32,
+
+    extent: vk::Extent2D,
+    sampler: Arc<Sampler>,
+    descriptor_pool: DescriptorPool,
+    context: Arc<Context>,
+}
+
+struct SelectionTargets {
+    /// Kept alive for the framebuffer
+    _mask: Arc<ImageView>,
+    mask_framebuffer: vk::Framebuffer,
+    /// One per swapchain image
+    outline_framebuffers: Vec<vk::Framebuffer>,
+    descriptor_set: DescriptorSet,
+}
+
+impl SelectionPass {
+    pub const MASK_FORMAT: vk::Format = vk::Format::R8_UNORM;
+    pub const MAX_THICKNESS: u32 = 8;
+
+    pub fn new(
+        context: Arc<Context>,
+        swapchain: &SwapchainContainer,
+        descriptor_pool: &DescriptorPool,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Self {
+        let descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
+            context.clone(),
+            &[vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build()],
+            None,
+        ));
+
+        let sampler = {
+            let create_info = vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::NEAREST)
+                .min_filter(vk::Filter::NEAREST)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .max_lod(vk::LOD_CLAMP_NONE);
+
+            let sampler = unsafe { context.device.create_sampler(&create_info, None) }
+                .expect("Could not create sampler");
+
+            Arc::new(Sampler::new(sampler, context.clone()))
+        };
+
+        let mask_render_pass = create_mask_render_pass(context.clone());
+        let outline_render_pass = create_outline_render_pass(context.clone(), swapchain.format);
+
+        let mask_pipelines =
+            create_mask_pipelines(context.clone(), mask_render_pass, set_layout_cache)
+                .expect("Could not compile selection mask shaders");
+        let (outline_pipeline, outline_pipeline_layout) =
+            create_outline_pipeline(context.clone(), outline_render_pass, &descriptor_set_layout)
+                .expect("Could not compile selection outline shaders");
+
+        let targets = create_targets(
+            context.clone(),
+            swapchain,
+            mask_render_pass,
+            outline_render_pass,
+            descriptor_pool,
+            &descriptor_set_layout,
+            &sampler,
+        );
+
+        SelectionPass {
+            mask_render_pass,
+            mask_pipelines,
+            outline_render_pass,
+            outline_pipeline,
+            outline_pipeline_layout,
+            descriptor_set_layout,
+            targets,
+
+            color: Vec3::new(1.0, 0.5, 0.0),
+            thickness: 2,
+
+            extent: swapchain.extent,
+            sampler,
+            descriptor_pool: descriptor_pool.clone(),
+            context,
+        }
+    }
+
+    /// Does nothing when no model is selected
+    pub fn render(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        scene: &Scene,
+        selected_model: Option<usize>,
+        camera_descriptor_set: &CameraDescriptorSet,
+        lod_selector: &LodSelector,
+        swapchain_index: SwapchainIndex,
+        viewport: vk::Viewport,
+    ) {
+        let Some(model) = selected_model.and_then(|index| scene.models.get(index)) else {
+            return;
+        };
+        let device = &self.context.device;
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.extent,
+        };
+
+        let clear_values = [vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 0.0],
+            },
+        }];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.mask_render_pass)
+            .framebuffer(self.targets.mask_framebuffer)
+            .render_area(render_area)
+            .clear_values(&clear_values);
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+        };
+
+        // the previous model matrix only matters for motion vectors
+        let model_matrix: ultraviolet::Mat4 = model.transform.clone().into();
+        let entity = shader_types::Entity {
+            model: model_matrix,
+            previous_model: model_matrix,
+        };
+        for primitive in &model.primitives {
+            let deformation = VertexDeformation::of(model, primitive);
+            let (pipeline, pipeline_layout) = self.mask_pipelines[deformation as usize];
+            unsafe {
+                device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline_layout,
+                    0,
+                    std::slice::from_ref(&camera_descriptor_set.descriptor_set.inner),
+                    &[camera_descriptor_set.dynamic_offset()],
+                );
+            }
+            deformation.bind(
+                &self.context,
+                command_buffer,
+                pipeline_layout,
+                model,
+                primitive,
+            );
+
+            // the same level of detail as in the geometry pass, so that the outline matches
+            let lod = lod_selector.select(model, &primitive.mesh);
+            unsafe {
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    **primitive.mesh.index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+                device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    std::slice::from_ref(&*primitive.mesh.vertex_buffer),
+                    &[0],
+                );
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    entity.as_std140().as_bytes(),
+                );
+                device.cmd_draw_indexed(command_buffer, lod.num_indices, 1, lod.first_index, 0, 0);
+            }
+        }
+
+        unsafe { device.cmd_end_render_pass(command_buffer) };
+
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.outline_render_pass)
+            .framebuffer(self.targets.outline_framebuffers[swapchain_index.0])
+            .render_area(render_area);
+
+        let outline = shader_types::SelectionOutline {
+            color: self.color,
+            thickness: self.thickness.min(Self::MAX_THICKNESS),
+        };
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.outline_pipeline,
+            );
+            device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.outline_pipeline_layout,
+                0,
+                std::slice::from_ref(&self.targets.descriptor_set.inner),
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.outline_pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                outline.as_std140().as_bytes(),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        };
+    }
+
+    /// The pipelines must not be in use. Keeps the old pipelines when a shader doesn't compile.
+    pub fn reload_shaders(
+        &mut self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Result<(), ShaderError> {
+        let mask_pipelines = create_mask_pipelines(
+            self.context.clone(),
+            self.mask_render_pass,
+            set_layout_cache,
+        )?;
+        let outline_pipeline = create_outline_pipeline(
+            self.context.clone(),
+            self.outline_render_pass,
+            &self.descriptor_set_layout,
+        );
+        let (outline_pipeline, outline_pipeline_layout) = match outline_pipeline {
+            Ok(outline_pipeline) => outline_pipeline,
+            Err(error) => {
+                destroy_pipelines(&self.context, &mask_pipelines);
+                return Err(error);
+            }
+        };
+
+        destroy_pipelines(&self.context, &self.mask_pipelines);
+        destroy_pipelines(
+            &self.context,
+            &[(self.outline_pipeline, self.outline_pipeline_layout)],
+        );
+
+        self.mask_pipelines = mask_pipelines;
+        self.outline_pipeline = outline_pipeline;
+        self.outline_pipeline_layout = outline_pipeline_layout;
+        Ok(())
+    }
+
+    pub fn resize(&mut self, swapchain: &SwapchainContainer) {
+        destroy_framebuffers(&self.context, &self.targets);
+
+        self.targets = create_targets(
+            self.context.clone(),
+            swapchain,
+            self.mask_render_pass,
+            self.outline_render_pass,
+            &self.descriptor_pool,
+            &self.descriptor_set_layout,
+            &self.sampler,
+        );
+        self.extent = swapchain.extent;
+    }
+}
+
+impl Drop for SelectionPass {
+    fn drop(&mut self) {
+        let device = &self.context.device;
+
+        destroy_framebuffers(&self.context, &self.targets);
+        destroy_pipelines(&self.context, &self.mask_pipelines);
+        destroy_pipelines(
+            &self.context,
+            &[(self.outline_pipeline, self.outline_pipeline_layout)],
+        );
+
+        unsafe { device.destroy_render_pass(self.mask_render_pass, None) };
+        unsafe { device.destroy_render_pass(self.outline_render_pass, None) };
+    }
+}
+
+fn destroy_pipelines(context: &Context, pipelines: &[(vk::Pipeline, vk::PipelineLayout)]) {
+    for &(pipeline, pipeline_layout) in pipelines {
+        unsafe { context.device.destroy_pipeline(pipeline, None) };
+        unsafe {
+            context
+                .device
+                .destroy_pipeline_layout(pipeline_layout, None)
+        };
+    }
+}
+
+fn destroy_framebuffers(context: &Context, targets: &SelectionTargets) {
+    unsafe {
+        context
+            .device
+            .destroy_framebuffer(targets.mask_framebuffer, None)
+    };
+    for &framebuffer in targets.outline_framebuffers.iter() {
+        unsafe { context.device.destroy_framebuffer(framebuffer, None) };
+    }
+}
+
+fn create_targets(
+    context: Arc<Context>,
+    swapchain: &SwapchainContainer,
+    mask_render_pass: vk::RenderPass,
+    outline_render_pass: vk::RenderPass,
+    descriptor_pool: &DescriptorPool,
+    descriptor_set_layout: &Arc<DescriptorSetLayout>,
+    sampler: &Arc<Sampler>,
+) -> SelectionTargets {
+    let extent = swapchain.extent;
+    let mask = {
+        let create_info = vk::ImageCreateInfo {
+            extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            format: SelectionPass::MASK_FORMAT,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            ..simple_image_create_info()
+        };
+        let image = Arc::new(Image::new(context.clone(), &create_info));
+        Arc::new(ImageView::new_default(
+            context.clone(),
+            image,
+            vk::ImageAspectFlags::COLOR,
+        ))
+    };
+
+    let create_framebuffer = |render_pass: vk::RenderPass, image_view: vk::ImageView| {
+        let create_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass)
+            .attachments(std::slice::from_ref(&image_view))
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+
+        unsafe { context.device.create_framebuffer(&create_info, None) }
+            .expect("Could not create framebuffer")
+    };
+
+    let mask_framebuffer = create_framebuffer(mask_render_pass, mask.inner);
+    let outline_framebuffers = swapchain
+        .imageviews
+        .iter()
+        .map(|&swapchain_image| create_framebuffer(outline_render_pass, swapchain_image))
+        .collect();
+
+    let descriptor_set = DescriptorSet::new(
+        descriptor_pool,
+        descriptor_set_layout.clone(),
+        vec![WriteDescriptorSet::image_view_sampler_with_layout(
+            0,
+            mask.clone(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            sampler.clone(),
+        )],
+    );
+
+    SelectionTargets {
+        _mask: mask,
+        mask_framebuffer,
+        outline_framebuffers,
+        descriptor_set,
+    }
+}
+
+/// Destroys the already created pipelines when one of them fails
+fn create_mask_pipelines(
+    context: Arc<Context>,
+    render_pass: vk::RenderPass,
+    set_layout_cache: &DescriptorSetLayoutCache,
+) -> Result<Vec<(vk::Pipeline, vk::PipelineLayout)>, ShaderError> {
+    let mut pipelines = Vec::with_capacity(VertexDeformation::ALL.len());
+    for deformation in VertexDeformation::ALL {
+        match create_mask_pipeline(context.clone(), render_pass, set_layout_cache, deformation) {
+            Ok(pipeline) => pipelines.push(pipeline),
+            Err(error) => {
+                destroy_pipelines(&context, &pipelines);
+                return Err(error);
+            }
+        }
+    }
+    Ok(pipelines)
+}
+
+/// Uses the vertex shaders of the geometry pass, without the TAA jitter
+fn create_mask_pipeline(
+    context: Arc<Context>,
+    render_pass: vk::RenderPass,
+    set_layout_cache: &DescriptorSetLayoutCache,
+    deformation: VertexDeformation,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let device = &context.device;
+
+    let mut vertex_shader = deformation.vertex_shader(context.clone())?;
+    let mut fragment_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::FRAGMENT,
+        "/selection_mask.frag.spv"
+    )?;
+
+    // IS_UNJITTERED
+    let specialization_data = vk::TRUE.to_ne_bytes();
+    let specialization_map_entry = vk::SpecializationMapEntry {
+        constant_id: 0,
+        offset: 0,
+        size: specialization_data.len(),
+    };
+    let specialization_info = vk::SpecializationInfo::builder()
+        .map_entries(std::slice::from_ref(&specialization_map_entry))
+        .data(&specialization_data);
+
+    let mut vertex_stage = vertex_shader.build();
+    vertex_stage.p_specialization_info = &*specialization_info;
+    let shader_stages = [vertex_stage, fragment_shader.build()];
+
+    let (vertex_input_binding_descriptions, vertex_input_attribute_descriptions) =
+        deformation.vertex_input_descriptions();
+
+    let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&vertex_input_binding_descriptions)
+        .vertex_attribute_descriptions(&vertex_input_attribute_descriptions);
+
+    let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    let scissors = [vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+            // Evaluation of (offset.x + extent.width) must not cause a ***signed*** integer addition overflow
+            width: i32::MAX as u32,
+            height: i32::MAX as u32,
+        },
+    }];
+
+    let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissors(&scissors);
+
+    // only the silhouette matters, so nothing gets culled
+    let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .line_width(1.0)
+        .polygon_mode(vk::PolygonMode::FILL);
+
+    let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    // the outline is also visible through other models
+    let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false);
+
+    let color_blend_attachment_state = super::opaque_color_blend_attachment();
+
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op(vk::LogicOp::CLEAR)
+        .attachments(std::slice::from_ref(&color_blend_attachment_state));
+
+    let descriptor_set_layouts = deformation.descriptor_set_layouts(set_layout_cache);
+
+    let push_constants_ranges = context.push_constant_range(
+        vk::ShaderStageFlags::VERTEX,
+        std::mem::size_of::<shader_types::Std140Entity>(),
+    );
+
+    let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&descriptor_set_layouts)
+        .push_constant_ranges(std::slice::from_ref(&push_constants_ranges))
+        .build();
+
+    let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
+        .expect("Could not create pipeline layout");
+
+    let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+        .dynamic_states(std::slice::from_ref(&vk::DynamicState::VIEWPORT));
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_state_create_info)
+        .input_assembly_state(&input_assembly_state_create_info)
+        .viewport_state(&viewport_state_create_info)
+        .rasterization_state(&rasterization_state_create_info)
+        .multisample_state(&multisample_state_create_info)
+        .depth_stencil_state(&depth_stencil_state_create_info)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(layout)
+        .render_pass(render_pass);
+
+    let pipeline = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&create_info),
+            None,
+        )
+    }
+    .expect("Could not create graphics pipeline");
+
+    Ok((pipeline[0], layout))
+}
+
+fn create_outline_pipeline(
+    context: Arc<Context>,
+    render_pass: vk::RenderPass,
+    descriptor_set_layout: &DescriptorSetLayout,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let device = &context.device;
+
+    let mut vertex_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::VERTEX,
+        "/base.vert.spv"
+    )?;
+    let mut fragment_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::FRAGMENT,
+        "/selection_outline.frag.spv"
+    )?;
+
+    let shader_stages = [vertex_shader.build(), fragment_shader.build()];
+
+    let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder();
+
+    let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    let scissors = [vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+            // Evaluation of (offset.x + extent.width) must not cause a ***signed*** integer addition overflow
+            width: i32::MAX as u32,
+            height: i32::MAX as u32,
+        },
+    }];
+
+    let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissors(&scissors);
+
+    let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .line_width(1.0)
+        .polygon_mode(vk::PolygonMode::FILL);
+
+    let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false);
+
+    // every other pixel gets discarded
+    let color_blend_attachment_state = super::opaque_color_blend_attachment();
+
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op(vk::LogicOp::CLEAR)
+        .attachments(std::slice::from_ref(&color_blend_attachment_state));
+
+    let push_constants_ranges = context.push_constant_range(
+        vk::ShaderStageFlags::FRAGMENT,
+        std::mem::size_of::<shader_types::Std140SelectionOutline>(),
+    );
+
+    let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(std::slice::from_ref(&descriptor_set_layout.inner))
+        .push_constant_ranges(std::slice::from_ref(&push_constants_ranges))
+        .build();
+
+    let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
+        .expect("Could not create pipeline layout");
+
+    let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+        .dynamic_states(std::slice::from_ref(&vk::DynamicState::VIEWPORT));
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_state_create_info)
+        .input_assembly_state(&input_assembly_state_create_info)
+        .viewport_state(&viewport_state_create_info)
+        .rasterization_state(&rasterization_state_create_info)
+        .multisample_state(&multisample_state_create_info)
+        .depth_stencil_state(&depth_stencil_state_create_info)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(layout)
+        .render_pass(render_pass);
+
+    let pipeline = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&create_info),
+            None,
+        )
+    }
+    .expect("Could not create graphics pipeline");
+
+    Ok((pipeline[0], layout))
+}
+
+fn create_mask_render_pass(context: Arc<Context>) -> vk::RenderPass {
+    let attachment = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: SelectionPass::MASK_FORMAT,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(std::slice::from_ref(&color_attachment_ref));
+
+    // the mask was read by the outline of the previous frame, and gets read by the outline of this frame
+    let dependencies = [
+        vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ..Default::default()
+        },
+        vk::SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            ..Default::default()
+        },
+    ];
+
+    let create_info = vk::RenderPassCreateInfo::builder()
+        .attachments(std::slice::from_ref(&attachment))
+        .subpasses(std::slice::from_ref(&subpass))
+        .dependencies(&dependencies);
+
+    unsafe { context.device.create_render_pass(&create_info, None) }
+        .expect("Could not create render pass")
+}
+
+/// Draws on top of the output of the TAA pass
+fn create_outline_render_pass(
+    context: Arc<Context>,
+    swapchain_format: vk::Format,
+) -> vk::RenderPass {
+    let attachment = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: swapchain_format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::LOAD,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(std::slice::from_ref(&color_attachment_ref));
+
+    let dependencies = [vk::SubpassDependency {
+        src_subpass: vk::SUBPASS_EXTERNAL,
+        dst_subpass: 0,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ..Default::default()
+    }];
+
+    let create_info = vk::RenderPassCreateInfo::builder()
+        .attachments(std::slice::from_ref(&attachment))
+        .subpasses(std::slice::from_ref(&subpass))
+        .dependencies(&dependencies);
+
+    unsafe { context.device.create_render_pass(&create_info, None) }
+        .expect("Could not create render pass")
+}
@@ -1,19 +1,23 @@
 use std::sync::Arc;
 
+use crate::vulkan::descriptor_pool::DescriptorPool;
 use ash::vk::{self, AccessFlags2, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2};
+use crevice::std140::AsStd140;
 
 use crate::{
     include_shader,
     render::{
-        gbuffer::GBuffer, set_layout_cache::DescriptorSetLayoutCache, CameraDescriptorSet,
-        SceneDescriptorSet,
+        gbuffer::GBuffer, set_layout_cache::DescriptorSetLayoutCache, shader_types,
+        CameraDescriptorSet, SceneDescriptorSet,
     },
+    scene::Scene,
     utility::aligned_size,
     vulkan::{
         acceleration_structure::AccelerationStructure,
         buffer::Buffer,
         context::Context,
         descriptor_set::{DescriptorSet, DescriptorSetLayout, WriteDescriptorSet},
+        shader_create_info::ShaderError,
     },
 };
 
@@ -21,12 +25,22 @@ pub struct ShadowPass {
     pipeline: vk::Pipeline,
     pipeline_layout: vk::PipelineLayout,
 
-    descriptor_pool: vk::DescriptorPool,
+    descriptor_pool: DescriptorPool,
     descriptor_set: DescriptorSet,
     shader_binding_tables: ShaderBindingTables,
 
     acceleration_structure: Arc<AccelerationStructure>,
 
+    /// Against shadow acne, in depth buffer units
+    pub depth_bias: f32,
+    /// Against shadow acne, in world units
+    pub normal_bias: f32,
+    /// Rays per pixel, more than one gives soft shadows
+    pub sample_count: u32,
+    /// In degrees, the sun is about 0.53. Only used for soft shadows.
+    pub light_angular_diameter: f32,
+    frame_index: u32,
+
     context: Arc<Context>,
 }
 
@@ -38,11 +52,11 @@ pub struct ShaderBindingTable {
 impl ShaderBindingTable {
     pub fn new(context: Arc<Context>, handle_count: u32) -> Self {
         let shader_group_handle_size = context
-            .context_raytracing
+            .raytracing()
             .physical_device_ray_tracing_pipeline_properties_khr
             .shader_group_handle_size;
         let shader_group_handle_alignment = context
-            .context_raytracing
+            .raytracing()
             .physical_device_ray_tracing_pipeline_properties_khr
             .shader_group_handle_alignment;
 
@@ -77,11 +91,13 @@ pub struct ShaderBindingTables {
 }
 
 impl ShadowPass {
+    pub const MAX_SAMPLE_COUNT: u32 = 16;
+
     pub fn new(
         context: Arc<Context>,
         gbuffer: &GBuffer,
         set_layout_cache: &DescriptorSetLayoutCache,
-        descriptor_pool: vk::DescriptorPool,
+        descriptor_pool: &DescriptorPool,
         acceleration_structure: Arc<AccelerationStructure>,
     ) -> Self {
         let descriptor_set = create_descriptor_set(
@@ -95,7 +111,8 @@ impl ShadowPass {
             context.clone(),
             set_layout_cache,
             descriptor_set.layout.inner,
-        );
+        )
+        .expect("Could not compile shadow shaders");
 
         let shader_binding_tables = create_shader_binding_tables(context.clone(), pipeline, 3); // todo: remove hardcoded value
 
@@ -103,18 +120,25 @@ impl ShadowPass {
             pipeline,
             pipeline_layout,
 
-            descriptor_pool,
+            descriptor_pool: descriptor_pool.clone(),
             descriptor_set,
             shader_binding_tables,
 
             acceleration_structure,
 
+            depth_bias: 0.00001,
+            normal_bias: 0.0,
+            sample_count: 1,
+            light_angular_diameter: 0.53,
+            frame_index: 0,
+
             context,
         }
     }
 
     pub fn render(
-        &self,
+        &mut self,
+        scene: &Scene,
         gbuffer: &GBuffer,
         scene_descriptor_set: &SceneDescriptorSet,
         camera_descriptor_set: &CameraDescriptorSet,
@@ -170,11 +194,18 @@ impl ShadowPass {
             )
         };
 
-        let descriptor_sets = [
+        let mut descriptor_sets = vec![
             scene_descriptor_set.descriptor_set.inner,
             camera_descriptor_set.descriptor_set.inner,
             self.descriptor_set.inner,
         ];
+        if let Some(bindless) = scene
+            .raytracing_scene
+            .as_ref()
+            .and_then(|raytracing_scene| raytracing_scene.bindless.as_ref())
+        {
+            descriptor_sets.push(bindless.descriptor_set.inner);
+        }
 
         unsafe {
             self.context.device.cmd_bind_descriptor_sets(
@@ -183,7 +214,25 @@ impl ShadowPass {
                 self.pipeline_layout,
                 0,
                 &descriptor_sets,
-                &[],
+                &[camera_descriptor_set.dynamic_offset()],
+            )
+        };
+
+        self.frame_index = self.frame_index.wrapping_add(1);
+        let raytraced_shadow = shader_types::RaytracedShadow {
+            depth_bias: self.depth_bias,
+            normal_bias: self.normal_bias,
+            light_angular_radius: self.light_angular_diameter.to_radians() / 2.0,
+            sample_count: self.sample_count.clamp(1, ShadowPass::MAX_SAMPLE_COUNT),
+            frame_index: self.frame_index,
+        };
+        unsafe {
+            self.context.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::RAYGEN_KHR,
+                0,
+                raytraced_shadow.as_std140().as_bytes(),
             )
         };
 
@@ -191,7 +240,7 @@ impl ShadowPass {
 
         unsafe {
             self.context
-                .context_raytracing
+                .raytracing()
                 .ray_tracing_pipeline
                 .cmd_trace_rays(
                     command_buffer,
@@ -212,10 +261,33 @@ impl ShadowPass {
         };
     }
 
+    /// The pipeline must not be in use. The shader group handles change, so the shader binding tables get recreated as well.
+    /// Keeps the old pipeline when a shader doesn't compile.
+    pub fn reload_shaders(
+        &mut self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Result<(), ShaderError> {
+        let (pipeline, pipeline_layout) = create_pipeline(
+            self.context.clone(),
+            set_layout_cache,
+            self.descriptor_set.layout.inner,
+        )?;
+
+        let device = &self.context.device;
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+
+        self.pipeline = pipeline;
+        self.pipeline_layout = pipeline_layout;
+        self.shader_binding_tables =
+            create_shader_binding_tables(self.context.clone(), self.pipeline, 3);
+        Ok(())
+    }
+
     pub fn resize(&mut self, gbuffer: &GBuffer) {
         self.descriptor_set = create_descriptor_set(
             self.context.clone(),
-            self.descriptor_pool,
+            &self.descriptor_pool,
             self.acceleration_structure.clone(),
             gbuffer,
         );
@@ -234,12 +306,16 @@ fn create_pipeline(
     context: Arc<Context>,
     set_layout_cache: &DescriptorSetLayoutCache,
     set_layout: vk::DescriptorSetLayout,
-) -> (vk::Pipeline, vk::PipelineLayout) {
-    let set_layouts = [
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let mut set_layouts = vec![
         set_layout_cache.scene().inner,
         set_layout_cache.camera().inner,
         set_layout,
     ];
+    let bindless_set_layout = set_layout_cache.bindless();
+    if let Some(bindless_set_layout) = &bindless_set_layout {
+        set_layouts.push(bindless_set_layout.inner);
+    }
 
     let mut shader_stages = vec![];
     let mut shader_groups = vec![];
@@ -248,7 +324,7 @@ fn create_pipeline(
         context.clone(),
         vk::ShaderStageFlags::RAYGEN_KHR,
         "/shadow/shadow.rgen.spv"
-    );
+    )?;
     shader_stages.push(raygen_shader.build());
     shader_groups.push(
         vk::RayTracingShaderGroupCreateInfoKHR::builder()
@@ -264,7 +340,7 @@ fn create_pipeline(
         context.clone(),
         vk::ShaderStageFlags::MISS_KHR,
         "/shadow/shadow.rmiss.spv"
-    );
+    )?;
     shader_stages.push(miss_shader.build());
     shader_groups.push(
         vk::RayTracingShaderGroupCreateInfoKHR::builder()
@@ -280,7 +356,7 @@ fn create_pipeline(
         context.clone(),
         vk::ShaderStageFlags::CLOSEST_HIT_KHR,
         "/shadow/shadow.rchit.spv"
-    );
+    )?;
     shader_stages.push(hit_shader.build());
     shader_groups.push(
         vk::RayTracingShaderGroupCreateInfoKHR::builder()
@@ -292,8 +368,14 @@ fn create_pipeline(
             .build(),
     );
 
-    let pipeline_layout_create_info =
-        vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+    let push_constant_range = context.push_constant_range(
+        vk::ShaderStageFlags::RAYGEN_KHR,
+        std::mem::size_of::<shader_types::Std140RaytracedShadow>(),
+    );
+
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(std::slice::from_ref(&push_constant_range));
     let pipeline_layout = unsafe {
         context
             .device
@@ -308,10 +390,10 @@ fn create_pipeline(
         .layout(pipeline_layout)
         .build();
 
-    (
+    Ok((
         unsafe {
             context
-                .context_raytracing
+                .raytracing()
                 .ray_tracing_pipeline
                 .create_ray_tracing_pipelines(
                     vk::DeferredOperationKHR::null(),
@@ -322,7 +404,7 @@ fn create_pipeline(
         }
         .expect("Could not create raytracing pipeline")[0],
         pipeline_layout,
-    )
+    ))
 }
 
 fn create_shader_binding_tables(
@@ -331,7 +413,7 @@ fn create_shader_binding_tables(
     num_shader_groups: u32,
 ) -> ShaderBindingTables {
     let rt_properties = context
-        .context_raytracing
+        .raytracing()
         .physical_device_ray_tracing_pipeline_properties_khr;
     let handle_size = rt_properties.shader_group_handle_size;
     let handle_size_aligned = aligned_size(
@@ -343,7 +425,7 @@ fn create_shader_binding_tables(
 
     let shader_handle_storage = unsafe {
         context
-            .context_raytracing
+            .raytracing()
             .ray_tracing_pipeline
             .get_ray_tracing_shader_group_handles(pipeline, 0, group_count, sbt_size as usize)
     }
@@ -373,7 +455,7 @@ fn create_shader_binding_tables(
 
 fn create_descriptor_set(
     context: Arc<Context>,
-    descriptor_pool: vk::DescriptorPool,
+    descriptor_pool: &DescriptorPool,
     acceleration_structure: Arc<AccelerationStructure>,
     gbuffer: &GBuffer,
 ) -> DescriptorSet {
@@ -403,7 +485,6 @@ fn create_descriptor_set(
     ));
 
     DescriptorSet::new(
-        context.clone(),
         descriptor_pool,
         set_layout,
         vec![
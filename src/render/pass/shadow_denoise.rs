@@ -0,0 +1,435 @@
+use std::sync::Arc;
+
+use ash::vk::{self, AccessFlags2, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2};
+use crevice::std140::AsStd140;
+
+use crate::vulkan::context::Context;
+use crate::vulkan::descriptor_pool::DescriptorPool;
+use crate::vulkan::descriptor_set::{DescriptorSet, DescriptorSetLayout, WriteDescriptorSet};
+use crate::vulkan::image::{simple_image_create_info, Image};
+use crate::vulkan::image_view::ImageView;
+use crate::vulkan::sampler::Sampler;
+use crate::vulkan::shader_create_info::ShaderError;
+use crate::{
+    include_shader,
+    render::{gbuffer::GBuffer, shader_types},
+};
+
+/// Local size of the denoise compute shader
+const DENOISE_WORKGROUP_SIZE: u32 = 8;
+
+/// Temporal accumulation of the raytraced shadows, which are noisy when they are soft.
+/// Blends the shadow buffer with the result of the previous frames, reprojected with the motion vectors,
+/// and writes the result back into the shadow buffer.
+pub struct ShadowDenoisePass {
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    targets: ShadowDenoiseTargets,
+
+    pub enabled: bool,
+    /// How much of the history is kept every frame
+    pub accumulation_weight: f32,
+    /// The history image that gets written this frame, the other one gets read
+    history_index: usize,
+    /// Frames since the history images were created, the very first frame has no history to read
+    history_frame_count: usize,
+    /// Frames since the last camera cut, the history is only as long as this
+    accumulated_frame_count: u32,
+
+    sampler: Arc<Sampler>,
+    descriptor_pool: DescriptorPool,
+    context: Arc<Context>,
+}
+
+struct ShadowDenoiseTargets {
+    history: [Arc<ImageView>; 2],
+    /// Index `i` is used while writing history image `i`, so it reads the other one
+    descriptor_sets: [DescriptorSet; 2],
+}
+
+impl ShadowDenoisePass {
+    pub fn new(context: Arc<Context>, gbuffer: &GBuffer, descriptor_pool: &DescriptorPool) -> Self {
+        let descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
+            context.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(2)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(3)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            ],
+            None,
+        ));
+
+        // bilinear, since the reprojected history is not aligned to the pixels
+        let sampler = {
+            let create_info = vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::LINEAR)
+                .min_filter(vk::Filter::LINEAR)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .max_lod(vk::LOD_CLAMP_NONE);
+
+            let sampler = unsafe { context.device.create_sampler(&create_info, None) }
+                .expect("Could not create sampler");
+
+            Arc::new(Sampler::new(sampler, context.clone()))
+        };
+
+        let (pipeline, pipeline_layout) = create_pipeline(context.clone(), &descriptor_set_layout)
+            .expect("Could not compile shadow denoise shader");
+
+        let targets = create_targets(
+            context.clone(),
+            gbuffer,
+            descriptor_pool,
+            &descriptor_set_layout,
+            &sampler,
+        );
+
+        ShadowDenoisePass {
+            pipeline,
+            pipeline_layout,
+            descriptor_set_layout,
+            targets,
+
+            enabled: true,
+            accumulation_weight: 0.9,
+            history_index: 0,
+            history_frame_count: 0,
+            accumulated_frame_count: 0,
+
+            sampler,
+            descriptor_pool: descriptor_pool.clone(),
+            context,
+        }
+    }
+
+    /// Throws away the history, for example after a camera cut
+    pub fn reset_history(&mut self) {
+        self.accumulated_frame_count = 0;
+    }
+
+    /// Runs after the raytraced shadow pass. Leaves the motion vectors in the layout that the TAA pass expects.
+    pub fn render(
+        &mut self,
+        gbuffer: &GBuffer,
+        extent: vk::Extent2D,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        if !self.enabled {
+            self.reset_history();
+            return;
+        }
+
+        self.history_index = 1 - self.history_index;
+        let write_history = &self.targets.history[self.history_index];
+        let read_history = &self.targets.history[1 - self.history_index];
+        // the history image that gets read was never written in the very first frame
+        let read_history_layout = if self.history_frame_count == 0 {
+            ImageLayout::UNDEFINED
+        } else {
+            ImageLayout::GENERAL
+        };
+
+        // a running average until the history is long enough
+        let accumulated_frame_count = self.accumulated_frame_count as f32;
+        let push_constants = shader_types::ShadowDenoise {
+            history_weight: self
+                .accumulation_weight
+                .min(accumulated_frame_count / (accumulated_frame_count + 1.0)),
+        };
+        self.history_frame_count += 1;
+        self.accumulated_frame_count = self.accumulated_frame_count.saturating_add(1);
+
+        let barrier =
+            |image: &ImageView,
+             old_layout: ImageLayout,
+             new_layout: ImageLayout,
+             (src_stage_mask, src_access_mask),
+             (dst_stage_mask, dst_access_mask)| ImageMemoryBarrier2 {
+                src_stage_mask,
+                src_access_mask,
+                dst_stage_mask,
+                dst_access_mask,
+                old_layout,
+                new_layout,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: image.image.inner,
+                subresource_range: image.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            };
+        let geometry_pass_write = (
+            PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        );
+        let compute_read = (
+            PipelineStageFlags2::COMPUTE_SHADER,
+            AccessFlags2::SHADER_READ,
+        );
+        let compute_write = (
+            PipelineStageFlags2::COMPUTE_SHADER,
+            AccessFlags2::SHADER_WRITE,
+        );
+
+        let image_memory_barriers = [
+            barrier(
+                &gbuffer.shadow_buffer,
+                ImageLayout::GENERAL,
+                ImageLayout::GENERAL,
+                (
+                    PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+                    AccessFlags2::SHADER_WRITE,
+                ),
+                (
+                    PipelineStageFlags2::COMPUTE_SHADER,
+                    AccessFlags2::SHADER_READ | AccessFlags2::SHADER_WRITE,
+                ),
+            ),
+            barrier(
+                &gbuffer.motion_vector_buffer,
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                geometry_pass_write,
+                compute_read,
+            ),
+            // written in the previous frame
+            barrier(
+                read_history,
+                read_history_layout,
+                ImageLayout::GENERAL,
+                compute_write,
+                compute_read,
+            ),
+            // read in the previous frame
+            barrier(
+                write_history,
+                ImageLayout::UNDEFINED,
+                ImageLayout::GENERAL,
+                compute_read,
+                compute_write,
+            ),
+        ];
+        self.pipeline_barrier(command_buffer, &image_memory_barriers);
+
+        unsafe {
+            self.context.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            )
+        };
+
+        unsafe {
+            self.context.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                push_constants.as_std140().as_bytes(),
+            )
+        };
+
+        unsafe {
+            self.context.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                std::slice::from_ref(&self.targets.descriptor_sets[self.history_index].inner),
+                &[],
+            )
+        };
+
+        unsafe {
+            self.context.device.cmd_dispatch(
+                command_buffer,
+                (extent.width + DENOISE_WORKGROUP_SIZE - 1) / DENOISE_WORKGROUP_SIZE,
+                (extent.height + DENOISE_WORKGROUP_SIZE - 1) / DENOISE_WORKGROUP_SIZE,
+                1,
+            )
+        };
+
+        let image_memory_barrier = barrier(
+            &gbuffer.motion_vector_buffer,
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            (PipelineStageFlags2::COMPUTE_SHADER, AccessFlags2::NONE),
+            geometry_pass_write,
+        );
+        self.pipeline_barrier(command_buffer, std::slice::from_ref(&image_memory_barrier));
+    }
+
+    /// The pipeline must not be in use. Keeps the old pipeline when the shader doesn't compile.
+    pub fn reload_shaders(&mut self) -> Result<(), ShaderError> {
+        let (pipeline, pipeline_layout) =
+            create_pipeline(self.context.clone(), &self.descriptor_set_layout)?;
+
+        let device = &self.context.device;
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+
+        self.pipeline = pipeline;
+        self.pipeline_layout = pipeline_layout;
+        Ok(())
+    }
+
+    /// Throws away the history
+    pub fn resize(&mut self, gbuffer: &GBuffer) {
+        self.targets = create_targets(
+            self.context.clone(),
+            gbuffer,
+            &self.descriptor_pool,
+            &self.descriptor_set_layout,
+            &self.sampler,
+        );
+        self.history_frame_count = 0;
+        self.reset_history();
+    }
+
+    fn pipeline_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image_memory_barriers: &[ImageMemoryBarrier2],
+    ) {
+        let dependency_info =
+            vk::DependencyInfo::builder().image_memory_barriers(image_memory_barriers);
+
+        unsafe {
+            self.context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+    }
+}
+
+impl Drop for ShadowDenoisePass {
+    fn drop(&mut self) {
+        let device = &self.context.device;
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+    }
+}
+
+fn create_targets(
+    context: Arc<Context>,
+    gbuffer: &GBuffer,
+    descriptor_pool: &DescriptorPool,
+    descriptor_set_layout: &Arc<DescriptorSetLayout>,
+    sampler: &Arc<Sampler>,
+) -> ShadowDenoiseTargets {
+    let create_image_view = || {
+        let create_info = vk::ImageCreateInfo {
+            extent: gbuffer.shadow_buffer.image.extent,
+            format: GBuffer::SHADOW_FORMAT,
+            usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            ..simple_image_create_info()
+        };
+        let image = Arc::new(Image::new(context.clone(), &create_info));
+        Arc::new(ImageView::new_default(
+            context.clone(),
+            image,
+            vk::ImageAspectFlags::COLOR,
+        ))
+    };
+    let history = [create_image_view(), create_image_view()];
+
+    let descriptor_sets = [1, 0].map(|read_index| {
+        DescriptorSet::new(
+            descriptor_pool,
+            descriptor_set_layout.clone(),
+            vec![
+                WriteDescriptorSet::storage_image_view_with_layout(
+                    0,
+                    gbuffer.shadow_buffer.clone(),
+                    vk::ImageLayout::GENERAL,
+                ),
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    1,
+                    gbuffer.motion_vector_buffer.clone(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    gbuffer.sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    2,
+                    history[read_index].clone(),
+                    vk::ImageLayout::GENERAL,
+                    sampler.clone(),
+                ),
+                WriteDescriptorSet::storage_image_view_with_layout(
+                    3,
+                    history[1 - read_index].clone(),
+                    vk::ImageLayout::GENERAL,
+                ),
+            ],
+        )
+    });
+
+    ShadowDenoiseTargets {
+        history,
+        descriptor_sets,
+    }
+}
+
+fn create_pipeline(
+    context: Arc<Context>,
+    descriptor_set_layout: &DescriptorSetLayout,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let device = &context.device;
+
+    let mut compute_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::COMPUTE,
+        "/shadow/denoise.comp.spv"
+    )?;
+
+    let push_constants_ranges = context.push_constant_range(
+        vk::ShaderStageFlags::COMPUTE,
+        std::mem::size_of::<shader_types::Std140ShadowDenoise>(),
+    );
+
+    let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(std::slice::from_ref(&descriptor_set_layout.inner))
+        .push_constant_ranges(std::slice::from_ref(&push_constants_ranges));
+
+    let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
+        .expect("Could not create shadow denoise pipeline layout");
+
+    let create_info = vk::ComputePipelineCreateInfo::builder()
+        .stage(compute_shader.build())
+        .layout(layout);
+
+    let pipeline = unsafe {
+        device.create_compute_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&create_info),
+            None,
+        )
+    }
+    .expect("Could not create shadow denoise pipeline");
+
+    Ok((pipeline[0], layout))
+}
@@ -0,0 +1,799 @@
+use std::sync::Arc;
+
+use crate::vulkan::descriptor_pool::DescriptorPool;
+use ash::vk::{self, AccessFlags2, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2};
+use crevice::std140::AsStd140;
+use ultraviolet::{projection, Mat4, Vec3, Vec4};
+
+use crate::{
+    camera::Camera,
+    config_loader::ShadowMapSettings,
+    include_shader,
+    render::{
+        gbuffer::GBuffer,
+        set_layout_cache::DescriptorSetLayoutCache,
+        shader_types::{self, MAX_SHADOW_CASCADES},
+        CameraDescriptorSet,
+    },
+    scene::{Scene, Vertex},
+    vulkan::{
+        buffer::Buffer,
+        context::Context,
+        descriptor_set::{DescriptorSet, DescriptorSetLayout, WriteDescriptorSet},
+        image::{simple_image_create_info, Image},
+        image_view::ImageView,
+        sampler::Sampler,
+        shader_create_info::ShaderError,
+    },
+};
+
+/// How far behind the camera frustum objects can still cast shadows
+const SHADOW_CASTER_DISTANCE: f32 = 50.0;
+
+/// Local size of the resolve compute shader
+const RESOLVE_WORKGROUP_SIZE: u32 = 8;
+
+/// Rasterized cascaded shadow maps, for when raytracing isn't available.
+/// Renders the scene from the sun into one depth image, with the cascades side by side,
+/// and then resolves it into the shadow buffer of the gbuffer.
+pub struct ShadowMapPass {
+    render_pass: vk::RenderPass,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    framebuffer: vk::Framebuffer,
+
+    resolve_pipeline: vk::Pipeline,
+    resolve_pipeline_layout: vk::PipelineLayout,
+
+    shadow_map: Arc<ImageView>,
+    shadow_map_sampler: Arc<Sampler>,
+    cascades: shader_types::ShadowCascades,
+    cascades_buffer: Buffer<shader_types::ShadowCascades>,
+    cascade_splits: Vec<f32>,
+    resolution: u32,
+
+    descriptor_pool: DescriptorPool,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    descriptor_set: DescriptorSet,
+
+    context: Arc<Context>,
+}
+
+impl ShadowMapPass {
+    pub const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+    pub fn new(
+        context: Arc<Context>,
+        gbuffer: &GBuffer,
+        set_layout_cache: &DescriptorSetLayoutCache,
+        descriptor_pool: &DescriptorPool,
+        settings: &ShadowMapSettings,
+    ) -> Self {
+        let cascade_splits = settings.cascade_splits.clone();
+        assert!(
+            (1..=MAX_SHADOW_CASCADES).contains(&cascade_splits.len()),
+            "Shadow maps need between 1 and {} cascades",
+            MAX_SHADOW_CASCADES
+        );
+        let resolution = settings.resolution;
+
+        let shadow_map = {
+            let create_info = vk::ImageCreateInfo {
+                extent: vk::Extent3D {
+                    width: resolution * cascade_splits.len() as u32,
+                    height: resolution,
+                    depth: 1,
+                },
+                format: ShadowMapPass::DEPTH_FORMAT,
+                usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                ..simple_image_create_info()
+            };
+
+            let image = Arc::new(Image::new(context.clone(), &create_info));
+
+            Arc::new(ImageView::new_default(
+                context.clone(),
+                image,
+                vk::ImageAspectFlags::DEPTH,
+            ))
+        };
+
+        let shadow_map_sampler = {
+            let create_info = vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::LINEAR)
+                .min_filter(vk::Filter::LINEAR)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+                .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+                .mip_lod_bias(0.0)
+                .anisotropy_enable(false)
+                .compare_enable(true)
+                .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+                .min_lod(0.0)
+                .max_lod(vk::LOD_CLAMP_NONE);
+
+            let sampler = unsafe { context.device.create_sampler(&create_info, None) }
+                .expect("Could not create shadow map sampler");
+
+            Arc::new(Sampler::new(sampler, context.clone()))
+        };
+
+        let cascades_buffer = Buffer::new(
+            context.clone(),
+            std::mem::size_of::<shader_types::ShadowCascades>() as u64,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        let cascades = shader_types::ShadowCascades {
+            light_view_proj: [Mat4::identity(); MAX_SHADOW_CASCADES],
+            split_depths: Vec4::zero(),
+            cascade_count: cascade_splits.len() as u32,
+        };
+        cascades_buffer.copy_data(&cascades);
+
+        let render_pass = create_render_pass(&context.device);
+
+        let (pipeline, pipeline_layout) = create_pipeline(context.clone(), render_pass)
+            .expect("Could not compile shadow map shaders");
+
+        let framebuffer = create_framebuffer(
+            &context.device,
+            render_pass,
+            &shadow_map,
+            resolution * cascade_splits.len() as u32,
+            resolution,
+        );
+
+        let descriptor_set_layout = set_layout_cache.shadow_map_resolve();
+        let descriptor_set = create_descriptor_set(
+            descriptor_pool,
+            descriptor_set_layout.clone(),
+            &cascades_buffer,
+            shadow_map.clone(),
+            shadow_map_sampler.clone(),
+            gbuffer,
+        );
+
+        let (resolve_pipeline, resolve_pipeline_layout) = create_resolve_pipeline(
+            context.clone(),
+            set_layout_cache,
+            descriptor_set.layout.inner,
+        )
+        .expect("Could not compile shadow map resolve shader");
+
+        ShadowMapPass {
+            render_pass,
+            pipeline,
+            pipeline_layout,
+            framebuffer,
+
+            resolve_pipeline,
+            resolve_pipeline_layout,
+
+            shadow_map,
+            shadow_map_sampler,
+            cascades,
+            cascades_buffer,
+            cascade_splits,
+            resolution,
+
+            descriptor_pool: descriptor_pool.clone(),
+            descriptor_set_layout,
+            descriptor_set,
+
+            context,
+        }
+    }
+
+    /// Fits the cascades to the camera frustum
+    pub fn update(&mut self, camera: &Camera, sun_direction: Vec3) {
+        self.cascades =
+            calculate_cascades(camera, sun_direction, &self.cascade_splits, self.resolution);
+        self.cascades_buffer.copy_data(&self.cascades);
+    }
+
+    pub fn render(
+        &self,
+        scene: &Scene,
+        gbuffer: &GBuffer,
+        camera_descriptor_set: &CameraDescriptorSet,
+        extent: vk::Extent2D,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        self.render_cascades(scene, command_buffer);
+        self.resolve(gbuffer, camera_descriptor_set, extent, command_buffer);
+    }
+
+    /// The pipelines must not be in use. Keeps the old pipelines when a shader doesn't compile.
+    pub fn reload_shaders(
+        &mut self,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Result<(), ShaderError> {
+        let device = &self.context.device;
+
+        let (pipeline, pipeline_layout) = create_pipeline(self.context.clone(), self.render_pass)?;
+        let (resolve_pipeline, resolve_pipeline_layout) = match create_resolve_pipeline(
+            self.context.clone(),
+            set_layout_cache,
+            self.descriptor_set.layout.inner,
+        ) {
+            Ok(resolve_pipeline) => resolve_pipeline,
+            Err(error) => {
+                unsafe { device.destroy_pipeline(pipeline, None) };
+                unsafe { device.destroy_pipeline_layout(pipeline_layout, None) };
+                return Err(error);
+            }
+        };
+
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+        unsafe { device.destroy_pipeline(self.resolve_pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.resolve_pipeline_layout, None) };
+
+        self.pipeline = pipeline;
+        self.pipeline_layout = pipeline_layout;
+        self.resolve_pipeline = resolve_pipeline;
+        self.resolve_pipeline_layout = resolve_pipeline_layout;
+        Ok(())
+    }
+
+    pub fn resize(&mut self, gbuffer: &GBuffer) {
+        self.descriptor_set = create_descriptor_set(
+            &self.descriptor_pool,
+            self.descriptor_set_layout.clone(),
+            &self.cascades_buffer,
+            self.shadow_map.clone(),
+            self.shadow_map_sampler.clone(),
+            gbuffer,
+        );
+    }
+
+    fn render_cascades(&self, scene: &Scene, command_buffer: vk::CommandBuffer) {
+        let cascade_count = self.cascade_splits.len() as u32;
+
+        let clear_values = [vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+        }];
+
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D {
+                    width: self.resolution * cascade_count,
+                    height: self.resolution,
+                },
+            })
+            .clear_values(&clear_values);
+
+        unsafe {
+            self.context.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+
+        unsafe {
+            self.context.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            )
+        };
+
+        for cascade in 0..cascade_count {
+            // every cascade gets its own tile of the shadow map
+            let viewport = vk::Viewport {
+                x: (cascade * self.resolution) as f32,
+                y: 0.0,
+                width: self.resolution as f32,
+                height: self.resolution as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+
+            unsafe {
+                self.context.device.cmd_set_viewport(
+                    command_buffer,
+                    0,
+                    std::slice::from_ref(&viewport),
+                )
+            };
+
+            let light_view_proj = self.cascades.light_view_proj[cascade as usize];
+
+            for model in scene.models.iter().filter(|model| model.casts_shadows) {
+                let entity = shader_types::ShadowMapEntity {
+                    light_view_proj,
+                    model: model.transform.clone().into(),
+                };
+
+                for primitive in &model.primitives {
+                    unsafe {
+                        self.context.device.cmd_bind_index_buffer(
+                            command_buffer,
+                            **primitive.mesh.index_buffer,
+                            0,
+                            vk::IndexType::UINT32,
+                        )
+                    };
+
+                    let vertex_buffer_offsets = vec![0];
+                    unsafe {
+                        self.context.device.cmd_bind_vertex_buffers(
+                            command_buffer,
+                            0,
+                            std::slice::from_ref(&*primitive.mesh.vertex_buffer),
+                            vertex_buffer_offsets.as_slice(),
+                        )
+                    }
+
+                    unsafe {
+                        self.context.device.cmd_push_constants(
+                            command_buffer,
+                            self.pipeline_layout,
+                            vk::ShaderStageFlags::VERTEX,
+                            0,
+                            entity.as_std140().as_bytes(),
+                        );
+                    }
+
+                    unsafe {
+                        self.context.device.cmd_draw_indexed(
+                            command_buffer,
+                            primitive.mesh.num_indices,
+                            1,
+                            0,
+                            0,
+                            0,
+                        )
+                    };
+                }
+            }
+        }
+
+        unsafe { self.context.device.cmd_end_render_pass(command_buffer) };
+    }
+
+    fn resolve(
+        &self,
+        gbuffer: &GBuffer,
+        camera_descriptor_set: &CameraDescriptorSet,
+        extent: vk::Extent2D,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let image_memory_barriers = [
+            vk::ImageMemoryBarrier2 {
+                src_stage_mask: PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                src_access_mask: AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_stage_mask: PipelineStageFlags2::COMPUTE_SHADER,
+                dst_access_mask: AccessFlags2::SHADER_READ,
+                old_layout: ImageLayout::ATTACHMENT_OPTIMAL,
+                new_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: gbuffer.depth_buffer.image.inner,
+                subresource_range: gbuffer.depth_buffer.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            },
+            vk::ImageMemoryBarrier2 {
+                src_stage_mask: PipelineStageFlags2::FRAGMENT_SHADER,
+                src_access_mask: AccessFlags2::SHADER_READ,
+                dst_stage_mask: PipelineStageFlags2::COMPUTE_SHADER,
+                dst_access_mask: AccessFlags2::SHADER_WRITE,
+                old_layout: ImageLayout::UNDEFINED,
+                new_layout: ImageLayout::GENERAL,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: gbuffer.shadow_buffer.image.inner,
+                subresource_range: gbuffer.shadow_buffer.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            },
+        ];
+
+        let dependency_info =
+            vk::DependencyInfo::builder().image_memory_barriers(&image_memory_barriers);
+
+        unsafe {
+            self.context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+
+        unsafe {
+            self.context.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.resolve_pipeline,
+            )
+        };
+
+        let descriptor_sets = [
+            camera_descriptor_set.descriptor_set.inner,
+            self.descriptor_set.inner,
+        ];
+
+        unsafe {
+            self.context.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.resolve_pipeline_layout,
+                0,
+                &descriptor_sets,
+                &[camera_descriptor_set.dynamic_offset()],
+            )
+        };
+
+        unsafe {
+            self.context.device.cmd_dispatch(
+                command_buffer,
+                (extent.width + RESOLVE_WORKGROUP_SIZE - 1) / RESOLVE_WORKGROUP_SIZE,
+                (extent.height + RESOLVE_WORKGROUP_SIZE - 1) / RESOLVE_WORKGROUP_SIZE,
+                1,
+            )
+        };
+    }
+}
+
+impl Drop for ShadowMapPass {
+    fn drop(&mut self) {
+        let device = &self.context.device;
+
+        unsafe { device.destroy_framebuffer(self.framebuffer, None) };
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+        unsafe { device.destroy_pipeline(self.resolve_pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.resolve_pipeline_layout, None) };
+
+        unsafe { device.destroy_render_pass(self.render_pass, None) };
+    }
+}
+
+/// Splits the camera frustum at the given view space distances and fits an orthographic
+/// projection from the sun around each part
+fn calculate_cascades(
+    camera: &Camera,
+    sun_direction: Vec3,
+    cascade_splits: &[f32],
+    resolution: u32,
+) -> shader_types::ShadowCascades {
+    let light_direction = sun_direction.normalized();
+    // the light must not look along the up vector
+    let up = if light_direction.y.abs() > 0.99 {
+        Vec3::unit_z()
+    } else {
+        Vec3::unit_y()
+    };
+
+    let view_inv = camera.view_matrix().inversed();
+    let proj = camera.projection_matrix();
+    let tan_half_fov = 1.0 / proj[1][1].abs();
+    let aspect_ratio = (proj[1][1] / proj[0][0]).abs();
+
+    let mut cascades = shader_types::ShadowCascades {
+        light_view_proj: [Mat4::identity(); MAX_SHADOW_CASCADES],
+        split_depths: Vec4::zero(),
+        cascade_count: cascade_splits.len() as u32,
+    };
+
+    let mut near = camera.settings.z_near;
+    for (i, &split) in cascade_splits.iter().enumerate() {
+        let far = split.clamp(near, camera.settings.z_far);
+
+        // in world space
+        let corners: Vec<Vec3> = [near, far]
+            .into_iter()
+            .flat_map(|distance| {
+                let half_height = distance * tan_half_fov;
+                let half_width = half_height * aspect_ratio;
+                [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+                    view_inv.transform_point3(Vec3::new(x * half_width, y * half_height, -distance))
+                })
+            })
+            .collect();
+
+        let center = corners
+            .iter()
+            .fold(Vec3::zero(), |sum, &corner| sum + corner)
+            / corners.len() as f32;
+
+        // A bounding sphere doesn't change size when the camera rotates, which avoids shimmering
+        let radius = corners
+            .iter()
+            .map(|&corner| (corner - center).mag())
+            .fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        let eye = center - light_direction * (radius + SHADOW_CASTER_DISTANCE);
+        let light_view = Mat4::look_at(eye, center, up);
+        let mut light_proj = projection::rh_yup::orthographic_vk(
+            -radius,
+            radius,
+            -radius,
+            radius,
+            0.0,
+            2.0 * radius + SHADOW_CASTER_DISTANCE,
+        );
+
+        // Snap to whole texels, otherwise the edges flicker when the camera moves
+        let texels_per_unit = resolution as f32 / 2.0;
+        let origin = (light_proj * light_view).transform_point3(Vec3::zero()) * texels_per_unit;
+        light_proj[3].x += (origin.x.round() - origin.x) / texels_per_unit;
+        light_proj[3].y += (origin.y.round() - origin.y) / texels_per_unit;
+
+        cascades.light_view_proj[i] = light_proj * light_view;
+        cascades.split_depths[i] = far;
+
+        near = far;
+    }
+
+    cascades
+}
+
+fn create_framebuffer(
+    device: &ash::Device,
+    render_pass: vk::RenderPass,
+    shadow_map: &ImageView,
+    width: u32,
+    height: u32,
+) -> vk::Framebuffer {
+    let create_info = vk::FramebufferCreateInfo::builder()
+        .render_pass(render_pass)
+        .attachments(std::slice::from_ref(&shadow_map.inner))
+        .width(width)
+        .height(height)
+        .layers(1);
+
+    unsafe { device.create_framebuffer(&create_info, None) }
+        .expect("Could not create shadow map framebuffer")
+}
+
+fn create_pipeline(
+    context: Arc<Context>,
+    render_pass: vk::RenderPass,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let device = &context.device;
+
+    let mut vertex_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::VERTEX,
+        "/shadow_map/shadow_map.vert.spv"
+    )?;
+
+    let shader_stages = [vertex_shader.build()];
+
+    // only the position is needed, but this way the vertex buffers can be shared
+    let (vertex_input_binding_descriptions, vertex_input_attribute_descriptions) = (
+        Vertex::binding_descriptions(),
+        Vertex::attribute_descriptions(),
+    );
+
+    let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&vertex_input_binding_descriptions)
+        .vertex_attribute_descriptions(&vertex_input_attribute_descriptions);
+
+    let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    let scissors = [vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+            // Evaluation of (offset.x + extent.width) must not cause a ***signed*** integer addition overflow
+            width: i32::MAX as u32,
+            height: i32::MAX as u32,
+        },
+    }];
+
+    let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissors(&scissors);
+
+    // No culling, since not every mesh is closed.
+    // The depth bias hides most of the shadow acne.
+    let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .line_width(1.0)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .depth_bias_enable(true)
+        .depth_bias_constant_factor(1.25)
+        .depth_bias_slope_factor(1.75);
+
+    let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    let stencil_state = vk::StencilOpState {
+        fail_op: vk::StencilOp::KEEP,
+        pass_op: vk::StencilOp::KEEP,
+        depth_fail_op: vk::StencilOp::KEEP,
+        compare_op: vk::CompareOp::ALWAYS,
+        compare_mask: 0,
+        write_mask: 0,
+        reference: 0,
+    };
+
+    let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(true)
+        .depth_write_enable(true)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false)
+        .front(stencil_state)
+        .back(stencil_state)
+        .max_depth_bounds(1.0)
+        .min_depth_bounds(0.0);
+
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder();
+
+    let push_constants_ranges = context.push_constant_range(
+        vk::ShaderStageFlags::VERTEX,
+        std::mem::size_of::<shader_types::Std140ShadowMapEntity>(),
+    );
+
+    let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .push_constant_ranges(std::slice::from_ref(&push_constants_ranges))
+        .build();
+
+    let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
+        .expect("Could not create shadow map pipeline layout");
+
+    let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+        .dynamic_states(std::slice::from_ref(&vk::DynamicState::VIEWPORT));
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_state_create_info)
+        .input_assembly_state(&input_assembly_state_create_info)
+        .viewport_state(&viewport_state_create_info)
+        .rasterization_state(&rasterization_state_create_info)
+        .multisample_state(&multisample_state_create_info)
+        .depth_stencil_state(&depth_stencil_state_create_info)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(layout)
+        .render_pass(render_pass);
+
+    let pipeline = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&create_info),
+            None,
+        )
+    }
+    .expect("Could not create shadow map pipeline");
+
+    Ok((pipeline[0], layout))
+}
+
+fn create_resolve_pipeline(
+    context: Arc<Context>,
+    set_layout_cache: &DescriptorSetLayoutCache,
+    set_layout: vk::DescriptorSetLayout,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let device = &context.device;
+
+    let mut compute_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::COMPUTE,
+        "/shadow_map/resolve.comp.spv"
+    )?;
+
+    let set_layouts = [set_layout_cache.camera().inner, set_layout];
+
+    let layout_create_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+
+    let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
+        .expect("Could not create shadow map resolve pipeline layout");
+
+    let create_info = vk::ComputePipelineCreateInfo::builder()
+        .stage(compute_shader.build())
+        .layout(layout);
+
+    let pipeline = unsafe {
+        device.create_compute_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&create_info),
+            None,
+        )
+    }
+    .expect("Could not create shadow map resolve pipeline");
+
+    Ok((pipeline[0], layout))
+}
+
+fn create_render_pass(device: &ash::Device) -> vk::RenderPass {
+    let depth_attachment = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: ShadowMapPass::DEPTH_FORMAT,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .depth_stencil_attachment(&depth_attachment_ref);
+
+    let dependencies = [
+        // the resolve of the previous frame has to be done
+        vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            src_access_mask: vk::AccessFlags::SHADER_READ,
+            dst_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            dependency_flags: vk::DependencyFlags::empty(),
+        },
+        vk::SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+            src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            dependency_flags: vk::DependencyFlags::empty(),
+        },
+    ];
+
+    let create_info = vk::RenderPassCreateInfo::builder()
+        .attachments(std::slice::from_ref(&depth_attachment))
+        .subpasses(std::slice::from_ref(&subpass))
+        .dependencies(&dependencies);
+
+    unsafe { device.create_render_pass(&create_info, None) }
+        .expect("Could not create shadow map render pass")
+}
+
+fn create_descriptor_set(
+    descriptor_pool: &DescriptorPool,
+    set_layout: Arc<DescriptorSetLayout>,
+    cascades_buffer: &Buffer<shader_types::ShadowCascades>,
+    shadow_map: Arc<ImageView>,
+    shadow_map_sampler: Arc<Sampler>,
+    gbuffer: &GBuffer,
+) -> DescriptorSet {
+    DescriptorSet::new(
+        descriptor_pool,
+        set_layout,
+        vec![
+            WriteDescriptorSet::buffer(0, cascades_buffer),
+            WriteDescriptorSet::image_view_sampler_with_layout(
+                1,
+                gbuffer.depth_buffer.clone(),
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                gbuffer.sampler.clone(),
+            ),
+            WriteDescriptorSet::image_view_sampler_with_layout(
+                2,
+                shadow_map,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                shadow_map_sampler,
+            ),
+            WriteDescriptorSet::storage_image_view_with_layout(
+                3,
+                gbuffer.shadow_buffer.clone(),
+                vk::ImageLayout::GENERAL,
+            ),
+        ],
+    )
+}
@@ -0,0 +1,683 @@
+use std::sync::Arc;
+
+use ash::vk::{self, AccessFlags2, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2};
+use crevice::std140::AsStd140;
+use ultraviolet::{Vec2, Vec3};
+
+use crate::camera::Camera;
+use crate::render::shader_types;
+use crate::vulkan::context::Context;
+use crate::vulkan::descriptor_pool::DescriptorPool;
+use crate::vulkan::descriptor_set::{DescriptorSet, DescriptorSetLayout, WriteDescriptorSet};
+use crate::vulkan::image::{simple_image_create_info, Image};
+use crate::vulkan::image_view::ImageView;
+use crate::vulkan::sampler::Sampler;
+use crate::vulkan::shader_create_info::ShaderError;
+use crate::vulkan::swapchain::SwapchainContainer;
+use crate::{
+    include_shader,
+    render::{gbuffer::GBuffer, SwapchainIndex},
+};
+
+/// How much of the history is kept every frame
+const HISTORY_WEIGHT: f32 = 0.9;
+/// Moving further than this in a single frame counts as a camera cut
+const CAMERA_CUT_DISTANCE: f32 = 2.0;
+/// Cosine of the angle that the camera has to turn in a single frame for a camera cut
+const CAMERA_CUT_COS_ANGLE: f32 = 0.8;
+/// Length of the jitter sequence
+const JITTER_SAMPLE_COUNT: usize = 8;
+
+/// Temporal anti-aliasing. The projection is jittered by a subpixel offset every frame,
+/// and the lit image is blended with the result of the previous frames, reprojected with the motion vectors.
+pub struct TaaPass {
+    render_pass: vk::RenderPass,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    targets: TaaTargets,
+    /// One per swapchain image and history image, see [`TaaPass::framebuffer_index`]
+    framebuffers: Vec<vk::Framebuffer>,
+
+    pub enabled: bool,
+    /// The history image that gets written this frame, the other one gets read
+    history_index: usize,
+    /// Frames since the history images were created, the very first frame has no history to read
+    history_frame_count: usize,
+    /// False after a camera cut or a resize
+    is_history_valid: bool,
+    /// Whether the camera jumped in the current frame
+    is_camera_cut: bool,
+    frame_index: usize,
+    previous_camera: Option<(Vec3, Vec3)>,
+    push_constants: shader_types::Taa,
+    /// The swapchain format is not sRGB, so the shader encodes the output
+    encode_srgb: bool,
+
+    extent: vk::Extent2D,
+    sampler: Arc<Sampler>,
+    descriptor_pool: DescriptorPool,
+    context: Arc<Context>,
+}
+
+struct TaaTargets {
+    /// The lighting pass renders into this
+    color: Arc<ImageView>,
+    history: [Arc<ImageView>; 2],
+    /// Index `i` is used while writing history image `i`, so it reads the other one
+    descriptor_sets: [DescriptorSet; 2],
+}
+
+impl TaaPass {
+    pub const COLOR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    pub fn new(
+        context: Arc<Context>,
+        swapchain: &SwapchainContainer,
+        gbuffer: &GBuffer,
+        descriptor_pool: &DescriptorPool,
+    ) -> Self {
+        let descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
+            context.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ],
+            None,
+        ));
+
+        // bilinear, since the reprojected history is not aligned to the pixels
+        let sampler = {
+            let create_info = vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::LINEAR)
+                .min_filter(vk::Filter::LINEAR)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .max_lod(vk::LOD_CLAMP_NONE);
+
+            let sampler = unsafe { context.device.create_sampler(&create_info, None) }
+                .expect("Could not create sampler");
+
+            Arc::new(Sampler::new(sampler, context.clone()))
+        };
+
+        let render_pass = create_render_pass(context.clone(), swapchain.format);
+
+        let encode_srgb = !swapchain.is_srgb();
+        let (pipeline, pipeline_layout) = create_pipeline(
+            context.clone(),
+            render_pass,
+            gbuffer,
+            &descriptor_set_layout,
+            encode_srgb,
+        )
+        .expect("Could not compile TAA shaders");
+
+        let targets = create_targets(
+            context.clone(),
+            swapchain.extent,
+            descriptor_pool,
+            &descriptor_set_layout,
+            &sampler,
+        );
+        let framebuffers = create_framebuffers(context.clone(), swapchain, render_pass, &targets);
+
+        TaaPass {
+            render_pass,
+            pipeline,
+            pipeline_layout,
+            descriptor_set_layout,
+            targets,
+            framebuffers,
+
+            enabled: true,
+            history_index: 0,
+            history_frame_count: 0,
+            is_history_valid: false,
+            is_camera_cut: true,
+            frame_index: 0,
+            previous_camera: None,
+            push_constants: shader_types::Taa {
+                history_weight: 0.0,
+            },
+            encode_srgb,
+
+            extent: swapchain.extent,
+            sampler,
+            descriptor_pool: descriptor_pool.clone(),
+            context,
+        }
+    }
+
+    /// Other passes with a history throw it away as well
+    pub fn is_camera_cut(&self) -> bool {
+        self.is_camera_cut
+    }
+
+    /// The image that the lighting pass renders into
+    pub fn color_target(&self) -> &Arc<ImageView> {
+        &self.targets.color
+    }
+
+    /// Starts a new frame. Returns the jitter in normalized device coordinates,
+    /// which has to be applied to the projection of the camera.
+    pub fn update(&mut self, camera: &Camera) -> Vec2 {
+        let forward = camera.orientation * Camera::forward();
+        let is_camera_cut = match self.previous_camera {
+            Some((position, previous_forward)) => {
+                (camera.position - position).mag() > CAMERA_CUT_DISTANCE
+                    || previous_forward.dot(forward) < CAMERA_CUT_COS_ANGLE
+            }
+            None => true,
+        };
+        self.previous_camera = Some((camera.position, forward));
+        self.is_camera_cut = is_camera_cut;
+
+        let has_history = self.enabled && self.is_history_valid && !is_camera_cut;
+        self.push_constants = shader_types::Taa {
+            history_weight: if has_history { HISTORY_WEIGHT } else { 0.0 },
+        };
+
+        self.history_index = 1 - self.history_index;
+        self.history_frame_count += 1;
+        self.is_history_valid = true;
+        self.frame_index = (self.frame_index + 1) % JITTER_SAMPLE_COUNT;
+
+        if !self.enabled {
+            return Vec2::zero();
+        }
+        // in pixels, between -0.5 and 0.5
+        let jitter = Vec2::new(
+            halton(self.frame_index + 1, 2) - 0.5,
+            halton(self.frame_index + 1, 3) - 0.5,
+        );
+        Vec2::new(
+            jitter.x * 2.0 / self.extent.width as f32,
+            jitter.y * 2.0 / self.extent.height as f32,
+        )
+    }
+
+    pub fn render(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        gbuffer: &GBuffer,
+        swapchain_index: SwapchainIndex,
+        viewport: vk::Viewport,
+    ) {
+        let read_history = &self.targets.history[1 - self.history_index];
+        // the history image that gets read was never written in the very first frame
+        let read_history_layout = if self.history_frame_count <= 1 {
+            ImageLayout::UNDEFINED
+        } else {
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        };
+
+        let image_memory_barriers = [
+            ImageMemoryBarrier2 {
+                src_stage_mask: PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                src_access_mask: AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                dst_stage_mask: PipelineStageFlags2::FRAGMENT_SHADER,
+                dst_access_mask: AccessFlags2::SHADER_READ,
+                old_layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                new_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: self.targets.color.image.inner,
+                subresource_range: self.targets.color.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            },
+            // written by the geometry pass
+            ImageMemoryBarrier2 {
+                src_stage_mask: PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                src_access_mask: AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                dst_stage_mask: PipelineStageFlags2::FRAGMENT_SHADER,
+                dst_access_mask: AccessFlags2::SHADER_READ,
+                old_layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                new_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: gbuffer.motion_vector_buffer.image.inner,
+                subresource_range: gbuffer.motion_vector_buffer.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            },
+            ImageMemoryBarrier2 {
+                src_stage_mask: PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                src_access_mask: AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                dst_stage_mask: PipelineStageFlags2::FRAGMENT_SHADER,
+                dst_access_mask: AccessFlags2::SHADER_READ,
+                old_layout: read_history_layout,
+                new_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: read_history.image.inner,
+                subresource_range: read_history.subresource_range(),
+                ..ImageMemoryBarrier2::default()
+            },
+        ];
+
+        let dependency_info =
+            vk::DependencyInfo::builder().image_memory_barriers(&image_memory_barriers);
+
+        unsafe {
+            self.context
+                .synchronisation2_loader
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffers[self.framebuffer_index(swapchain_index)])
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.extent,
+            });
+
+        unsafe {
+            self.context.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+
+        unsafe {
+            self.context.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            )
+        };
+
+        unsafe {
+            self.context
+                .device
+                .cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport))
+        };
+
+        let descriptor_sets = [
+            gbuffer.descriptor_set.inner,
+            self.targets.descriptor_sets[self.history_index].inner,
+        ];
+
+        unsafe {
+            self.context.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                self.push_constants.as_std140().as_bytes(),
+            )
+        }
+
+        unsafe {
+            self.context.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &descriptor_sets,
+                &[],
+            )
+        };
+
+        unsafe { self.context.device.cmd_draw(command_buffer, 3, 1, 0, 0) };
+
+        unsafe { self.context.device.cmd_end_render_pass(command_buffer) };
+    }
+
+    /// The pipeline must not be in use. Keeps the old pipeline when a shader doesn't compile.
+    pub fn reload_shaders(&mut self, gbuffer: &GBuffer) -> Result<(), ShaderError> {
+        let (pipeline, pipeline_layout) = create_pipeline(
+            self.context.clone(),
+            self.render_pass,
+            gbuffer,
+            &self.descriptor_set_layout,
+            self.encode_srgb,
+        )?;
+
+        let device = &self.context.device;
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+
+        self.pipeline = pipeline;
+        self.pipeline_layout = pipeline_layout;
+        Ok(())
+    }
+
+    /// Throws away the history
+    pub fn resize(&mut self, swapchain: &SwapchainContainer) {
+        let device = &self.context.device;
+        for &framebuffer in self.framebuffers.iter() {
+            unsafe { device.destroy_framebuffer(framebuffer, None) };
+        }
+
+        self.targets = create_targets(
+            self.context.clone(),
+            swapchain.extent,
+            &self.descriptor_pool,
+            &self.descriptor_set_layout,
+            &self.sampler,
+        );
+        self.framebuffers = create_framebuffers(
+            self.context.clone(),
+            swapchain,
+            self.render_pass,
+            &self.targets,
+        );
+        self.extent = swapchain.extent;
+        self.history_frame_count = 0;
+        self.is_history_valid = false;
+    }
+
+    fn framebuffer_index(&self, swapchain_index: SwapchainIndex) -> usize {
+        swapchain_index.0 * 2 + self.history_index
+    }
+}
+
+/// Low discrepancy sequence between 0 and 1
+fn halton(mut index: usize, base: usize) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+fn create_targets(
+    context: Arc<Context>,
+    extent: vk::Extent2D,
+    descriptor_pool: &DescriptorPool,
+    descriptor_set_layout: &Arc<DescriptorSetLayout>,
+    sampler: &Arc<Sampler>,
+) -> TaaTargets {
+    let create_image_view = |usage: vk::ImageUsageFlags| {
+        let create_info = vk::ImageCreateInfo {
+            extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            format: TaaPass::COLOR_FORMAT,
+            usage,
+            ..simple_image_create_info()
+        };
+        let image = Arc::new(Image::new(context.clone(), &create_info));
+        Arc::new(ImageView::new_default(
+            context.clone(),
+            image,
+            vk::ImageAspectFlags::COLOR,
+        ))
+    };
+
+    let usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED;
+    // the non-finite readback copies the lit image
+    let color = create_image_view(usage | vk::ImageUsageFlags::TRANSFER_SRC);
+    let history = [create_image_view(usage), create_image_view(usage)];
+
+    let descriptor_sets = [1, 0].map(|read_index| {
+        DescriptorSet::new(
+            descriptor_pool,
+            descriptor_set_layout.clone(),
+            vec![
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    0,
+                    color.clone(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler_with_layout(
+                    1,
+                    history[read_index].clone(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler.clone(),
+                ),
+            ],
+        )
+    });
+
+    TaaTargets {
+        color,
+        history,
+        descriptor_sets,
+    }
+}
+
+fn create_framebuffers(
+    context: Arc<Context>,
+    swapchain: &SwapchainContainer,
+    render_pass: vk::RenderPass,
+    targets: &TaaTargets,
+) -> Vec<vk::Framebuffer> {
+    swapchain
+        .imageviews
+        .iter()
+        .flat_map(|swapchain_image| {
+            targets.history.iter().map(|history| {
+                let image_views = [swapchain_image.clone(), history.inner];
+
+                let create_info = vk::FramebufferCreateInfo::builder()
+                    .render_pass(render_pass)
+                    .attachments(&image_views)
+                    .width(swapchain.extent.width)
+                    .height(swapchain.extent.height)
+                    .layers(1);
+
+                unsafe { context.device.create_framebuffer(&create_info, None) }
+                    .expect("Could not create framebuffer")
+            })
+        })
+        .collect::<Vec<_>>()
+}
+
+fn create_pipeline(
+    context: Arc<Context>,
+    render_pass: vk::RenderPass,
+    gbuffer: &GBuffer,
+    descriptor_set_layout: &DescriptorSetLayout,
+    encode_srgb: bool,
+) -> Result<(vk::Pipeline, vk::PipelineLayout), ShaderError> {
+    let device = &context.device;
+
+    let mut vertex_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::VERTEX,
+        "/base.vert.spv"
+    )?;
+    let mut fragment_shader = include_shader!(
+        context.clone(),
+        vk::ShaderStageFlags::FRAGMENT,
+        "/taa.frag.spv"
+    )?;
+
+    // ENCODE_SRGB
+    let specialization_data = vk::Bool32::from(encode_srgb).to_ne_bytes();
+    let specialization_map_entry = vk::SpecializationMapEntry {
+        constant_id: 0,
+        offset: 0,
+        size: specialization_data.len(),
+    };
+    let specialization_info = vk::SpecializationInfo::builder()
+        .map_entries(std::slice::from_ref(&specialization_map_entry))
+        .data(&specialization_data);
+
+    let mut fragment_stage = fragment_shader.build();
+    fragment_stage.p_specialization_info = &*specialization_info;
+    let shader_stages = [vertex_shader.build(), fragment_stage];
+
+    let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder();
+
+    let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    let scissors = [vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+            // Evaluation of (offset.x + extent.width) must not cause a ***signed*** integer addition overflow
+            width: i32::MAX as u32,
+            height: i32::MAX as u32,
+        },
+    }];
+
+    let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissors(&scissors);
+
+    let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .line_width(1.0)
+        .polygon_mode(vk::PolygonMode::FILL);
+
+    let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false);
+
+    // the swapchain image and the next history image
+    let color_blend_attachment_states = [
+        super::opaque_color_blend_attachment(),
+        super::opaque_color_blend_attachment(),
+    ];
+
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op(vk::LogicOp::CLEAR)
+        .attachments(&color_blend_attachment_states);
+
+    let descriptor_set_layouts = [
+        gbuffer.descriptor_set.layout.inner,
+        descriptor_set_layout.inner,
+    ];
+
+    let push_constants_ranges = context.push_constant_range(
+        vk::ShaderStageFlags::FRAGMENT,
+        std::mem::size_of::<shader_types::Std140Taa>(),
+    );
+
+    let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&descriptor_set_layouts)
+        .push_constant_ranges(std::slice::from_ref(&push_constants_ranges))
+        .build();
+
+    let layout = unsafe { device.create_pipeline_layout(&layout_create_info, None) }
+        .expect("Could not create pipeline layout");
+
+    let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+        .dynamic_states(std::slice::from_ref(&vk::DynamicState::VIEWPORT));
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_state_create_info)
+        .input_assembly_state(&input_assembly_state_create_info)
+        .viewport_state(&viewport_state_create_info)
+        .rasterization_state(&rasterization_state_create_info)
+        .multisample_state(&multisample_state_create_info)
+        .depth_stencil_state(&depth_stencil_state_create_info)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(layout)
+        .render_pass(render_pass);
+
+    let pipeline = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            std::slice::from_ref(&create_info),
+            None,
+        )
+    }
+    .expect("Could not create graphics pipeline");
+
+    Ok((pipeline[0], layout))
+}
+
+fn create_render_pass(context: Arc<Context>, swapchain_format: vk::Format) -> vk::RenderPass {
+    // both attachments are completely overwritten
+    let attachments = [
+        vk::AttachmentDescription {
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            format: swapchain_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        },
+        vk::AttachmentDescription {
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            format: TaaPass::COLOR_FORMAT,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+    ];
+
+    let color_attachment_refs = [
+        vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        },
+        vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        },
+    ];
+
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color_attachment_refs);
+
+    // the history image that gets written was read two frames ago
+    let dependencies = [vk::SubpassDependency {
+        src_subpass: vk::SUBPASS_EXTERNAL,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ..Default::default()
+    }];
+
+    let create_info = vk::RenderPassCreateInfo::builder()
+        .attachments(&attachments)
+        .subpasses(std::slice::from_ref(&subpass))
+        .dependencies(&dependencies);
+
+    unsafe { context.device.create_render_pass(&create_info, None) }
+        .expect("Could not create render pass")
+}
+
+impl Drop for TaaPass {
+    fn drop(&mut self) {
+        let device = &self.context.device;
+
+        for &framebuffer in self.framebuffers.iter() {
+            unsafe { device.destroy_framebuffer(framebuffer, None) };
+        }
+        unsafe { device.destroy_pipeline(self.pipeline, None) };
+        unsafe { device.destroy_pipeline_layout(self.pipeline_layout, None) };
+
+        unsafe { device.destroy_render_pass(self.render_pass, None) };
+    }
+}
@@ -3,14 +3,29 @@ use std::sync::Arc;
 use crate::vulkan::{context::Context, descriptor_set::DescriptorSetLayout};
 use ash::vk;
 
+/// Upper bound for the texture array in the bindless descriptor set
+pub const MAX_BINDLESS_TEXTURES: u32 = 1024;
+
 pub struct DescriptorSetLayoutCache {
     scene_descriptor_set_layout: Arc<DescriptorSetLayout>,
     camera_descriptor_set_layout: Arc<DescriptorSetLayout>,
     material_descriptor_set_layout: Arc<DescriptorSetLayout>,
+    skin_descriptor_set_layout: Arc<DescriptorSetLayout>,
+    morph_targets_descriptor_set_layout: Arc<DescriptorSetLayout>,
+    shadow_map_resolve_descriptor_set_layout: Arc<DescriptorSetLayout>,
+    /// Only exists when the device supports descriptor indexing and raytracing
+    bindless_descriptor_set_layout: Option<Arc<DescriptorSetLayout>>,
 }
 
 impl DescriptorSetLayoutCache {
     pub fn new(context: Arc<Context>) -> Self {
+        // Raytracing stages are only valid when the device supports raytracing
+        let raygen_stage = if context.context_raytracing.is_some() {
+            vk::ShaderStageFlags::RAYGEN_KHR
+        } else {
+            vk::ShaderStageFlags::empty()
+        };
+
         let scene_descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
             context.clone(),
             &[vk::DescriptorSetLayoutBinding::builder()
@@ -18,24 +33,25 @@ impl DescriptorSetLayoutCache {
                 .descriptor_count(1)
                 .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
                 .stage_flags(
-                    vk::ShaderStageFlags::VERTEX
-                        | vk::ShaderStageFlags::FRAGMENT
-                        | vk::ShaderStageFlags::RAYGEN_KHR,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT | raygen_stage,
                 )
                 .build()],
             None,
         ));
 
+        // the compute stage is for resolving the shadow map
+        // dynamic, so that every frame in flight reads its own slice of the camera buffer
         let camera_descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
             context.clone(),
             &[vk::DescriptorSetLayoutBinding::builder()
                 .binding(0)
                 .descriptor_count(1)
-                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
                 .stage_flags(
                     vk::ShaderStageFlags::VERTEX
                         | vk::ShaderStageFlags::FRAGMENT
-                        | vk::ShaderStageFlags::RAYGEN_KHR,
+                        | vk::ShaderStageFlags::COMPUTE
+                        | raygen_stage,
                 )
                 .build()],
             None,
@@ -68,14 +84,140 @@ impl DescriptorSetLayoutCache {
                     .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
                     .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
                     .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(4)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(5)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
             ],
             None,
         ));
 
+        // joint matrices of a skinned model
+        let skin_descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
+            context.clone(),
+            &[vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .build()],
+            None,
+        ));
+
+        // morph target deltas of a mesh, and the weights of a model
+        let morph_targets_descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
+            context.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .build(),
+            ],
+            None,
+        ));
+
+        // cascades, depth buffer, shadow map and the shadow buffer that the resolve writes to
+        let shadow_map_resolve_descriptor_set_layout = Arc::new(DescriptorSetLayout::new(
+            context.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(2)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(3)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            ],
+            None,
+        ));
+
+        // per instance material indices, materials and all textures of the scene
+        // only used by the hit shaders, so it also needs raytracing support
+        let supports_bindless = context.context_raytracing.is_some()
+            && context.descriptor_indexing_features.supports_bindless();
+        let bindless_descriptor_set_layout = supports_bindless.then(|| {
+            Arc::new(DescriptorSetLayout::new_with_binding_flags(
+                context.clone(),
+                &[
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(0)
+                        .descriptor_count(1)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .stage_flags(
+                            vk::ShaderStageFlags::CLOSEST_HIT_KHR
+                                | vk::ShaderStageFlags::ANY_HIT_KHR,
+                        )
+                        .build(),
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(1)
+                        .descriptor_count(1)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .stage_flags(
+                            vk::ShaderStageFlags::CLOSEST_HIT_KHR
+                                | vk::ShaderStageFlags::ANY_HIT_KHR,
+                        )
+                        .build(),
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(2)
+                        .descriptor_count(MAX_BINDLESS_TEXTURES)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .stage_flags(
+                            vk::ShaderStageFlags::CLOSEST_HIT_KHR
+                                | vk::ShaderStageFlags::ANY_HIT_KHR,
+                        )
+                        .build(),
+                ],
+                &[
+                    vk::DescriptorBindingFlags::empty(),
+                    vk::DescriptorBindingFlags::empty(),
+                    vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT
+                        | vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+                ],
+                None,
+            ))
+        });
+
         Self {
             scene_descriptor_set_layout,
             camera_descriptor_set_layout,
             material_descriptor_set_layout,
+            skin_descriptor_set_layout,
+            morph_targets_descriptor_set_layout,
+            shadow_map_resolve_descriptor_set_layout,
+            bindless_descriptor_set_layout,
         }
     }
 
@@ -90,4 +232,20 @@ impl DescriptorSetLayoutCache {
     pub fn material(&self) -> Arc<DescriptorSetLayout> {
         self.material_descriptor_set_layout.clone()
     }
+
+    pub fn skin(&self) -> Arc<DescriptorSetLayout> {
+        self.skin_descriptor_set_layout.clone()
+    }
+
+    pub fn morph_targets(&self) -> Arc<DescriptorSetLayout> {
+        self.morph_targets_descriptor_set_layout.clone()
+    }
+
+    pub fn shadow_map_resolve(&self) -> Arc<DescriptorSetLayout> {
+        self.shadow_map_resolve_descriptor_set_layout.clone()
+    }
+
+    pub fn bindless(&self) -> Option<Arc<DescriptorSetLayout>> {
+        self.bindless_descriptor_set_layout.clone()
+    }
 }
@@ -1,10 +1,14 @@
 use crevice::std140::AsStd140;
-use ultraviolet::{Mat4, Vec3};
+use ultraviolet::{Mat4, Vec3, Vec4};
 
+use crate::vulkan::buffer::PlainData;
+
+/// The normal matrix is computed in the shader, which keeps this within the guaranteed 128 bytes of push constants
 #[derive(AsStd140)]
 pub struct Entity {
     pub model: Mat4,
-    pub normal_matrix: Mat4,
+    /// For motion vectors, equal to the model matrix for static models
+    pub previous_model: Mat4,
 }
 
 #[derive(AsStd140)]
@@ -14,9 +18,17 @@ pub struct DirectionalLight {
     pub intensity: f32,
 }
 
+/// Constant fill light, so that the shadowed areas aren't black
+#[derive(AsStd140)]
+pub struct AmbientLight {
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
 #[derive(AsStd140)]
 pub struct Scene {
     pub directional_light: DirectionalLight,
+    pub ambient_light: AmbientLight,
 }
 
 #[derive(AsStd140)]
@@ -25,8 +37,15 @@ pub struct Material {
     pub emissivity: Vec3,
     pub roughness: f32,
     pub metallic: f32,
+    pub transmission: f32,
+    pub ior: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
 }
 
+// std140 structs make their padding explicit, and only contain floats
+unsafe impl PlainData for Std140Material {}
+
 #[derive(AsStd140)]
 pub struct Camera {
     pub view: Mat4,
@@ -34,9 +53,79 @@ pub struct Camera {
     pub view_inv: Mat4,
     pub proj_inv: Mat4,
     pub position: Vec3,
+    /// Both without the TAA jitter, for motion vectors
+    pub unjittered_view_proj: Mat4,
+    pub previous_unjittered_view_proj: Mat4,
+    /// Seconds since startup, for animated shading
+    pub time: f32,
 }
 
 #[derive(AsStd140)]
 pub struct PostProcessing {
     pub brightness: f32,
+    /// A [`crate::render::DebugView`]
+    pub debug_view: u32,
+    /// A [`crate::render::BackgroundMode`]
+    pub background_mode: u32,
+    pub background_color: Vec3,
+}
+
+#[derive(AsStd140)]
+pub struct RaytracedShadow {
+    /// Moves the ray origin towards the camera, in depth buffer units
+    pub depth_bias: f32,
+    /// Moves the ray origin along the surface normal, in world units
+    pub normal_bias: f32,
+    /// Half of the angular diameter of the light, in radians
+    pub light_angular_radius: f32,
+    pub sample_count: u32,
+    /// Changes the jitter of the soft shadow rays every frame
+    pub frame_index: u32,
+}
+
+#[derive(AsStd140)]
+pub struct ShadowDenoise {
+    /// 0 when there is no usable history
+    pub history_weight: f32,
+}
+
+#[derive(AsStd140)]
+pub struct Taa {
+    pub history_weight: f32,
+}
+
+#[derive(AsStd140)]
+pub struct SelectionOutline {
+    pub color: Vec3,
+    /// In pixels
+    pub thickness: u32,
+}
+
+/// Indices into the bindless texture array.
+/// Only contains u32s, so the std430 layout matches the Rust layout.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BindlessMaterial {
+    pub base_color_texture: u32,
+    pub normal_texture: u32,
+    pub metallic_roughness_texture: u32,
+}
+
+#[derive(AsStd140)]
+pub struct ShadowMapEntity {
+    pub light_view_proj: Mat4,
+    pub model: Mat4,
+}
+
+/// Maximum number of cascades, has to match MAX_CASCADES in the shadow map shaders
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+/// crevice doesn't support arrays, but this already matches the std140 layout
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ShadowCascades {
+    pub light_view_proj: [Mat4; MAX_SHADOW_CASCADES],
+    /// View space distance where each cascade ends
+    pub split_depths: Vec4,
+    pub cascade_count: u32,
 }
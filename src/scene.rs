@@ -1,30 +1,187 @@
 mod material;
 mod mesh;
+mod morph_weights;
+mod sampler;
+mod skin;
 mod texture;
 mod vertex;
 
 pub use material::*;
 pub use mesh::*;
+pub use morph_weights::*;
+pub use sampler::*;
+pub use skin::*;
 pub use texture::*;
 pub use vertex::*;
 
-use crate::{transform::Transform, vulkan::acceleration_structure::AccelerationStructure};
+use crate::{
+    loader::{Light, MaterialOverrides, MorphWeightsAnimation, NodeAnimation, SceneGraph},
+    render::shader_types,
+    transform::Transform,
+    vulkan::{
+        acceleration_structure::AccelerationStructure, buffer::Buffer,
+        descriptor_set::DescriptorSet, image_view::ImageView, sampler::Sampler,
+    },
+};
+use std::collections::HashSet;
 use std::sync::Arc;
+use ultraviolet::Mat4;
 
 pub struct Scene {
     pub models: Vec<Model>,
-    pub raytracing_scene: RaytracingScene,
+    pub scene_graph: SceneGraph,
+    pub morph_weights_animations: Vec<MorphWeightsAnimation>,
+    pub node_animations: Vec<NodeAnimation>,
+    pub lights: Vec<Light>,
+    /// None when the device doesn't support raytracing
+    pub raytracing_scene: Option<RaytracingScene>,
+    pub samplers: Vec<SceneSampler>,
+    pub sampler_overrides: SamplerOverrides,
+    /// Set when the overrides changed, see [`crate::scene_uploader::apply_sampler_overrides`]
+    pub are_samplers_outdated: bool,
+    /// Model and primitive index, see [`crate::scene_uploader::fork_material`]
+    pub material_to_fork: Option<(usize, usize)>,
 }
 
 pub struct Model {
     pub transform: Transform,
+    /// Index of the node in the scene graph
+    pub node: usize,
     pub primitives: Vec<Primitive>,
+    /// Only used for primitives with a skinned mesh
+    pub skin: Option<Skin>,
+    /// Only exists when the meshes have morph targets
+    pub morph_weights: Option<MorphWeights>,
+    /// Models that don't cast shadows are skipped by the shadow rays and the shadow maps
+    pub casts_shadows: bool,
+}
+
+impl Scene {
+    /// Without any models, the scene uploader adds them
+    pub fn new(sampler_overrides: SamplerOverrides) -> Self {
+        Self {
+            models: Vec::new(),
+            scene_graph: SceneGraph::new(),
+            morph_weights_animations: Vec::new(),
+            node_animations: Vec::new(),
+            lights: Vec::new(),
+            raytracing_scene: None,
+            samplers: Vec::new(),
+            sampler_overrides,
+            are_samplers_outdated: false,
+            material_to_fork: None,
+        }
+    }
+
+    pub fn set_sampler_overrides(&mut self, sampler_overrides: SamplerOverrides) {
+        self.sampler_overrides = sampler_overrides;
+        self.are_samplers_outdated = true;
+    }
+
+    /// How many primitives would change when the material of this one gets edited
+    pub fn material_user_count(&self, model_index: usize, primitive_index: usize) -> usize {
+        let material = &self.models[model_index].primitives[primitive_index].material;
+        self.models
+            .iter()
+            .flat_map(|model| &model.primitives)
+            .filter(|primitive| Arc::ptr_eq(&primitive.material, material))
+            .count()
+    }
+
+    /// Adds the edited factors to the overrides.
+    /// A forked material has the index of its original, so the one that comes last wins.
+    pub fn add_edited_materials(&self, material_overrides: &mut MaterialOverrides) {
+        let mut materials = HashSet::new();
+        for primitive in self.models.iter().flat_map(|model| &model.primitives) {
+            let material = &primitive.material;
+            if !materials.insert(Arc::as_ptr(material)) {
+                continue;
+            }
+            if let (Some(index), Some(material_override)) =
+                (material.source_index, material.edited_factors())
+            {
+                material_overrides.merge(index, material_override);
+            }
+        }
+    }
+
+    pub fn set_casts_shadows(&mut self, model_index: usize, casts_shadows: bool) {
+        self.models[model_index].casts_shadows = casts_shadows;
+        if let Some(raytracing_scene) = &mut self.raytracing_scene {
+            raytracing_scene.is_tlas_outdated = true;
+        }
+    }
+
+    /// Samples the node animations, with the time in seconds, and moves the models along with their nodes
+    pub fn update_node_animations(&mut self, time: f32) {
+        if self.node_animations.is_empty() {
+            return;
+        }
+        for animation in &self.node_animations {
+            let mut local_transform = self.scene_graph.nodes[animation.node]
+                .local_transform
+                .clone();
+            animation.apply(time, &mut local_transform);
+            self.scene_graph
+                .set_local_transform(animation.node, local_transform);
+        }
+
+        let has_moved_models = self.scene_graph.update_model_transforms(&mut self.models);
+        if has_moved_models {
+            if let Some(raytracing_scene) = &mut self.raytracing_scene {
+                raytracing_scene.is_tlas_outdated = true;
+            }
+        }
+    }
+
+    /// Computes the joint matrices from the current node transforms, before a frame gets rendered
+    pub fn update_skins(&mut self) {
+        for model in &self.models {
+            let Some(skin) = &model.skin else {
+                continue;
+            };
+
+            // the vertex shader applies the model transform afterwards
+            let model_inverse = Mat4::from(self.scene_graph.world_transform(model.node)).inversed();
+            let joint_matrices: Vec<Mat4> = skin
+                .joints
+                .iter()
+                .zip(skin.inverse_bind_matrices.iter())
+                .map(|(&joint, inverse_bind_matrix)| {
+                    model_inverse
+                        * Mat4::from(self.scene_graph.world_transform(joint))
+                        * *inverse_bind_matrix
+                })
+                .collect();
+            skin.joint_matrices_buffer.copy_data(&joint_matrices);
+        }
+    }
+
+    /// Samples the morph target weight animations, with the time in seconds
+    pub fn update_morph_weights(&mut self, time: f32) {
+        for animation in &self.morph_weights_animations {
+            let weights = animation.sample(time);
+            for model in self
+                .models
+                .iter_mut()
+                .filter(|model| model.node == animation.node)
+            {
+                if let Some(morph_weights) = &mut model.morph_weights {
+                    let count = morph_weights.weights.len().min(weights.len());
+                    morph_weights.weights[..count].copy_from_slice(&weights[..count]);
+                    morph_weights.buffer.copy_data(&morph_weights.weights);
+                }
+            }
+        }
+    }
 }
 
 pub struct Primitive {
     pub material: Arc<Material>,
     pub mesh: Arc<Mesh>,
-    pub raytracing_geometry: RaytracingGeometry,
+    pub raytracing_geometry: Option<RaytracingGeometry>,
+    /// The morph targets of the mesh, and the weights of the model
+    pub morph_targets_descriptor_set: Option<DescriptorSet>,
 }
 
 #[derive(Clone)]
@@ -34,4 +191,23 @@ pub struct RaytracingGeometry {
 
 pub struct RaytracingScene {
     pub tlas: Arc<AccelerationStructure>,
+    /// Set when the instances changed, see [`crate::scene_uploader::rebuild_tlas`]
+    pub is_tlas_outdated: bool,
+
+    /// Only exists when the device supports descriptor indexing
+    pub bindless: Option<BindlessScene>,
+}
+
+impl RaytracingScene {
+    /// Instance mask bit of the models that cast shadows, the shadow rays only trace against it
+    pub const SHADOW_CASTER_MASK: u8 = 0b1;
+}
+
+pub struct BindlessScene {
+    /// Indexed with the instance custom index of the TLAS instances
+    pub descriptor_set: DescriptorSet,
+    /// In the order of the texture array
+    pub textures: Vec<(Arc<ImageView>, Arc<Sampler>)>,
+    pub _instance_materials_buffer: Buffer<u32>,
+    pub _materials_buffer: Buffer<shader_types::BindlessMaterial>,
 }
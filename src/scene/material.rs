@@ -1,12 +1,17 @@
+use crevice::std140::AsStd140;
 use ultraviolet::Vec3;
 
+use crate::loader::MaterialOverride;
 use crate::render::shader_types;
 use crate::vulkan::buffer::Buffer;
 use crate::vulkan::descriptor_set::DescriptorSet;
 
 use super::Texture;
 
+/// The factors are the loaded ones, the shaders see the ones in the buffer, which can be edited
 pub struct Material {
+    /// Index of the material in the glTF file, for saving the edited factors
+    pub source_index: Option<usize>,
     pub base_color: Vec3,
     pub base_color_texture: Texture,
     pub normal_texture: Texture,
@@ -14,7 +19,40 @@ pub struct Material {
     pub metallic_factor: f32,
     pub metallic_roughness_texture: Texture,
     pub emissivity: Vec3,
+    pub double_sided: bool,
+    pub clearcoat_factor: f32,
+    pub clearcoat_texture: Texture,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_roughness_texture: Texture,
 
     pub descriptor_set: DescriptorSet,
     pub descriptor_set_buffer: Buffer<shader_types::Std140Material>,
 }
+
+impl Material {
+    /// The factors that the shaders currently see
+    pub fn read_factors(&self) -> shader_types::Material {
+        shader_types::Material::from_std140(self.descriptor_set_buffer.read_to_vec()[0])
+    }
+
+    /// Changes every primitive with this material, starting with the next frame
+    pub fn write_factors(&self, factors: &shader_types::Material) {
+        self.descriptor_set_buffer.copy_data(&factors.as_std140());
+    }
+
+    /// The factors that differ from the loaded ones, None when nothing was edited
+    pub fn edited_factors(&self) -> Option<MaterialOverride> {
+        fn changed<T: PartialEq>(edited: T, loaded: T) -> Option<T> {
+            (edited != loaded).then_some(edited)
+        }
+
+        let factors = self.read_factors();
+        let material_override = MaterialOverride {
+            base_color: changed(factors.base_color, self.base_color),
+            emissivity: changed(factors.emissivity, self.emissivity),
+            roughness: changed(factors.roughness, self.roughness_factor),
+            metallic: changed(factors.metallic, self.metallic_factor),
+        };
+        (material_override != MaterialOverride::default()).then_some(material_override)
+    }
+}
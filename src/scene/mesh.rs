@@ -1,11 +1,105 @@
 use std::sync::Arc;
 
-use super::Vertex;
+use ultraviolet::{Mat4, Vec3};
+
+use super::{MorphVertexDelta, SkinVertex, Vertex};
 use crate::vulkan::buffer::Buffer;
 
+/// Including the full resolution mesh
+pub const MAX_LOD_COUNT: usize = 4;
+
 pub struct Mesh {
+    /// All levels of detail, one after the other
     pub index_buffer: Arc<Buffer<u32>>,
     pub vertex_buffer: Arc<Buffer<Vertex>>,
+    /// Only exists for skinned meshes
+    pub skin_buffer: Option<Arc<Buffer<SkinVertex>>>,
+    /// Only exists for meshes with morph targets. Has all targets of a vertex next to each other.
+    pub morph_targets_buffer: Option<Arc<Buffer<MorphVertexDelta>>>,
+    /// Of the full resolution mesh, which always starts at index 0
     pub num_indices: u32,
     pub num_vertices: u32,
+    /// From the most detailed to the coarsest
+    pub lods: Vec<MeshLod>,
+    pub bounds: Aabb,
+}
+
+/// A range in the index buffer of a mesh
+#[derive(Clone, Copy, Debug)]
+pub struct MeshLod {
+    pub first_index: u32,
+    pub num_indices: u32,
+}
+
+/// Axis aligned bounding box, in model space
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        vertices.iter().fold(
+            Self {
+                min: Vec3::broadcast(f32::INFINITY),
+                max: Vec3::broadcast(f32::NEG_INFINITY),
+            },
+            |aabb, vertex| {
+                let position = Vec3::from(vertex.position);
+                Self {
+                    min: aabb.min.min_by_component(position),
+                    max: aabb.max.max_by_component(position),
+                }
+            },
+        )
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// In the order of the bits of the index, x being the lowest bit
+    pub fn corners(&self) -> [Vec3; 8] {
+        std::array::from_fn(|index| {
+            Vec3::new(
+                if index & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                },
+                if index & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                },
+                if index & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                },
+            )
+        })
+    }
+
+    /// Encloses the transformed box, which makes it larger for rotations
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        self.corners().iter().fold(
+            Self {
+                min: Vec3::broadcast(f32::INFINITY),
+                max: Vec3::broadcast(f32::NEG_INFINITY),
+            },
+            |aabb, &corner| {
+                let position = matrix.transform_point3(corner);
+                Self {
+                    min: aabb.min.min_by_component(position),
+                    max: aabb.max.max_by_component(position),
+                }
+            },
+        )
+    }
 }
@@ -0,0 +1,8 @@
+use crate::vulkan::buffer::Buffer;
+
+/// How much each morph target of the meshes of a model is applied
+pub struct MorphWeights {
+    pub weights: Vec<f32>,
+    /// Host visible, rewritten whenever the weights change
+    pub buffer: Buffer<f32>,
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+use crate::vulkan::sampler::Sampler;
+
+/// A sampler of the scene, which gets recreated when the sampler overrides change
+pub struct SceneSampler {
+    /// From the glTF file, without the overrides
+    pub create_info: vk::SamplerCreateInfo,
+    /// The one that the textures were uploaded with
+    pub sampler: Arc<Sampler>,
+    /// Used by the descriptor sets instead, once the overrides changed
+    pub overridden: Option<Arc<Sampler>>,
+}
+
+/// Applies to every sampler of the scene, for comparing the quality and the performance
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SamplerOverrides {
+    pub filter: FilterOverride,
+    /// 1 turns anisotropic filtering off, gets clamped to what the device supports
+    pub max_anisotropy: f32,
+}
+
+impl Default for SamplerOverrides {
+    fn default() -> Self {
+        Self {
+            filter: FilterOverride::None,
+            max_anisotropy: 16.0,
+        }
+    }
+}
+
+impl SamplerOverrides {
+    pub fn apply(
+        &self,
+        create_info: &vk::SamplerCreateInfo,
+        max_sampler_anisotropy: f32,
+    ) -> vk::SamplerCreateInfo {
+        let mut create_info = *create_info;
+        let filter = match self.filter {
+            FilterOverride::None => None,
+            FilterOverride::Nearest => Some((vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST)),
+            FilterOverride::Linear => Some((vk::Filter::LINEAR, vk::SamplerMipmapMode::LINEAR)),
+        };
+        if let Some((filter, mipmap_mode)) = filter {
+            create_info.mag_filter = filter;
+            create_info.min_filter = filter;
+            create_info.mipmap_mode = mipmap_mode;
+        }
+
+        let max_anisotropy = self.max_anisotropy.clamp(1.0, max_sampler_anisotropy);
+        create_info.anisotropy_enable = (max_anisotropy > 1.0).into();
+        create_info.max_anisotropy = max_anisotropy;
+        create_info
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOverride {
+    /// Keeps the filters of the glTF file
+    None,
+    Nearest,
+    Linear,
+}
+
+impl FilterOverride {
+    pub const ALL: [FilterOverride; 3] = [
+        FilterOverride::None,
+        FilterOverride::Nearest,
+        FilterOverride::Linear,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterOverride::None => "From the scene",
+            FilterOverride::Nearest => "Nearest",
+            FilterOverride::Linear => "Linear",
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use ultraviolet::Mat4;
+
+use crate::vulkan::{buffer::Buffer, descriptor_set::DescriptorSet};
+
+/// The joint matrices of a skinned model, which the vertex shader blends for every vertex
+pub struct Skin {
+    /// Nodes of the scene graph
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+    /// Host visible, rewritten every frame
+    pub joint_matrices_buffer: Buffer<Mat4>,
+    pub descriptor_set: DescriptorSet,
+}
@@ -1,4 +1,5 @@
 use ash::vk;
+use ultraviolet::{Vec3, Vec4};
 
 use crate::offset_of;
 
@@ -12,6 +13,19 @@ pub struct Vertex {
 }
 
 impl Vertex {
+    /// How far the length of a normal or tangent may be from 1
+    const UNIT_LENGTH_TOLERANCE: f32 = 1e-3;
+
+    /// The lighting produces NaNs for normals and tangents that are zero, not finite, or not normalized
+    pub fn has_valid_normal_and_tangent(&self) -> bool {
+        // the comparison is false for NaN and infinite lengths
+        let is_unit_length =
+            |vector: Vec3| (vector.mag() - 1.0).abs() < Vertex::UNIT_LENGTH_TOLERANCE;
+        is_unit_length(self.normal.into())
+            && is_unit_length(Vec4::from(self.tangent).xyz())
+            && self.tangent[3].is_finite()
+    }
+
     pub fn binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
         [vk::VertexInputBindingDescription {
             binding: 0,
@@ -49,3 +63,48 @@ impl Vertex {
         ]
     }
 }
+
+/// The joints that move a vertex of a skinned mesh. Lives in its own vertex buffer,
+/// so that meshes without a skin don't pay for it.
+#[derive(Clone, Debug, Copy, Default)]
+#[repr(C)]
+pub struct SkinVertex {
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+impl SkinVertex {
+    pub fn binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
+        [vk::VertexInputBindingDescription {
+            binding: 1,
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    /// Continues after the locations of the normal vertex attributes
+    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
+        [
+            vk::VertexInputAttributeDescription {
+                location: 4,
+                binding: 1,
+                format: vk::Format::R32G32B32A32_UINT,
+                offset: offset_of!(Self, joints) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 5,
+                binding: 1,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, weights) as u32,
+            },
+        ]
+    }
+}
+
+/// How far one morph target moves a vertex, padded like a vec4 in std430
+#[derive(Clone, Debug, Copy, Default)]
+#[repr(C)]
+pub struct MorphVertexDelta {
+    pub position: [f32; 4],
+    pub normal: [f32; 4],
+}
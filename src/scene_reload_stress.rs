@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Loads and unloads scenes over and over, see `--reload-scene-count`.
+/// Switches back and forth between two scenes. Every time one has finished loading,
+/// the number of allocated descriptor sets has to match the previous load of the same scene.
+pub struct SceneReloadStress {
+    remaining_reloads: u32,
+    scene_paths: [PathBuf; 2],
+    descriptor_set_counts: HashMap<PathBuf, usize>,
+}
+
+impl SceneReloadStress {
+    /// Both paths can be the same scene
+    pub fn new(reload_count: u32, first_scene_path: PathBuf, second_scene_path: PathBuf) -> Self {
+        Self {
+            remaining_reloads: reload_count,
+            scene_paths: [first_scene_path, second_scene_path],
+            descriptor_set_counts: HashMap::new(),
+        }
+    }
+
+    /// Call once the scene has finished loading.
+    /// Returns the scene to switch to, or None once all reloads are done.
+    pub fn next_scene(
+        &mut self,
+        loaded_scene_path: &Path,
+        descriptor_set_count: usize,
+    ) -> Option<PathBuf> {
+        let previous_count = self
+            .descriptor_set_counts
+            .insert(loaded_scene_path.to_path_buf(), descriptor_set_count);
+        if let Some(previous_count) = previous_count {
+            assert_eq!(
+                previous_count,
+                descriptor_set_count,
+                "Reloading {} changed the number of allocated descriptor sets",
+                loaded_scene_path.display()
+            );
+        }
+
+        if self.remaining_reloads == 0 {
+            return None;
+        }
+        self.remaining_reloads -= 1;
+        log::info!(
+            "Reloaded scenes, {} reloads left, {} descriptor sets allocated",
+            self.remaining_reloads,
+            descriptor_set_count
+        );
+        let next_scene_path = if loaded_scene_path == self.scene_paths[0] {
+            &self.scene_paths[1]
+        } else {
+            &self.scene_paths[0]
+        };
+        Some(next_scene_path.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternates_between_the_scenes() {
+        let [first, second] = ["a.glb", "b.glb"].map(PathBuf::from);
+        let mut stress = SceneReloadStress::new(3, first.clone(), second.clone());
+
+        assert_eq!(stress.next_scene(&first, 10), Some(second.clone()));
+        assert_eq!(stress.next_scene(&second, 20), Some(first.clone()));
+        assert_eq!(stress.next_scene(&first, 10), Some(second.clone()));
+        assert_eq!(stress.next_scene(&second, 20), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "changed the number of allocated descriptor sets")]
+    fn leaked_descriptor_sets_panic() {
+        let scene = PathBuf::from("a.glb");
+        let mut stress = SceneReloadStress::new(3, scene.clone(), scene.clone());
+
+        stress.next_scene(&scene, 10);
+        stress.next_scene(&scene, 11);
+    }
+}
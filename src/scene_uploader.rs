@@ -1,423 +1,1446 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{Receiver, TryRecvError};
 use std::sync::Arc;
 
-use ash::vk::{self, ImageUsageFlags};
+use ash::vk::{self, AccessFlags2, ImageUsageFlags, PipelineStageFlags2};
 use crevice::std140::AsStd140;
-use ultraviolet::Mat4;
+use ultraviolet::{Mat4, Vec3};
 
-use crate::loader::LoadedTexture;
-use crate::scene::{RaytracingGeometry, RaytracingScene};
+use crate::loader::{LoadedTexture, SceneStreamEvent};
+use crate::scene::{BindlessScene, RaytracingGeometry, RaytracingScene};
 use crate::transform::Transform;
-use crate::vulkan::acceleration_structure::AccelerationStructure;
+use crate::vulkan::acceleration_structure::{AccelerationStructure, ScratchBuffer};
 use crate::vulkan::buffer::Buffer;
 use crate::vulkan::command_buffer::{
     AccelerationStructureBuildGeometryInfoKHR, AccelerationStructureGeometryData,
-    BeginCommandBuffer, CmdBuildAccelerationStructures, EndCommandBuffer,
+    BeginCommandBuffer, CmdBeginDebugLabel, CmdBuildAccelerationStructures,
+    CmdCopyAccelerationStructure, CmdEndDebugLabel, CmdPipelineBarrier,
+    CmdWriteAccelerationStructuresProperties, EndCommandBuffer, MemoryBarrier, QueueFamilyAccess,
 };
 use crate::vulkan::command_buffer::{CommandBuffer, CommandBufferAllocateInfo};
 use crate::vulkan::command_pool::CommandPool;
 use crate::vulkan::context::Context;
+use crate::vulkan::descriptor_pool::DescriptorPool;
 use crate::vulkan::descriptor_set::{DescriptorSet, WriteDescriptorSet};
 use crate::vulkan::image::Image;
 use crate::vulkan::image_view::ImageView;
+use crate::vulkan::image_view_cache::ImageViewCache;
 use crate::vulkan::sampler::Sampler;
+use crate::vulkan::timeline_semaphore::{TimelineSemaphore, TimelineSubmission};
 use crate::{
     loader::{self, Asset, LoadedImage, LoadedSampler},
-    render::{set_layout_cache::DescriptorSetLayoutCache, shader_types},
-    scene::{Material, Mesh, Model, Primitive, Scene, Texture},
+    render::{
+        set_layout_cache::{DescriptorSetLayoutCache, MAX_BINDLESS_TEXTURES},
+        shader_types,
+    },
+    scene::{
+        Material, Mesh, MeshLod, Model, MorphVertexDelta, MorphWeights, Primitive,
+        SamplerOverrides, Scene, SceneSampler, Skin, Texture, Vertex,
+    },
+    utility::GetVecSize,
 };
 
 pub fn setup(
     loaded_scene: loader::LoadedScene,
     context: Arc<Context>,
-    descriptor_pool: vk::DescriptorPool,
+    descriptor_pool: &DescriptorPool,
     set_layout_cache: &DescriptorSetLayoutCache,
+    image_view_cache: &mut ImageViewCache,
     queue: vk::Queue,
     command_pool: CommandPool,
+    compact_acceleration_structures: bool,
+    sampler_overrides: SamplerOverrides,
 ) -> Scene {
-    let device = &context.clone().device;
-
-    let mut setup_command_buffer = CommandBuffer::new(
+    let uploader = SceneUploader::new(
+        context,
+        image_view_cache,
+        queue,
         command_pool,
-        CommandBufferAllocateInfo {
-            level: vk::CommandBufferLevel::PRIMARY,
-            count: 1,
-        },
+        compact_acceleration_structures,
+        sampler_overrides,
     );
-    setup_command_buffer.add_cmd(BeginCommandBuffer {
-        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
-    });
+    let mut scene = Scene::new(sampler_overrides);
+    uploader.finish(
+        &mut scene,
+        loaded_scene,
+        descriptor_pool,
+        set_layout_cache,
+        image_view_cache,
+    );
+    scene
+}
 
-    let default_sampler = {
-        let sampler_info = vk::SamplerCreateInfo::builder().build();
-        let sampler = unsafe { device.create_sampler(&sampler_info, None) }
-            .expect("Could not create sampler");
-        Arc::new(Sampler::new(sampler, context.clone()))
-    };
-    let (default_base_color_image_view, default_normal_map_image_view) = {
-        let image_info = vk::ImageCreateInfo::builder()
-            .image_type(vk::ImageType::TYPE_2D)
-            .format(vk::Format::R8G8B8A8_UNORM)
-            .extent(vk::Extent3D {
-                width: 1,
-                height: 1,
-                depth: 1,
-            })
-            .mip_levels(1)
-            .array_layers(1)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .usage(
-                ImageUsageFlags::SAMPLED
-                    | ImageUsageFlags::TRANSFER_DST
-                    | ImageUsageFlags::TRANSFER_SRC,
-            )
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .build();
+/// A scene that is still being loaded on another thread. Its models are uploaded as they arrive,
+/// with one batch per frame. The uploader keeps the meshes, materials and textures across the batches.
+pub struct SceneStream {
+    receiver: Receiver<SceneStreamEvent>,
+    uploader: SceneUploader,
+}
 
-        // default base color should be a 1x1 white image (255, 255, 255)
-        let base_color = {
-            let image = Arc::new(Image::new(context.clone(), &image_info));
+impl SceneStream {
+    pub fn new(receiver: Receiver<SceneStreamEvent>, uploader: SceneUploader) -> Self {
+        Self { receiver, uploader }
+    }
 
-            let image_data_buffer: Buffer<u8> = Buffer::new(
-                context.clone(),
-                4, // A single 32 bit pixels = 4 bytes
-                vk::BufferUsageFlags::TRANSFER_SRC,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            );
-            image_data_buffer.copy_data(&vec![0xFFu8, 0xFF, 0xFF, 0xFF]);
-            image.copy_from_buffer_for_texture(&mut setup_command_buffer, image_data_buffer.into());
+    /// Uploads the models that arrived since the last call.
+    /// Returns the rest of the scene once it is loaded, which goes to [`SceneStream::finish`].
+    pub fn upload_arrived_models(
+        &mut self,
+        scene: &mut Scene,
+        descriptor_pool: &DescriptorPool,
+        set_layout_cache: &DescriptorSetLayoutCache,
+        image_view_cache: &mut ImageViewCache,
+    ) -> Option<loader::LoadedScene> {
+        let mut loaded_models = vec![];
+        let mut loaded_scene = None;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(SceneStreamEvent::Model(loaded_model)) => loaded_models.push(loaded_model),
+                Ok(SceneStreamEvent::Finished(result)) => {
+                    loaded_scene = Some(result.expect("Could not load scene"));
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => panic!("Scene loading thread stopped"),
+            }
+        }
 
-            Arc::new(ImageView::new_default(
-                context.clone(),
-                image,
-                vk::ImageAspectFlags::COLOR,
-            ))
-        };
+        // Streamed models never have a skin, the models with a skin arrive with the rest of the scene.
+        // The models before Finished are still in this batch, so they get uploaded before the rest.
+        self.uploader.upload_models(
+            &mut scene.models,
+            loaded_models,
+            &[],
+            descriptor_pool,
+            set_layout_cache,
+            image_view_cache,
+        );
+        loaded_scene
+    }
 
-        // default normal map should be a 1x1 purple image (128, 128, 255)
-        let normal_map = {
-            let image = Arc::new(Image::new(context.clone(), &image_info));
+    pub fn finish(
+        self,
+        scene: &mut Scene,
+        loaded_scene: loader::LoadedScene,
+        descriptor_pool: &DescriptorPool,
+        set_layout_cache: &DescriptorSetLayoutCache,
+        image_view_cache: &mut ImageViewCache,
+    ) {
+        self.uploader.finish(
+            scene,
+            loaded_scene,
+            descriptor_pool,
+            set_layout_cache,
+            image_view_cache,
+        );
+    }
+}
 
-            let image_data_buffer: Buffer<u8> = Buffer::new(
-                context.clone(),
-                4, // A single 32 bit pixels = 4 bytes
-                vk::BufferUsageFlags::TRANSFER_SRC,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            );
-            image_data_buffer.copy_data(&vec![0x80u8, 0x80, 0xFF, 0xFF]);
-            image.copy_from_buffer_for_texture(&mut setup_command_buffer, image_data_buffer.into());
+/// Uploads the models of a scene, either all at once or in batches while the scene is loading.
+/// Meshes, materials and textures are shared between the batches.
+pub struct SceneUploader {
+    context: Arc<Context>,
+    queue: vk::Queue,
+    command_pool: CommandPool,
+    compact_acceleration_structures: bool,
+    /// The samplers get created with them
+    sampler_overrides: SamplerOverrides,
 
-            Arc::new(ImageView::new_default(
-                context.clone(),
-                image,
-                vk::ImageAspectFlags::COLOR,
-            ))
-        };
+    default_sampler: Arc<Sampler>,
+    default_base_color_image_view: Arc<ImageView>,
+    default_white_image_view: Arc<ImageView>,
+    default_normal_map_image_view: Arc<ImageView>,
 
-        (base_color, normal_map)
-    };
+    sampler_map: HashMap<loader::AssetId, SceneSampler>,
+    texture_map: HashMap<loader::AssetId, Arc<Image>>,
+    material_map: HashMap<loader::AssetId, Arc<Material>>,
+    mesh_map: HashMap<loader::AssetId, Arc<Mesh>>,
+    raytracing_geometry_map: HashMap<loader::AssetId, RaytracingGeometry>,
+    scratch_buffer: ScratchBuffer,
+}
 
-    let mut sampler_map = HashMap::new();
-    let mut texture_map = HashMap::new();
-    let mut material_map = HashMap::new();
-    let mut model_map = HashMap::new();
-    let mut raytracing_geometry_map = HashMap::new();
-
-    let mut models = vec![];
-    for loaded_model in loaded_scene.models {
-        let mut model = Model {
-            transform: loaded_model.transform,
-            primitives: vec![],
+impl SceneUploader {
+    pub fn new(
+        context: Arc<Context>,
+        image_view_cache: &mut ImageViewCache,
+        queue: vk::Queue,
+        command_pool: CommandPool,
+        compact_acceleration_structures: bool,
+        sampler_overrides: SamplerOverrides,
+    ) -> Self {
+        let mut setup_command_buffer = CommandBuffer::new(
+            command_pool.clone(),
+            CommandBufferAllocateInfo {
+                level: vk::CommandBufferLevel::PRIMARY,
+                count: 1,
+            },
+        );
+        setup_command_buffer.add_cmd(BeginCommandBuffer {
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            inheritance_info: None,
+        });
+
+        let default_sampler = create_sampler(
+            context.clone(),
+            &vk::SamplerCreateInfo::default(),
+            sampler_overrides,
+        );
+        let (
+            default_base_color_image_view,
+            default_white_image_view,
+            default_normal_map_image_view,
+        ) = {
+            let image_info = vk::ImageCreateInfo::builder()
+                .image_type(vk::ImageType::TYPE_2D)
+                .format(vk::Format::R8G8B8A8_UNORM)
+                .extent(vk::Extent3D {
+                    width: 1,
+                    height: 1,
+                    depth: 1,
+                })
+                .mip_levels(1)
+                .array_layers(1)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .usage(
+                    ImageUsageFlags::SAMPLED
+                        | ImageUsageFlags::TRANSFER_DST
+                        | ImageUsageFlags::TRANSFER_SRC,
+                )
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .build();
+
+            // default base color should be a 1x1 white image (255, 255, 255)
+            // sRGB like the base color textures, so that untextured materials are treated the same way
+            let base_color = {
+                let image_info = vk::ImageCreateInfo {
+                    format: vk::Format::R8G8B8A8_SRGB,
+                    ..image_info
+                };
+                let image = Arc::new(Image::new(context.clone(), &image_info));
+
+                let image_data_buffer: Buffer<u8> = Buffer::new(
+                    context.clone(),
+                    4, // A single 32 bit pixels = 4 bytes
+                    vk::BufferUsageFlags::TRANSFER_SRC,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                );
+                image_data_buffer.copy_data(&vec![0xFFu8, 0xFF, 0xFF, 0xFF]);
+                image.copy_from_buffer_for_texture(
+                    &mut setup_command_buffer,
+                    image_data_buffer.into(),
+                );
+
+                image_view_cache.get_default(&image, vk::ImageAspectFlags::COLOR)
+            };
+
+            // the same in linear space, for the metallic roughness textures
+            let white = {
+                let image = Arc::new(Image::new(context.clone(), &image_info));
+
+                let image_data_buffer: Buffer<u8> = Buffer::new(
+                    context.clone(),
+                    4, // A single 32 bit pixels = 4 bytes
+                    vk::BufferUsageFlags::TRANSFER_SRC,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                );
+                image_data_buffer.copy_data(&vec![0xFFu8, 0xFF, 0xFF, 0xFF]);
+                image.copy_from_buffer_for_texture(
+                    &mut setup_command_buffer,
+                    image_data_buffer.into(),
+                );
+
+                image_view_cache.get_default(&image, vk::ImageAspectFlags::COLOR)
+            };
+
+            // default normal map should be a 1x1 purple image (128, 128, 255)
+            let normal_map = {
+                let image = Arc::new(Image::new(context.clone(), &image_info));
+
+                let image_data_buffer: Buffer<u8> = Buffer::new(
+                    context.clone(),
+                    4, // A single 32 bit pixels = 4 bytes
+                    vk::BufferUsageFlags::TRANSFER_SRC,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                );
+                image_data_buffer.copy_data(&vec![0x80u8, 0x80, 0xFF, 0xFF]);
+                image.copy_from_buffer_for_texture(
+                    &mut setup_command_buffer,
+                    image_data_buffer.into(),
+                );
+
+                image_view_cache.get_default(&image, vk::ImageAspectFlags::COLOR)
+            };
+
+            (base_color, white, normal_map)
         };
 
-        for loaded_primitive in loaded_model.primitives {
-            let material = material_map
-                .entry(loaded_primitive.material.id())
-                .or_insert_with(|| {
-                    let base_color_texture = load_texture(
-                        context.clone(),
-                        &mut setup_command_buffer,
-                        loaded_primitive.material.base_color_texture.as_ref(),
-                        &mut texture_map,
-                        &mut sampler_map,
-                        default_base_color_image_view.clone(),
-                        default_sampler.clone(),
-                        true,
-                    );
+        setup_command_buffer.add_cmd(EndCommandBuffer {});
+        let recorded = setup_command_buffer.record(context.clone());
+        let timeline_semaphore = Arc::new(TimelineSemaphore::new(context.clone()));
+        recorded.submit(queue, &[], timeline_semaphore).wait();
 
-                    let normal_texture = load_texture(
-                        context.clone(),
-                        &mut setup_command_buffer,
-                        loaded_primitive.material.normal_texture.as_ref(),
-                        &mut texture_map,
-                        &mut sampler_map,
-                        default_normal_map_image_view.clone(),
-                        default_sampler.clone(),
-                        true,
-                    );
+        Self {
+            context: context.clone(),
+            queue,
+            command_pool,
+            compact_acceleration_structures,
+            sampler_overrides,
+            default_sampler,
+            default_base_color_image_view,
+            default_white_image_view,
+            default_normal_map_image_view,
+            sampler_map: HashMap::new(),
+            texture_map: HashMap::new(),
+            material_map: HashMap::new(),
+            mesh_map: HashMap::new(),
+            raytracing_geometry_map: HashMap::new(),
+            scratch_buffer: ScratchBuffer::new(context),
+        }
+    }
 
-                    let metallic_roughness_texture = load_texture(
-                        context.clone(),
-                        &mut setup_command_buffer,
-                        loaded_primitive
-                            .material
-                            .metallic_roughness_texture
-                            .as_ref(),
-                        &mut texture_map,
-                        &mut sampler_map,
-                        default_base_color_image_view.clone(),
-                        default_sampler.clone(),
-                        false,
-                    );
+    /// Uploads the models that are left, and builds the TLAS over all the models
+    pub fn finish(
+        mut self,
+        scene: &mut Scene,
+        loaded_scene: loader::LoadedScene,
+        descriptor_pool: &DescriptorPool,
+        set_layout_cache: &DescriptorSetLayoutCache,
+        image_view_cache: &mut ImageViewCache,
+    ) {
+        self.upload_models(
+            &mut scene.models,
+            loaded_scene.models,
+            &loaded_scene.skins,
+            descriptor_pool,
+            set_layout_cache,
+            image_view_cache,
+        );
+        scene.scene_graph = loaded_scene.scene_graph;
+        scene.morph_weights_animations = loaded_scene.morph_weights_animations;
+        scene.node_animations = loaded_scene.node_animations;
+        scene.lights = loaded_scene.lights;
+        scene.raytracing_scene =
+            self.create_raytracing_scene(&scene.models, descriptor_pool, set_layout_cache);
+        self.log_summary(scene);
+
+        scene.samplers = self.sampler_map.into_values().collect();
+        scene.samplers.push(SceneSampler {
+            create_info: vk::SamplerCreateInfo::default(),
+            sampler: self.default_sampler,
+            overridden: None,
+        });
+        // they could have changed while the scene was streaming in
+        scene.are_samplers_outdated |= scene.sampler_overrides != self.sampler_overrides;
+    }
 
-                    let material_buffer = Buffer::new(
+    /// Every buffer and image of the scene has its own allocation, so their sizes add up to the memory that the scene uses
+    fn log_summary(&self, scene: &Scene) {
+        let vertex_bytes: vk::DeviceSize = self
+            .mesh_map
+            .values()
+            .map(|mesh| {
+                mesh.vertex_buffer.get_untyped().size
+                    + mesh
+                        .skin_buffer
+                        .as_ref()
+                        .map_or(0, |buffer| buffer.get_untyped().size)
+                    + mesh
+                        .morph_targets_buffer
+                        .as_ref()
+                        .map_or(0, |buffer| buffer.get_untyped().size)
+            })
+            .sum();
+        let index_bytes: vk::DeviceSize = self
+            .mesh_map
+            .values()
+            .map(|mesh| mesh.index_buffer.get_untyped().size)
+            .sum();
+        let image_bytes: vk::DeviceSize = self
+            .texture_map
+            .values()
+            .map(|image| unsafe { self.context.device.get_image_memory_requirements(image.inner) }.size)
+            .sum();
+        let acceleration_structure_bytes: vk::DeviceSize = self
+            .raytracing_geometry_map
+            .values()
+            .map(|geometry| geometry.blas.buffer.get_untyped().size)
+            .chain(
+                scene
+                    .raytracing_scene
+                    .iter()
+                    .map(|raytracing_scene| raytracing_scene.tlas.buffer.get_untyped().size),
+            )
+            .sum();
+        let primitive_count: usize = scene
+            .models
+            .iter()
+            .map(|model| model.primitives.len())
+            .sum();
+
+        log::info!(
+            "Uploaded {} models, {} primitives, {} materials, {} textures and {} BLASes",
+            scene.models.len(),
+            primitive_count,
+            self.material_map.len(),
+            self.texture_map.len(),
+            self.raytracing_geometry_map.len()
+        );
+        log::info!(
+            "Scene memory: vertices {}, indices {}, images {}, acceleration structures {}, total {}",
+            format_bytes(vertex_bytes),
+            format_bytes(index_bytes),
+            format_bytes(image_bytes),
+            format_bytes(acceleration_structure_bytes),
+            format_bytes(vertex_bytes + index_bytes + image_bytes + acceleration_structure_bytes)
+        );
+    }
+
+    /// Appends the models, and waits until their uploads are done.
+    /// The skins are only needed for models that have one.
+    pub fn upload_models(
+        &mut self,
+        models: &mut Vec<Model>,
+        loaded_models: Vec<loader::LoadedModel>,
+        skins: &[loader::LoadedSkin],
+        descriptor_pool: &DescriptorPool,
+        set_layout_cache: &DescriptorSetLayoutCache,
+        image_view_cache: &mut ImageViewCache,
+    ) {
+        if loaded_models.is_empty() {
+            return;
+        }
+        let context = self.context.clone();
+        let compact_acceleration_structures = self.compact_acceleration_structures;
+
+        let mut setup_command_buffer = CommandBuffer::new(
+            self.command_pool.clone(),
+            CommandBufferAllocateInfo {
+                level: vk::CommandBufferLevel::PRIMARY,
+                count: 1,
+            },
+        );
+        setup_command_buffer.add_cmd(BeginCommandBuffer {
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            inheritance_info: None,
+        });
+
+        // Only the meshes and BLASes of this batch, the earlier ones are already in use
+        let mut new_meshes = vec![];
+        let mut new_blases = vec![];
+        // Built together after all the meshes are known, with their scratch sizes
+        let mut blas_builds = vec![];
+
+        // Meshes are uploaded first, so that they can go through the transfer queue
+        // while the graphics queue uploads the textures
+        let mut transfer_command_buffer = context.transfer_queue.as_ref().map(|transfer_queue| {
+            let mut command_buffer = CommandBuffer::new(
+                CommandPool::new_for_queue_family(
+                    context.clone(),
+                    transfer_queue.queue_family_index,
+                ),
+                CommandBufferAllocateInfo {
+                    level: vk::CommandBufferLevel::PRIMARY,
+                    count: 1,
+                },
+            );
+            command_buffer.add_cmd(BeginCommandBuffer {
+                flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                inheritance_info: None,
+            });
+            command_buffer
+        });
+        for loaded_primitive in loaded_models
+            .iter()
+            .flat_map(|loaded_model| loaded_model.primitives.iter())
+        {
+            self.mesh_map
+                .entry(loaded_primitive.mesh.id())
+                .or_insert_with(|| {
+                    let upload_command_buffer = transfer_command_buffer
+                        .as_mut()
+                        .unwrap_or(&mut setup_command_buffer);
+                    let mesh = create_mesh(
                         context.clone(),
-                        shader_types::Material::std140_size_static() as u64,
-                        vk::BufferUsageFlags::UNIFORM_BUFFER,
-                        vk::MemoryPropertyFlags::HOST_VISIBLE
-                            | vk::MemoryPropertyFlags::HOST_COHERENT,
+                        upload_command_buffer,
+                        loaded_primitive.mesh.clone(),
                     );
+                    new_meshes.push(mesh.clone());
+                    mesh
+                });
+        }
 
-                    let material = shader_types::Material {
-                        base_color: loaded_primitive.material.base_color,
-                        emissivity: loaded_primitive.material.emissivity,
-                        roughness: loaded_primitive.material.roughness_factor,
-                        metallic: loaded_primitive.material.metallic_factor,
-                    };
-                    material_buffer.copy_data(&material.as_std140());
+        // The buffers move from the transfer queue family to the graphics queue family
+        let mut mesh_acquire_stages =
+            PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT | PipelineStageFlags2::INDEX_INPUT;
+        if context.context_raytracing.is_some() {
+            mesh_acquire_stages |= PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR;
+        }
+        // the morph targets are a storage buffer of the vertex shader
+        if new_meshes
+            .iter()
+            .any(|mesh| mesh.morph_targets_buffer.is_some())
+        {
+            mesh_acquire_stages |= PipelineStageFlags2::VERTEX_SHADER;
+        }
+        let mesh_transfer = transfer_command_buffer
+            .filter(|_| !new_meshes.is_empty())
+            .map(|mut transfer_command_buffer| {
+                let transfer_queue = context.transfer_queue.as_ref().unwrap();
+                let buffers: Vec<_> = new_meshes
+                    .iter()
+                    .flat_map(|mesh: &Arc<Mesh>| {
+                        [
+                            Some(mesh.vertex_buffer.get_untyped().clone()),
+                            Some(mesh.index_buffer.get_untyped().clone()),
+                            mesh.skin_buffer
+                                .as_ref()
+                                .map(|skin_buffer| skin_buffer.get_untyped().clone()),
+                            mesh.morph_targets_buffer
+                                .as_ref()
+                                .map(|morph_targets_buffer| {
+                                    morph_targets_buffer.get_untyped().clone()
+                                }),
+                        ]
+                    })
+                    .flatten()
+                    .collect();
+
+                let (release, acquire) = CmdPipelineBarrier::queue_ownership_transfer(
+                    &buffers,
+                    QueueFamilyAccess {
+                        queue_family_index: transfer_queue.queue_family_index,
+                        stage_mask: PipelineStageFlags2::COPY,
+                        access_mask: AccessFlags2::TRANSFER_WRITE,
+                    },
+                    QueueFamilyAccess {
+                        queue_family_index: context.queue_family_index,
+                        stage_mask: mesh_acquire_stages,
+                        access_mask: AccessFlags2::VERTEX_ATTRIBUTE_READ
+                            | AccessFlags2::INDEX_READ
+                            | AccessFlags2::SHADER_READ,
+                    },
+                );
+                transfer_command_buffer.add_cmd(release);
+                transfer_command_buffer.add_cmd(EndCommandBuffer {});
+                setup_command_buffer.add_cmd(acquire);
+
+                let recorded = transfer_command_buffer.record(context.clone());
+                let submission = recorded.submit(
+                    transfer_queue.queue,
+                    &[],
+                    Arc::new(TimelineSemaphore::new(context.clone())),
+                );
+                (recorded, submission)
+            });
 
-                    let descriptor_set = DescriptorSet::new(
+        let first_new_model = models.len();
+        for loaded_model in loaded_models {
+            let mut model = Model {
+                transform: loaded_model.transform,
+                node: loaded_model.node,
+                primitives: vec![],
+                casts_shadows: true,
+                skin: loaded_model.skin.map(|skin| {
+                    create_skin(
                         context.clone(),
                         descriptor_pool,
-                        set_layout_cache.material(),
-                        vec![
-                            WriteDescriptorSet::buffer(0, &material_buffer),
-                            WriteDescriptorSet::image_view_sampler(
-                                1,
-                                base_color_texture.image_view.clone(),
-                                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                                base_color_texture.sampler.clone(),
-                            ),
-                            WriteDescriptorSet::image_view_sampler(
-                                2,
-                                normal_texture.image_view.clone(),
-                                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                                normal_texture.sampler.clone(),
-                            ),
-                            WriteDescriptorSet::image_view_sampler(
-                                3,
-                                metallic_roughness_texture.image_view.clone(),
-                                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                                metallic_roughness_texture.sampler.clone(),
-                            ),
-                        ],
-                    );
+                        set_layout_cache,
+                        &skins[skin],
+                    )
+                }),
+                morph_weights: (!loaded_model.morph_weights.is_empty())
+                    .then(|| create_morph_weights(context.clone(), &loaded_model.morph_weights)),
+            };
+            let mut has_dropped_morph_targets = false;
+
+            for loaded_primitive in loaded_model.primitives {
+                let material = self
+                    .material_map
+                    .entry(loaded_primitive.material.id())
+                    .or_insert_with(|| {
+                        let base_color_texture = load_texture(
+                            context.clone(),
+                            &mut setup_command_buffer,
+                            loaded_primitive.material.base_color_texture.as_ref(),
+                            &mut self.texture_map,
+                            &mut self.sampler_map,
+                            self.sampler_overrides,
+                            image_view_cache,
+                            self.default_base_color_image_view.clone(),
+                            self.default_sampler.clone(),
+                            true,
+                        );
+
+                        let normal_texture = load_texture(
+                            context.clone(),
+                            &mut setup_command_buffer,
+                            loaded_primitive.material.normal_texture.as_ref(),
+                            &mut self.texture_map,
+                            &mut self.sampler_map,
+                            self.sampler_overrides,
+                            image_view_cache,
+                            self.default_normal_map_image_view.clone(),
+                            self.default_sampler.clone(),
+                            true,
+                        );
+
+                        let metallic_roughness_texture = load_texture(
+                            context.clone(),
+                            &mut setup_command_buffer,
+                            loaded_primitive
+                                .material
+                                .metallic_roughness_texture
+                                .as_ref(),
+                            &mut self.texture_map,
+                            &mut self.sampler_map,
+                            self.sampler_overrides,
+                            image_view_cache,
+                            self.default_white_image_view.clone(),
+                            self.default_sampler.clone(),
+                            false,
+                        );
+
+                        let clearcoat_texture = load_texture(
+                            context.clone(),
+                            &mut setup_command_buffer,
+                            loaded_primitive.material.clearcoat_texture.as_ref(),
+                            &mut self.texture_map,
+                            &mut self.sampler_map,
+                            self.sampler_overrides,
+                            image_view_cache,
+                            self.default_white_image_view.clone(),
+                            self.default_sampler.clone(),
+                            false,
+                        );
+
+                        let clearcoat_roughness_texture = load_texture(
+                            context.clone(),
+                            &mut setup_command_buffer,
+                            loaded_primitive
+                                .material
+                                .clearcoat_roughness_texture
+                                .as_ref(),
+                            &mut self.texture_map,
+                            &mut self.sampler_map,
+                            self.sampler_overrides,
+                            image_view_cache,
+                            self.default_white_image_view.clone(),
+                            self.default_sampler.clone(),
+                            false,
+                        );
 
-                    Arc::new(Material {
-                        base_color: loaded_primitive.material.base_color,
-                        base_color_texture: base_color_texture.clone(),
-                        normal_texture: normal_texture.clone(),
-                        roughness_factor: loaded_primitive.material.roughness_factor,
-                        metallic_factor: loaded_primitive.material.metallic_factor,
-                        metallic_roughness_texture: metallic_roughness_texture.clone(),
-                        emissivity: loaded_primitive.material.emissivity,
-                        descriptor_set,
-                        descriptor_set_buffer: material_buffer,
+                        let material_buffer = Buffer::new(
+                            context.clone(),
+                            shader_types::Material::std140_size_static() as u64,
+                            vk::BufferUsageFlags::UNIFORM_BUFFER,
+                            vk::MemoryPropertyFlags::HOST_VISIBLE
+                                | vk::MemoryPropertyFlags::HOST_COHERENT,
+                        );
+
+                        let material = shader_types::Material {
+                            base_color: loaded_primitive.material.base_color,
+                            emissivity: loaded_primitive.material.emissivity,
+                            roughness: loaded_primitive.material.roughness_factor,
+                            metallic: loaded_primitive.material.metallic_factor,
+                            transmission: loaded_primitive.material.transmission_factor,
+                            ior: loaded_primitive.material.ior,
+                            clearcoat: loaded_primitive.material.clearcoat_factor,
+                            clearcoat_roughness: loaded_primitive
+                                .material
+                                .clearcoat_roughness_factor,
+                        };
+                        material_buffer.copy_data(&material.as_std140());
+
+                        let mut writes = vec![WriteDescriptorSet::buffer(0, &material_buffer)];
+                        writes.extend(material_texture_writes(
+                            [
+                                &base_color_texture,
+                                &normal_texture,
+                                &metallic_roughness_texture,
+                                &clearcoat_texture,
+                                &clearcoat_roughness_texture,
+                            ],
+                            |texture| texture.sampler.clone(),
+                        ));
+                        let descriptor_set = DescriptorSet::new(
+                            descriptor_pool,
+                            set_layout_cache.material(),
+                            writes,
+                        );
+
+                        Arc::new(Material {
+                            source_index: loaded_primitive.material.source_index,
+                            base_color: loaded_primitive.material.base_color,
+                            base_color_texture: base_color_texture.clone(),
+                            normal_texture: normal_texture.clone(),
+                            roughness_factor: loaded_primitive.material.roughness_factor,
+                            metallic_factor: loaded_primitive.material.metallic_factor,
+                            metallic_roughness_texture: metallic_roughness_texture.clone(),
+                            emissivity: loaded_primitive.material.emissivity,
+                            double_sided: loaded_primitive.material.double_sided,
+                            clearcoat_factor: loaded_primitive.material.clearcoat_factor,
+                            clearcoat_texture,
+                            clearcoat_roughness_factor: loaded_primitive
+                                .material
+                                .clearcoat_roughness_factor,
+                            clearcoat_roughness_texture,
+                            descriptor_set,
+                            descriptor_set_buffer: material_buffer,
+                        })
                     })
-                })
-                .clone();
+                    .clone();
 
-            let mesh = model_map
-                .entry(loaded_primitive.mesh.id())
-                .or_insert_with(|| {
-                    let mesh = loaded_primitive.mesh.clone();
-                    create_mesh(context.clone(), &mut setup_command_buffer, mesh)
-                })
-                .clone();
+                let mesh = self.mesh_map[&loaded_primitive.mesh.id()].clone();
 
-            let raytracing_geometry = raytracing_geometry_map
-                .entry(loaded_primitive.mesh.id())
-                .or_insert_with(|| {
-                    let triangle_count = mesh.num_indices / 3;
-
-                    let geometry_data = AccelerationStructureGeometryData::Triangles {
-                        vertex_format: vk::Format::R32G32B32_SFLOAT,
-                        vertex_data: mesh.vertex_buffer.clone(),
-                        vertex_stride: std::mem::size_of::<crate::scene::Vertex>() as u64,
-                        max_vertex: mesh.num_vertices - 1,
-                        index_type: vk::IndexType::UINT32,
-                        index_data: mesh.index_buffer.clone(),
-                        transform_data: None,
-                        flags: vk::GeometryFlagsKHR::OPAQUE,
-                    };
-                    let mut geometry_build_info = AccelerationStructureBuildGeometryInfoKHR {
-                        ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
-                        flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
-                        mode: vk::BuildAccelerationStructureModeKHR::BUILD,
-                        dst_acceleration_structure: None,
-                        src_acceleration_structure: None,
-                        geometry: Cow::Owned(vec![geometry_data]),
-                        scratch_data: None,
-                    };
+                let raytracing_geometry = context.context_raytracing.is_some().then(|| {
+                    self.raytracing_geometry_map
+                        .entry(loaded_primitive.mesh.id())
+                        .or_insert_with(|| {
+                            let triangle_count = mesh.num_indices / 3;
 
-                    let build_sizes_info = unsafe {
-                        let (g, _a) = geometry_build_info.as_unsafe_vk();
-                        context
-                            .context_raytracing
-                            .acceleration_structure
-                            .get_acceleration_structure_build_sizes(
-                                vk::AccelerationStructureBuildTypeKHR::DEVICE,
-                                &g,
-                                std::slice::from_ref(&triangle_count),
-                            )
-                    };
-                    let blas = Arc::new(AccelerationStructure::new(
-                        context.clone(),
-                        vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
-                        build_sizes_info,
-                    ));
+                            let geometry_data = AccelerationStructureGeometryData::Triangles {
+                                vertex_format: vk::Format::R32G32B32_SFLOAT,
+                                vertex_data: mesh.vertex_buffer.clone(),
+                                vertex_stride: std::mem::size_of::<crate::scene::Vertex>() as u64,
+                                max_vertex: mesh.num_vertices - 1,
+                                index_type: vk::IndexType::UINT32,
+                                index_data: mesh.index_buffer.clone(),
+                                transform_data: None,
+                                flags: vk::GeometryFlagsKHR::OPAQUE,
+                            };
+                            let mut geometry_build_info =
+                                AccelerationStructureBuildGeometryInfoKHR {
+                                    ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+                                    flags: if compact_acceleration_structures {
+                                        vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+                                        | vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION
+                                    } else {
+                                        vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+                                    },
+                                    mode: vk::BuildAccelerationStructureModeKHR::BUILD,
+                                    dst_acceleration_structure: None,
+                                    src_acceleration_structure: None,
+                                    geometry: Cow::Owned(vec![geometry_data]),
+                                    scratch_data: None,
+                                    scratch_offset: 0,
+                                };
 
-                    let scratch_buffer = Arc::new(Buffer::new(
-                        context.clone(),
-                        build_sizes_info.build_scratch_size,
-                        vk::BufferUsageFlags::STORAGE_BUFFER
-                            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-                        vk::MemoryPropertyFlags::DEVICE_LOCAL,
-                    ));
-                    geometry_build_info.dst_acceleration_structure = Some(blas.clone());
-                    geometry_build_info.scratch_data = Some(scratch_buffer);
-
-                    let build_range_info = vk::AccelerationStructureBuildRangeInfoKHR {
-                        primitive_count: triangle_count,
-                        primitive_offset: 0,
-                        first_vertex: 0,
-                        transform_offset: 0,
+                            let build_sizes_info = unsafe {
+                                let (g, _a) = geometry_build_info.as_unsafe_vk();
+                                context
+                                    .raytracing()
+                                    .acceleration_structure
+                                    .get_acceleration_structure_build_sizes(
+                                        vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                                        &g,
+                                        std::slice::from_ref(&triangle_count),
+                                    )
+                            };
+                            let blas = Arc::new(AccelerationStructure::new(
+                                context.clone(),
+                                vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+                                build_sizes_info,
+                            ));
+
+                            geometry_build_info.dst_acceleration_structure = Some(blas.clone());
+
+                            let build_range_info = vk::AccelerationStructureBuildRangeInfoKHR {
+                                primitive_count: triangle_count,
+                                primitive_offset: 0,
+                                first_vertex: 0,
+                                transform_offset: 0,
+                            };
+
+                            new_blases.push(blas.clone());
+                            blas_builds.push((
+                                geometry_build_info,
+                                vec![build_range_info],
+                                build_sizes_info.build_scratch_size,
+                            ));
+
+                            RaytracingGeometry { blas }
+                        })
+                        .clone()
+                });
+                // skinning takes precedence, the skinned vertex shader doesn't apply morph targets
+                let morph_targets_descriptor_set =
+                    match (&model.morph_weights, &mesh.morph_targets_buffer) {
+                        (Some(_), Some(_))
+                            if model.skin.is_some() && mesh.skin_buffer.is_some() =>
+                        {
+                            has_dropped_morph_targets = true;
+                            None
+                        }
+                        (Some(morph_weights), Some(morph_targets_buffer)) => {
+                            Some(DescriptorSet::new(
+                                descriptor_pool,
+                                set_layout_cache.morph_targets(),
+                                vec![
+                                    WriteDescriptorSet::storage_buffer(0, morph_targets_buffer),
+                                    WriteDescriptorSet::storage_buffer(1, &morph_weights.buffer),
+                                ],
+                            ))
+                        }
+                        _ => None,
                     };
 
-                    setup_command_buffer.add_cmd(CmdBuildAccelerationStructures {
-                        build_infos: vec![(geometry_build_info, vec![build_range_info])],
-                    });
+                let primitive = Primitive {
+                    material,
+                    mesh,
+                    raytracing_geometry,
+                    morph_targets_descriptor_set,
+                };
+                model.primitives.push(primitive)
+            }
+            if has_dropped_morph_targets {
+                log::warn!(
+                    "The mesh of node {} is skinned and has morph targets, only skinning it",
+                    model.node
+                );
+            }
+            models.push(model);
+        }
 
-                    RaytracingGeometry { blas }
-                })
-                .clone();
-            let primitive = Primitive {
-                material,
-                mesh,
-                raytracing_geometry,
+        if !blas_builds.is_empty() {
+            setup_command_buffer.add_cmd(CmdBeginDebugLabel {
+                name: "BLAS builds",
+            });
+            setup_command_buffer.add_cmd(build_blases(&mut self.scratch_buffer, blas_builds));
+            setup_command_buffer.add_cmd(CmdEndDebugLabel {});
+        }
+
+        let mut wait_for: Vec<_> = mesh_transfer
+            .iter()
+            .map(|(_, submission)| (submission, mesh_acquire_stages))
+            .collect();
+
+        // The originals are still in use until the copies are done
+        let uncompacted_blases = new_blases;
+        if compact_acceleration_structures && !uncompacted_blases.is_empty() {
+            let compacted_blases;
+            (setup_command_buffer, compacted_blases) = compact_blases(
+                context.clone(),
+                self.queue,
+                self.command_pool.clone(),
+                setup_command_buffer,
+                &wait_for,
+                &uncompacted_blases,
+            );
+            // Already waited for
+            wait_for.clear();
+
+            // Later batches reuse the BLASes of the meshes
+            let use_compacted_blas = |raytracing_geometry: &mut RaytracingGeometry| {
+                if let Some(compacted_blas) = compacted_blases.get(&raytracing_geometry.blas.inner)
+                {
+                    raytracing_geometry.blas = compacted_blas.clone();
+                }
             };
-            model.primitives.push(primitive)
+            self.raytracing_geometry_map
+                .values_mut()
+                .for_each(use_compacted_blas);
+            models[first_new_model..]
+                .iter_mut()
+                .flat_map(|model| &mut model.primitives)
+                .filter_map(|primitive| primitive.raytracing_geometry.as_mut())
+                .for_each(use_compacted_blas);
         }
-        models.push(model);
+
+        setup_command_buffer.add_cmd(EndCommandBuffer {});
+
+        // submit, and only wait for the uploads instead of the whole device
+        let recorded = setup_command_buffer.record(context.clone());
+        let timeline_semaphore = Arc::new(TimelineSemaphore::new(context.clone()));
+        recorded
+            .submit(self.queue, &wait_for, timeline_semaphore)
+            .wait();
+        drop(uncompacted_blases);
     }
 
-    let raytracing_scene = {
-        let mut instances = vec![];
-        for model in &models {
+    /// Needs all the models, since the TLAS and the bindless materials are built once
+    fn create_raytracing_scene(
+        &mut self,
+        models: &[Model],
+        descriptor_pool: &DescriptorPool,
+        set_layout_cache: &DescriptorSetLayoutCache,
+    ) -> Option<RaytracingScene> {
+        if self.context.context_raytracing.is_none() {
+            return None;
+        }
+        // a TLAS and the bindless buffers can't be empty
+        if models.iter().all(|model| model.primitives.is_empty()) {
+            log::info!("The scene has no primitives to raytrace");
+            return None;
+        }
+        let context = self.context.clone();
+
+        let mut command_buffer = CommandBuffer::new(
+            self.command_pool.clone(),
+            CommandBufferAllocateInfo {
+                level: vk::CommandBufferLevel::PRIMARY,
+                count: 1,
+            },
+        );
+        command_buffer.add_cmd(BeginCommandBuffer {
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            inheritance_info: None,
+        });
+
+        let mut bindless_textures = vec![];
+        let mut bindless_texture_indices = HashMap::new();
+        let mut bindless_materials = vec![];
+        let mut bindless_material_indices = HashMap::new();
+        let mut instance_materials = vec![];
+
+        for model in models {
             for primitive in &model.primitives {
-                let transform = to_vk_transform(model.transform.clone());
-                let instance = vk::AccelerationStructureInstanceKHR {
-                    transform,
-                    instance_custom_index_and_mask: vk::Packed24_8::new(0, 0xFF),
-                    instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
-                        0,
-                        // Hmm
-                        vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw() as u8,
-                    ),
-                    acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
-                        device_handle: primitive.raytracing_geometry.blas.device_address,
-                    },
-                };
-                instances.push(instance);
+                let material_index = *bindless_material_indices
+                    .entry(Arc::as_ptr(&primitive.material))
+                    .or_insert_with(|| {
+                        let mut texture_index = |texture: &Texture| -> u32 {
+                            *bindless_texture_indices
+                                .entry((texture.image_view.inner, texture.sampler.inner))
+                                .or_insert_with(|| {
+                                    bindless_textures.push((
+                                        texture.image_view.clone(),
+                                        texture.sampler.clone(),
+                                    ));
+                                    bindless_textures.len() as u32 - 1
+                                })
+                        };
+
+                        bindless_materials.push(shader_types::BindlessMaterial {
+                            base_color_texture: texture_index(
+                                &primitive.material.base_color_texture,
+                            ),
+                            normal_texture: texture_index(&primitive.material.normal_texture),
+                            metallic_roughness_texture: texture_index(
+                                &primitive.material.metallic_roughness_texture,
+                            ),
+                        });
+                        bindless_materials.len() as u32 - 1
+                    });
+
+                // In the same order as the TLAS instances, see tlas_instances
+                instance_materials.push(material_index);
             }
         }
 
-        let instances_vec_size = instances.get_vec_size();
-        let instances_count = instances.len() as u32;
-        let instances_buffer: Arc<Buffer<vk::AccelerationStructureInstanceKHR>> =
-            Arc::new(Buffer::new(
+        // Reuses the scratch memory of the BLAS builds
+        let tlas = build_tlas(
+            context.clone(),
+            &mut command_buffer,
+            &mut self.scratch_buffer,
+            models,
+            None,
+        );
+
+        let bindless = set_layout_cache.bindless().map(|set_layout| {
+            assert!(
+                bindless_textures.len() as u32 <= MAX_BINDLESS_TEXTURES,
+                "Too many textures for the bindless descriptor set"
+            );
+
+            let instance_materials_buffer = Buffer::new(
                 context.clone(),
-                instances_vec_size,
-                vk::BufferUsageFlags::TRANSFER_DST
-                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
-                    | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
-                vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            ));
-        instances_buffer.copy_from_host(&mut setup_command_buffer, &instances, instances_vec_size);
-        // Wait for copy to finish before building acceleration structure
-
-        let acceleration_structure_geometry =
-            AccelerationStructureGeometryData::<(), ()>::Instances {
-                is_array_of_pointers: false,
-                data: instances_buffer,
-                flags: vk::GeometryFlagsKHR::OPAQUE,
-            };
+                instance_materials
+                    .get_vec_size()
+                    .expect("Too many instances for the instance materials buffer"),
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+            instance_materials_buffer.copy_data(&instance_materials);
 
-        let mut geometry_build_info = AccelerationStructureBuildGeometryInfoKHR {
-            ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
-            flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
-            mode: vk::BuildAccelerationStructureModeKHR::BUILD,
-            dst_acceleration_structure: None,
-            src_acceleration_structure: None,
-            geometry: Cow::Owned(vec![acceleration_structure_geometry]),
-            scratch_data: None,
-        };
+            let materials_buffer = Buffer::new(
+                context.clone(),
+                bindless_materials
+                    .get_vec_size()
+                    .expect("Too many materials for the bindless materials buffer"),
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+            materials_buffer.copy_data(&bindless_materials);
 
-        let build_size_info = unsafe {
-            let (g, _a) = geometry_build_info.as_unsafe_vk();
-            context
-                .context_raytracing
-                .acceleration_structure
-                .get_acceleration_structure_build_sizes(
-                    vk::AccelerationStructureBuildTypeKHR::DEVICE,
-                    &g,
-                    std::slice::from_ref(&instances_count),
-                )
-        };
+            let mut writes = vec![
+                WriteDescriptorSet::storage_buffer(0, &instance_materials_buffer),
+                WriteDescriptorSet::storage_buffer(1, &materials_buffer),
+            ];
+            // Writing an empty array is not allowed
+            if !bindless_textures.is_empty() {
+                writes.push(WriteDescriptorSet::image_view_sampler_array(
+                    2,
+                    &bindless_textures,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ));
+            }
+
+            let descriptor_set = DescriptorSet::new_with_variable_descriptor_count(
+                descriptor_pool,
+                set_layout,
+                bindless_textures.len() as u32,
+                writes,
+            );
+
+            BindlessScene {
+                descriptor_set,
+                textures: bindless_textures,
+                _instance_materials_buffer: instance_materials_buffer,
+                _materials_buffer: materials_buffer,
+            }
+        });
+
+        command_buffer.add_cmd(EndCommandBuffer {});
+        let recorded = command_buffer.record(context.clone());
+        let timeline_semaphore = Arc::new(TimelineSemaphore::new(context.clone()));
+        recorded.submit(self.queue, &[], timeline_semaphore).wait();
+
+        Some(RaytracingScene {
+            tlas: tlas,
+            is_tlas_outdated: false,
+            bindless,
+        })
+    }
+}
+
+/// Bindings 1 to 5 of the material descriptor set
+fn material_texture_writes(
+    textures: [&Texture; 5],
+    sampler: impl Fn(&Texture) -> Arc<Sampler>,
+) -> Vec<WriteDescriptorSet> {
+    textures
+        .into_iter()
+        .zip(1..)
+        .map(|(texture, binding)| {
+            WriteDescriptorSet::image_view_sampler(
+                binding,
+                texture.image_view.clone(),
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                sampler(texture),
+            )
+        })
+        .collect()
+}
 
-        let tlas = Arc::new(AccelerationStructure::new(
+/// Recreates the samplers with the new overrides, and points the descriptor sets at them.
+/// The descriptor sets must not be in use.
+pub fn apply_sampler_overrides(scene: &mut Scene, context: Arc<Context>) {
+    let mut samplers = HashMap::new();
+    for scene_sampler in &mut scene.samplers {
+        let sampler = create_sampler(
             context.clone(),
-            vk::AccelerationStructureTypeKHR::TOP_LEVEL,
-            build_size_info,
+            &scene_sampler.create_info,
+            scene.sampler_overrides,
+        );
+        samplers.insert(scene_sampler.sampler.inner, sampler.clone());
+        scene_sampler.overridden = Some(sampler);
+    }
+    let sampler = |texture: &Texture| samplers[&texture.sampler.inner].clone();
+
+    let mut materials = HashSet::new();
+    for primitive in scene.models.iter().flat_map(|model| &model.primitives) {
+        let material = &primitive.material;
+        if !materials.insert(Arc::as_ptr(material)) {
+            continue;
+        }
+        material.descriptor_set.update(material_texture_writes(
+            [
+                &material.base_color_texture,
+                &material.normal_texture,
+                &material.metallic_roughness_texture,
+                &material.clearcoat_texture,
+                &material.clearcoat_roughness_texture,
+            ],
+            sampler,
         ));
+    }
+
+    if let Some(bindless) = scene
+        .raytracing_scene
+        .as_ref()
+        .and_then(|raytracing_scene| raytracing_scene.bindless.as_ref())
+        .filter(|bindless| !bindless.textures.is_empty())
+    {
+        let textures: Vec<_> = bindless
+            .textures
+            .iter()
+            .map(|(image_view, sampler)| (image_view.clone(), samplers[&sampler.inner].clone()))
+            .collect();
+        bindless
+            .descriptor_set
+            .update(vec![WriteDescriptorSet::image_view_sampler_array(
+                2,
+                &textures,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )]);
+    }
+    scene.are_samplers_outdated = false;
+}
+
+/// Gives a primitive its own copy of its material, so that editing it leaves the other primitives alone.
+/// The old material stays in use by them, so this can happen while it is being rendered.
+pub fn fork_material(
+    scene: &mut Scene,
+    model_index: usize,
+    primitive_index: usize,
+    context: Arc<Context>,
+    descriptor_pool: &DescriptorPool,
+    set_layout_cache: &DescriptorSetLayoutCache,
+) {
+    // the textures keep the samplers that they were uploaded with, the overrides live in the scene
+    let samplers: HashMap<_, _> = scene
+        .samplers
+        .iter()
+        .map(|scene_sampler| {
+            let sampler = scene_sampler
+                .overridden
+                .clone()
+                .unwrap_or_else(|| scene_sampler.sampler.clone());
+            (scene_sampler.sampler.inner, sampler)
+        })
+        .collect();
+    let sampler = |texture: &Texture| {
+        samplers
+            .get(&texture.sampler.inner)
+            .cloned()
+            .unwrap_or_else(|| texture.sampler.clone())
+    };
+
+    let primitive = &mut scene.models[model_index].primitives[primitive_index];
+    let material = &primitive.material;
+
+    let material_buffer = Buffer::new(
+        context.clone(),
+        shader_types::Material::std140_size_static() as u64,
+        vk::BufferUsageFlags::UNIFORM_BUFFER,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    );
+    material_buffer.copy_data(&material.read_factors().as_std140());
 
-        let scratch_buffer = Arc::new(Buffer::new(
+    let mut writes = vec![WriteDescriptorSet::buffer(0, &material_buffer)];
+    writes.extend(material_texture_writes(
+        [
+            &material.base_color_texture,
+            &material.normal_texture,
+            &material.metallic_roughness_texture,
+            &material.clearcoat_texture,
+            &material.clearcoat_roughness_texture,
+        ],
+        sampler,
+    ));
+    let descriptor_set = DescriptorSet::new(descriptor_pool, set_layout_cache.material(), writes);
+
+    primitive.material = Arc::new(Material {
+        source_index: material.source_index,
+        base_color: material.base_color,
+        base_color_texture: material.base_color_texture.clone(),
+        normal_texture: material.normal_texture.clone(),
+        roughness_factor: material.roughness_factor,
+        metallic_factor: material.metallic_factor,
+        metallic_roughness_texture: material.metallic_roughness_texture.clone(),
+        emissivity: material.emissivity,
+        double_sided: material.double_sided,
+        clearcoat_factor: material.clearcoat_factor,
+        clearcoat_texture: material.clearcoat_texture.clone(),
+        clearcoat_roughness_factor: material.clearcoat_roughness_factor,
+        clearcoat_roughness_texture: material.clearcoat_roughness_texture.clone(),
+        descriptor_set,
+        descriptor_set_buffer: material_buffer,
+    });
+}
+
+/// Rebuilds the TLAS after the instances changed, for example when a model stops casting shadows.
+/// The TLAS must not be in use.
+pub fn rebuild_tlas(
+    scene: &mut Scene,
+    context: Arc<Context>,
+    queue: vk::Queue,
+    command_pool: CommandPool,
+) {
+    let Some(raytracing_scene) = &mut scene.raytracing_scene else {
+        return;
+    };
+
+    let mut command_buffer = CommandBuffer::new(
+        command_pool,
+        CommandBufferAllocateInfo {
+            level: vk::CommandBufferLevel::PRIMARY,
+            count: 1,
+        },
+    );
+    command_buffer.add_cmd(BeginCommandBuffer {
+        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        inheritance_info: None,
+    });
+    let mut scratch_buffer = ScratchBuffer::new(context.clone());
+    // Same instance count, so it fits into the old TLAS, and the descriptor sets stay valid
+    build_tlas(
+        context.clone(),
+        &mut command_buffer,
+        &mut scratch_buffer,
+        &scene.models,
+        Some(raytracing_scene.tlas.clone()),
+    );
+    command_buffer.add_cmd(EndCommandBuffer {});
+
+    let recorded = command_buffer.record(context.clone());
+    let timeline_semaphore = Arc::new(TimelineSemaphore::new(context.clone()));
+    recorded.submit(queue, &[], timeline_semaphore).wait();
+    raytracing_scene.is_tlas_outdated = false;
+}
+
+/// One instance per primitive, their custom index is the index into the instance materials
+fn tlas_instances(models: &[Model]) -> Vec<vk::AccelerationStructureInstanceKHR> {
+    let mut instances = vec![];
+    for model in models {
+        let mask = if model.casts_shadows {
+            0xFF
+        } else {
+            0xFF & !RaytracingScene::SHADOW_CASTER_MASK
+        };
+        for primitive in &model.primitives {
+            // The custom index is only 24 bits wide
+            let instance_index = instances.len() as u32;
+            assert!(instance_index < (1 << 24), "Too many raytracing instances");
+
+            let transform = to_vk_transform(model.transform.clone());
+            let instance = vk::AccelerationStructureInstanceKHR {
+                transform,
+                instance_custom_index_and_mask: vk::Packed24_8::new(instance_index, mask),
+                instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
+                    0,
+                    // Hmm
+                    vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw() as u8,
+                ),
+                acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                    device_handle: primitive
+                        .raytracing_geometry
+                        .as_ref()
+                        .expect("Raytracing geometry should exist for every primitive")
+                        .blas
+                        .device_address,
+                },
+            };
+            instances.push(instance);
+        }
+    }
+    instances
+}
+
+/// Builds into the given TLAS, or into a new one
+fn build_tlas<'a>(
+    context: Arc<Context>,
+    command_buffer: &mut CommandBuffer<'a>,
+    scratch_buffer: &mut ScratchBuffer,
+    models: &[Model],
+    tlas: Option<Arc<AccelerationStructure>>,
+) -> Arc<AccelerationStructure> {
+    let instances = tlas_instances(models);
+    let instances_vec_size = instances
+        .get_vec_size()
+        .expect("Too many instances for the TLAS instance buffer");
+    let instances_count = instances.len() as u32;
+    let instances_buffer: Arc<Buffer<vk::AccelerationStructureInstanceKHR>> =
+        Arc::new(Buffer::new(
             context.clone(),
-            build_size_info.build_scratch_size,
-            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            instances_vec_size,
+            vk::BufferUsageFlags::TRANSFER_DST
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         ));
+    instances_buffer.copy_from_host(command_buffer, &instances, instances_vec_size);
+    // Wait for copy to finish before building acceleration structure
 
-        geometry_build_info.dst_acceleration_structure = Some(tlas.clone());
-        geometry_build_info.scratch_data = Some(scratch_buffer);
+    // The TLAS build does not know which BLASes the instances reference
+    command_buffer.add_cmd(CmdPipelineBarrier {
+        dependency_flags: vk::DependencyFlags::empty(),
+        memory_barriers: vec![MemoryBarrier {
+            src_stage_mask: vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR,
+            src_access_mask: vk::AccessFlags2::ACCELERATION_STRUCTURE_WRITE_KHR,
+            dst_stage_mask: vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR,
+            dst_access_mask: vk::AccessFlags2::ACCELERATION_STRUCTURE_READ_KHR,
+        }],
+        buffer_memory_barriers: vec![],
+        image_memory_barriers: vec![],
+    });
 
-        let build_range_info = vk::AccelerationStructureBuildRangeInfoKHR {
-            primitive_count: instances_count,
-            primitive_offset: 0,
-            first_vertex: 0,
-            transform_offset: 0,
-        };
+    let acceleration_structure_geometry = AccelerationStructureGeometryData::<(), ()>::Instances {
+        is_array_of_pointers: false,
+        data: instances_buffer,
+        flags: vk::GeometryFlagsKHR::OPAQUE,
+    };
 
-        setup_command_buffer.add_cmd(CmdBuildAccelerationStructures {
-            build_infos: vec![(geometry_build_info, vec![build_range_info])],
-        });
+    let mut geometry_build_info = AccelerationStructureBuildGeometryInfoKHR {
+        ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+        flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
+        mode: vk::BuildAccelerationStructureModeKHR::BUILD,
+        dst_acceleration_structure: None,
+        src_acceleration_structure: None,
+        geometry: Cow::Owned(vec![acceleration_structure_geometry]),
+        scratch_data: None,
+        scratch_offset: 0,
+    };
+
+    let build_size_info = unsafe {
+        let (g, _a) = geometry_build_info.as_unsafe_vk();
+        context
+            .raytracing()
+            .acceleration_structure
+            .get_acceleration_structure_build_sizes(
+                vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                &g,
+                std::slice::from_ref(&instances_count),
+            )
+    };
+
+    let tlas = tlas.unwrap_or_else(|| {
+        Arc::new(AccelerationStructure::new(
+            context.clone(),
+            vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+            build_size_info,
+        ))
+    });
+    assert!(
+        build_size_info.acceleration_structure_size <= tlas.size,
+        "The TLAS is too small for the instances"
+    );
+
+    let (scratch_buffer, scratch_offsets) =
+        scratch_buffer.allocate(&[build_size_info.build_scratch_size]);
+
+    geometry_build_info.dst_acceleration_structure = Some(tlas.clone());
+    geometry_build_info.scratch_data = Some(scratch_buffer);
+    geometry_build_info.scratch_offset = scratch_offsets[0];
+
+    let build_range_info = vk::AccelerationStructureBuildRangeInfoKHR {
+        primitive_count: instances_count,
+        primitive_offset: 0,
+        first_vertex: 0,
+        transform_offset: 0,
+    };
+
+    command_buffer.add_cmd(CmdBeginDebugLabel { name: "TLAS build" });
+    command_buffer.add_cmd(CmdBuildAccelerationStructures {
+        build_infos: vec![(geometry_build_info, vec![build_range_info])],
+    });
+    command_buffer.add_cmd(CmdEndDebugLabel {});
 
-        RaytracingScene { tlas: tlas }
+    tlas
+}
+
+/// One build command for all the BLASes, sharing one scratch buffer
+fn build_blases<'a>(
+    scratch_buffer: &mut ScratchBuffer,
+    blas_builds: Vec<(
+        AccelerationStructureBuildGeometryInfoKHR<'a, Vertex, u32>,
+        Vec<vk::AccelerationStructureBuildRangeInfoKHR>,
+        vk::DeviceSize,
+    )>,
+) -> CmdBuildAccelerationStructures<'a, Vertex, u32> {
+    let build_scratch_sizes: Vec<_> = blas_builds
+        .iter()
+        .map(|(_, _, build_scratch_size)| *build_scratch_size)
+        .collect();
+    let (scratch_buffer, scratch_offsets) = scratch_buffer.allocate(&build_scratch_sizes);
+
+    CmdBuildAccelerationStructures {
+        build_infos: blas_builds
+            .into_iter()
+            .zip(scratch_offsets)
+            .map(
+                |((mut geometry_build_info, build_range_infos, _), scratch_offset)| {
+                    geometry_build_info.scratch_data = Some(scratch_buffer.clone());
+                    geometry_build_info.scratch_offset = scratch_offset;
+                    (geometry_build_info, build_range_infos)
+                },
+            )
+            .collect(),
+    }
+}
+
+/// Submits the command buffer that builds the BLASes, since the compacted sizes have to be read back.
+/// Returns a new command buffer with the copies, and the compacted BLASes by their originals.
+fn compact_blases<'a>(
+    context: Arc<Context>,
+    queue: vk::Queue,
+    command_pool: CommandPool,
+    mut command_buffer: CommandBuffer<'a>,
+    wait_for: &[(&TimelineSubmission, PipelineStageFlags2)],
+    blases: &[Arc<AccelerationStructure>],
+) -> (
+    CommandBuffer<'a>,
+    HashMap<vk::AccelerationStructureKHR, Arc<AccelerationStructure>>,
+) {
+    let device = &context.device;
+    let query_pool = {
+        let create_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR)
+            .query_count(blases.len() as u32);
+        unsafe { device.create_query_pool(&create_info, None) }
+            .expect("Could not create query pool")
     };
 
-    setup_command_buffer.add_cmd(EndCommandBuffer {});
+    command_buffer.add_cmd(CmdWriteAccelerationStructuresProperties {
+        acceleration_structures: blases.to_vec(),
+        query_type: vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR,
+        query_pool,
+    });
+    command_buffer.add_cmd(EndCommandBuffer {});
+    let recorded = command_buffer.record(context.clone());
+    let timeline_semaphore = Arc::new(TimelineSemaphore::new(context.clone()));
+    recorded.submit(queue, wait_for, timeline_semaphore).wait();
 
-    // submit
-    let recorded = setup_command_buffer.record(context.clone());
-    recorded.submit(queue);
+    let mut compacted_sizes = vec![0u64; blases.len()];
+    unsafe {
+        device.get_query_pool_results(
+            query_pool,
+            0,
+            blases.len() as u32,
+            &mut compacted_sizes,
+            vk::QueryResultFlags::WAIT | vk::QueryResultFlags::TYPE_64,
+        )
+    }
+    .expect("Could not get compacted acceleration structure sizes");
+    unsafe { device.destroy_query_pool(query_pool, None) };
 
-    context.wait_idle();
+    let mut command_buffer = CommandBuffer::new(
+        command_pool,
+        CommandBufferAllocateInfo {
+            level: vk::CommandBufferLevel::PRIMARY,
+            count: 1,
+        },
+    );
+    command_buffer.add_cmd(BeginCommandBuffer {
+        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        inheritance_info: None,
+    });
 
-    Scene {
-        models,
-        raytracing_scene,
+    let mut compacted_blases = HashMap::new();
+    for (blas, &compacted_size) in blases.iter().zip(compacted_sizes.iter()) {
+        let compacted_blas = Arc::new(AccelerationStructure::with_size(
+            context.clone(),
+            vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+            compacted_size,
+        ));
+        command_buffer.add_cmd(CmdCopyAccelerationStructure {
+            src: blas.clone(),
+            dst: compacted_blas.clone(),
+            mode: vk::CopyAccelerationStructureModeKHR::COMPACT,
+        });
+        compacted_blases.insert(blas.inner, compacted_blas);
     }
+
+    let original_size: u64 = blases.iter().map(|blas| blas.size).sum();
+    let compacted_size: u64 = compacted_sizes.iter().sum();
+    log::info!(
+        "Compacted {} BLASes from {:.2} MiB to {:.2} MiB",
+        blases.len(),
+        original_size as f64 / (1024.0 * 1024.0),
+        compacted_size as f64 / (1024.0 * 1024.0)
+    );
+
+    (command_buffer, compacted_blases)
 }
 
 fn create_mesh<'a, 'cmd>(
@@ -428,63 +1451,183 @@ fn create_mesh<'a, 'cmd>(
 where
     'a: 'cmd,
 {
+    // Only the acceleration structure builds read the mesh through its device address
+    let raytracing_usage = if context.context_raytracing.is_some() {
+        vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+            | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+    } else {
+        vk::BufferUsageFlags::empty()
+    };
+
+    assert!(
+        mesh.vertices
+            .iter()
+            .all(Vertex::has_valid_normal_and_tangent),
+        "Mesh {:?} has normals or tangents that are not finite and normalized",
+        mesh.id
+    );
+
     let vertex_buffer = {
+        let size = mesh
+            .vertices
+            .get_vec_size()
+            .expect("Too many vertices for a vertex buffer");
         let buffer = Arc::new(Buffer::new(
             context.clone(),
-            mesh.vertices.get_vec_size(),
+            size,
             vk::BufferUsageFlags::TRANSFER_DST
                 | vk::BufferUsageFlags::VERTEX_BUFFER
-                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
-                | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
+                | raytracing_usage,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         ));
-        buffer.copy_from_host(
-            &mut setup_command_buffer,
-            &mesh.vertices,
-            mesh.vertices.get_vec_size(),
-        );
+        buffer.copy_from_host(&mut setup_command_buffer, &mesh.vertices, size);
         buffer
     };
 
+    // LOD0 comes first, so that the acceleration structure builds only see the full resolution mesh
+    let mut indices = mesh.indices.clone();
+    let mut lods = vec![MeshLod {
+        first_index: 0,
+        num_indices: mesh.indices.len() as u32,
+    }];
+    for lod in mesh.lods.iter() {
+        lods.push(MeshLod {
+            first_index: indices.len() as u32,
+            num_indices: lod.len() as u32,
+        });
+        indices.extend_from_slice(lod);
+    }
+
+    let skin_buffer = mesh.skin_vertices.as_ref().map(|skin_vertices| {
+        let size = skin_vertices
+            .get_vec_size()
+            .expect("Too many vertices for a skin buffer");
+        let buffer = Arc::new(Buffer::new(
+            context.clone(),
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        ));
+        buffer.copy_from_host(&mut setup_command_buffer, skin_vertices, size);
+        buffer
+    });
+
+    let morph_targets_buffer = (!mesh.morph_targets.is_empty()).then(|| {
+        let morph_vertex_deltas: Vec<_> = (0..mesh.vertices.len())
+            .flat_map(|vertex| {
+                mesh.morph_targets
+                    .iter()
+                    .map(move |morph_target| MorphVertexDelta {
+                        position: Vec3::from(morph_target.position_deltas[vertex])
+                            .into_homogeneous_vector()
+                            .into(),
+                        normal: Vec3::from(morph_target.normal_deltas[vertex])
+                            .into_homogeneous_vector()
+                            .into(),
+                    })
+            })
+            .collect();
+        let size = morph_vertex_deltas
+            .get_vec_size()
+            .expect("Too many morph targets for a morph targets buffer");
+        let buffer = Arc::new(Buffer::new(
+            context.clone(),
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        ));
+        buffer.copy_from_host(&mut setup_command_buffer, &morph_vertex_deltas, size);
+        buffer
+    });
+
     let index_buffer = {
+        let size = indices
+            .get_vec_size()
+            .expect("Too many indices for an index buffer");
         let buffer = Arc::new(Buffer::new(
             context.clone(),
-            mesh.indices.get_vec_size(),
+            size,
             vk::BufferUsageFlags::TRANSFER_DST
                 | vk::BufferUsageFlags::INDEX_BUFFER
-                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
-                | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
+                | raytracing_usage,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         ));
-        buffer.copy_from_host(
-            &mut setup_command_buffer,
-            &mesh.indices,
-            mesh.indices.get_vec_size(),
-        );
+        buffer.copy_from_host(&mut setup_command_buffer, &indices, size);
         buffer
     };
 
     Arc::new(Mesh {
         index_buffer,
         vertex_buffer,
+        skin_buffer,
+        morph_targets_buffer,
         num_indices: mesh.indices.len() as u32,
         num_vertices: mesh.vertices.len() as u32,
+        lods,
+        bounds: mesh.bounds,
     })
 }
 
+fn create_skin(
+    context: Arc<Context>,
+    descriptor_pool: &DescriptorPool,
+    set_layout_cache: &DescriptorSetLayoutCache,
+    skin: &loader::LoadedSkin,
+) -> Skin {
+    let joint_matrices_buffer = Buffer::new(
+        context.clone(),
+        (std::mem::size_of::<Mat4>() * skin.joints.len()) as u64,
+        vk::BufferUsageFlags::STORAGE_BUFFER,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    );
+
+    let descriptor_set = DescriptorSet::new(
+        descriptor_pool,
+        set_layout_cache.skin(),
+        vec![WriteDescriptorSet::storage_buffer(
+            0,
+            &joint_matrices_buffer,
+        )],
+    );
+
+    Skin {
+        joints: skin.joints.clone(),
+        inverse_bind_matrices: skin.inverse_bind_matrices.clone(),
+        joint_matrices_buffer,
+        descriptor_set,
+    }
+}
+
+fn create_morph_weights(context: Arc<Context>, weights: &[f32]) -> MorphWeights {
+    let buffer = Buffer::new(
+        context,
+        std::mem::size_of_val(weights) as u64,
+        vk::BufferUsageFlags::STORAGE_BUFFER,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    );
+    buffer.copy_data(weights);
+
+    MorphWeights {
+        weights: weights.to_vec(),
+        buffer,
+    }
+}
+
 fn load_texture<'a>(
     context: Arc<Context>,
     setup_command_buffer: &mut CommandBuffer<'a>,
     loaded_texture: Option<&LoadedTexture>,
-    texture_map: &mut HashMap<loader::AssetId, Arc<ImageView>>,
-    sampler_map: &mut HashMap<loader::AssetId, Arc<Sampler>>,
-    default_base_color_image_view: Arc<ImageView>,
+    texture_map: &mut HashMap<loader::AssetId, Arc<Image>>,
+    sampler_map: &mut HashMap<loader::AssetId, SceneSampler>,
+    sampler_overrides: SamplerOverrides,
+    image_view_cache: &mut ImageViewCache,
+    default_image_view: Arc<ImageView>,
     default_sampler: Arc<Sampler>,
     create_mipmapping: bool,
 ) -> Texture {
     loaded_texture
         .map(|v| {
-            let image_view = texture_map
+            let image = texture_map
                 .entry(v.image.id())
                 .or_insert_with(|| {
                     create_image(
@@ -495,9 +1638,18 @@ fn load_texture<'a>(
                     )
                 })
                 .clone();
+            let image_view = image_view_cache.get_default(&image, vk::ImageAspectFlags::COLOR);
             let sampler = sampler_map
                 .entry(v.sampler.id())
-                .or_insert_with(|| create_sampler(v.sampler.clone(), context.clone()))
+                .or_insert_with(|| {
+                    let create_info = sampler_create_info(&v.sampler);
+                    SceneSampler {
+                        sampler: create_sampler(context.clone(), &create_info, sampler_overrides),
+                        create_info,
+                        overridden: None,
+                    }
+                })
+                .sampler
                 .clone();
             Texture {
                 image_view,
@@ -505,12 +1657,13 @@ fn load_texture<'a>(
             }
         })
         .unwrap_or_else(|| Texture {
-            image_view: default_base_color_image_view.clone(),
+            image_view: default_image_view.clone(),
             sampler: default_sampler.clone(),
         })
 }
 
-fn create_sampler(loaded_sampler: Arc<LoadedSampler>, context: Arc<Context>) -> Arc<Sampler> {
+/// Without the anisotropy, which comes from the sampler overrides
+fn sampler_create_info(loaded_sampler: &LoadedSampler) -> vk::SamplerCreateInfo {
     fn convert_filter(filter: &loader::Filter) -> vk::Filter {
         match filter {
             loader::Filter::Nearest => vk::Filter::NEAREST,
@@ -526,12 +1679,10 @@ fn create_sampler(loaded_sampler: Arc<LoadedSampler>, context: Arc<Context>) ->
         }
     }
 
-    let sampler_info = vk::SamplerCreateInfo::builder()
+    vk::SamplerCreateInfo::builder()
         .flags(vk::SamplerCreateFlags::empty())
         .mag_filter(convert_filter(&loaded_sampler.sampler_info.mag_filter))
         .min_filter(convert_filter(&loaded_sampler.sampler_info.min_filter))
-        .anisotropy_enable(true)
-        .max_anisotropy(16.0)
         .mipmap_mode(match &loaded_sampler.sampler_info.mipmap_mode {
             loader::MipmapMode::Nearest => vk::SamplerMipmapMode::NEAREST,
             loader::MipmapMode::Linear => vk::SamplerMipmapMode::LINEAR,
@@ -547,8 +1698,22 @@ fn create_sampler(loaded_sampler: Arc<LoadedSampler>, context: Arc<Context>) ->
         ))
         .min_lod(0.0)
         .max_lod(vk::LOD_CLAMP_NONE)
-        .build();
-    let sampler = unsafe { context.device.create_sampler(&sampler_info, None) }
+        .build()
+}
+
+fn create_sampler(
+    context: Arc<Context>,
+    create_info: &vk::SamplerCreateInfo,
+    sampler_overrides: SamplerOverrides,
+) -> Arc<Sampler> {
+    let create_info = sampler_overrides.apply(
+        create_info,
+        context
+            .physical_device_properties
+            .limits
+            .max_sampler_anisotropy,
+    );
+    let sampler = unsafe { context.device.create_sampler(&create_info, None) }
         .expect("Could not create sampler");
     Arc::new(Sampler::new(sampler, context.clone()))
 }
@@ -558,7 +1723,7 @@ fn create_image(
     context: Arc<Context>,
     setup_command_buffer: &mut CommandBuffer,
     create_mipmapping: bool,
-) -> Arc<ImageView> {
+) -> Arc<Image> {
     fn convert_format(format: (loader::ImageFormat, loader::ColorSpace)) -> vk::Format {
         match format {
             (loader::ImageFormat::R8_UNORM, loader::ColorSpace::Linear) => vk::Format::R8_UNORM,
@@ -630,20 +1795,17 @@ fn create_image(
         .build();
     let image = Arc::new(Image::new(context.clone(), &image_info));
 
+    let pixels = loaded_image.data.pixels();
     let image_data_buffer: Buffer<u8> = Buffer::new(
         context.clone(),
-        loaded_image.data.bytes.len() as u64,
+        pixels.len() as u64,
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
     );
-    image_data_buffer.copy_data(&loaded_image.data.bytes);
+    image_data_buffer.copy_data(pixels.as_ref());
     image.copy_from_buffer_for_texture(setup_command_buffer, image_data_buffer.into());
 
-    Arc::new(ImageView::new_default(
-        context.clone(),
-        image,
-        vk::ImageAspectFlags::COLOR,
-    ))
+    image
 }
 
 fn to_vk_transform(transform: Transform) -> vk::TransformMatrixKHR {
@@ -655,12 +1817,6 @@ fn to_vk_transform(transform: Transform) -> vk::TransformMatrixKHR {
     }
 }
 
-trait GetVecSize {
-    fn get_vec_size(&self) -> u64;
-}
-
-impl<T> GetVecSize for Vec<T> {
-    fn get_vec_size(&self) -> u64 {
-        std::mem::size_of::<T>() as u64 * self.len() as u64
-    }
+fn format_bytes(bytes: vk::DeviceSize) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
 }
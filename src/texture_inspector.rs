@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use egui_winit_ash_integration::{AllocatorTrait, Integration};
+
+use crate::scene::{Scene, Texture};
+
+/// Lists the textures of the scene, and shows the selected one
+pub struct TextureInspector {
+    /// With the material and the usage, the first material wins when a texture is shared
+    textures: Vec<(String, Texture)>,
+    selected: Option<usize>,
+    /// Only the selected texture is registered with egui
+    texture_id: Option<egui::TextureId>,
+}
+
+impl TextureInspector {
+    const PREVIEW_SIZE: f32 = 256.0;
+
+    pub fn new(scene: &Scene) -> Self {
+        let mut materials = HashSet::new();
+        let mut image_views = HashSet::new();
+        let mut textures = vec![];
+        for material in scene
+            .models
+            .iter()
+            .flat_map(|model| model.primitives.iter())
+            .map(|primitive| &primitive.material)
+        {
+            if !materials.insert(Arc::as_ptr(material)) {
+                continue;
+            }
+            let material_index = materials.len() - 1;
+            for (usage, texture) in [
+                ("base color", &material.base_color_texture),
+                ("normal", &material.normal_texture),
+                ("metallic roughness", &material.metallic_roughness_texture),
+                ("clearcoat", &material.clearcoat_texture),
+                ("clearcoat roughness", &material.clearcoat_roughness_texture),
+            ] {
+                if image_views.insert(texture.image_view.inner) {
+                    textures.push((
+                        format!("Material {} {}", material_index, usage),
+                        texture.clone(),
+                    ));
+                }
+            }
+        }
+
+        Self {
+            textures,
+            selected: None,
+            texture_id: None,
+        }
+    }
+
+    /// Has to be called before the textures get destroyed, for example when switching scenes
+    pub fn unregister<A: AllocatorTrait>(&mut self, egui_integration: &mut Integration<A>) {
+        if let Some(texture_id) = self.texture_id.take() {
+            egui_integration.unregister_user_texture(texture_id);
+        }
+    }
+
+    pub fn render_ui<A: AllocatorTrait>(&mut self, egui_integration: &mut Integration<A>) {
+        egui::Window::new("Textures")
+            .default_open(false)
+            .resizable(true)
+            .show(&egui_integration.context(), |ui| {
+                let mut selected = self.selected;
+                egui::ComboBox::from_label("Texture")
+                    .selected_text(
+                        selected
+                            .map(|index| self.textures[index].0.as_str())
+                            .unwrap_or("None"),
+                    )
+                    .show_ui(ui, |ui| {
+                        for (index, (name, _)) in self.textures.iter().enumerate() {
+                            ui.selectable_value(&mut selected, Some(index), name);
+                        }
+                    });
+                if selected != self.selected {
+                    self.unregister(egui_integration);
+                    self.selected = selected;
+                }
+
+                let Some((_, texture)) = self.selected.map(|index| &self.textures[index]) else {
+                    return;
+                };
+                let texture_id = *self.texture_id.get_or_insert_with(|| {
+                    egui_integration
+                        .register_user_texture(texture.image_view.inner, texture.sampler.inner)
+                });
+
+                let image = &texture.image_view.image;
+                ui.label(format!(
+                    "{}x{}, {:?}, {} mip levels",
+                    image.extent.width, image.extent.height, image.format, image.mip_levels
+                ));
+                let size = egui::vec2(image.extent.width as f32, image.extent.height as f32);
+                ui.image((
+                    texture_id,
+                    size * (TextureInspector::PREVIEW_SIZE / size.max_elem()),
+                ));
+            });
+    }
+}
@@ -13,6 +13,18 @@ macro_rules! offset_of {
     }};
 }
 
+/// Size in bytes, for creating buffers and copying the data into them.
+/// None when the size doesn't fit into a `vk::DeviceSize`.
+pub trait GetVecSize {
+    fn get_vec_size(&self) -> Option<vk::DeviceSize>;
+}
+
+impl<T> GetVecSize for Vec<T> {
+    fn get_vec_size(&self) -> Option<vk::DeviceSize> {
+        (std::mem::size_of::<T>() as vk::DeviceSize).checked_mul(self.len() as vk::DeviceSize)
+    }
+}
+
 pub fn aligned_size(value: u32, alignment: u32) -> u32 {
     assert!(alignment.is_power_of_two());
     (value + alignment - 1) & !(alignment - 1)
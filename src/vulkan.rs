@@ -3,11 +3,17 @@ pub mod buffer;
 pub mod command_buffer;
 pub mod command_pool;
 pub mod context;
+pub mod debug_label;
+pub mod descriptor_pool;
 pub mod descriptor_set;
 pub mod image;
 pub mod image_view;
+pub mod image_view_cache;
+pub mod memory_stats;
 pub mod sampler;
 pub mod shader_create_info;
 pub mod swapchain;
 pub mod sync_manager;
+pub mod timeline_semaphore;
+pub mod validation;
 pub mod window_settings;
@@ -9,6 +9,8 @@ pub struct AccelerationStructure {
     pub context: Arc<Context>,
     pub buffer: Buffer<u8>,
     pub device_address: vk::DeviceAddress,
+    /// In bytes
+    pub size: vk::DeviceSize,
 }
 
 impl AccelerationStructure {
@@ -17,10 +19,23 @@ impl AccelerationStructure {
         context: Arc<Context>,
         structure_type: vk::AccelerationStructureTypeKHR,
         build_size_info: vk::AccelerationStructureBuildSizesInfoKHR,
+    ) -> Self {
+        AccelerationStructure::with_size(
+            context,
+            structure_type,
+            build_size_info.acceleration_structure_size,
+        )
+    }
+
+    /// For example for the destination of a compacting copy
+    pub fn with_size(
+        context: Arc<Context>,
+        structure_type: vk::AccelerationStructureTypeKHR,
+        size: vk::DeviceSize,
     ) -> Self {
         let buffer: Buffer<u8> = Buffer::new(
             context.clone(),
-            build_size_info.acceleration_structure_size,
+            size,
             vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
                 | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
@@ -28,12 +43,12 @@ impl AccelerationStructure {
 
         let create_info = vk::AccelerationStructureCreateInfoKHR::builder()
             .buffer(*buffer)
-            .size(build_size_info.acceleration_structure_size)
+            .size(size)
             .ty(structure_type);
 
         let inner = unsafe {
             context
-                .context_raytracing
+                .raytracing()
                 .acceleration_structure
                 .create_acceleration_structure(&create_info, None)
         }
@@ -46,7 +61,7 @@ impl AccelerationStructure {
 
             unsafe {
                 context
-                    .context_raytracing
+                    .raytracing()
                     .acceleration_structure
                     .get_acceleration_structure_device_address(
                         &acceleration_structure_device_address_info,
@@ -59,15 +74,78 @@ impl AccelerationStructure {
             context,
             buffer,
             device_address,
+            size,
         }
     }
 }
 
+/// Sub-allocates scratch memory for acceleration structure builds.
+/// The buffer is reused, and only grows when a bigger one is needed.
+/// Builds that reuse it are serialized by the barriers of the sync manager.
+pub struct ScratchBuffer {
+    context: Arc<Context>,
+    buffer: Option<Arc<Buffer<u8>>>,
+    /// In bytes, the device address of the buffer itself might not be aligned
+    base_offset: vk::DeviceSize,
+    /// In bytes, starting at the base offset
+    capacity: vk::DeviceSize,
+}
+
+impl ScratchBuffer {
+    pub fn new(context: Arc<Context>) -> Self {
+        Self {
+            context,
+            buffer: None,
+            base_offset: 0,
+            capacity: 0,
+        }
+    }
+
+    /// Returns the buffer, and an aligned offset into it for every build.
+    /// The ranges don't overlap, so the builds can run at the same time.
+    pub fn allocate(
+        &mut self,
+        build_scratch_sizes: &[vk::DeviceSize],
+    ) -> (Arc<Buffer<u8>>, Vec<vk::DeviceSize>) {
+        let alignment = self.context.raytracing().min_scratch_offset_alignment();
+        let mut offsets = Vec::with_capacity(build_scratch_sizes.len());
+        let mut size = 0;
+        for build_scratch_size in build_scratch_sizes {
+            offsets.push(size);
+            size = (size + build_scratch_size).next_multiple_of(alignment);
+        }
+
+        let buffer = match &self.buffer {
+            Some(buffer) if self.capacity >= size => buffer.clone(),
+            _ => {
+                let buffer = Arc::new(Buffer::new(
+                    self.context.clone(),
+                    size + alignment,
+                    vk::BufferUsageFlags::STORAGE_BUFFER
+                        | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                ));
+                let device_address = buffer.get_device_address();
+                self.base_offset = device_address.next_multiple_of(alignment) - device_address;
+                self.capacity = size + alignment - self.base_offset;
+                self.buffer = Some(buffer.clone());
+                buffer
+            }
+        };
+
+        let offsets = offsets
+            .into_iter()
+            .map(|offset| self.base_offset + offset)
+            .collect();
+        (buffer, offsets)
+    }
+}
+
 impl Drop for AccelerationStructure {
     fn drop(&mut self) {
         unsafe {
             self.context
-                .context_raytracing
+                .raytracing()
                 .acceleration_structure
                 .destroy_acceleration_structure(self.inner, None);
         }
@@ -33,17 +33,39 @@ impl<T> IntoSlice<T> for Vec<T> {
     }
 }
 
+/// Types where every bit pattern is a valid value, so that they can be read back from device memory.
+///
+/// # Safety
+/// The type must not contain padding, references or types with invalid bit patterns like `bool`.
+pub unsafe trait PlainData: Copy {}
+
+unsafe impl PlainData for u8 {}
+unsafe impl PlainData for u16 {}
+unsafe impl PlainData for u32 {}
+unsafe impl PlainData for i32 {}
+unsafe impl PlainData for f32 {}
+// Arrays have no padding between their elements
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {}
+
 pub struct UntypedBuffer {
     pub inner: vk::Buffer,
     pub usage: vk::BufferUsageFlags,
     pub memory: vk::DeviceMemory,
+    /// Of the memory, which can be larger than the requested size
     pub size: vk::DeviceSize,
+    pub requested_size: vk::DeviceSize,
+    pub memory_property_flags: vk::MemoryPropertyFlags,
     pub(super) resource: BufferResource,
     context: Arc<Context>,
 }
 
 impl UntypedBuffer {
     pub fn get_device_address(&self) -> vk::DeviceAddress {
+        debug_assert!(
+            self.usage
+                .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS),
+            "Buffer needs the SHADER_DEVICE_ADDRESS usage for its device address"
+        );
         let info = vk::BufferDeviceAddressInfo::builder().buffer(self.inner);
         unsafe {
             self.context
@@ -59,6 +81,9 @@ Buffers could work like "FullBuffer (mostly internal) and Buffer<T> (has a Arc<F
 In our case, the FullBuffer is the UntypedBuffer.
 (invariant: Buffer<T> ranges never overlap. The API lets you split and join adjacent buffers) */
 
+/// Vulkan guarantees at least this alignment for mapped memory (minMemoryMapAlignment)
+const MIN_MEMORY_MAP_ALIGNMENT: usize = 64;
+
 pub struct Buffer<T: ?Sized> {
     inner: Arc<UntypedBuffer>,
     _marker: PhantomData<T>,
@@ -71,6 +96,7 @@ impl<T> Buffer<T> {
         usage: vk::BufferUsageFlags,
         memory_property_flags: vk::MemoryPropertyFlags,
     ) -> Buffer<T> {
+        validate_buffer_flags(&context, size, usage, memory_property_flags);
         let device = &context.device;
         let resource = context.sync_manager.get_buffer();
 
@@ -91,8 +117,12 @@ impl<T> Buffer<T> {
         )
         .expect("Could not find memorytype for buffer");
 
-        let mut allocate_flags_info =
-            vk::MemoryAllocateFlagsInfo::builder().flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS); // TODO: Make configureable
+        let allocate_flags = if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS
+        } else {
+            vk::MemoryAllocateFlags::empty()
+        };
+        let mut allocate_flags_info = vk::MemoryAllocateFlagsInfo::builder().flags(allocate_flags);
 
         let allocate_info = vk::MemoryAllocateInfo::builder()
             .allocation_size(buffer_memory_requirements.size)
@@ -101,6 +131,9 @@ impl<T> Buffer<T> {
 
         let memory = unsafe { device.allocate_memory(&allocate_info, None) }
             .expect("Could not allocate memory for buffer");
+        context
+            .memory_stats
+            .allocated(buffer_memory_requirements.size, memory_property_flags);
 
         unsafe { device.bind_buffer_memory(buffer, memory, 0) }
             .expect("Could not bind buffer memory for buffer");
@@ -110,6 +143,8 @@ impl<T> Buffer<T> {
             usage,
             memory,
             size: buffer_memory_requirements.size,
+            requested_size: size,
+            memory_property_flags,
             resource,
             context,
         });
@@ -120,6 +155,42 @@ impl<T> Buffer<T> {
     }
 }
 
+/// Catches flags that the context can't provide, before they turn into obscure Vulkan errors
+fn validate_buffer_flags(
+    context: &Context,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    memory_property_flags: vk::MemoryPropertyFlags,
+) {
+    assert!(size > 0, "Buffer size must be greater than 0");
+    assert!(!usage.is_empty(), "Buffer usage must not be empty");
+
+    // bufferDeviceAddress is a required feature, and is always enabled when creating the device
+    let raytracing_usage = vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
+        | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+        | vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR;
+    assert!(
+        !usage.intersects(raytracing_usage) || context.context_raytracing.is_some(),
+        "Buffer usage {:?} needs raytracing, which the device does not support",
+        usage
+    );
+
+    let memory_properties = &context.device_memory_properties;
+    let memory_types =
+        &memory_properties.memory_types[..memory_properties.memory_type_count as usize];
+    assert!(
+        memory_types
+            .iter()
+            .any(|memory_type| memory_type.property_flags.contains(memory_property_flags)),
+        "No memory type has the properties {:?}, the device has {:?}",
+        memory_property_flags,
+        memory_types
+            .iter()
+            .map(|memory_type| memory_type.property_flags)
+            .collect::<Vec<_>>()
+    );
+}
+
 impl<T> Buffer<T> {
     pub fn get_vk_buffer(&self) -> vk::Buffer {
         self.inner.inner
@@ -134,13 +205,23 @@ impl<T> Buffer<T> {
     }
 
     pub fn copy_data<U: IntoSlice<T> + ?Sized>(&self, data: &U) {
+        self.copy_data_at_offset(0, data);
+    }
+
+    /// Writes the data starting at a byte offset, for example into one slice of a larger buffer
+    pub fn copy_data_at_offset<U: IntoSlice<T> + ?Sized>(&self, offset: vk::DeviceSize, data: &U) {
         let data = data.as_sliced();
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        assert!(
+            offset + size <= self.inner.size,
+            "Cannot write past the end of the buffer"
+        );
 
         let buffer_ptr = unsafe {
             self.get_device().map_memory(
                 self.inner.memory,
-                0,
-                self.inner.size,
+                offset,
+                size,
                 vk::MemoryMapFlags::empty(),
             )
         }
@@ -151,6 +232,70 @@ impl<T> Buffer<T> {
         unsafe { self.get_device().unmap_memory(self.inner.memory) };
     }
 
+    /// Reads back all elements of the requested size
+    pub fn read_to_vec(&self) -> Vec<T>
+    where
+        T: PlainData,
+    {
+        let element_size = std::mem::size_of::<T>() as vk::DeviceSize;
+        let len = self.inner.requested_size / element_size;
+        self.read_bytes(len * element_size, elements_from_bytes)
+    }
+
+    /// Reads back the first `data.len()` elements
+    pub fn read_to_slice(&self, data: &mut [T])
+    where
+        T: PlainData,
+    {
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        self.read_bytes(size, |bytes| copy_from_bytes(bytes, data));
+    }
+
+    /// Maps the first `size` bytes for reading
+    fn read_bytes<R>(&self, size: vk::DeviceSize, read: impl FnOnce(&[u8]) -> R) -> R {
+        assert!(
+            self.inner
+                .memory_property_flags
+                .contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+            "Only host visible buffers can be read back"
+        );
+        assert!(
+            size <= self.inner.requested_size,
+            "Cannot read more than the size of the buffer"
+        );
+        if size == 0 {
+            return read(&[]);
+        }
+
+        let buffer_ptr = unsafe {
+            self.get_device()
+                .map_memory(self.inner.memory, 0, size, vk::MemoryMapFlags::empty())
+        }
+        .expect("Could not map memory") as *const u8;
+
+        // Without this, writes from the device might not be visible yet
+        if !self
+            .inner
+            .memory_property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+        {
+            let memory_range = vk::MappedMemoryRange::builder()
+                .memory(self.inner.memory)
+                .offset(0)
+                .size(vk::WHOLE_SIZE);
+            unsafe {
+                self.get_device()
+                    .invalidate_mapped_memory_ranges(std::slice::from_ref(&memory_range))
+            }
+            .expect("Could not invalidate mapped memory");
+        }
+
+        let result = read(unsafe { std::slice::from_raw_parts(buffer_ptr, size as usize) });
+
+        unsafe { self.get_device().unmap_memory(self.inner.memory) };
+        result
+    }
+
     pub fn copy_from(
         self: &Arc<Self>,
         dst_offset: vk::DeviceSize,
@@ -180,6 +325,26 @@ impl<T> Buffer<T> {
         });
     }
 
+    /// Reinterprets the elements of the buffer as `U`, for example to upload bytes and use them as a typed buffer.
+    /// Both buffers share the same memory.
+    ///
+    /// Returns `None` if the requested size isn't a multiple of the size of `U`,
+    /// or if `U` needs a larger alignment than mapped memory guarantees.
+    ///
+    /// # Safety invariant
+    /// Every bit pattern is a valid `U`, which `PlainData` guarantees,
+    /// so reading the shared memory back as `U` can not produce invalid values.
+    pub fn cast<U: PlainData>(&self) -> Option<Buffer<U>> {
+        if !can_cast_to::<U>(self.inner.requested_size) {
+            return None;
+        }
+
+        Some(Buffer {
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        })
+    }
+
     pub fn get_untyped(&self) -> &Arc<UntypedBuffer> {
         &self.inner
     }
@@ -214,7 +379,46 @@ impl Drop for UntypedBuffer {
         let device = &self.context.device;
         unsafe { device.destroy_buffer(self.inner, None) };
         unsafe { device.free_memory(self.memory, None) };
+        self.context
+            .memory_stats
+            .freed(self.size, self.memory_property_flags);
+    }
+}
+
+/// Whether a buffer with this many bytes can be read as whole elements of `U`
+fn can_cast_to<U>(size: vk::DeviceSize) -> bool {
+    let element_size = std::mem::size_of::<U>() as vk::DeviceSize;
+    element_size != 0
+        && size % element_size == 0
+        && std::mem::align_of::<U>() <= MIN_MEMORY_MAP_ALIGNMENT
+}
+
+/// As many whole elements as fit into the bytes
+fn elements_from_bytes<T: PlainData>(bytes: &[u8]) -> Vec<T> {
+    let len = bytes.len() / std::mem::size_of::<T>();
+    let mut data = Vec::with_capacity(len);
+    // Safety: the elements are fully written before the length is set
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            bytes.as_ptr(),
+            data.as_mut_ptr() as *mut u8,
+            len * std::mem::size_of::<T>(),
+        );
+        data.set_len(len);
     }
+    data
+}
+
+fn copy_from_bytes<T: PlainData>(bytes: &[u8], data: &mut [T]) {
+    assert_eq!(
+        bytes.len(),
+        std::mem::size_of_val(data),
+        "The bytes have to fill the elements exactly"
+    );
+    // Safety: every bit pattern is a valid `T`
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), data.as_mut_ptr() as *mut u8, bytes.len())
+    };
 }
 
 impl<T> Deref for Buffer<T> {
@@ -224,3 +428,45 @@ impl<T> Deref for Buffer<T> {
         &self.inner.inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_back_round_trip() {
+        let values = [1.5f32, -2.0, 0.0, f32::MAX];
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+
+        assert_eq!(elements_from_bytes::<f32>(&bytes), values);
+
+        let mut read_back = [0.0f32; 4];
+        copy_from_bytes(&bytes, &mut read_back);
+        assert_eq!(read_back, values);
+    }
+
+    #[test]
+    fn cast_needs_whole_elements() {
+        assert!(can_cast_to::<u32>(16));
+        assert!(can_cast_to::<u8>(7));
+        assert!(!can_cast_to::<u32>(6));
+        assert!(!can_cast_to::<u16>(7));
+        assert!(!can_cast_to::<()>(16));
+
+        #[repr(align(128))]
+        struct OverAligned(#[allow(dead_code)] [u8; 128]);
+        assert!(!can_cast_to::<OverAligned>(256));
+    }
+
+    #[test]
+    fn read_back_ignores_partial_elements() {
+        let bytes = [1u8, 0, 2, 0, 3];
+        assert_eq!(
+            elements_from_bytes::<u16>(&bytes),
+            [u16::from_ne_bytes([1, 0]), u16::from_ne_bytes([2, 0])]
+        );
+    }
+}
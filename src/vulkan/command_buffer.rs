@@ -10,7 +10,13 @@ use ash::vk::{self};
 
 use self::cmd_args::CommandBufferCmdArgs;
 
-use super::{buffer::UntypedBuffer, command_pool::CommandPool, context::Context, image::Image};
+use super::{
+    buffer::UntypedBuffer,
+    command_pool::CommandPool,
+    context::Context,
+    image::Image,
+    timeline_semaphore::{TimelineSemaphore, TimelineSubmission},
+};
 
 #[must_use]
 pub struct CommandBuffer<'a> {
@@ -20,33 +26,71 @@ pub struct CommandBuffer<'a> {
 }
 
 /// CommandBuffer has to be kept alive as long as the GPU is still executing it
+/// Secondary command buffers get executed by primary ones, with [`CmdExecuteCommands`].
+/// Each thread that records them needs its own command pool.
 pub struct RecordedCommandBuffer {
     command_buffer: vk::CommandBuffer,
     command_pool: CommandPool,
+    level: vk::CommandBufferLevel,
 
     // references to resources to prevent dropping them too early
     _buffer_resources: Vec<Arc<UntypedBuffer>>,
     _image_resources: Vec<Arc<Image>>,
+    _secondary_command_buffers: Vec<Arc<RecordedCommandBuffer>>,
 }
 
 impl RecordedCommandBuffer {
+    /// Signals the next value of the timeline semaphore once the command buffer has finished.
+    /// The given stages wait for the earlier submissions, for example ones on other queues.
     pub fn submit(
         &self,
         queue: vk::Queue,
-        //submits: &[vk::SubmitInfo],
-        //fence: vk::Fence,)
-    ) {
-        let submit_info =
-            vk::SubmitInfo::builder().command_buffers(std::slice::from_ref(&self.command_buffer));
-
+        wait_for: &[(&TimelineSubmission, vk::PipelineStageFlags2)],
+        timeline_semaphore: Arc<TimelineSemaphore>,
+    ) -> TimelineSubmission {
+        assert_eq!(
+            self.level,
+            vk::CommandBufferLevel::PRIMARY,
+            "Only primary command buffers can be submitted"
+        );
+        let value = timeline_semaphore.next_value();
+
+        let wait_semaphore_infos: Vec<_> = wait_for
+            .iter()
+            .map(|(submission, stage_mask)| {
+                vk::SemaphoreSubmitInfo::builder()
+                    .semaphore(**submission.semaphore)
+                    .value(submission.value)
+                    .stage_mask(*stage_mask)
+                    .build()
+            })
+            .collect();
+        let signal_semaphore_info = vk::SemaphoreSubmitInfo::builder()
+            .semaphore(**timeline_semaphore)
+            .value(value)
+            .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS);
+        let command_buffer_info =
+            vk::CommandBufferSubmitInfo::builder().command_buffer(self.command_buffer);
+
+        let submit_info = vk::SubmitInfo2::builder()
+            .wait_semaphore_infos(&wait_semaphore_infos)
+            .command_buffer_infos(std::slice::from_ref(&command_buffer_info))
+            .signal_semaphore_infos(std::slice::from_ref(&signal_semaphore_info));
+
+        let context = self.command_pool.context();
         unsafe {
-            self.command_pool.context().device.queue_submit(
+            context.synchronisation2_loader.queue_submit2(
                 queue,
                 std::slice::from_ref(&submit_info),
                 vk::Fence::null(),
             )
         }
         .expect("Could not submit to queue");
+
+        TimelineSubmission {
+            semaphore: timeline_semaphore,
+            value,
+        }
     }
 }
 
@@ -72,10 +116,6 @@ impl<'a> CommandBuffer<'a> {
             allocate_info.count == 1,
             "Only one command buffer is supported"
         );
-        assert!(
-            allocate_info.level == vk::CommandBufferLevel::PRIMARY,
-            "Only primary command buffers are supported"
-        );
         Self {
             command_pool,
             allocate_info,
@@ -108,6 +148,7 @@ impl<'a> CommandBuffer<'a> {
 
         let mut buffer_resources = Vec::new();
         let mut image_resources = Vec::new();
+        let mut secondary_command_buffers = Vec::new();
         let mut sync_manager_lock = context.sync_manager.lock();
         for command in self.commands {
             command.execute(CommandBufferCmdArgs::new(
@@ -116,14 +157,17 @@ impl<'a> CommandBuffer<'a> {
                 &mut sync_manager_lock,
                 &mut buffer_resources,
                 &mut image_resources,
+                &mut secondary_command_buffers,
             ));
         }
 
         RecordedCommandBuffer {
             command_buffer,
             command_pool: self.command_pool,
+            level: self.allocate_info.level,
             _buffer_resources: buffer_resources,
             _image_resources: image_resources,
+            _secondary_command_buffers: secondary_command_buffers,
         }
     }
 }
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use ash::vk;
 
+use super::RecordedCommandBuffer;
 use crate::vulkan::{
     buffer::UntypedBuffer,
     context::Context,
@@ -18,6 +19,7 @@ pub struct CommandBufferCmdArgs<'a, 'b> {
     sync_manager: &'a mut SyncManagerLock<'b>,
     buffer_resources: &'a mut Vec<Arc<UntypedBuffer>>,
     image_resources: &'a mut Vec<Arc<Image>>,
+    secondary_command_buffers: &'a mut Vec<Arc<RecordedCommandBuffer>>,
 }
 
 impl<'a, 'b> CommandBufferCmdArgs<'a, 'b> {
@@ -27,6 +29,7 @@ impl<'a, 'b> CommandBufferCmdArgs<'a, 'b> {
         sync_manager: &'a mut SyncManagerLock<'b>,
         buffer_resources: &'a mut Vec<Arc<UntypedBuffer>>,
         image_resources: &'a mut Vec<Arc<Image>>,
+        secondary_command_buffers: &'a mut Vec<Arc<RecordedCommandBuffer>>,
     ) -> Self {
         Self {
             command_buffer,
@@ -34,9 +37,18 @@ impl<'a, 'b> CommandBufferCmdArgs<'a, 'b> {
             sync_manager,
             buffer_resources,
             image_resources,
+            secondary_command_buffers,
         }
     }
 
+    #[allow(dead_code)] // nothing records secondary command buffers yet
+    pub fn add_secondary_command_buffers(
+        &mut self,
+        command_buffers: Vec<Arc<RecordedCommandBuffer>>,
+    ) {
+        self.secondary_command_buffers.extend(command_buffers);
+    }
+
     pub fn add_accesses(
         &mut self,
         buffer_accesses: Vec<BufferAccess>,
@@ -6,23 +6,44 @@ use crate::vulkan::{
     acceleration_structure::AccelerationStructure,
     buffer::{Buffer, UntypedBuffer},
     context::Context,
+    debug_label::{begin_debug_label, end_debug_label},
     image::Image,
     sync_manager::resource_access::{BufferAccess, ImageAccess},
 };
 
 // TODO: More granular barriers (for example, only for a specific image mip map layer)
 
-use super::{CommandBufferCmd, CommandBufferCmdArgs};
+use super::{CommandBufferCmd, CommandBufferCmdArgs, RecordedCommandBuffer};
 
 pub struct BeginCommandBuffer {
     pub flags: vk::CommandBufferUsageFlags,
-    //inheritance_info: Option<()>,
+    /// Only for secondary command buffers that continue a render pass
+    pub inheritance_info: Option<CommandBufferInheritance>,
+}
+
+/// The render pass that a secondary command buffer gets executed in
+#[derive(Clone, Copy, Debug)]
+pub struct CommandBufferInheritance {
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    /// Can be null when it is not known yet, but a known one can be faster
+    pub framebuffer: vk::Framebuffer,
 }
 
 impl<'a> CommandBufferCmd<'a> for BeginCommandBuffer {
     fn execute(self: Box<Self>, args: CommandBufferCmdArgs) {
-        let begin_info = vk::CommandBufferBeginInfo::builder().flags(self.flags);
-        // .inheritance_info(self.inheritance_info.as_ref());
+        // secondary command buffers always need one, primary ones ignore it
+        let mut inheritance_info = vk::CommandBufferInheritanceInfo::default();
+        let mut flags = self.flags;
+        if let Some(inheritance) = self.inheritance_info {
+            inheritance_info.render_pass = inheritance.render_pass;
+            inheritance_info.subpass = inheritance.subpass;
+            inheritance_info.framebuffer = inheritance.framebuffer;
+            flags |= vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE;
+        }
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(flags)
+            .inheritance_info(&inheritance_info);
         unsafe {
             args.context
                 .device
@@ -42,6 +63,28 @@ impl<'cmd, 'a> CommandBufferCmd<'cmd> for CmdManualCommand<'a> {
     }
 }
 
+/// See [`crate::vulkan::debug_label::DebugLabel`]
+pub struct CmdBeginDebugLabel<'a> {
+    pub name: &'a str,
+}
+
+impl<'cmd, 'a> CommandBufferCmd<'cmd> for CmdBeginDebugLabel<'a>
+where
+    'a: 'cmd,
+{
+    fn execute(self: Box<Self>, args: CommandBufferCmdArgs) {
+        begin_debug_label(&args.context, args.command_buffer, self.name);
+    }
+}
+
+pub struct CmdEndDebugLabel {}
+
+impl<'cmd> CommandBufferCmd<'cmd> for CmdEndDebugLabel {
+    fn execute(self: Box<Self>, args: CommandBufferCmdArgs) {
+        end_debug_label(&args.context, args.command_buffer);
+    }
+}
+
 pub struct CmdCopyBuffer<'a, T> {
     pub src_buffer: Arc<Buffer<T>>,
     pub dst_buffer: Arc<Buffer<T>>,
@@ -213,6 +256,8 @@ pub struct AccelerationStructureBuildGeometryInfoKHR<'a, V, I> {
     pub src_acceleration_structure: Option<Arc<AccelerationStructure>>,
     pub geometry: Cow<'a, [AccelerationStructureGeometryData<V, I>]>,
     pub scratch_data: Option<Arc<Buffer<u8>>>,
+    /// In bytes, lets multiple builds share one scratch buffer
+    pub scratch_offset: vk::DeviceSize,
 }
 
 impl<'a, V, I> AccelerationStructureBuildGeometryInfoKHR<'a, V, I> {
@@ -245,7 +290,7 @@ impl<'a, V, I> AccelerationStructureBuildGeometryInfoKHR<'a, V, I> {
                     self.scratch_data
                         .as_ref()
                         .map(|v| vk::DeviceOrHostAddressKHR {
-                            device_address: v.get_device_address(),
+                            device_address: v.get_device_address() + self.scratch_offset,
                         })
                         .unwrap_or_default(),
                 )
@@ -503,7 +548,7 @@ where
 
         unsafe {
             args.context
-                .context_raytracing
+                .raytracing()
                 .acceleration_structure
                 .cmd_build_acceleration_structures(
                     args.command_buffer,
@@ -514,6 +559,121 @@ where
     }
 }
 
+/// Resets the queries, and then writes one query per acceleration structure
+pub struct CmdWriteAccelerationStructuresProperties {
+    pub acceleration_structures: Vec<Arc<AccelerationStructure>>,
+    pub query_type: vk::QueryType,
+    pub query_pool: vk::QueryPool,
+}
+
+impl<'cmd> CommandBufferCmd<'cmd> for CmdWriteAccelerationStructuresProperties {
+    fn execute(self: Box<Self>, mut args: CommandBufferCmdArgs) {
+        args.add_accesses(
+            self.acceleration_structures
+                .iter()
+                .map(|acceleration_structure| {
+                    BufferAccess::entire_buffer(
+                        acceleration_structure.buffer.get_untyped().clone(),
+                        vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR,
+                        vk::AccessFlags2::ACCELERATION_STRUCTURE_READ_KHR,
+                    )
+                })
+                .collect(),
+            vec![],
+        );
+
+        let acceleration_structures = self
+            .acceleration_structures
+            .iter()
+            .map(|acceleration_structure| acceleration_structure.inner)
+            .collect::<Vec<_>>();
+        unsafe {
+            args.context.device.cmd_reset_query_pool(
+                args.command_buffer,
+                self.query_pool,
+                0,
+                acceleration_structures.len() as u32,
+            );
+            args.context
+                .raytracing()
+                .acceleration_structure
+                .cmd_write_acceleration_structures_properties(
+                    args.command_buffer,
+                    &acceleration_structures,
+                    self.query_type,
+                    self.query_pool,
+                    0,
+                )
+        }
+    }
+}
+
+pub struct CmdCopyAccelerationStructure {
+    pub src: Arc<AccelerationStructure>,
+    pub dst: Arc<AccelerationStructure>,
+    pub mode: vk::CopyAccelerationStructureModeKHR,
+}
+
+impl<'cmd> CommandBufferCmd<'cmd> for CmdCopyAccelerationStructure {
+    fn execute(self: Box<Self>, mut args: CommandBufferCmdArgs) {
+        args.add_accesses(
+            vec![
+                BufferAccess::entire_buffer(
+                    self.src.buffer.get_untyped().clone(),
+                    vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR,
+                    vk::AccessFlags2::ACCELERATION_STRUCTURE_READ_KHR,
+                ),
+                BufferAccess::entire_buffer(
+                    self.dst.buffer.get_untyped().clone(),
+                    vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR,
+                    vk::AccessFlags2::ACCELERATION_STRUCTURE_WRITE_KHR,
+                ),
+            ],
+            vec![],
+        );
+
+        let copy_info = vk::CopyAccelerationStructureInfoKHR::builder()
+            .src(self.src.inner)
+            .dst(self.dst.inner)
+            .mode(self.mode);
+        unsafe {
+            args.context
+                .raytracing()
+                .acceleration_structure
+                .cmd_copy_acceleration_structure(args.command_buffer, &copy_info)
+        }
+    }
+}
+
+/// Runs recorded secondary command buffers, and keeps them alive until this one is dropped.
+/// Their barriers were recorded into them, so the ones that continue a render pass must not need any.
+#[allow(dead_code)] // nothing records secondary command buffers yet
+pub struct CmdExecuteCommands {
+    pub command_buffers: Vec<Arc<RecordedCommandBuffer>>,
+}
+
+impl<'cmd> CommandBufferCmd<'cmd> for CmdExecuteCommands {
+    fn execute(self: Box<Self>, mut args: CommandBufferCmdArgs) {
+        assert!(
+            self.command_buffers
+                .iter()
+                .all(|command_buffer| command_buffer.level == vk::CommandBufferLevel::SECONDARY),
+            "Only secondary command buffers can be executed"
+        );
+        let command_buffers: Vec<_> = self
+            .command_buffers
+            .iter()
+            .map(|command_buffer| command_buffer.command_buffer)
+            .collect();
+        unsafe {
+            args.context
+                .device
+                .cmd_execute_commands(args.command_buffer, &command_buffers)
+        };
+        args.add_secondary_command_buffers(self.command_buffers);
+    }
+}
+
 pub struct EndCommandBuffer {}
 
 impl<'a> CommandBufferCmd<'a> for EndCommandBuffer {
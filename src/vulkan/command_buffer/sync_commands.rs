@@ -71,7 +71,55 @@ pub struct ImageMemoryBarrier {
     pub subresource_range: vk::ImageSubresourceRange,
 }
 
+/// Where a buffer was last used on the old queue, or where it will first be used on the new queue
+#[derive(Clone, Copy)]
+pub struct QueueFamilyAccess {
+    pub queue_family_index: u32,
+    pub stage_mask: vk::PipelineStageFlags2,
+    pub access_mask: vk::AccessFlags2,
+}
+
 impl CmdPipelineBarrier {
+    /// Moves whole buffers with exclusive sharing to another queue family.
+    /// Returns the release barrier for the old queue, and the acquire barrier for the new queue.
+    /// The new queue has to wait for the old queue with a semaphore in between.
+    pub fn queue_ownership_transfer(
+        buffers: &[Arc<UntypedBuffer>],
+        src: QueueFamilyAccess,
+        dst: QueueFamilyAccess,
+    ) -> (Self, Self) {
+        let barriers =
+            |src_access: (vk::PipelineStageFlags2, vk::AccessFlags2),
+             dst_access: (vk::PipelineStageFlags2, vk::AccessFlags2)| {
+                CmdPipelineBarrier {
+                    dependency_flags: vk::DependencyFlags::empty(),
+                    memory_barriers: vec![],
+                    buffer_memory_barriers: buffers
+                        .iter()
+                        .map(|buffer| BufferMemoryBarrier {
+                            src_stage_mask: src_access.0,
+                            src_access_mask: src_access.1,
+                            dst_stage_mask: dst_access.0,
+                            dst_access_mask: dst_access.1,
+                            src_queue_family_index: src.queue_family_index,
+                            dst_queue_family_index: dst.queue_family_index,
+                            buffer: buffer.clone(),
+                            offset: 0,
+                            size: vk::WHOLE_SIZE,
+                        })
+                        .collect(),
+                    image_memory_barriers: vec![],
+                }
+            };
+        let none = (vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE);
+
+        // the dst access of the release and the src access of the acquire are ignored
+        (
+            barriers((src.stage_mask, src.access_mask), none),
+            barriers(none, (dst.stage_mask, dst.access_mask)),
+        )
+    }
+
     pub fn execute(self, command_buffer: vk::CommandBuffer, context: &Context) {
         let memory_barriers: Vec<_> = self
             .memory_barriers
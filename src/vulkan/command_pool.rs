@@ -11,8 +11,14 @@ pub struct CommandPool {
 
 impl CommandPool {
     pub fn new(context: Arc<Context>) -> Self {
+        let queue_family_index = context.queue_family_index;
+        Self::new_for_queue_family(context, queue_family_index)
+    }
+
+    /// Command buffers from this pool can only be submitted to queues of that family
+    pub fn new_for_queue_family(context: Arc<Context>, queue_family_index: u32) -> Self {
         let create_info = vk::CommandPoolCreateInfo::builder()
-            .queue_family_index(context.queue_family_index)
+            .queue_family_index(queue_family_index)
             .flags(
                 vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER
                     | vk::CommandPoolCreateFlags::TRANSIENT,
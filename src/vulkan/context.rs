@@ -1,36 +1,77 @@
 use std::ffi::CStr;
 
 use ash::{
-    extensions::khr::{
-        AccelerationStructure, BufferDeviceAddress, RayTracingPipeline, Synchronization2,
+    extensions::{
+        ext::DebugUtils,
+        khr::{AccelerationStructure, BufferDeviceAddress, RayTracingPipeline, Synchronization2},
     },
     vk::{self, ApplicationInfo, DeviceCreateInfo, DeviceQueueCreateInfo, InstanceCreateInfo},
 };
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use winit::{event_loop::EventLoop, window::Window};
 
+use super::memory_stats::MemoryStats;
 use super::sync_manager::SyncManager;
+use super::validation::{DebugMessenger, ValidationSettings};
 
 pub struct Context {
     _entry: ash::Entry,
     pub instance: ash::Instance,
+    /// Only exists when validation is enabled
+    debug_messenger: Option<DebugMessenger>,
+    /// Only exists when the debug labels are enabled, see [`super::debug_label`]
+    pub debug_utils: Option<DebugUtils>,
 
     pub surface_loader: ash::extensions::khr::Surface,
     pub surface: vk::SurfaceKHR,
 
     pub synchronisation2_loader: ash::extensions::khr::Synchronization2,
     pub sync_manager: SyncManager,
+    /// Of the buffers and images, see [`MemoryStats`]
+    pub memory_stats: MemoryStats,
 
     pub physical_device: vk::PhysicalDevice,
     pub queue_family_index: u32,
 
     pub device: ash::Device,
     pub queue: vk::Queue,
+    /// Only exists when the device has a queue family that can only do transfers
+    pub transfer_queue: Option<TransferQueue>,
 
     pub buffer_device_address: BufferDeviceAddress,
     pub device_memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub physical_device_properties: vk::PhysicalDeviceProperties,
 
-    pub context_raytracing: ContextRaytracing,
+    /// None when the device doesn't support raytracing
+    pub context_raytracing: Option<ContextRaytracing>,
+    pub descriptor_indexing_features: DescriptorIndexingFeatures,
+}
+
+/// Descriptor indexing features are optional, they are only enabled when the device supports them
+#[derive(Debug, Clone, Copy)]
+pub struct DescriptorIndexingFeatures {
+    pub descriptor_indexing: bool,
+    pub runtime_descriptor_array: bool,
+    pub shader_sampled_image_array_non_uniform_indexing: bool,
+    pub descriptor_binding_partially_bound: bool,
+    pub descriptor_binding_variable_descriptor_count: bool,
+}
+
+impl DescriptorIndexingFeatures {
+    /// Whether everything that a bindless texture array needs is enabled
+    pub fn supports_bindless(&self) -> bool {
+        self.descriptor_indexing
+            && self.runtime_descriptor_array
+            && self.shader_sampled_image_array_non_uniform_indexing
+            && self.descriptor_binding_partially_bound
+            && self.descriptor_binding_variable_descriptor_count
+    }
+}
+
+/// Usually a DMA engine on discrete GPUs, which can copy data while the graphics queue is busy
+pub struct TransferQueue {
+    pub queue_family_index: u32,
+    pub queue: vk::Queue,
 }
 
 pub struct ContextRaytracing {
@@ -43,21 +84,50 @@ pub struct ContextRaytracing {
         vk::PhysicalDeviceAccelerationStructurePropertiesKHR,
 }
 
+impl ContextRaytracing {
+    /// For the scratch data addresses of acceleration structure builds
+    pub fn min_scratch_offset_alignment(&self) -> vk::DeviceSize {
+        self.physical_device_acceleration_structure_properties_khr
+            .min_acceleration_structure_scratch_offset_alignment as vk::DeviceSize
+    }
+}
+
 impl Context {
     pub fn new(event_loop: &EventLoop<()>, window: &Window) -> Self {
         let entry = unsafe { ash::Entry::load() }.expect("Could not load vulkan library");
 
+        let validation = ValidationSettings::from_env().check_layer_support(&entry);
+
         let instance = {
             let surface_extension =
                 ash_window::enumerate_required_extensions(event_loop.raw_display_handle()).unwrap();
+            let extension_names: Vec<_> = surface_extension
+                .iter()
+                .copied()
+                .chain(validation.extension_names())
+                .collect();
+            let layer_names = validation.layer_names();
+            let mut messenger_create_info = validation.messenger_create_info();
 
             let app_info = ApplicationInfo::builder().api_version(vk::API_VERSION_1_3);
-            let create_info = InstanceCreateInfo::builder()
+            let mut create_info = InstanceCreateInfo::builder()
                 .application_info(&app_info)
-                .enabled_extension_names(surface_extension);
+                .enabled_extension_names(&extension_names)
+                .enabled_layer_names(&layer_names);
+            if validation.enabled {
+                create_info = create_info.push_next(&mut messenger_create_info);
+            }
             unsafe { entry.create_instance(&create_info, None) }.expect("Could not create instance")
         };
 
+        let debug_messenger = validation
+            .enabled
+            .then(|| DebugMessenger::new(&entry, &instance, &validation));
+
+        let debug_utils = validation
+            .debug_labels
+            .then(|| DebugUtils::new(&entry, &instance));
+
         let (surface, surface_loader) = {
             let surface = unsafe {
                 ash_window::create_surface(
@@ -78,36 +148,53 @@ impl Context {
         let (physical_device, queue_family_index) =
             find_physical_device(&instance, &surface, &surface_loader);
 
-        let device = create_logical_device(&instance, &physical_device);
+        let transfer_queue_family_index = find_transfer_queue_family(&instance, &physical_device);
+
+        let (device, descriptor_indexing_features, supports_raytracing) = create_logical_device(
+            &instance,
+            &physical_device,
+            queue_family_index,
+            transfer_queue_family_index,
+        );
 
         let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+        let transfer_queue = transfer_queue_family_index.map(|queue_family_index| TransferQueue {
+            queue_family_index,
+            queue: unsafe { device.get_device_queue(queue_family_index, 0) },
+        });
 
         let synchronisation2_loader = Synchronization2::new(&instance, &device);
         let sync_manager = SyncManager::new();
 
-        let ray_tracing_pipeline = RayTracingPipeline::new(&instance, &device);
-        let physical_device_ray_tracing_pipeline_properties_khr =
-            unsafe { RayTracingPipeline::get_properties(&instance, physical_device) };
+        let context_raytracing = supports_raytracing.then(|| {
+            let ray_tracing_pipeline = RayTracingPipeline::new(&instance, &device);
+            let physical_device_ray_tracing_pipeline_properties_khr =
+                unsafe { RayTracingPipeline::get_properties(&instance, physical_device) };
 
-        let acceleration_structure = AccelerationStructure::new(&instance, &device);
-        let physical_device_acceleration_structure_properties_khr =
-            unsafe { AccelerationStructure::get_properties(&instance, physical_device) };
+            let acceleration_structure = AccelerationStructure::new(&instance, &device);
+            let physical_device_acceleration_structure_properties_khr =
+                unsafe { AccelerationStructure::get_properties(&instance, physical_device) };
 
-        let buffer_device_address = BufferDeviceAddress::new(&instance, &device);
+            ContextRaytracing {
+                ray_tracing_pipeline,
+                physical_device_ray_tracing_pipeline_properties_khr,
+                acceleration_structure,
+                physical_device_acceleration_structure_properties_khr,
+            }
+        });
 
-        let context_raytracing = ContextRaytracing {
-            ray_tracing_pipeline,
-            physical_device_ray_tracing_pipeline_properties_khr,
-            acceleration_structure,
-            physical_device_acceleration_structure_properties_khr,
-        };
+        let buffer_device_address = BufferDeviceAddress::new(&instance, &device);
 
         let device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let physical_device_properties =
+            unsafe { instance.get_physical_device_properties(physical_device) };
 
         Self {
             _entry: entry,
             instance,
+            debug_messenger,
+            debug_utils,
 
             surface,
             surface_loader,
@@ -115,14 +202,51 @@ impl Context {
             context_raytracing,
             synchronisation2_loader,
             sync_manager,
+            memory_stats: MemoryStats::new(),
 
             physical_device,
             queue_family_index,
 
             device,
             queue,
+            transfer_queue,
             buffer_device_address,
             device_memory_properties,
+            physical_device_properties,
+            descriptor_indexing_features,
+        }
+    }
+
+    /// Only call this on code paths that are skipped when raytracing isn't supported
+    pub fn raytracing(&self) -> &ContextRaytracing {
+        self.context_raytracing
+            .as_ref()
+            .expect("Raytracing is not supported on this device")
+    }
+
+    /// At least 128 bytes on every device
+    pub fn max_push_constants_size(&self) -> u32 {
+        self.physical_device_properties
+            .limits
+            .max_push_constants_size
+    }
+
+    /// Checks that the push constants fit, when creating a pipeline layout
+    pub fn push_constant_range(
+        &self,
+        stage_flags: vk::ShaderStageFlags,
+        size: usize,
+    ) -> vk::PushConstantRange {
+        assert!(
+            size <= self.max_push_constants_size() as usize,
+            "Could not fit {} bytes of push constants into the {} bytes that the device supports",
+            size,
+            self.max_push_constants_size()
+        );
+        vk::PushConstantRange {
+            stage_flags,
+            offset: 0,
+            size: size as u32,
         }
     }
 
@@ -138,6 +262,10 @@ impl Drop for Context {
 
         unsafe { self.surface_loader.destroy_surface(self.surface, None) };
 
+        if let Some(debug_messenger) = &mut self.debug_messenger {
+            unsafe { debug_messenger.destroy() };
+        }
+
         unsafe { self.instance.destroy_instance(None) };
     }
 }
@@ -198,10 +326,28 @@ fn find_physical_device(
     (physical_device, queue_family_index)
 }
 
+/// A queue family with only transfer support, without graphics or compute
+fn find_transfer_queue_family(
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+) -> Option<u32> {
+    unsafe { instance.get_physical_device_queue_family_properties(*physical_device) }
+        .iter()
+        .position(|info| {
+            info.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                && !info
+                    .queue_flags
+                    .intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+        })
+        .map(|index| index as u32)
+}
+
 fn create_logical_device(
     instance: &ash::Instance,
     physical_device: &vk::PhysicalDevice,
-) -> ash::Device {
+    queue_family_index: u32,
+    transfer_queue_family_index: Option<u32>,
+) -> (ash::Device, DescriptorIndexingFeatures, bool) {
     let swapchain_extension = ash::extensions::khr::Swapchain::name();
     let synchronisation2_extension = ash::extensions::khr::Synchronization2::name();
     let acceleration_structure_extension = ash::extensions::khr::AccelerationStructure::name();
@@ -209,30 +355,136 @@ fn create_logical_device(
     let deferred_host_operations_extension = ash::extensions::khr::DeferredHostOperations::name();
     let device_address_extension = ash::extensions::khr::BufferDeviceAddress::name();
 
-    let device_extensions = [
+    let extension_properties =
+        unsafe { instance.enumerate_device_extension_properties(*physical_device) }
+            .expect("Could not enumerate device extension properties");
+    let is_extension_supported = |extension: &CStr| {
+        extension_properties.iter().any(
+            |property| unsafe { CStr::from_ptr(property.extension_name.as_ptr()) } == extension,
+        )
+    };
+
+    let raytracing_extensions = [
+        acceleration_structure_extension,
+        ray_tracing_pipeline_extension,
+        deferred_host_operations_extension,
+    ];
+    let supports_raytracing_extensions = raytracing_extensions
+        .iter()
+        .all(|extension| is_extension_supported(extension));
+
+    let mut supported_vulkan12_features = vk::PhysicalDeviceVulkan12Features::default();
+    let mut supported_vulkan13_features = vk::PhysicalDeviceVulkan13Features::default();
+    let mut supported_ray_tracing_pipeline_features =
+        vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::default();
+    let mut supported_acceleration_structure_features =
+        vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+    {
+        let mut supported_features = vk::PhysicalDeviceFeatures2::builder()
+            .push_next(&mut supported_vulkan12_features)
+            .push_next(&mut supported_vulkan13_features);
+        // The raytracing feature structs may only be queried when the extensions exist
+        if supports_raytracing_extensions {
+            supported_features = supported_features
+                .push_next(&mut supported_ray_tracing_pipeline_features)
+                .push_next(&mut supported_acceleration_structure_features);
+        }
+        unsafe {
+            instance.get_physical_device_features2(*physical_device, &mut supported_features)
+        };
+    }
+
+    let required_features = [
+        (
+            "bufferDeviceAddress",
+            supported_vulkan12_features.buffer_device_address,
+        ),
+        (
+            "synchronization2",
+            supported_vulkan13_features.synchronization2,
+        ),
+        (
+            "timelineSemaphore",
+            supported_vulkan12_features.timeline_semaphore,
+        ),
+    ];
+    let missing_features: Vec<&str> = required_features
+        .iter()
+        .filter(|(_, supported)| *supported != vk::TRUE)
+        .map(|(name, _)| *name)
+        .collect();
+    if !missing_features.is_empty() {
+        panic!(
+            "The device does not support the required features: {}",
+            missing_features.join(", ")
+        );
+    }
+
+    let supports_raytracing = supports_raytracing_extensions
+        && supported_ray_tracing_pipeline_features.ray_tracing_pipeline == vk::TRUE
+        && supported_acceleration_structure_features.acceleration_structure == vk::TRUE;
+
+    let descriptor_indexing_features = DescriptorIndexingFeatures {
+        descriptor_indexing: supported_vulkan12_features.descriptor_indexing == vk::TRUE,
+        runtime_descriptor_array: supported_vulkan12_features.runtime_descriptor_array == vk::TRUE,
+        shader_sampled_image_array_non_uniform_indexing: supported_vulkan12_features
+            .shader_sampled_image_array_non_uniform_indexing
+            == vk::TRUE,
+        descriptor_binding_partially_bound: supported_vulkan12_features
+            .descriptor_binding_partially_bound
+            == vk::TRUE,
+        descriptor_binding_variable_descriptor_count: supported_vulkan12_features
+            .descriptor_binding_variable_descriptor_count
+            == vk::TRUE,
+    };
+
+    let mut device_extensions = vec![
         swapchain_extension.as_ptr(),
         synchronisation2_extension.as_ptr(),
-        acceleration_structure_extension.as_ptr(),
-        ray_tracing_pipeline_extension.as_ptr(),
-        deferred_host_operations_extension.as_ptr(),
         device_address_extension.as_ptr(),
     ];
+    if supports_raytracing {
+        device_extensions.extend(
+            raytracing_extensions
+                .iter()
+                .map(|extension| extension.as_ptr()),
+        );
+    }
 
     let queue_priorities = [1.0];
-    let queue_create_info = DeviceQueueCreateInfo::builder()
-        .queue_family_index(0)
-        .queue_priorities(&queue_priorities);
+    let queue_create_infos: Vec<_> = std::iter::once(queue_family_index)
+        .chain(transfer_queue_family_index)
+        .map(|queue_family_index| {
+            DeviceQueueCreateInfo::builder()
+                .queue_family_index(queue_family_index)
+                .queue_priorities(&queue_priorities)
+                .build()
+        })
+        .collect();
 
     let mut physical_device_vulkan13_features = vk::PhysicalDeviceVulkan13Features {
         synchronization2: vk::TRUE,
         ..vk::PhysicalDeviceVulkan13Features::default()
     };
 
-    let mut enabled_buffer_device_address_features =
-        vk::PhysicalDeviceBufferDeviceAddressFeatures {
-            buffer_device_address: vk::TRUE,
-            ..vk::PhysicalDeviceBufferDeviceAddressFeatures::default()
-        };
+    // Buffer device address has to be enabled here, since the Vulkan 1.2 struct
+    // can't be chained together with PhysicalDeviceBufferDeviceAddressFeatures
+    let mut physical_device_vulkan12_features = vk::PhysicalDeviceVulkan12Features {
+        buffer_device_address: vk::TRUE,
+        timeline_semaphore: vk::TRUE,
+        descriptor_indexing: descriptor_indexing_features.descriptor_indexing.into(),
+        runtime_descriptor_array: descriptor_indexing_features.runtime_descriptor_array.into(),
+        shader_sampled_image_array_non_uniform_indexing: descriptor_indexing_features
+            .shader_sampled_image_array_non_uniform_indexing
+            .into(),
+        descriptor_binding_partially_bound: descriptor_indexing_features
+            .descriptor_binding_partially_bound
+            .into(),
+        descriptor_binding_variable_descriptor_count: descriptor_indexing_features
+            .descriptor_binding_variable_descriptor_count
+            .into(),
+        ..vk::PhysicalDeviceVulkan12Features::default()
+    };
 
     let mut enabled_ray_tracing_pipeline_features =
         vk::PhysicalDeviceRayTracingPipelineFeaturesKHR {
@@ -251,16 +503,20 @@ fn create_logical_device(
         ..Default::default()
     };
 
-    let create_info = DeviceCreateInfo::builder()
-        .queue_create_infos(std::slice::from_ref(&queue_create_info))
+    let mut create_info = DeviceCreateInfo::builder()
+        .queue_create_infos(&queue_create_infos)
         .enabled_extension_names(&device_extensions)
         .enabled_features(&device_features)
-        .push_next(&mut physical_device_vulkan13_features)
-        .push_next(&mut enabled_buffer_device_address_features)
-        .push_next(&mut enabled_ray_tracing_pipeline_features)
-        .push_next(&mut enabled_acceleration_structure_features)
-        .build();
-
-    unsafe { instance.create_device(*physical_device, &create_info, None) }
-        .expect("Could not create logical device")
+        .push_next(&mut physical_device_vulkan12_features)
+        .push_next(&mut physical_device_vulkan13_features);
+    if supports_raytracing {
+        create_info = create_info
+            .push_next(&mut enabled_ray_tracing_pipeline_features)
+            .push_next(&mut enabled_acceleration_structure_features);
+    }
+
+    let device = unsafe { instance.create_device(*physical_device, &create_info, None) }
+        .expect("Could not create logical device");
+
+    (device, descriptor_indexing_features, supports_raytracing)
 }
@@ -0,0 +1,44 @@
+use std::ffi::CString;
+
+use ash::vk;
+
+use super::context::Context;
+
+/// Groups the commands in RenderDoc and other frame debuggers, until it gets dropped.
+/// Does nothing without the debug utils extension.
+pub struct DebugLabel<'a> {
+    context: &'a Context,
+    command_buffer: vk::CommandBuffer,
+}
+
+impl<'a> DebugLabel<'a> {
+    pub fn new(context: &'a Context, command_buffer: vk::CommandBuffer, name: &str) -> Self {
+        begin_debug_label(context, command_buffer, name);
+        Self {
+            context,
+            command_buffer,
+        }
+    }
+}
+
+impl Drop for DebugLabel<'_> {
+    fn drop(&mut self) {
+        end_debug_label(self.context, self.command_buffer);
+    }
+}
+
+pub fn begin_debug_label(context: &Context, command_buffer: vk::CommandBuffer, name: &str) {
+    let Some(debug_utils) = &context.debug_utils else {
+        return;
+    };
+    let name = CString::new(name).expect("Could not convert debug label name");
+    let label = vk::DebugUtilsLabelEXT::builder().label_name(&name);
+    unsafe { debug_utils.cmd_begin_debug_utils_label(command_buffer, &label) };
+}
+
+pub fn end_debug_label(context: &Context, command_buffer: vk::CommandBuffer) {
+    let Some(debug_utils) = &context.debug_utils else {
+        return;
+    };
+    unsafe { debug_utils.cmd_end_debug_utils_label(command_buffer) };
+}
@@ -0,0 +1,86 @@
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use ash::vk;
+
+use super::context::Context;
+
+/// Every descriptor set keeps its pool alive, and frees itself back into it when dropped
+#[derive(Clone)]
+pub struct DescriptorPool {
+    inner: Arc<DescriptorPoolImpl>,
+}
+
+impl DescriptorPool {
+    pub fn new(
+        context: Arc<Context>,
+        pool_sizes: &[vk::DescriptorPoolSize],
+        max_sets: u32,
+    ) -> Self {
+        let create_info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+            .max_sets(max_sets)
+            .pool_sizes(pool_sizes);
+
+        let descriptor_pool = unsafe { context.device.create_descriptor_pool(&create_info, None) }
+            .expect("Could not create descriptor pool");
+
+        Self {
+            inner: Arc::new(DescriptorPoolImpl {
+                inner: descriptor_pool,
+                allocated_set_count: AtomicUsize::new(0),
+                context,
+            }),
+        }
+    }
+
+    pub fn context(&self) -> &Arc<Context> {
+        &self.inner.context
+    }
+
+    /// How many descriptor sets are currently allocated from the pool, to find leaks
+    pub fn allocated_set_count(&self) -> usize {
+        self.inner.allocated_set_count.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_allocated(&self) {
+        self.inner
+            .allocated_set_count
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn set_freed(&self) {
+        self.inner
+            .allocated_set_count
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct DescriptorPoolImpl {
+    pub inner: vk::DescriptorPool,
+    allocated_set_count: AtomicUsize,
+    pub context: Arc<Context>,
+}
+
+impl Drop for DescriptorPoolImpl {
+    fn drop(&mut self) {
+        unsafe {
+            self.context
+                .device
+                .destroy_descriptor_pool(self.inner, None)
+        };
+    }
+}
+
+impl Deref for DescriptorPool {
+    type Target = vk::DescriptorPool;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner.inner
+    }
+}
@@ -7,10 +7,12 @@ use crate::vulkan::sampler::Sampler;
 use ash::vk;
 
 use super::acceleration_structure::AccelerationStructure;
+use super::descriptor_pool::DescriptorPool;
 
 pub struct DescriptorSet {
     pub inner: vk::DescriptorSet,
     pub layout: Arc<DescriptorSetLayout>,
+    descriptor_pool: DescriptorPool,
 }
 
 pub struct DescriptorSetLayout {
@@ -39,6 +41,40 @@ impl DescriptorSetLayout {
 
         Self { context, inner }
     }
+
+    /// Creates a layout where every binding has its own binding flags, e.g. for variable sized arrays
+    pub fn new_with_binding_flags(
+        context: Arc<Context>,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+        binding_flags: &[vk::DescriptorBindingFlags],
+        flags: Option<vk::DescriptorSetLayoutCreateFlags>,
+    ) -> Self {
+        assert_eq!(
+            bindings.len(),
+            binding_flags.len(),
+            "Every binding needs its binding flags"
+        );
+
+        let mut binding_flags_create_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder().binding_flags(binding_flags);
+
+        let mut create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(bindings)
+            .push_next(&mut binding_flags_create_info);
+
+        if let Some(flags) = flags {
+            create_info = create_info.flags(flags);
+        }
+
+        let inner = unsafe {
+            context
+                .device
+                .create_descriptor_set_layout(&create_info, None)
+        }
+        .expect("Could not create descriptor set layout");
+
+        Self { context, inner }
+    }
 }
 
 impl Drop for DescriptorSetLayout {
@@ -53,52 +89,125 @@ impl Drop for DescriptorSetLayout {
 
 impl DescriptorSet {
     pub fn new(
-        context: Arc<Context>,
-        descriptor_pool: vk::DescriptorPool,
+        descriptor_pool: &DescriptorPool,
         set_layout: Arc<DescriptorSetLayout>,
-        mut write_descriptor_sets: Vec<WriteDescriptorSet>,
+        write_descriptor_sets: Vec<WriteDescriptorSet>,
     ) -> Self {
-        let device = &context.device;
+        let set_layouts = [set_layout.inner];
         let allocate_info = vk::DescriptorSetAllocateInfo::builder()
-            .descriptor_pool(descriptor_pool)
-            .set_layouts(std::slice::from_ref(&set_layout.inner));
+            .descriptor_pool(**descriptor_pool)
+            .set_layouts(&set_layouts);
 
+        Self::allocate(
+            descriptor_pool,
+            &allocate_info,
+            set_layout,
+            write_descriptor_sets,
+        )
+    }
+
+    /// For layouts where the last binding has the VARIABLE_DESCRIPTOR_COUNT flag
+    pub fn new_with_variable_descriptor_count(
+        descriptor_pool: &DescriptorPool,
+        set_layout: Arc<DescriptorSetLayout>,
+        variable_descriptor_count: u32,
+        write_descriptor_sets: Vec<WriteDescriptorSet>,
+    ) -> Self {
+        let set_layouts = [set_layout.inner];
+        let mut variable_descriptor_count_info =
+            vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+                .descriptor_counts(std::slice::from_ref(&variable_descriptor_count));
+
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(**descriptor_pool)
+            .set_layouts(&set_layouts)
+            .push_next(&mut variable_descriptor_count_info);
+
+        Self::allocate(
+            descriptor_pool,
+            &allocate_info,
+            set_layout,
+            write_descriptor_sets,
+        )
+    }
+
+    fn allocate(
+        descriptor_pool: &DescriptorPool,
+        allocate_info: &vk::DescriptorSetAllocateInfo,
+        set_layout: Arc<DescriptorSetLayout>,
+        write_descriptor_sets: Vec<WriteDescriptorSet>,
+    ) -> Self {
+        let device = &descriptor_pool.context().device;
         let descriptor_set = unsafe {
             device
-                .allocate_descriptor_sets(&allocate_info)
+                .allocate_descriptor_sets(allocate_info)
                 .expect("Could not create descriptor set")
         }[0];
-
-        let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = write_descriptor_sets
-            .iter_mut()
-            .map(|write| {
-                let mut vk_write = vk::WriteDescriptorSet::builder()
-                    .dst_binding(write.binding)
-                    .descriptor_type(write.info.descriptor_type())
-                    .dst_set(descriptor_set);
-
-                match &mut write.info {
-                    DescriptorInfo::Buffer(info) => {
-                        vk_write = vk_write.buffer_info(std::slice::from_ref(info))
-                    }
-                    DescriptorInfo::SampledImage(info) | DescriptorInfo::StorageImage(info) => {
-                        vk_write = vk_write.image_info(std::slice::from_ref(info))
-                    }
-                    DescriptorInfo::AccelerationStructure(info) => {
-                        vk_write.descriptor_count = info.acceleration_structure_count;
-                        vk_write = vk_write.push_next(info)
-                    }
-                }
-                vk_write.build()
-            })
-            .collect();
-
-        unsafe { device.update_descriptor_sets(&write_descriptor_sets, &[]) };
+        descriptor_pool.set_allocated();
+        write_descriptors(device, descriptor_set, write_descriptor_sets);
 
         Self {
             inner: descriptor_set,
             layout: set_layout,
+            descriptor_pool: descriptor_pool.clone(),
+        }
+    }
+
+    /// Overwrites the given bindings. The descriptor set must not be in use.
+    pub fn update(&self, write_descriptor_sets: Vec<WriteDescriptorSet>) {
+        write_descriptors(
+            &self.descriptor_pool.context().device,
+            self.inner,
+            write_descriptor_sets,
+        );
+    }
+}
+
+fn write_descriptors(
+    device: &ash::Device,
+    descriptor_set: vk::DescriptorSet,
+    mut write_descriptor_sets: Vec<WriteDescriptorSet>,
+) {
+    let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = write_descriptor_sets
+        .iter_mut()
+        .map(|write| {
+            let mut vk_write = vk::WriteDescriptorSet::builder()
+                .dst_binding(write.binding)
+                .descriptor_type(write.info.descriptor_type())
+                .dst_set(descriptor_set);
+
+            match &mut write.info {
+                DescriptorInfo::Buffer(info)
+                | DescriptorInfo::UniformBufferDynamic(info)
+                | DescriptorInfo::StorageBuffer(info) => {
+                    vk_write = vk_write.buffer_info(std::slice::from_ref(info))
+                }
+                DescriptorInfo::SampledImage(info) | DescriptorInfo::StorageImage(info) => {
+                    vk_write = vk_write.image_info(std::slice::from_ref(info))
+                }
+                DescriptorInfo::SampledImageArray(infos) => vk_write = vk_write.image_info(infos),
+                DescriptorInfo::AccelerationStructure(info) => {
+                    vk_write.descriptor_count = info.acceleration_structure_count;
+                    vk_write = vk_write.push_next(info)
+                }
+            }
+            vk_write.build()
+        })
+        .collect();
+
+    unsafe { device.update_descriptor_sets(&write_descriptor_sets, &[]) };
+}
+
+impl Drop for DescriptorSet {
+    fn drop(&mut self) {
+        unsafe {
+            self.descriptor_pool
+                .context()
+                .device
+                .free_descriptor_sets(*self.descriptor_pool, std::slice::from_ref(&self.inner))
         }
+        .expect("Could not free descriptor set");
+        self.descriptor_pool.set_freed();
     }
 }
 
@@ -109,7 +218,10 @@ pub struct WriteDescriptorSet {
 
 pub enum DescriptorInfo {
     Buffer(vk::DescriptorBufferInfo),
+    UniformBufferDynamic(vk::DescriptorBufferInfo),
+    StorageBuffer(vk::DescriptorBufferInfo),
     SampledImage(vk::DescriptorImageInfo),
+    SampledImageArray(Vec<vk::DescriptorImageInfo>),
     StorageImage(vk::DescriptorImageInfo),
     AccelerationStructure(vk::WriteDescriptorSetAccelerationStructureKHR),
 }
@@ -118,7 +230,11 @@ impl DescriptorInfo {
     pub fn descriptor_type(&self) -> vk::DescriptorType {
         match self {
             DescriptorInfo::Buffer(_) => vk::DescriptorType::UNIFORM_BUFFER,
-            DescriptorInfo::SampledImage(_) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            DescriptorInfo::UniformBufferDynamic(_) => vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            DescriptorInfo::StorageBuffer(_) => vk::DescriptorType::STORAGE_BUFFER,
+            DescriptorInfo::SampledImage(_) | DescriptorInfo::SampledImageArray(_) => {
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            }
             DescriptorInfo::StorageImage(_) => vk::DescriptorType::STORAGE_IMAGE,
             DescriptorInfo::AccelerationStructure(_) => {
                 vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
@@ -141,6 +257,37 @@ impl WriteDescriptorSet {
         }
     }
 
+    /// Only binds `range` bytes, the offset into the buffer is given when binding the descriptor set
+    pub fn uniform_buffer_dynamic<T>(
+        binding: u32,
+        buffer: &Buffer<T>,
+        range: vk::DeviceSize,
+    ) -> WriteDescriptorSet {
+        let info = vk::DescriptorBufferInfo::builder()
+            .buffer(buffer.get_vk_buffer())
+            .offset(0)
+            .range(range)
+            .build();
+
+        WriteDescriptorSet {
+            binding,
+            info: DescriptorInfo::UniformBufferDynamic(info),
+        }
+    }
+
+    pub fn storage_buffer<T>(binding: u32, buffer: &Buffer<T>) -> WriteDescriptorSet {
+        let info = vk::DescriptorBufferInfo::builder()
+            .buffer(buffer.get_vk_buffer())
+            .offset(0)
+            .range(vk::WHOLE_SIZE)
+            .build();
+
+        WriteDescriptorSet {
+            binding,
+            info: DescriptorInfo::StorageBuffer(info),
+        }
+    }
+
     pub fn image_view_sampler(
         binding: u32,
         image_view: Arc<ImageView>,
@@ -177,6 +324,29 @@ impl WriteDescriptorSet {
         }
     }
 
+    /// Writes all textures into an array binding, starting at index 0
+    pub fn image_view_sampler_array(
+        binding: u32,
+        textures: &[(Arc<ImageView>, Arc<Sampler>)],
+        image_layout: vk::ImageLayout,
+    ) -> WriteDescriptorSet {
+        let infos = textures
+            .iter()
+            .map(|(image_view, sampler)| {
+                vk::DescriptorImageInfo::builder()
+                    .sampler(sampler.inner)
+                    .image_view(image_view.inner)
+                    .image_layout(image_layout)
+                    .build()
+            })
+            .collect();
+
+        WriteDescriptorSet {
+            binding,
+            info: DescriptorInfo::SampledImageArray(infos),
+        }
+    }
+
     pub fn storage_image_view_with_layout(
         binding: u32,
         image_view: Arc<ImageView>,
@@ -66,6 +66,10 @@ impl Image {
 
         let memory = unsafe { device.allocate_memory(&allocate_info, None) }
             .expect("Could not allocate memory for image");
+        context.memory_stats.allocated(
+            memory_requirements.size,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
 
         unsafe { device.bind_image_memory(image, memory, 0) }.expect("Could not bind image memory");
 
@@ -255,7 +259,48 @@ pub fn simple_image_create_info() -> vk::ImageCreateInfo {
 
 impl Drop for Image {
     fn drop(&mut self) {
+        let memory_requirements = unsafe {
+            self.context
+                .device
+                .get_image_memory_requirements(self.inner)
+        };
         unsafe { self.context.device.destroy_image(self.inner, None) };
         unsafe { self.context.device.free_memory(self.memory, None) };
+        self.context.memory_stats.freed(
+            memory_requirements.size,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(width: u32, height: u32) -> vk::Extent3D {
+        vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        }
+    }
+
+    #[test]
+    fn max_mip_levels() {
+        assert_eq!(Image::max_mip_levels(extent(1, 1)), 1);
+        assert_eq!(Image::max_mip_levels(extent(2, 1)), 2);
+        assert_eq!(Image::max_mip_levels(extent(256, 256)), 9);
+        assert_eq!(Image::max_mip_levels(extent(255, 1)), 8);
+        assert_eq!(Image::max_mip_levels(extent(5, 3)), 3);
+        assert_eq!(Image::max_mip_levels(extent(1, 1000)), 10);
+        assert_eq!(Image::max_mip_levels(extent(1920, 1080)), 11);
+    }
+
+    #[test]
+    fn mip_level_stops_at_one_pixel() {
+        assert_eq!(Image::mip_level(extent(1, 1), 1), None);
+        let last_level = Image::mip_level(extent(5, 3), 2).unwrap();
+        assert_eq!((last_level.width, last_level.height), (1, 1));
+        assert_eq!(Image::mip_level(extent(5, 3), 3), None);
     }
 }
@@ -9,17 +9,18 @@ pub struct ImageView {
 
     pub image: Arc<Image>,
     context: Arc<Context>,
-    aspect_mask: vk::ImageAspectFlags,
+    subresource_range: vk::ImageSubresourceRange,
 }
 
 impl ImageView {
-    pub fn new_default(
+    pub fn new(
         context: Arc<Context>,
         image: Arc<Image>,
-        aspect_mask: vk::ImageAspectFlags,
+        view_type: vk::ImageViewType,
+        subresource_range: vk::ImageSubresourceRange,
     ) -> Self {
         let create_info = vk::ImageViewCreateInfo::builder()
-            .view_type(vk::ImageViewType::TYPE_2D)
+            .view_type(view_type)
             .format(image.format)
             .components(vk::ComponentMapping {
                 r: vk::ComponentSwizzle::IDENTITY,
@@ -27,7 +28,7 @@ impl ImageView {
                 b: vk::ComponentSwizzle::IDENTITY,
                 a: vk::ComponentSwizzle::IDENTITY,
             })
-            .subresource_range(image.full_subresource_range(aspect_mask))
+            .subresource_range(subresource_range)
             .image(image.inner);
 
         let imageview = unsafe { context.device.create_image_view(&create_info, None) }
@@ -37,12 +38,26 @@ impl ImageView {
             inner: imageview,
             image,
             context,
-            aspect_mask,
+            subresource_range,
         }
     }
 
+    pub fn new_default(
+        context: Arc<Context>,
+        image: Arc<Image>,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Self {
+        let subresource_range = image.full_subresource_range(aspect_mask);
+        Self::new(
+            context,
+            image,
+            vk::ImageViewType::TYPE_2D,
+            subresource_range,
+        )
+    }
+
     pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
-        self.image.full_subresource_range(self.aspect_mask)
+        self.subresource_range
     }
 }
 
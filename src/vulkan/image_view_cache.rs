@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::vulkan::context::Context;
+use crate::vulkan::image::Image;
+use crate::vulkan::image_view::ImageView;
+
+/// Hands out shared image views, so that the same view of an image is only created once.
+pub struct ImageViewCache {
+    image_views: HashMap<ImageViewKey, Arc<ImageView>>,
+    context: Arc<Context>,
+}
+
+/// The image handle stays unique, because every cached view keeps its image alive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct ImageViewKey {
+    image: vk::Image,
+    view_type: vk::ImageViewType,
+    aspect_mask: vk::ImageAspectFlags,
+    base_mip_level: u32,
+    level_count: u32,
+    base_array_layer: u32,
+    layer_count: u32,
+}
+
+impl ImageViewCache {
+    pub fn new(context: Arc<Context>) -> Self {
+        Self {
+            image_views: HashMap::new(),
+            context,
+        }
+    }
+
+    /// A 2D view of all mip levels of the image
+    pub fn get_default(
+        &mut self,
+        image: &Arc<Image>,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Arc<ImageView> {
+        self.get(
+            image,
+            vk::ImageViewType::TYPE_2D,
+            image.full_subresource_range(aspect_mask),
+        )
+    }
+
+    pub fn get(
+        &mut self,
+        image: &Arc<Image>,
+        view_type: vk::ImageViewType,
+        subresource_range: vk::ImageSubresourceRange,
+    ) -> Arc<ImageView> {
+        let key = ImageViewKey {
+            image: image.inner,
+            view_type,
+            aspect_mask: subresource_range.aspect_mask,
+            base_mip_level: subresource_range.base_mip_level,
+            level_count: subresource_range.level_count,
+            base_array_layer: subresource_range.base_array_layer,
+            layer_count: subresource_range.layer_count,
+        };
+
+        self.image_views
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(ImageView::new(
+                    self.context.clone(),
+                    image.clone(),
+                    view_type,
+                    subresource_range,
+                ))
+            })
+            .clone()
+    }
+
+    /// Destroys the views that are only kept alive by the cache, e.g. after switching scenes
+    pub fn remove_unused(&mut self) {
+        self.image_views
+            .retain(|_, image_view| Arc::strong_count(image_view) > 1);
+    }
+}
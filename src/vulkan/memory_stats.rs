@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use ash::vk;
+
+/// Keeps track of the device memory that buffers and images allocate themselves.
+/// They don't go through the gpu-allocator, which is only used by the UI.
+pub struct MemoryStats {
+    inner: Mutex<MemoryStatsInternal>,
+}
+
+#[derive(Default)]
+struct MemoryStatsInternal {
+    device_local_bytes: vk::DeviceSize,
+    host_visible_bytes: vk::DeviceSize,
+    /// Number of live allocations per size, for the largest one
+    allocation_sizes: BTreeMap<vk::DeviceSize, usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryReport {
+    pub device_local_bytes: vk::DeviceSize,
+    /// Includes memory that is both host visible and device local
+    pub host_visible_bytes: vk::DeviceSize,
+    pub allocation_count: usize,
+    pub largest_allocation: vk::DeviceSize,
+}
+
+impl MemoryStats {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Default::default()),
+        }
+    }
+
+    pub fn allocated(&self, size: vk::DeviceSize, memory_property_flags: vk::MemoryPropertyFlags) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.bytes_for(memory_property_flags) += size;
+        *inner.allocation_sizes.entry(size).or_default() += 1;
+    }
+
+    pub fn freed(&self, size: vk::DeviceSize, memory_property_flags: vk::MemoryPropertyFlags) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.bytes_for(memory_property_flags) -= size;
+        let count = inner
+            .allocation_sizes
+            .get_mut(&size)
+            .expect("Freed an allocation that was never tracked");
+        *count -= 1;
+        if *count == 0 {
+            inner.allocation_sizes.remove(&size);
+        }
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        let inner = self.inner.lock().unwrap();
+        MemoryReport {
+            device_local_bytes: inner.device_local_bytes,
+            host_visible_bytes: inner.host_visible_bytes,
+            allocation_count: inner.allocation_sizes.values().sum(),
+            largest_allocation: inner
+                .allocation_sizes
+                .last_key_value()
+                .map_or(0, |(size, _)| *size),
+        }
+    }
+}
+
+impl MemoryStatsInternal {
+    fn bytes_for(&mut self, memory_property_flags: vk::MemoryPropertyFlags) -> &mut vk::DeviceSize {
+        if memory_property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            &mut self.host_visible_bytes
+        } else {
+            &mut self.device_local_bytes
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use std::{ffi::CStr, io::Cursor, sync::Arc};
+use std::{
+    ffi::CStr,
+    fmt,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
 
 use ash::vk;
 
@@ -10,6 +17,22 @@ pub struct ShaderCreateInfo<'a> {
     shader_module: vk::ShaderModule,
 }
 
+/// A shader that failed to compile at runtime
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    pub path: PathBuf,
+    /// The output of the compiler
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
 const SHADER_ENTRY_NAME: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") };
 
 impl<'a> ShaderCreateInfo<'a> {
@@ -36,6 +59,34 @@ impl<'a> ShaderCreateInfo<'a> {
         }
     }
 
+    /// Compiles the GLSL source at runtime, so that shaders can be changed without rebuilding
+    pub fn from_source_file(
+        context: Arc<Context>,
+        stage: vk::ShaderStageFlags,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, ShaderError> {
+        let path = path.as_ref();
+        let output = Command::new("glslc")
+            .arg("--target-spv=spv1.6")
+            .arg(path)
+            .arg("-o")
+            .arg("-")
+            .output()
+            .map_err(|error| ShaderError {
+                path: path.to_path_buf(),
+                message: format!("Could not run glslc: {}", error),
+            })?;
+
+        if !output.status.success() {
+            return Err(ShaderError {
+                path: path.to_path_buf(),
+                message: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
+        }
+
+        Ok(Self::new(context, stage, &output.stdout))
+    }
+
     pub fn build(&mut self) -> vk::PipelineShaderStageCreateInfo {
         self.builder.take().unwrap().build()
     }
@@ -52,13 +103,33 @@ impl<'a> Drop for ShaderCreateInfo<'a> {
 }
 
 // Macro
+// Debug builds compile the shaders at runtime, which allows reloading them.
+// The path is the one of the compiled shader, like "/base.frag.spv".
+// Evaluates to a Result<ShaderCreateInfo, ShaderError>, which is always Ok in release builds.
+#[cfg(debug_assertions)]
 #[macro_export]
 macro_rules! include_shader {
     ($context:expr, $stage:expr, $path:literal) => {
-        crate::vulkan::shader_create_info::ShaderCreateInfo::new(
+        crate::vulkan::shader_create_info::ShaderCreateInfo::from_source_file(
             $context,
             $stage,
-            &include_bytes!(concat!(env!("OUT_DIR"), $path))[..],
+            concat!("assets/shaders", $path)
+                .strip_suffix(".spv")
+                .unwrap(),
+        )
+    };
+}
+
+#[cfg(not(debug_assertions))]
+#[macro_export]
+macro_rules! include_shader {
+    ($context:expr, $stage:expr, $path:literal) => {
+        Ok::<_, crate::vulkan::shader_create_info::ShaderError>(
+            crate::vulkan::shader_create_info::ShaderCreateInfo::new(
+                $context,
+                $stage,
+                &include_bytes!(concat!(env!("OUT_DIR"), $path))[..],
+            ),
         )
     };
 }
@@ -47,14 +47,12 @@ impl SwapchainContainer {
         }
         .expect("Could not get present modes from physical device");
 
-        let image_format = formats
-            .into_iter()
-            .min_by_key(|fmt| match (fmt.format, fmt.color_space) {
-                (vk::Format::B8G8R8A8_SRGB, _) => 1,
-                (vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR) => 2,
-                (_, _) => 3,
-            })
-            .expect("Could not fetch image format");
+        let image_format = choose_surface_format(&formats);
+        log::info!(
+            "Swapchain format {:?} with color space {:?}",
+            image_format.format,
+            image_format.color_space
+        );
 
         let present_mode = present_modes
             .into_iter()
@@ -235,6 +233,41 @@ impl SwapchainContainer {
         self.images = images;
         self.imageviews = imageviews;
     }
+
+    /// Whether writes to the swapchain images get encoded to sRGB by the hardware.
+    /// Otherwise the shaders that write to them have to do it.
+    pub fn is_srgb(&self) -> bool {
+        matches!(
+            self.format,
+            vk::Format::B8G8R8A8_SRGB
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::A8B8G8R8_SRGB_PACK32
+        )
+    }
+}
+
+/// The sRGB formats come first, since the hardware then encodes the linear output of the shaders.
+/// The others need the shaders to encode the output, see [`SwapchainContainer::is_srgb`].
+const PREFERRED_FORMATS: [vk::Format; 5] = [
+    vk::Format::B8G8R8A8_SRGB,
+    vk::Format::R8G8B8A8_SRGB,
+    vk::Format::A8B8G8R8_SRGB_PACK32,
+    vk::Format::B8G8R8A8_UNORM,
+    vk::Format::R8G8B8A8_UNORM,
+];
+
+/// Falls back to the first format that the surface reports
+fn choose_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
+    PREFERRED_FORMATS
+        .iter()
+        .find_map(|&format| {
+            formats.iter().copied().find(|surface_format| {
+                surface_format.format == format
+                    && surface_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+            })
+        })
+        .or_else(|| formats.first().copied())
+        .expect("Could not find a surface format")
 }
 
 impl Drop for SwapchainContainer {
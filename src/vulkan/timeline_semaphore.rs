@@ -0,0 +1,77 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use ash::vk;
+
+use super::context::Context;
+
+/// A semaphore with a counter, which the GPU increases when a submission finishes.
+/// Allows waiting for one specific submission, instead of waiting for the whole device.
+pub struct TimelineSemaphore {
+    inner: vk::Semaphore,
+    /// The last value that was handed out for a submission
+    last_value: AtomicU64,
+    context: Arc<Context>,
+}
+
+impl TimelineSemaphore {
+    pub fn new(context: Arc<Context>) -> Self {
+        let mut type_create_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let create_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_create_info);
+
+        let inner = unsafe { context.device.create_semaphore(&create_info, None) }
+            .expect("Could not create timeline semaphore");
+
+        Self {
+            inner,
+            last_value: AtomicU64::new(0),
+            context,
+        }
+    }
+
+    /// The value that the next submission should signal
+    pub fn next_value(&self) -> u64 {
+        self.last_value.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn wait(&self, value: u64) {
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(std::slice::from_ref(&self.inner))
+            .values(std::slice::from_ref(&value));
+
+        unsafe { self.context.device.wait_semaphores(&wait_info, u64::MAX) }
+            .expect("Could not wait for timeline semaphore");
+    }
+}
+
+impl std::ops::Deref for TimelineSemaphore {
+    type Target = vk::Semaphore;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl Drop for TimelineSemaphore {
+    fn drop(&mut self) {
+        unsafe { self.context.device.destroy_semaphore(self.inner, None) };
+    }
+}
+
+/// Returned by a submission that signals a timeline semaphore
+#[must_use]
+pub struct TimelineSubmission {
+    pub semaphore: Arc<TimelineSemaphore>,
+    pub value: u64,
+}
+
+impl TimelineSubmission {
+    /// Blocks until the GPU has finished the submission
+    pub fn wait(&self) {
+        self.semaphore.wait(self.value);
+    }
+}
@@ -0,0 +1,171 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ash::{extensions::ext::DebugUtils, vk};
+
+const VALIDATION_LAYER_NAME: &CStr =
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") };
+
+/// Set this to 1 to enable the validation layers in release builds
+const VALIDATION_ENV_VAR: &str = "RTR_VALIDATION";
+/// Set this to 1 to panic on validation errors, useful for test runs
+const PANIC_ON_ERROR_ENV_VAR: &str = "RTR_VALIDATION_PANIC";
+
+static PANIC_ON_ERROR: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationSettings {
+    pub enabled: bool,
+    pub panic_on_error: bool,
+    /// Labels for RenderDoc and other frame debuggers, they need the debug utils extension
+    pub debug_labels: bool,
+}
+
+impl ValidationSettings {
+    /// Always enabled in debug builds, and in release builds when `RTR_VALIDATION=1`
+    pub fn from_env() -> Self {
+        let is_set = |name: &str| std::env::var(name).map_or(false, |value| value == "1");
+
+        let enabled = cfg!(debug_assertions) || is_set(VALIDATION_ENV_VAR);
+        Self {
+            enabled,
+            panic_on_error: is_set(PANIC_ON_ERROR_ENV_VAR),
+            debug_labels: enabled,
+        }
+    }
+
+    /// Disables validation when the layer isn't installed.
+    /// The debug labels still work without it, since frame debuggers provide the extension.
+    pub fn check_layer_support(self, entry: &ash::Entry) -> Self {
+        let has_debug_utils = entry
+            .enumerate_instance_extension_properties(None)
+            .expect("Could not enumerate instance extensions")
+            .iter()
+            .any(|extension| {
+                let extension_name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
+                extension_name == DebugUtils::name()
+            });
+        let debug_labels = self.debug_labels && has_debug_utils;
+
+        if !self.enabled {
+            return Self {
+                debug_labels,
+                ..self
+            };
+        }
+
+        let has_layer = entry
+            .enumerate_instance_layer_properties()
+            .expect("Could not enumerate instance layers")
+            .iter()
+            .any(|layer| {
+                let layer_name = unsafe { CStr::from_ptr(layer.layer_name.as_ptr()) };
+                layer_name == VALIDATION_LAYER_NAME
+            });
+
+        if !has_layer {
+            log::warn!("Validation was requested, but the validation layer is not installed");
+        }
+
+        Self {
+            enabled: has_layer,
+            debug_labels,
+            ..self
+        }
+    }
+
+    pub fn layer_names(&self) -> Vec<*const c_char> {
+        if self.enabled {
+            vec![VALIDATION_LAYER_NAME.as_ptr()]
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn extension_names(&self) -> Vec<*const c_char> {
+        if self.enabled || self.debug_labels {
+            vec![DebugUtils::name().as_ptr()]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Also chained into the instance create info, so that instance creation and destruction are validated
+    pub fn messenger_create_info(&self) -> vk::DebugUtilsMessengerCreateInfoEXT {
+        PANIC_ON_ERROR.store(self.panic_on_error, Ordering::Relaxed);
+
+        vk::DebugUtilsMessengerCreateInfoEXT::builder()
+            .message_severity(
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            )
+            .message_type(
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            )
+            .pfn_user_callback(Some(debug_callback))
+            .build()
+    }
+}
+
+/// Has to be destroyed before the instance
+pub struct DebugMessenger {
+    loader: DebugUtils,
+    messenger: vk::DebugUtilsMessengerEXT,
+}
+
+impl DebugMessenger {
+    pub fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        settings: &ValidationSettings,
+    ) -> Self {
+        let loader = DebugUtils::new(entry, instance);
+        let messenger =
+            unsafe { loader.create_debug_utils_messenger(&settings.messenger_create_info(), None) }
+                .expect("Could not create debug messenger");
+
+        Self { loader, messenger }
+    }
+
+    /// # Safety
+    /// Must be called exactly once, before the instance is destroyed
+    pub unsafe fn destroy(&mut self) {
+        self.loader
+            .destroy_debug_utils_messenger(self.messenger, None);
+    }
+}
+
+/// Routes the validation messages through the log crate.
+/// Panicking here aborts the process, since the panic can't unwind through the driver.
+unsafe extern "system" fn debug_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    _user_data: *mut c_void,
+) -> vk::Bool32 {
+    let message = if callback_data.is_null() || (*callback_data).p_message.is_null() {
+        "".into()
+    } else {
+        CStr::from_ptr((*callback_data).p_message).to_string_lossy()
+    };
+
+    let level = match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Debug,
+        _ => log::Level::Trace,
+    };
+    log::log!(target: "vulkan", level, "{:?}: {}", message_type, message);
+
+    if message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+        && PANIC_ON_ERROR.load(Ordering::Relaxed)
+    {
+        panic!("Vulkan validation error: {}", message);
+    }
+
+    vk::FALSE
+}